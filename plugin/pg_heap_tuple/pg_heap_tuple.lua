@@ -0,0 +1,37 @@
+-- 从选区起始位置解析 HeapTupleHeaderData
+local off = chap.sel_start
+local big = chap.args.endian == "big"
+if not big and chap.args.endian ~= "little" then
+    error("endian must be little or big")
+end
+local u16 = big and chap.u16be or chap.u16le
+local u32 = big and chap.u32be or chap.u32le
+
+local infomask2 = u16(off + 18)
+local infomask = u16(off + 20)
+local natts = chap.args.natts
+if natts == 0 then
+    natts = infomask2 & 0x07FF
+end
+local has_nulls = (infomask & 0x0001) ~= 0
+local bitmap_len = has_nulls and (natts + 7) // 8 or 0
+
+local fields = {
+    { name = "t_xmin",      offset = off,      length = 4, value = u32(off) },
+    { name = "t_xmax",      offset = off + 4,  length = 4, value = u32(off + 4) },
+    { name = "t_cid",       offset = off + 8,  length = 4, value = u32(off + 8) },
+    { name = "t_ctid",      offset = off + 12, length = 6,
+      value = string.format("(%d,%d)", (u16(off + 12) << 16) | u16(off + 14), u16(off + 16)) },
+    { name = "t_infomask2", offset = off + 18, length = 2, value = string.format("0x%04x", infomask2) },
+    { name = "t_infomask",  offset = off + 20, length = 2, value = string.format("0x%04x", infomask) },
+    { name = "t_hoff",      offset = off + 22, length = 1, value = chap.u8(off + 22) },
+    { name = "natts",       offset = off + 18, length = 2, value = natts },
+}
+if bitmap_len > 0 then
+    local bits = {}
+    for i = 0, bitmap_len - 1 do
+        bits[#bits + 1] = string.format("%02x", chap.u8(off + 23 + i))
+    end
+    fields[#fields + 1] = { name = "t_bits", offset = off + 23, length = bitmap_len, value = table.concat(bits, " ") }
+end
+return fields
@@ -0,0 +1,36 @@
+-- 遍历选区起始位置页面的所有行指针 标注每个元组
+local page = chap.sel_start
+if page + 24 > chap.size() then
+    return "pg_tuples: page header < 24"
+end
+
+local HEADER_SIZE = 24
+local pd_lower = chap.u16le(page + 12)
+local count = (pd_lower <= HEADER_SIZE) and 0 or (pd_lower - HEADER_SIZE) // 4
+local colors = { "cyan", "magenta" }
+
+chap.clear_highlights()
+local fields = {}
+for i = 1, count do
+    local lp = page + HEADER_SIZE + (i - 1) * 4
+    local v = chap.u32le(lp)
+    local lp_off = v & 0x7FFF
+    local lp_flags = (v >> 15) & 0x3
+    local lp_len = v >> 17
+    chap.highlight(lp, 4, "yellow")
+    if lp_len > 0 then
+        chap.highlight(page + lp_off, lp_len, colors[(i - 1) % 2 + 1])
+    end
+    fields[#fields + 1] = {
+        name = "lp " .. i,
+        offset = page + lp_off,
+        length = lp_len,
+        value = string.format("off=%d flags=%d len=%d", lp_off, lp_flags, lp_len),
+    }
+end
+
+if count > 0 then
+    chap.select(page + HEADER_SIZE, count * 4)
+end
+chap.message(string.format("%d line pointers", count))
+return fields
@@ -0,0 +1,329 @@
+use crate::history::Splice;
+use crate::plugin::PluginField;
+use crate::wal::crc32c;
+use crate::ChapResult;
+use std::fs;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+use std::path::PathBuf;
+
+// 会话目录 相对于 home 目录
+pub(crate) const SESSION_DIR: &str = ".chap/sessions";
+// 会话文件扩展名
+const SESSION_EXT: &str = "session";
+// 计算文件指纹时读取头尾各这么多字节
+const HASH_SAMPLE: u64 = 64 * 1024;
+
+// 一个书签 覆盖 [start, end) 可以带注释
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Bookmark {
+    pub(crate) name: String,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) comment: String,
+}
+
+// 一个文件的书签 按文件路径和内容指纹保存在会话目录
+// 偏移对应编辑中的内容 插入删除后跟着移动 文件保存之前不写入会话目录
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Session {
+    path: PathBuf,
+    hash: String,
+    marks: Vec<Bookmark>,
+    unsaved: bool, //有还没保存到文件的插入删除 偏移和磁盘上的文件对不上
+}
+
+// 文件指纹: 大小加上头尾采样的 crc32c 大文件也能很快算出
+fn file_hash(path: &Path) -> ChapResult<String> {
+    let mut file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut buf = Vec::new();
+    (&mut file).take(HASH_SAMPLE).read_to_end(&mut buf)?;
+    if size > HASH_SAMPLE {
+        file.seek(SeekFrom::Start(
+            size.saturating_sub(HASH_SAMPLE).max(HASH_SAMPLE),
+        ))?;
+        file.read_to_end(&mut buf)?;
+    }
+    Ok(format!("{}:{:08x}", size, crc32c(&buf)))
+}
+
+// 注释里的制表符和换行会破坏行格式
+fn clean(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c == '\t' || c == '\n' || c == '\r' {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+impl Session {
+    // 先按路径找会话 找不到再按指纹找 (文件被移动或改名)
+    pub(crate) fn open(dir: &Path, file: &Path) -> ChapResult<Session> {
+        let path = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        let hash = file_hash(&path).unwrap_or_default();
+        let mut by_hash = None;
+        if let Ok(entries) = fs::read_dir(dir) {
+            for entry in entries.flatten() {
+                let p = entry.path();
+                if p.extension().and_then(|e| e.to_str()) != Some(SESSION_EXT) {
+                    continue;
+                }
+                // 读不出来的会话文件跳过 不影响打开文件
+                let text = match fs::read_to_string(&p) {
+                    Ok(text) => text,
+                    Err(_) => continue,
+                };
+                let session = Session::parse(&text);
+                if session.path == path {
+                    return Ok(Session { hash, ..session });
+                }
+                if by_hash.is_none() && !hash.is_empty() && session.hash == hash {
+                    by_hash = Some(session);
+                }
+            }
+        }
+        let marks = by_hash.map(|s| s.marks).unwrap_or_default();
+        Ok(Session {
+            path,
+            hash,
+            marks,
+            unsaved: false,
+        })
+    }
+
+    // 会话文件名取自路径的 crc32c 文件有未保存的插入删除时不写
+    pub(crate) fn save(&self, dir: &Path) -> ChapResult<()> {
+        if self.unsaved {
+            return Ok(());
+        }
+        fs::create_dir_all(dir)?;
+        let key = crc32c(self.path.to_string_lossy().as_bytes());
+        let file = dir.join(format!("{:08x}.{}", key, SESSION_EXT));
+        if self.marks.is_empty() {
+            if file.exists() {
+                fs::remove_file(file)?;
+            }
+            return Ok(());
+        }
+        fs::write(file, self.to_text())?;
+        Ok(())
+    }
+
+    // 每行一条记录 字段用制表符分隔 无法识别的行忽略
+    fn parse(text: &str) -> Session {
+        let mut session = Session::default();
+        for line in text.lines() {
+            let parts: Vec<&str> = line.split('\t').collect();
+            match parts.as_slice() {
+                ["path", path] => session.path = PathBuf::from(path),
+                ["hash", hash] => session.hash = hash.to_string(),
+                ["mark", name, start, end, comment] => {
+                    if let (Ok(start), Ok(end)) = (start.parse(), end.parse()) {
+                        session.add(Bookmark {
+                            name: name.to_string(),
+                            start,
+                            end,
+                            comment: comment.to_string(),
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+        session
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = format!(
+            "path\t{}\nhash\t{}\n",
+            clean(&self.path.to_string_lossy()),
+            self.hash
+        );
+        for m in &self.marks {
+            text.push_str(&format!(
+                "mark\t{}\t{}\t{}\t{}\n",
+                clean(&m.name),
+                m.start,
+                m.end,
+                clean(&m.comment)
+            ));
+        }
+        text
+    }
+
+    // 添加或替换同名书签 按起点排序 外层范围排在内层前面
+    pub(crate) fn add(&mut self, mark: Bookmark) {
+        self.marks.retain(|m| m.name != mark.name);
+        let pos = self
+            .marks
+            .iter()
+            .position(|m| (m.start, mark.end) > (mark.start, m.end))
+            .unwrap_or(self.marks.len());
+        self.marks.insert(pos, mark);
+    }
+
+    // 插入删除之后移动书签 被删掉的范围收缩到删除位置 至少保留一个字节
+    pub(crate) fn shift(&mut self, splice: &Splice) {
+        for m in self.marks.iter_mut() {
+            let (start, end) = splice.map_range(m.start, m.end);
+            m.start = start;
+            m.end = end.max(start + 1);
+        }
+        self.unsaved = true;
+    }
+
+    // 文件保存后 偏移和文件重新对应 更新指纹
+    pub(crate) fn file_saved(&mut self) {
+        self.hash = file_hash(&self.path).unwrap_or_default();
+        self.unsaved = false;
+    }
+
+    pub(crate) fn remove(&mut self, name: &str) -> Option<Bookmark> {
+        let pos = self.marks.iter().position(|m| m.name == name)?;
+        Some(self.marks.remove(pos))
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&Bookmark> {
+        self.marks.iter().find(|m| m.name == name)
+    }
+
+    pub(crate) fn marks(&self) -> &[Bookmark] {
+        &self.marks
+    }
+
+    // 包含 pos 的最内层书签
+    pub(crate) fn at(&self, pos: usize) -> Option<&Bookmark> {
+        self.marks
+            .iter()
+            .filter(|m| pos >= m.start && pos < m.end)
+            .min_by_key(|m| m.end - m.start)
+    }
+
+    // 书签列表 显示在结构树中
+    pub(crate) fn fields(&self) -> Vec<PluginField> {
+        self.marks
+            .iter()
+            .map(|m| PluginField {
+                name: m.name.clone(),
+                offset: m.start,
+                length: m.end - m.start,
+                value: m.comment.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(name: &str, start: usize, end: usize, comment: &str) -> Bookmark {
+        Bookmark {
+            name: name.to_string(),
+            start,
+            end,
+            comment: comment.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_session_marks() {
+        let mut s = Session::default();
+        s.add(mark("b", 16, 20, ""));
+        s.add(mark("hdr", 0, 32, "file header"));
+        s.add(mark("a", 0, 4, "magic"));
+        let names: Vec<&str> = s.marks().iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["hdr", "a", "b"]);
+        assert_eq!(s.at(2).unwrap().name, "a");
+        assert_eq!(s.at(8).unwrap().name, "hdr");
+        assert_eq!(s.at(32), None);
+
+        // 同名替换
+        s.add(mark("a", 40, 41, "tab\there"));
+        assert_eq!(s.get("a").unwrap().start, 40);
+        assert_eq!(s.marks().len(), 3);
+        assert_eq!(s.remove("b").unwrap().end, 20);
+        assert_eq!(s.remove("b"), None);
+
+        let text = s.to_text();
+        let parsed = Session::parse(&format!("{}garbage\nmark\tx\t1\n", text));
+        assert_eq!(parsed.marks().len(), 2);
+        assert_eq!(parsed.get("a").unwrap().comment, "tab here");
+        assert_eq!(parsed.get("hdr").unwrap().end, 32);
+    }
+
+    #[test]
+    fn test_session_shift() {
+        let dir = std::env::temp_dir().join("chap_test_sessions_shift");
+        let _ = fs::remove_dir_all(&dir);
+        let mut s = Session::default();
+        s.add(mark("head", 0, 4, ""));
+        s.add(mark("body", 8, 16, ""));
+        s.add(mark("gone", 20, 22, ""));
+        s.shift(&Splice {
+            offset: 4,
+            removed: 0,
+            inserted: 3,
+        });
+        s.shift(&Splice {
+            offset: 20,
+            removed: 6,
+            inserted: 0,
+        });
+        assert_eq!(s.get("head").unwrap().end, 4);
+        assert_eq!(s.get("body").unwrap().start, 11);
+        assert_eq!(s.get("body").unwrap().end, 19);
+        assert_eq!(s.get("gone").unwrap().start, 20);
+        assert_eq!(s.get("gone").unwrap().end, 21);
+
+        // 文件保存之前偏移对不上 不写会话
+        s.save(&dir).unwrap();
+        assert!(!dir.exists());
+        s.unsaved = false;
+        s.save(&dir).unwrap();
+        assert!(dir.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_session_persist() {
+        let dir = std::env::temp_dir().join("chap_test_sessions");
+        let _ = fs::remove_dir_all(&dir);
+        let file = std::env::temp_dir().join("chap_test_session_a.bin");
+        let moved = std::env::temp_dir().join("chap_test_session_b.bin");
+        fs::write(&file, vec![7u8; 200_000]).unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("bad.session"), [0xffu8, 0xfe, 0x00]).unwrap();
+
+        let mut s = Session::open(&dir, &file).unwrap();
+        assert!(s.marks().is_empty());
+        s.add(mark("m", 10, 20, "some note"));
+        s.save(&dir).unwrap();
+        let s = Session::open(&dir, &file).unwrap();
+        assert_eq!(s.get("m").unwrap().comment, "some note");
+
+        // 文件改名后按指纹找回
+        fs::rename(&file, &moved).unwrap();
+        let mut s = Session::open(&dir, &moved).unwrap();
+        assert_eq!(s.marks().len(), 1);
+
+        s.save(&dir).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+        // 删除最后一个书签后会话文件也删除
+        s.remove("m");
+        s.save(&dir).unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::write(&moved, b"other").unwrap();
+        assert!(Session::open(&dir, &moved).unwrap().marks().is_empty());
+        let _ = fs::remove_file(&moved);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
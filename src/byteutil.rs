@@ -228,11 +228,11 @@ impl Display for VarlenaData {
 }
 
 #[derive(Debug)]
-pub(crate) struct VarlenaData(VarlenaType, u32);
+pub(crate) struct VarlenaData(pub(crate) VarlenaType, pub(crate) u32);
 
 /// 解析 PostgreSQL varlena header，识别类型并返回长度 + payload 起始偏移
-#[derive(Debug, PartialEq)]
-enum VarlenaType {
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum VarlenaType {
     ShortInline,          // varattrib_1b
     ToastPointer,         // varattrib_1b_e
     FourByteUncompressed, // varattrib_4b uncompressed
@@ -240,7 +240,7 @@ enum VarlenaType {
     Unknown,
 }
 
-fn parse_varlena_header(data: &[u8]) -> Option<VarlenaData> {
+pub(crate) fn parse_varlena_header(data: &[u8]) -> Option<VarlenaData> {
     if data.is_empty() {
         return None;
     }
@@ -264,7 +264,8 @@ fn parse_varlena_header(data: &[u8]) -> Option<VarlenaData> {
         }
         let hdr_le = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
         let total_len = hdr_le >> 2;
-        let is_compressed = (data[0] & 0x0010) != 0;
+        // 低2位 00 未压缩 10 压缩
+        let is_compressed = (data[0] & 0x03) == 0x02;
         let typ = if is_compressed {
             VarlenaType::FourByteCompressed
         } else {
@@ -274,6 +275,26 @@ fn parse_varlena_header(data: &[u8]) -> Option<VarlenaData> {
     }
 }
 
+// varlena 的类型和 header 之后的数据
+pub(crate) fn varlena_payload(data: &[u8]) -> Option<(VarlenaType, &[u8])> {
+    let VarlenaData(typ, total_len) = parse_varlena_header(data)?;
+    let header_len = match typ {
+        VarlenaType::ShortInline => 1,
+        VarlenaType::ToastPointer => 2,
+        VarlenaType::FourByteUncompressed | VarlenaType::FourByteCompressed => 4,
+        VarlenaType::Unknown => return None,
+    };
+    //toast 指针的长度由 tag 决定 这里返回剩余的全部数据
+    let end = match typ {
+        VarlenaType::ToastPointer => data.len(),
+        _ => total_len as usize,
+    };
+    if end < header_len || end > data.len() {
+        return None;
+    }
+    Some((typ, &data[header_len..end]))
+}
+
 pub fn format_va_extinfo(buf: &[u8]) -> String {
     // 确保至少能读取 va_extinfo （4 bytes）在 offset 4，从 va_rawsize 后
     if buf.len() < 8 {
@@ -323,5 +344,23 @@ mod tests {
         println!("bv: {:?}", bv.to_varlena());
     }
 
+    #[test]
+    fn test_varlena_payload() {
+        // 4字节 header 长度 10 压缩
+        let data = [0x2a, 0, 0, 0, 1, 2, 3, 4, 5, 6, 0xff];
+        let (typ, payload) = varlena_payload(&data).unwrap();
+        assert_eq!(typ, VarlenaType::FourByteCompressed);
+        assert_eq!(payload, &[1, 2, 3, 4, 5, 6]);
+        // 4字节 header 未压缩
+        let (typ, _) = varlena_payload(&[0x28, 0, 0, 0, 1, 2, 3, 4, 5, 6]).unwrap();
+        assert_eq!(typ, VarlenaType::FourByteUncompressed);
+        // 1字节 header 长度 3
+        let (typ, payload) = varlena_payload(&[0x07, b'a', b'b']).unwrap();
+        assert_eq!(typ, VarlenaType::ShortInline);
+        assert_eq!(payload, b"ab");
+        // 长度超出数据
+        assert!(varlena_payload(&[0x2a, 0, 0, 0, 1]).is_none());
+    }
+
     //  fn test
 }
@@ -1,8 +1,11 @@
+use crate::bookmark::SESSION_DIR;
 use crate::cli::Cli;
+use crate::template::TEMPLATE_DIR;
 use crate::ChapResult;
 use crate::ChapTui;
 use once_cell::sync::Lazy;
 use simplelog::*;
+use std::env;
 use std::fs;
 use std::fs::File;
 use std::path::Path;
@@ -28,7 +31,44 @@ pub(crate) static RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
 pub(crate) struct Chappie {
     tui: ChapTui,
     chap_log_dir: PathBuf,
-    chap_plugin_dir: PathBuf,
+    chap_plugin_dirs: Vec<PathBuf>,
+}
+
+// 插件目录环境变量 多个目录用 : 分隔
+const CHAP_PLUGIN_PATH: &str = "CHAP_PLUGIN_PATH";
+
+// 配置文件中的插件目录 每行一个 plugin_dir = <path>
+fn config_plugin_dirs(content: &str, home: &Path) -> Vec<PathBuf> {
+    content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .filter(|(key, _)| key.trim() == "plugin_dir")
+        .map(|(_, value)| match value.trim().strip_prefix("~/") {
+            Some(rest) => home.join(rest),
+            None => PathBuf::from(value.trim()),
+        })
+        .collect()
+}
+
+// 合并插件目录 命令行 > 环境变量 > 配置文件 > ~/.chap/plugin
+fn plugin_dirs(cli: &Cli, home: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = cli.get_plugin_dirs().to_vec();
+    if let Some(paths) = env::var_os(CHAP_PLUGIN_PATH) {
+        dirs.extend(env::split_paths(&paths).filter(|p| !p.as_os_str().is_empty()));
+    }
+    if let Ok(content) = fs::read_to_string(home.join(".chap/config")) {
+        dirs.extend(config_plugin_dirs(&content, home));
+    }
+    dirs.push(home.join(".chap/plugin"));
+    let mut merged: Vec<PathBuf> = Vec::with_capacity(dirs.len());
+    for dir in dirs {
+        if !merged.contains(&dir) {
+            merged.push(dir);
+        }
+    }
+    merged
 }
 //
 impl Chappie {
@@ -52,9 +92,11 @@ impl Chappie {
         let (llm_res_tx, llm_res_rx) = mpsc::channel::<String>(1);
         let home = dirs::home_dir().expect("Failed to get home directory");
         let chap_log_dir = home.join(".chap/log");
-        let chap_plugin_dir = PathBuf::from("/home/postgres/rsproject/chappie/plugin"); //home.join(".chap/plugin");
+        let chap_plugin_dirs = plugin_dirs(cli, &home);
         fs::create_dir_all(&chap_log_dir)?;
-        fs::create_dir_all(&chap_plugin_dir)?;
+        fs::create_dir_all(home.join(".chap/plugin"))?;
+        fs::create_dir_all(home.join(TEMPLATE_DIR))?;
+        fs::create_dir_all(home.join(SESSION_DIR))?;
         // 配置日志输出到文件
         WriteLogger::init(
             LevelFilter::Debug,                           // 设置日志级别
@@ -73,12 +115,22 @@ impl Chappie {
         Ok(Self {
             tui: chap_ui,
             chap_log_dir: chap_log_dir,
-            chap_plugin_dir: chap_plugin_dir,
+            chap_plugin_dirs: chap_plugin_dirs,
         })
     }
 
     pub(crate) fn run<P: AsRef<Path>>(&mut self, p: P) -> ChapResult<()> {
-        RUNTIME.block_on(async move { self.tui.render(p, self.chap_plugin_dir.as_path()).await })
+        RUNTIME.block_on(async move { self.tui.render(p, &self.chap_plugin_dirs).await })
+    }
+
+    // 并排比较两个文件
+    pub(crate) fn run_diff<P: AsRef<Path>>(
+        &mut self,
+        left: P,
+        right: P,
+        aligned: bool,
+    ) -> ChapResult<()> {
+        RUNTIME.block_on(async move { self.tui.render_diff(left, right, aligned).await })
     }
 }
 
@@ -117,3 +169,22 @@ impl Chappie {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_plugin_dirs() {
+        let home = Path::new("/home/chap");
+        let content =
+            "# plugins\nplugin_dir = ~/work/plugin\nplugin_dir=/opt/chap/plugin\nother = x\n";
+        assert_eq!(
+            config_plugin_dirs(content, home),
+            vec![
+                PathBuf::from("/home/chap/work/plugin"),
+                PathBuf::from("/opt/chap/plugin")
+            ]
+        );
+    }
+}
@@ -2,12 +2,16 @@ use crate::error::{ChapError, ChapResult};
 use crate::tui::ChapMod;
 use clap::Parser;
 use clap::ValueEnum;
+use std::path::PathBuf;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub(crate) struct Cli {
     #[arg(value_name = "FILE")]
     filepath: Option<String>,
 
+    #[arg(value_name = "OTHER")]
+    other: Option<String>, //--diff 时比较的第二个文件
+
     #[arg(short = 'l', long, default_value = "groq", env = "CHAP_LLM_NAME")]
     llm: String,
 
@@ -42,6 +46,15 @@ pub(crate) struct Cli {
 
     #[arg(short = 'q', long = "que", default_value_t = false)]
     question: bool,
+
+    #[arg(short = 'p', long = "plugin-dir", value_name = "DIR")]
+    plugin_dirs: Vec<PathBuf>, //插件目录 可以指定多个
+
+    #[arg(long = "diff", default_value_t = false)]
+    diff: bool, //并排比较两个文件
+
+    #[arg(long = "align", default_value_t = false)]
+    align: bool, //比较时检测插入和删除
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, ValueEnum)]
@@ -59,6 +72,20 @@ impl Cli {
         }
     }
 
+    // --diff 需要两个文件 第二个文件只用于 --diff
+    pub(crate) fn get_diff_path(&self) -> ChapResult<Option<&str>> {
+        match (&self.other, self.diff) {
+            (Some(p), true) => Ok(Some(p)),
+            (None, true) => Err("--diff needs two files".into()),
+            (Some(_), false) => Err("a second file is only used with --diff".into()),
+            (None, false) => Ok(None),
+        }
+    }
+
+    pub(crate) fn get_align(&self) -> bool {
+        self.align
+    }
+
     pub(crate) fn get_llm(&self) -> &str {
         &self.llm
     }
@@ -87,6 +114,10 @@ impl Cli {
         self.question
     }
 
+    pub(crate) fn get_plugin_dirs(&self) -> &[PathBuf] {
+        &self.plugin_dirs
+    }
+
     pub(crate) fn get_chap_mod(&self) -> ChapMod {
         if self.insert {
             return ChapMod::Edit;
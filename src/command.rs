@@ -10,8 +10,31 @@ pub(crate) enum Command {
     Unknown(String),   // unknown command
     Cut(CutFile),
     CutSel(CutSelFile),
-    Call(String),
+    Call(String, Vec<(String, String)>), // plugin name, key=value args
     ListFunc,
+    Undo(usize),                       // undo n steps
+    Redo(usize),                       // redo n steps
+    NoHighlight,                       // clear plugin highlights
+    Reload,                            // rescan plugin directories
+    PgPage(usize, Option<String>), // decode postgres heap page by block number, optional column types
+    PgTuple(String),               // decode the heap tuple at the selection by column types
+    PgBtree(usize),                // decode postgres nbtree index page by block number
+    PgCheck(usize),                // verify page checksums, segment number
+    PgFix(usize),                  // rewrite checksum of the current page, segment number
+    Detoast(Option<String>),       // decompress selected varlena, optional file to save
+    Wal,                           // walk postgres WAL segment records
+    PgFile,                        // decode fsm, vm, pg_control or WAL by file name
+    SqlitePage(usize),             // decode sqlite b-tree page, page number from 1
+    Exe(bool),                     // decode ELF, PE or Mach-O; true lists sections only
+    Apply(String, Option<usize>),  // apply structure template, optional start address
+    Mark(String, String),          // bookmark the selection, name and comment
+    Unmark(String),                // remove a bookmark by name
+    Goto(String),                  // jump to a bookmark by name
+    Marks,                         // list bookmarks
+    NextHit,                       // jump to the next search hit
+    PrevHit,                       // jump to the previous search hit
+    Results,                       // list search hits
+    Replace(FindValue, Vec<u8>, bool), // pattern, replacement, confirm each
 }
 
 #[derive(Debug, PartialEq)]
@@ -52,9 +75,69 @@ impl CutSelFile {
 pub(crate) enum FindValue {
     Hex(Vec<u8>),
     Ascii(String),
+    Masked(Vec<(u8, u8)>), // value and mask per byte, ? is a wildcard nibble
+    NoCase(String),        // case-insensitive ASCII
+    Utf16(String, Endian), // UTF-16 encoded string
+    Regex(String),         // regular expression over bytes
+    Fuzzy(Vec<u8>, Option<usize>), // approximate match, optional max edit distance
+}
+
+// 解析带通配符的16进制 如 4A??0?FF 每个 ? 是一个半字节
+fn parse_masked(hex: &str) -> Option<Vec<(u8, u8)>> {
+    let nibbles: Vec<Option<u8>> = hex
+        .chars()
+        .map(|c| match c {
+            '?' => Some(None),
+            c => c.to_digit(16).map(|d| Some(d as u8)),
+        })
+        .collect::<Option<_>>()?;
+    if nibbles.is_empty() || !nibbles.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(
+        nibbles
+            .chunks(2)
+            .map(|pair| {
+                let (hi, hi_mask) = pair[0].map_or((0, 0), |n| (n << 4, 0xf0));
+                let (lo, lo_mask) = pair[1].map_or((0, 0), |n| (n, 0x0f));
+                (hi | lo, hi_mask | lo_mask)
+            })
+            .collect(),
+    )
+}
+
+// 一个查找值 0x 开头是16进制 可以带 ? 通配 否则是 ASCII
+fn parse_find_value(value: &str) -> Option<FindValue> {
+    match value.strip_prefix("0x") {
+        Some(hex) if hex.contains('?') => parse_masked(hex).map(FindValue::Masked),
+        Some(hex) => Some(FindValue::Hex(hex::decode(hex).unwrap_or_default())),
+        None => Some(FindValue::Ascii(value.to_string())),
+    }
+}
+
+// 替换的字节 0x 开头是16进制 单独的 0x 表示删除
+fn parse_bytes(value: &str) -> Option<Vec<u8>> {
+    match value.strip_prefix("0x") {
+        Some(hex) => hex::decode(hex).ok(),
+        None => Some(value.as_bytes().to_vec()),
+    }
+}
+
+// 命令名后面的全部输入 保留其中的空格
+fn rest_of(input: &str) -> String {
+    let input = input.trim_start();
+    let cmd_len = input.find(char::is_whitespace).unwrap_or(input.len());
+    input[cmd_len..].trim().to_string()
 }
 
 impl Command {
+    fn parse_replace(input: &str, pattern: &str, replacement: &str, confirm: bool) -> Command {
+        match (parse_find_value(pattern), parse_bytes(replacement)) {
+            (Some(pattern), Some(replacement)) => Command::Replace(pattern, replacement, confirm),
+            _ => Command::Unknown(input.to_string()),
+        }
+    }
+
     pub(crate) fn parse(input: &str) -> Command {
         let parts: Vec<&str> = input.split_whitespace().collect();
         match parts.as_slice() {
@@ -62,6 +145,8 @@ impl Command {
             ["g"] => Command::GTop,
             ["G"] => Command::GBottom,
             ["lf"] => Command::ListFunc,
+            ["nohl"] => Command::NoHighlight,
+            ["reload"] => Command::Reload,
             ["set", value] => {
                 let value_parts: Vec<&str> = value.split('=').collect();
                 match value_parts.as_slice() {
@@ -79,14 +164,36 @@ impl Command {
             ["j", address] if address.parse::<usize>().is_ok() => {
                 Command::Jump(address.parse().unwrap())
             }
-            ["f", value] => {
-                if value.starts_with("0x") {
-                    let hex_value = value.trim_start_matches("0x");
-                    let bytes = hex::decode(hex_value).unwrap_or_else(|_| vec![]);
-                    Command::Find(FindValue::Hex(bytes))
-                } else {
-                    Command::Find(FindValue::Ascii(value.to_string()))
+            ["f", value] => match parse_find_value(value) {
+                Some(value) => Command::Find(value),
+                None => Command::Unknown(input.to_string()),
+            },
+            // 多个16进制字节 如 f 4A ?? 0? FF
+            ["f", bytes @ ..] if bytes.len() > 1 => match parse_masked(&bytes.concat()) {
+                Some(bytes) if bytes.iter().all(|&(_, mask)| mask == 0xff) => {
+                    Command::Find(FindValue::Hex(bytes.into_iter().map(|(v, _)| v).collect()))
                 }
+                Some(bytes) => Command::Find(FindValue::Masked(bytes)),
+                None => Command::Unknown(input.to_string()),
+            },
+            ["fi", _, ..] => Command::Find(FindValue::NoCase(rest_of(input))),
+            ["fr", _, ..] => Command::Find(FindValue::Regex(rest_of(input))),
+            ["f16", _, ..] => Command::Find(FindValue::Utf16(rest_of(input), Endian::Little)),
+            ["f16be", _, ..] => Command::Find(FindValue::Utf16(rest_of(input), Endian::Big)),
+            // 近似查找 ff <pattern> [k]
+            ["ff", value] => match parse_bytes(value) {
+                Some(bytes) => Command::Find(FindValue::Fuzzy(bytes, None)),
+                None => Command::Unknown(input.to_string()),
+            },
+            ["ff", value, k] if k.parse::<usize>().is_ok() => match parse_bytes(value) {
+                Some(bytes) => Command::Find(FindValue::Fuzzy(bytes, k.parse().ok())),
+                None => Command::Unknown(input.to_string()),
+            },
+            ["replace", "-c", pattern, replacement] => {
+                Command::parse_replace(input, pattern, replacement, true)
+            }
+            ["replace", pattern, replacement] if *pattern != "-c" => {
+                Command::parse_replace(input, pattern, replacement, false)
             }
             ["cut", count, filepath] if count.parse::<usize>().is_ok() => Command::Cut(CutFile {
                 count: count.parse().unwrap(),
@@ -103,7 +210,60 @@ impl Command {
                     Command::Unknown(input.to_string())
                 }
             }
-            ["call", function] => Command::Call(function.to_string()),
+            ["call", function, args @ ..] => {
+                let mut call_args = Vec::with_capacity(args.len());
+                for arg in args {
+                    match arg.split_once('=') {
+                        Some((k, v)) if !k.is_empty() => {
+                            call_args.push((k.to_string(), v.to_string()))
+                        }
+                        _ => return Command::Unknown(input.to_string()),
+                    }
+                }
+                Command::Call(function.to_string(), call_args)
+            }
+            ["undo"] => Command::Undo(1),
+            ["undo", n] if n.parse::<usize>().is_ok() => Command::Undo(n.parse().unwrap()),
+            ["redo"] => Command::Redo(1),
+            ["redo", n] if n.parse::<usize>().is_ok() => Command::Redo(n.parse().unwrap()),
+            ["pgpage", blkno] if blkno.parse::<usize>().is_ok() => {
+                Command::PgPage(blkno.parse().unwrap(), None)
+            }
+            ["pgpage", blkno, types] if blkno.parse::<usize>().is_ok() => {
+                Command::PgPage(blkno.parse().unwrap(), Some(types.to_string()))
+            }
+            ["pgtuple", types] => Command::PgTuple(types.to_string()),
+            ["pgbtree", blkno] if blkno.parse::<usize>().is_ok() => {
+                Command::PgBtree(blkno.parse().unwrap())
+            }
+            ["pgcheck"] => Command::PgCheck(0),
+            ["pgcheck", segno] if segno.parse::<usize>().is_ok() => {
+                Command::PgCheck(segno.parse().unwrap())
+            }
+            ["wal"] => Command::Wal,
+            ["pgfile"] => Command::PgFile,
+            ["sqlite", "page", pgno] if pgno.parse::<usize>().is_ok() => {
+                Command::SqlitePage(pgno.parse().unwrap())
+            }
+            ["exe"] => Command::Exe(false),
+            ["exe", "sections"] => Command::Exe(true),
+            ["apply", name] => Command::Apply(name.to_string(), None),
+            ["apply", name, addr] if addr.parse::<usize>().is_ok() => {
+                Command::Apply(name.to_string(), Some(addr.parse().unwrap()))
+            }
+            ["mark", name, comment @ ..] => Command::Mark(name.to_string(), comment.join(" ")),
+            ["unmark", name] => Command::Unmark(name.to_string()),
+            ["goto", name] => Command::Goto(name.to_string()),
+            ["marks"] => Command::Marks,
+            ["n"] => Command::NextHit,
+            ["N"] => Command::PrevHit,
+            ["results"] => Command::Results,
+            ["detoast"] => Command::Detoast(None),
+            ["detoast", filepath] => Command::Detoast(Some(filepath.to_string())),
+            ["pgfix"] => Command::PgFix(0),
+            ["pgfix", segno] if segno.parse::<usize>().is_ok() => {
+                Command::PgFix(segno.parse().unwrap())
+            }
             _ => Command::Unknown(input.to_string()),
         }
     }
@@ -127,6 +287,48 @@ mod test {
             Command::parse("f eeee"),
             Command::Find(FindValue::Ascii("eeee".to_string()))
         );
+        assert_eq!(
+            Command::parse("f 4A ?? 0? FF"),
+            Command::Find(FindValue::Masked(vec![
+                (0x4a, 0xff),
+                (0, 0),
+                (0, 0xf0),
+                (0xff, 0xff)
+            ]))
+        );
+        assert_eq!(
+            Command::parse("f 0x4a?f"),
+            Command::Find(FindValue::Masked(vec![(0x4a, 0xff), (0x0f, 0x0f)]))
+        );
+        assert_eq!(
+            Command::parse("f 4a 0f"),
+            Command::Find(FindValue::Hex(vec![0x4a, 0x0f]))
+        );
+        assert!(matches!(Command::parse("f 4a 0"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("f 0x4?g"), Command::Unknown(_)));
+        assert_eq!(
+            Command::parse("fr  PK\\x03\\x04 .{2}"),
+            Command::Find(FindValue::Regex("PK\\x03\\x04 .{2}".to_string()))
+        );
+        assert_eq!(
+            Command::parse("fi Hello World"),
+            Command::Find(FindValue::NoCase("Hello World".to_string()))
+        );
+        assert_eq!(
+            Command::parse("f16be C:\\"),
+            Command::Find(FindValue::Utf16("C:\\".to_string(), Endian::Big))
+        );
+        assert!(matches!(Command::parse("f16"), Command::Unknown(_)));
+        assert_eq!(
+            Command::parse("ff 0x7f454c46 2"),
+            Command::Find(FindValue::Fuzzy(vec![0x7f, 0x45, 0x4c, 0x46], Some(2)))
+        );
+        assert_eq!(
+            Command::parse("ff SQLite"),
+            Command::Find(FindValue::Fuzzy(b"SQLite".to_vec(), None))
+        );
+        assert!(matches!(Command::parse("ff 0x4?"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("ff abc x"), Command::Unknown(_)));
         assert!(matches!(
             Command::parse("unknown command"),
             Command::Unknown(_)
@@ -147,5 +349,108 @@ mod test {
                 filepath: "xxx".to_string()
             })
         );
+        assert_eq!(Command::parse("undo"), Command::Undo(1));
+        assert_eq!(Command::parse("undo 3"), Command::Undo(3));
+        assert_eq!(Command::parse("redo 2"), Command::Redo(2));
+        assert!(matches!(Command::parse("redo x"), Command::Unknown(_)));
+        assert_eq!(Command::parse("nohl"), Command::NoHighlight);
+        assert_eq!(Command::parse("reload"), Command::Reload);
+        assert_eq!(Command::parse("pgpage 3"), Command::PgPage(3, None));
+        assert_eq!(
+            Command::parse("pgpage 3 int4,text"),
+            Command::PgPage(3, Some("int4,text".to_string()))
+        );
+        assert_eq!(
+            Command::parse("pgtuple int4,text"),
+            Command::PgTuple("int4,text".to_string())
+        );
+        assert!(matches!(Command::parse("pgtuple"), Command::Unknown(_)));
+        assert!(matches!(Command::parse("pgpage"), Command::Unknown(_)));
+        assert_eq!(Command::parse("pgbtree 1"), Command::PgBtree(1));
+        assert!(matches!(Command::parse("pgbtree x"), Command::Unknown(_)));
+        assert_eq!(Command::parse("pgcheck"), Command::PgCheck(0));
+        assert_eq!(Command::parse("pgcheck 2"), Command::PgCheck(2));
+        assert_eq!(Command::parse("pgfix"), Command::PgFix(0));
+        assert_eq!(Command::parse("pgfix 1"), Command::PgFix(1));
+        assert_eq!(Command::parse("wal"), Command::Wal);
+        assert_eq!(Command::parse("pgfile"), Command::PgFile);
+        assert_eq!(Command::parse("sqlite page 2"), Command::SqlitePage(2));
+        assert_eq!(Command::parse("exe"), Command::Exe(false));
+        assert_eq!(Command::parse("exe sections"), Command::Exe(true));
+        assert_eq!(
+            Command::parse("apply bmp"),
+            Command::Apply("bmp".to_string(), None)
+        );
+        assert_eq!(
+            Command::parse("apply bmp 1024"),
+            Command::Apply("bmp".to_string(), Some(1024))
+        );
+        assert_eq!(
+            Command::parse("mark hdr file  header"),
+            Command::Mark("hdr".to_string(), "file header".to_string())
+        );
+        assert_eq!(
+            Command::parse("mark hdr"),
+            Command::Mark("hdr".to_string(), String::new())
+        );
+        assert!(matches!(Command::parse("mark"), Command::Unknown(_)));
+        assert_eq!(
+            Command::parse("unmark hdr"),
+            Command::Unmark("hdr".to_string())
+        );
+        assert_eq!(Command::parse("goto hdr"), Command::Goto("hdr".to_string()));
+        assert_eq!(Command::parse("marks"), Command::Marks);
+        assert_eq!(Command::parse("n"), Command::NextHit);
+        assert_eq!(Command::parse("N"), Command::PrevHit);
+        assert_eq!(Command::parse("results"), Command::Results);
+        assert_eq!(
+            Command::parse("replace 0xdead 0xbeef00"),
+            Command::Replace(FindValue::Hex(vec![0xde, 0xad]), vec![0xbe, 0xef, 0], false)
+        );
+        assert_eq!(
+            Command::parse("replace -c foo 0x"),
+            Command::Replace(FindValue::Ascii("foo".to_string()), vec![], true)
+        );
+        assert_eq!(
+            Command::parse("replace 0x4a?? bar"),
+            Command::Replace(
+                FindValue::Masked(vec![(0x4a, 0xff), (0, 0)]),
+                b"bar".to_vec(),
+                false
+            )
+        );
+        assert!(matches!(
+            Command::parse("replace foo 0xzz"),
+            Command::Unknown(_)
+        ));
+        assert!(matches!(Command::parse("replace foo"), Command::Unknown(_)));
+        assert!(matches!(
+            Command::parse("replace -c foo"),
+            Command::Unknown(_)
+        ));
+        assert!(matches!(Command::parse("sqlite page"), Command::Unknown(_)));
+        assert_eq!(Command::parse("detoast"), Command::Detoast(None));
+        assert_eq!(
+            Command::parse("detoast out.bin"),
+            Command::Detoast(Some("out.bin".to_string()))
+        );
+        assert_eq!(
+            Command::parse("call pg_page_header"),
+            Command::Call("pg_page_header".to_string(), vec![])
+        );
+        assert_eq!(
+            Command::parse("call pg_heap_tuple natts=5 endian=big"),
+            Command::Call(
+                "pg_heap_tuple".to_string(),
+                vec![
+                    ("natts".to_string(), "5".to_string()),
+                    ("endian".to_string(), "big".to_string())
+                ]
+            )
+        );
+        assert!(matches!(
+            Command::parse("call pg_heap_tuple natts"),
+            Command::Unknown(_)
+        ));
     }
 }
@@ -0,0 +1,575 @@
+use crate::util::mmap_file;
+use crate::ChapResult;
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::path::Path;
+
+// 相隔不到这么多相同字节的差异合并成一段
+const MERGE_GAP: usize = 8;
+// 对齐模式 重新同步需要连续相同的字节数
+const ANCHOR: usize = 32;
+// 对齐模式 在差异之后这么多字节内寻找重新同步的位置
+const WINDOW: usize = 64 * 1024;
+// 只索引哈希高 3 位为 0 的位置 大约八分之一
+const SAMPLE_SHIFT: u32 = 61;
+const HASH_BASE: u64 = 0x100000001b3;
+const HASH_MIX: u64 = 0x9e3779b97f4a7c15;
+
+// 差异的种类
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DiffKind {
+    Changed,  // 两边都有 内容不同
+    Deleted,  // 只在左边
+    Inserted, // 只在右边
+}
+
+impl DiffKind {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            DiffKind::Changed => "changed",
+            DiffKind::Deleted => "deleted",
+            DiffKind::Inserted => "inserted",
+        }
+    }
+}
+
+// 一段差异 左右两边各自的范围 [start, end)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct DiffRange {
+    pub(crate) left: (usize, usize),
+    pub(crate) right: (usize, usize),
+}
+
+impl DiffRange {
+    pub(crate) fn kind(&self) -> DiffKind {
+        if self.left.0 == self.left.1 {
+            DiffKind::Inserted
+        } else if self.right.0 == self.right.1 {
+            DiffKind::Deleted
+        } else {
+            DiffKind::Changed
+        }
+    }
+
+    // 按 left 选出一边的范围 另一边放在后面
+    fn sides(&self, left: bool) -> ((usize, usize), (usize, usize)) {
+        if left {
+            (self.left, self.right)
+        } else {
+            (self.right, self.left)
+        }
+    }
+
+    // 两边长度相同 可以逐字节比较
+    fn in_place(&self) -> bool {
+        self.left.1 - self.left.0 == self.right.1 - self.right.0
+    }
+}
+
+// 两个文件的全部差异 按偏移排序
+#[derive(Debug, Default)]
+pub(crate) struct Diff {
+    ranges: Vec<DiffRange>,
+    aligned: bool,
+}
+
+// 开头相同的字节数 先按 64 字节比较
+fn common_prefix(a: &[u8], b: &[u8]) -> usize {
+    let n = a.len().min(b.len());
+    let mut i = 0;
+    while i + 64 <= n && a[i..i + 64] == b[i..i + 64] {
+        i += 64;
+    }
+    i + a[i..n]
+        .iter()
+        .zip(&b[i..n])
+        .take_while(|(x, y)| x == y)
+        .count()
+}
+
+// 与上一段之间相同的字节太少时合并
+fn push(ranges: &mut Vec<DiffRange>, left: (usize, usize), right: (usize, usize)) {
+    if let Some(last) = ranges.last_mut() {
+        if left.0 < last.left.1 + MERGE_GAP && right.0 < last.right.1 + MERGE_GAP {
+            last.left.1 = left.1;
+            last.right.1 = right.1;
+            return;
+        }
+    }
+    ranges.push(DiffRange { left, right });
+}
+
+// 逐字节比较相同偏移 较长文件多出的部分是一段删除或插入
+fn diff_bytes(a: &[u8], b: &[u8]) -> Vec<DiffRange> {
+    let mut ranges = Vec::new();
+    let n = a.len().min(b.len());
+    let mut i = 0;
+    while i < n {
+        i += common_prefix(&a[i..n], &b[i..n]);
+        if i >= n {
+            break;
+        }
+        let run = a[i..n]
+            .iter()
+            .zip(&b[i..n])
+            .take_while(|(x, y)| x != y)
+            .count();
+        push(&mut ranges, (i, i + run), (i, i + run));
+        i += run;
+    }
+    if a.len() != b.len() {
+        push(&mut ranges, (n, a.len()), (n, b.len()));
+    }
+    ranges
+}
+
+// 每个 ANCHOR 字节窗口的多项式滚动哈希
+fn rolling(data: &[u8]) -> impl Iterator<Item = (usize, u64)> + '_ {
+    let top = HASH_BASE.wrapping_pow(ANCHOR as u32 - 1);
+    let mut h = data[..ANCHOR].iter().fold(0u64, |h, &x| {
+        h.wrapping_mul(HASH_BASE).wrapping_add(x as u64)
+    });
+    (0..=data.len() - ANCHOR).map(move |i| {
+        if i > 0 {
+            h = h
+                .wrapping_sub((data[i - 1] as u64).wrapping_mul(top))
+                .wrapping_mul(HASH_BASE)
+                .wrapping_add(data[i + ANCHOR - 1] as u64);
+        }
+        (i, h)
+    })
+}
+
+fn sampled(h: u64) -> bool {
+    h.wrapping_mul(HASH_MIX) >> SAMPLE_SHIFT == 0
+}
+
+// 相同偏移处第一次连续 ANCHOR 个字节相同的位置
+fn same_run(a: &[u8], b: &[u8]) -> Option<usize> {
+    let mut run = 0;
+    for (k, (x, y)) in a.iter().zip(b).enumerate() {
+        run = if x == y { run + 1 } else { 0 };
+        if run == ANCHOR {
+            return Some(k + 1 - ANCHOR);
+        }
+    }
+    None
+}
+
+// a 和 b 从第一个不同的字节开始 找到再次相同的位置 (di, dj)
+// 两边移动的较大值最小 相同时原位修改优先于插入删除
+fn resync(a: &[u8], b: &[u8]) -> Option<(usize, usize)> {
+    let wa = a.len().min(WINDOW);
+    let wb = b.len().min(WINDOW);
+    if wa < ANCHOR || wb < ANCHOR {
+        return None;
+    }
+    // 先找原位修改后的同步点 只有两边移动都更少的插入删除才能比它好 只需索引它之前的部分
+    let mut best = same_run(&a[..wa], &b[..wb]).map(|d| (d, d));
+    let (la, lb) = match best {
+        Some((d, _)) => (
+            (d + ANCHOR).saturating_sub(1).min(wa),
+            (d + ANCHOR).saturating_sub(1).min(wb),
+        ),
+        None => (wa, wb),
+    };
+    let cost = |(di, dj): (usize, usize)| di.max(dj);
+    if la >= ANCHOR && lb >= ANCHOR {
+        let mut index: HashMap<u64, usize> = HashMap::new();
+        for (dj, h) in rolling(&b[..lb]) {
+            if sampled(h) {
+                index.entry(h).or_insert(dj);
+            }
+        }
+        for (di, h) in rolling(&a[..la]) {
+            if best.is_some_and(|b| cost(b) <= di) {
+                break;
+            }
+            if !sampled(h) {
+                continue;
+            }
+            if let Some(&dj) = index.get(&h) {
+                if a[di..di + ANCHOR] == b[dj..dj + ANCHOR]
+                    && best.is_none_or(|b| cost((di, dj)) < cost(b))
+                {
+                    best = Some((di, dj));
+                }
+            }
+        }
+    }
+    // 采样的位置可能晚于真正的同步点 向前扩展相同的字节
+    best.map(|(mut di, mut dj)| {
+        while di > 0 && dj > 0 && a[di - 1] == b[dj - 1] {
+            di -= 1;
+            dj -= 1;
+        }
+        (di, dj)
+    })
+}
+
+// 对齐比较 插入或删除一段后重新同步 后面移位的内容不算差异
+fn diff_aligned(a: &[u8], b: &[u8]) -> Vec<DiffRange> {
+    let mut ranges = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    loop {
+        let same = common_prefix(&a[i..], &b[j..]);
+        i += same;
+        j += same;
+        if i == a.len() || j == b.len() {
+            if i < a.len() || j < b.len() {
+                push(&mut ranges, (i, a.len()), (j, b.len()));
+            }
+            break;
+        }
+        let (di, dj) = resync(&a[i..], &b[j..])
+            .unwrap_or(((a.len() - i).min(WINDOW), (b.len() - j).min(WINDOW)));
+        push(&mut ranges, (i, i + di), (j, j + dj));
+        i += di;
+        j += dj;
+    }
+    ranges
+}
+
+impl Diff {
+    pub(crate) fn new(a: &[u8], b: &[u8], aligned: bool) -> Diff {
+        let ranges = if aligned {
+            diff_aligned(a, b)
+        } else {
+            diff_bytes(a, b)
+        };
+        Diff { ranges, aligned }
+    }
+
+    pub(crate) fn ranges(&self) -> &[DiffRange] {
+        &self.ranges
+    }
+
+    pub(crate) fn is_aligned(&self) -> bool {
+        self.aligned
+    }
+
+    // 一边的偏移对应另一边的偏移 差异内按段内偏移对应 插入点对应插入段的开头
+    pub(crate) fn map(&self, off: usize, left: bool) -> usize {
+        let i = self.ranges.partition_point(|r| r.sides(left).0 .0 < off);
+        if let Some(r) = self.ranges.get(i) {
+            let (from, to) = r.sides(left);
+            if from.0 == off {
+                return to.0;
+            }
+        }
+        match i.checked_sub(1).map(|i| self.ranges[i].sides(left)) {
+            Some((from, to)) if off < from.1 => to.0 + (off - from.0).min(to.1 - to.0),
+            Some((from, to)) => off - from.1 + to.1,
+            None => off,
+        }
+    }
+
+    // off 之后或之前最近的差异 返回下标
+    pub(crate) fn next(&self, off: usize, left: bool) -> Option<usize> {
+        let i = self.ranges.partition_point(|r| r.sides(left).0 .0 <= off);
+        (i < self.ranges.len()).then_some(i)
+    }
+
+    pub(crate) fn prev(&self, off: usize, left: bool) -> Option<usize> {
+        self.ranges
+            .partition_point(|r| r.sides(left).0 .0 < off)
+            .checked_sub(1)
+    }
+
+    // 与一边的 [start, end) 相交的差异
+    fn visible(&self, start: usize, end: usize, left: bool) -> &[DiffRange] {
+        let from = self.ranges.partition_point(|r| r.sides(left).0 .1 <= start);
+        let to = self.ranges.partition_point(|r| r.sides(left).0 .0 < end);
+        &self.ranges[from..to.max(from)]
+    }
+
+    // 差异段数和不同的字节数 (两边取较长的)
+    pub(crate) fn summary(&self) -> String {
+        let bytes: usize = self
+            .ranges
+            .iter()
+            .map(|r| (r.left.1 - r.left.0).max(r.right.1 - r.right.0))
+            .sum();
+        format!(
+            "{} ranges, {} bytes differ{}",
+            self.ranges.len(),
+            bytes,
+            if self.aligned { ", aligned" } else { "" }
+        )
+    }
+}
+
+// 并排比较两个文件 光标在两边同步移动
+pub(crate) struct DiffView {
+    left: Mmap,
+    right: Mmap,
+    diff: Diff,
+    cursor: (usize, usize), // 左右两边的光标偏移
+    active_left: bool,      // 左边跟随键盘移动 右边按差异对应
+    current: Option<usize>, // 最近跳到的差异
+}
+
+impl DiffView {
+    pub(crate) fn open(left: &Path, right: &Path, aligned: bool) -> ChapResult<DiffView> {
+        let left = mmap_file(left)?;
+        let right = mmap_file(right)?;
+        let diff = Diff::new(&left, &right, aligned);
+        Ok(DiffView {
+            left,
+            right,
+            diff,
+            cursor: (0, 0),
+            active_left: true,
+            current: None,
+        })
+    }
+
+    pub(crate) fn diff(&self) -> &Diff {
+        &self.diff
+    }
+
+    pub(crate) fn cursor(&self, left: bool) -> usize {
+        if left {
+            self.cursor.0
+        } else {
+            self.cursor.1
+        }
+    }
+
+    // 有焦点一边的光标
+    pub(crate) fn active_cursor(&self) -> usize {
+        self.cursor(self.active_left)
+    }
+
+    pub(crate) fn is_active(&self, left: bool) -> bool {
+        self.active_left == left
+    }
+
+    pub(crate) fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    pub(crate) fn size(&self, left: bool) -> usize {
+        if left {
+            self.left.len()
+        } else {
+            self.right.len()
+        }
+    }
+
+    // 移动有焦点一边的光标 另一边跟随
+    pub(crate) fn move_to(&mut self, off: usize) {
+        let left = self.active_left;
+        let off = off.min(self.size(left).saturating_sub(1));
+        let other = self
+            .diff
+            .map(off, left)
+            .min(self.size(!left).saturating_sub(1));
+        self.cursor = if left { (off, other) } else { (other, off) };
+    }
+
+    pub(crate) fn move_by(&mut self, delta: isize) {
+        let off = self.active_cursor().saturating_add_signed(delta);
+        self.move_to(off);
+    }
+
+    pub(crate) fn switch_side(&mut self) {
+        self.active_left = !self.active_left;
+    }
+
+    // 切换逐字节和对齐比较 重新计算差异
+    pub(crate) fn toggle_aligned(&mut self) {
+        self.diff = Diff::new(&self.left, &self.right, !self.diff.is_aligned());
+        self.current = None;
+        self.move_to(self.active_cursor());
+    }
+
+    // 跳到下一个或上一个差异 两边都停在差异开头 到头后从另一头继续
+    pub(crate) fn goto_range(&mut self, forward: bool) -> String {
+        let total = self.diff.ranges.len();
+        if total == 0 {
+            return "files are identical".to_string();
+        }
+        let left = self.active_left;
+        let off = self.cursor(left);
+        let (i, wrapped) = if forward {
+            self.diff.next(off, left).map_or((0, true), |i| (i, false))
+        } else {
+            self.diff
+                .prev(off, left)
+                .map_or((total - 1, true), |i| (i, false))
+        };
+        let r = &self.diff.ranges[i];
+        self.cursor = (
+            r.left.0.min(self.left.len().saturating_sub(1)),
+            r.right.0.min(self.right.len().saturating_sub(1)),
+        );
+        self.current = Some(i);
+        format!(
+            "diff {} of {}: {}{}",
+            i + 1,
+            total,
+            r.kind().name(),
+            if wrapped { ", wrapped" } else { "" }
+        )
+    }
+
+    // 一边 [start, end) 中要着色的字节 (start, end, 种类) 原位修改只标出不同的字节
+    pub(crate) fn marks(
+        &self,
+        start: usize,
+        end: usize,
+        left: bool,
+    ) -> Vec<(usize, usize, DiffKind)> {
+        let (this, other) = if left {
+            (&self.left, &self.right)
+        } else {
+            (&self.right, &self.left)
+        };
+        let mut marks = Vec::new();
+        for r in self.diff.visible(start, end, left) {
+            let (from, to) = r.sides(left);
+            let (lo, hi) = (from.0.max(start), from.1.min(end));
+            if lo >= hi {
+                continue;
+            }
+            if !r.in_place() {
+                marks.push((lo, hi, r.kind()));
+                continue;
+            }
+            let mut run: Option<usize> = None;
+            for pos in lo..hi {
+                let differs = this[pos] != other[pos - from.0 + to.0];
+                match (differs, run) {
+                    (true, None) => run = Some(pos),
+                    (false, Some(s)) => {
+                        marks.push((s, pos, DiffKind::Changed));
+                        run = None;
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(s) = run {
+                marks.push((s, hi, DiffKind::Changed));
+            }
+        }
+        marks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试用的伪随机字节
+    fn noise(n: usize, seed: u64) -> Vec<u8> {
+        let mut x = seed;
+        (0..n)
+            .map(|_| {
+                x = x
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (x >> 33) as u8
+            })
+            .collect()
+    }
+
+    fn pairs(diff: &Diff) -> Vec<((usize, usize), (usize, usize))> {
+        diff.ranges().iter().map(|r| (r.left, r.right)).collect()
+    }
+
+    #[test]
+    fn test_diff_bytes() {
+        let a = noise(1000, 1);
+        let mut b = a.clone();
+        b[10] ^= 1;
+        b[12] ^= 1; // 与上一处相隔太近 合并
+        b[500..504].iter_mut().for_each(|x| *x = !*x);
+        b.extend_from_slice(b"tail");
+        let diff = Diff::new(&a, &b, false);
+        assert_eq!(
+            pairs(&diff),
+            [
+                ((10, 13), (10, 13)),
+                ((500, 504), (500, 504)),
+                ((1000, 1000), (1000, 1004))
+            ]
+        );
+        assert_eq!(diff.ranges()[2].kind(), DiffKind::Inserted);
+        assert_eq!(diff.summary(), "3 ranges, 11 bytes differ");
+        assert!(Diff::new(&a, &a, false).ranges().is_empty());
+    }
+
+    #[test]
+    fn test_diff_aligned() {
+        let a = noise(20000, 2);
+        // 插入一块 修改几个字节 再删除一块
+        let mut b = a[..4096].to_vec();
+        b.extend_from_slice(&noise(300, 3));
+        b.extend_from_slice(&a[4096..9000]);
+        b.extend_from_slice(b"ZZ");
+        b.extend_from_slice(&a[9002..15000]);
+        b.extend_from_slice(&a[16000..]);
+        let diff = Diff::new(&a, &b, true);
+        assert_eq!(
+            pairs(&diff),
+            [
+                ((4096, 4096), (4096, 4396)),
+                ((9000, 9002), (9300, 9302)),
+                ((15000, 16000), (15300, 15300)),
+            ]
+        );
+        assert_eq!(diff.ranges()[2].kind(), DiffKind::Deleted);
+        // 逐字节比较时插入之后全部不同
+        assert_eq!(Diff::new(&a, &b, false).ranges().len(), 1);
+
+        // 对应的偏移
+        assert_eq!(diff.map(100, true), 100);
+        assert_eq!(diff.map(4096, true), 4096);
+        assert_eq!(diff.map(5000, true), 5300);
+        assert_eq!(diff.map(4200, false), 4096);
+        assert_eq!(diff.map(15500, true), 15300);
+        assert_eq!(diff.map(17000, true), 16300);
+        assert_eq!(diff.next(4096, true), Some(1));
+        assert_eq!(diff.prev(4096, true), None);
+        assert_eq!(diff.next(9300, false), Some(2));
+    }
+
+    #[test]
+    fn test_diff_view() {
+        let dir = std::env::temp_dir();
+        let (pa, pb) = (
+            dir.join("chap_test_diff_a.bin"),
+            dir.join("chap_test_diff_b.bin"),
+        );
+        let a = noise(256, 4);
+        let mut b = a.clone();
+        b[20] = !b[20];
+        b[22] = !b[22];
+        b.truncate(200);
+        std::fs::write(&pa, &a).unwrap();
+        std::fs::write(&pb, &b).unwrap();
+        let mut view = DiffView::open(&pa, &pb, false).unwrap();
+        // 原位修改只标出不同的字节
+        assert_eq!(
+            view.marks(0, 32, true),
+            [(20, 21, DiffKind::Changed), (22, 23, DiffKind::Changed)]
+        );
+        assert_eq!(view.marks(192, 256, true), [(200, 256, DiffKind::Deleted)]);
+        assert!(view.marks(192, 256, false).is_empty());
+
+        assert_eq!(view.goto_range(true), "diff 1 of 2: changed");
+        assert_eq!(view.goto_range(true), "diff 2 of 2: deleted");
+        assert_eq!((view.cursor(true), view.cursor(false)), (200, 199));
+        assert_eq!(view.goto_range(true), "diff 1 of 2: changed, wrapped");
+        view.move_by(1000);
+        assert_eq!((view.cursor(true), view.cursor(false)), (255, 199));
+        view.switch_side();
+        view.move_by(-100);
+        assert_eq!((view.cursor(true), view.cursor(false)), (99, 99));
+        view.toggle_aligned();
+        assert!(view.diff().is_aligned());
+        let _ = std::fs::remove_file(&pa);
+        let _ = std::fs::remove_file(&pb);
+    }
+}
@@ -1,8 +1,13 @@
 use crate::error::ChapError;
-use crate::fuzzy::boyermoore::BoyerMoore;
 use crate::gap_buffer::GapBytes;
 use crate::gap_buffer::GapBytesCharIter;
+use crate::history::ByteEdit;
+use crate::history::EditOp;
+use crate::history::History;
+use crate::history::Splice;
 use crate::mmap_file;
+use crate::plugin::ByteSource;
+use crate::search::SearchPattern;
 use crate::tui::TextSelect;
 use crate::util;
 use crate::{error::ChapResult, gap_buffer::GapBuffer};
@@ -82,9 +87,43 @@ pub(crate) trait TextOper {
 
     fn get_text_from_sel(&self, sel: &TextSelect) -> Vec<u8>;
 
-    fn find(&self, pattern: &[u8], line_file_start: usize) -> Option<usize>;
+    // 返回匹配相对于 line_file_start 的偏移和长度
+    fn find(&self, pattern: &SearchPattern, line_file_start: usize) -> Option<(usize, usize)>;
 
     fn get_file_size(&self) -> usize;
+
+    //覆盖写入字节
+    fn overwrite_bytes(&self, offset: usize, bytes: &[u8]) -> ChapResult<()>;
+
+    //插入字节
+    fn insert_bytes(&self, offset: usize, bytes: &[u8]) -> ChapResult<()>;
+
+    //删除字节
+    fn delete_bytes(&self, offset: usize, len: usize) -> ChapResult<()>;
+
+    //把多个不重叠的范围 (偏移, 长度) 替换为 bytes 作为一步撤销
+    fn replace_bytes(&self, ranges: &[(usize, usize)], bytes: &[u8]) -> ChapResult<()>;
+
+    //撤销n步 返回光标所在偏移
+    fn undo(&self, n: usize) -> ChapResult<Option<usize>>;
+
+    //重做n步 返回光标所在偏移
+    fn redo(&self, n: usize) -> ChapResult<Option<usize>>;
+
+    //光标移动后 后续键入不再和之前的合并撤销
+    fn end_typing(&self);
+
+    //文本编辑模式下 偏移转换为 (行号, 列)
+    fn text_position(&self, offset: usize) -> (usize, usize);
+
+    //编辑次数 内容改变后增加
+    fn version(&self) -> usize;
+
+    //取走上次之后插入删除造成的偏移变化
+    fn take_splices(&self) -> Vec<Splice>;
+
+    //当前内容的快照 只支持16进制模式
+    fn snapshot(&self) -> ChapResult<HexSnapshot>;
 }
 
 pub(crate) enum TextDisplay {
@@ -102,7 +141,7 @@ impl TextOper for TextDisplay {
         }
     }
 
-    fn find(&self, pattern: &[u8], line_file_start: usize) -> Option<usize> {
+    fn find(&self, pattern: &SearchPattern, line_file_start: usize) -> Option<(usize, usize)> {
         match self {
             TextDisplay::Text(v) => v.find(pattern, line_file_start),
             TextDisplay::Hex(v) => v.find(pattern, line_file_start),
@@ -164,7 +203,7 @@ impl TextOper for TextDisplay {
     fn save<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()> {
         match self {
             TextDisplay::Text(v) => Ok(()),
-            TextDisplay::Hex(v) => Ok(()),
+            TextDisplay::Hex(v) => v.save(filepath),
             TextDisplay::Edit(v) => v.save(filepath),
         }
     }
@@ -216,6 +255,108 @@ impl TextOper for TextDisplay {
             TextDisplay::Edit(v) => todo!("Not implement get_text_from_sel for EditTextWarp"),
         }
     }
+
+    fn overwrite_bytes(&self, offset: usize, bytes: &[u8]) -> ChapResult<()> {
+        match self {
+            TextDisplay::Text(v) => Ok(()),
+            TextDisplay::Hex(v) => v.overwrite_bytes(offset, bytes),
+            TextDisplay::Edit(v) => Ok(()),
+        }
+    }
+
+    fn insert_bytes(&self, offset: usize, bytes: &[u8]) -> ChapResult<()> {
+        match self {
+            TextDisplay::Text(v) => Ok(()),
+            TextDisplay::Hex(v) => v.insert_bytes(offset, bytes),
+            TextDisplay::Edit(v) => Ok(()),
+        }
+    }
+
+    fn delete_bytes(&self, offset: usize, len: usize) -> ChapResult<()> {
+        match self {
+            TextDisplay::Text(v) => Ok(()),
+            TextDisplay::Hex(v) => v.delete_bytes(offset, len),
+            TextDisplay::Edit(v) => Ok(()),
+        }
+    }
+
+    fn replace_bytes(&self, ranges: &[(usize, usize)], bytes: &[u8]) -> ChapResult<()> {
+        match self {
+            TextDisplay::Text(v) => Ok(()),
+            TextDisplay::Hex(v) => v.replace_bytes(ranges, bytes),
+            TextDisplay::Edit(v) => Ok(()),
+        }
+    }
+
+    fn undo(&self, n: usize) -> ChapResult<Option<usize>> {
+        match self {
+            TextDisplay::Text(v) => Ok(None),
+            TextDisplay::Hex(v) => v.undo(n),
+            TextDisplay::Edit(v) => v.undo(n),
+        }
+    }
+
+    fn redo(&self, n: usize) -> ChapResult<Option<usize>> {
+        match self {
+            TextDisplay::Text(v) => Ok(None),
+            TextDisplay::Hex(v) => v.redo(n),
+            TextDisplay::Edit(v) => v.redo(n),
+        }
+    }
+
+    fn end_typing(&self) {
+        match self {
+            TextDisplay::Text(v) => {}
+            TextDisplay::Hex(v) => v.end_typing(),
+            TextDisplay::Edit(v) => v.end_typing(),
+        }
+    }
+
+    fn text_position(&self, offset: usize) -> (usize, usize) {
+        match self {
+            TextDisplay::Edit(v) => v.position(offset),
+            _ => (0, offset),
+        }
+    }
+
+    fn version(&self) -> usize {
+        match self {
+            TextDisplay::Text(v) => 0,
+            TextDisplay::Hex(v) => v.version(),
+            TextDisplay::Edit(v) => v.version(),
+        }
+    }
+
+    fn take_splices(&self) -> Vec<Splice> {
+        match self {
+            TextDisplay::Text(v) => Vec::new(),
+            TextDisplay::Hex(v) => v.take_splices(),
+            TextDisplay::Edit(v) => v.take_splices(),
+        }
+    }
+
+    fn snapshot(&self) -> ChapResult<HexSnapshot> {
+        match self {
+            TextDisplay::Hex(v) => v.snapshot(),
+            _ => Err("snapshot only in hex mode".into()),
+        }
+    }
+}
+
+impl ByteSource for TextDisplay {
+    fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+        match self {
+            TextDisplay::Hex(v) => v.read_bytes(offset, len),
+            _ => Vec::new(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            TextDisplay::Hex(v) => v.get_file_size(),
+            _ => 0,
+        }
+    }
 }
 
 #[derive(Debug, Default)]
@@ -315,7 +456,7 @@ impl<T> RingVec<T> {
 
     //删除第最后元素 并返回这个元素
     pub(crate) fn remove_last(&mut self) -> Option<T> {
-        self.remove(self.cache.len() - 1)
+        self.remove(self.cache.len().saturating_sub(1))
     }
 
     //删除第n个元素 并返回这个元素
@@ -380,6 +521,14 @@ impl<T> RingVec<T> {
         Some(&self.cache[idx])
     }
 
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        if index >= self.cache.len() {
+            return None;
+        }
+        let idx = (self.start + index) % self.cache.len();
+        Some(&mut self.cache[idx])
+    }
+
     pub(crate) fn clear(&mut self) {
         self.cache.clear();
         self.start = 0;
@@ -665,10 +814,29 @@ trait TextIndex {
     fn set_page_offset(&mut self, page_num: usize, page_offset: PageOffset);
 }
 
-pub(crate) trait EditText {
-    fn insert(&mut self, cursor_y: usize, cursor_x: usize, line_meta: &EditLineMeta, c: char);
-    fn insert_newline(&mut self, cursor_y: usize, cursor_x: usize, line_meta: &EditLineMeta);
-    fn backspace(&mut self, cursor_y: usize, cursor_x: usize, line_meta: &EditLineMeta);
+// 编辑操作返回执行的 EditOp 用于记录历史
+pub(crate) trait EditText: ByteEdit {
+    fn insert(
+        &mut self,
+        cursor_y: usize,
+        cursor_x: usize,
+        line_meta: &EditLineMeta,
+        c: char,
+    ) -> Option<EditOp>;
+    // 偏移转换为 (行号, 列)
+    fn position(&self, offset: usize) -> (usize, usize);
+    fn insert_newline(
+        &mut self,
+        cursor_y: usize,
+        cursor_x: usize,
+        line_meta: &EditLineMeta,
+    ) -> Option<EditOp>;
+    fn backspace(
+        &mut self,
+        cursor_y: usize,
+        cursor_x: usize,
+        line_meta: &EditLineMeta,
+    ) -> Option<EditOp>;
     fn save<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()>;
 }
 
@@ -810,12 +978,25 @@ pub struct FileIoTextIter<'a> {
 #[derive(Clone)]
 pub(crate) struct Chunk {
     buffer: GapBuffer,
-    file_start: usize,
+    file_start: usize, //块在编辑后文件中的起始位置
     file_end: usize,
+    phys_start: usize, //块在磁盘原文件中的起始位置
+    phys_end: usize,
     is_modified: bool,
 }
 
 impl Chunk {
+    fn empty() -> Chunk {
+        Chunk {
+            buffer: GapBuffer::new(0),
+            file_start: 0,
+            file_end: 0,
+            phys_start: 0,
+            phys_end: 0,
+            is_modified: false,
+        }
+    }
+
     fn text(&self, range: impl RangeBounds<usize>) -> GapBytes<'_> {
         let start = match range.start_bound() {
             std::ops::Bound::Included(&start) => start,
@@ -835,16 +1016,47 @@ impl Chunk {
     fn text_len(&self) -> usize {
         self.buffer.text_len()
     }
+
+    // 块相对原文件的长度变化
+    fn len_delta(&self) -> isize {
+        self.text_len() as isize - (self.phys_end - self.phys_start) as isize
+    }
+
+    fn contains(&self, pos: usize) -> bool {
+        pos >= self.file_start && pos < self.file_end
+    }
 }
 
 const CHUNK_NUM: usize = 5;
 
+// 备份文件名 .{name}.chap
+fn get_backup_name<P: AsRef<Path>>(filepath: P) -> ChapResult<PathBuf> {
+    let mut path = filepath.as_ref().to_path_buf();
+    if let Some(file_name) = path.file_name() {
+        path.set_file_name(format!(".{}.{}", file_name.to_string_lossy(), "chap"));
+    }
+    Ok(path)
+}
+
+// 用备份文件替换原文件 保留原文件权限
+fn rename_backup<P1: AsRef<Path>, P2: AsRef<Path>>(
+    filepath: P1,
+    backup_name: P2,
+) -> ChapResult<()> {
+    if let Ok(meta) = fs::metadata(&filepath) {
+        fs::set_permissions(&backup_name, meta.permissions())?;
+    }
+    fs::rename(backup_name, filepath)?;
+    Ok(())
+}
+
 pub(crate) struct HexText {
     chunks: RingVec<Chunk>,
     chk_iter: Chunk,
     file: File,
-    cache: HashMap<usize, Chunk>,
-    file_size: usize,
+    cache: HashMap<usize, Chunk>, //被换出的已修改块 key为phys_start
+    file_size: usize,             //编辑后的文件大小
+    phys_size: usize,             //磁盘原文件大小
     height: usize,
 }
 
@@ -853,192 +1065,490 @@ impl HexText {
         filename: P,
         height: usize,
     ) -> ChapResult<HexText> {
-        let mut file = File::open(filename)?;
+        let file = File::open(filename)?;
         let file_size = file.metadata()?.len() as usize;
-        let mut chunks = RingVec::new(CHUNK_NUM);
-
-        let mut buf = [0u8; CHUNK_SIZE];
-        let mut bytes_start = 0;
-        for _ in 0..CHUNK_NUM {
-            let mut buffer = GapBuffer::new(CHUNK_SIZE + HEX_GAP_SIZE);
-            let mut bytes_read = 0;
-            while bytes_read < CHUNK_SIZE {
-                let n = file.read(&mut buf)?;
-                if n == 0 {
-                    //跳出 for 循环
-                    break;
-                }
-                bytes_read += n;
-                buffer.insert(buffer.text_len(), &buf[..n]);
-            }
-            if bytes_read == 0 {
-                break;
-            }
-            chunks.push(Chunk {
-                buffer: buffer,
-                file_start: bytes_start,
-                file_end: bytes_start + bytes_read,
-                is_modified: false,
-            });
-            bytes_start += bytes_read;
-        }
-        let chk_iter = chunks
-            .get(0)
-            .unwrap_or(&Chunk {
-                buffer: GapBuffer::new(0),
-                file_start: 0,
-                file_end: 0,
-                is_modified: false,
-            })
-            .clone();
-        Ok(HexText {
-            chunks: chunks,
-            chk_iter: chk_iter,
+        let mut hex_text = HexText {
+            chunks: RingVec::new(CHUNK_NUM),
+            chk_iter: Chunk::empty(),
             file,
             cache: HashMap::new(),
             file_size,
+            phys_size: file_size,
             height: height, // 初始高度为0，可以根据需要设置
-        })
+        };
+        hex_text.read_chunks(0)?;
+        hex_text.chk_iter = hex_text.chunks.get(0).unwrap_or(&Chunk::empty()).clone();
+        Ok(hex_text)
     }
 
     pub(crate) fn get_file_size(&self) -> usize {
         self.file_size
     }
 
-    pub(crate) fn reset_chunks(&mut self, line_file_start: usize) {
-        let n = (self.file_size + CHUNK_SIZE - 1) / CHUNK_SIZE;
-        let last_chunk_address = (n - CHUNK_NUM) * CHUNK_SIZE;
-        //如果没有找到块 从新重读chunks
-        //通过line_file_start 计算在哪一个块 每个块的大小是 CHUNK_SIZE
-        let chunk_start = (line_file_start / CHUNK_SIZE * CHUNK_SIZE).min(last_chunk_address);
-        self.read_chunks(chunk_start).unwrap();
+    // 原文件 phys_start 之前所有修改块带来的偏移
+    fn shift_before(&self, phys_start: usize) -> isize {
+        self.chunks
+            .iter()
+            .chain(self.cache.values())
+            .filter(|c| c.is_modified && c.phys_start < phys_start)
+            .map(|c| c.len_delta())
+            .sum()
     }
 
-    pub(crate) fn read_one_chunk(&mut self, chunk_seek: usize) -> ChapResult<Chunk> {
+    // 计算编辑后文件位置 pos 所在块在原文件中的起始位置
+    fn locate_phys(&self, pos: usize) -> usize {
+        let mut dirty: Vec<&Chunk> = self
+            .chunks
+            .iter()
+            .chain(self.cache.values())
+            .filter(|c| c.is_modified)
+            .collect();
+        dirty.sort_by_key(|c| c.phys_start);
+        let mut delta: isize = 0;
+        for c in dirty {
+            let start = (c.phys_start as isize + delta) as usize;
+            if pos < start {
+                break;
+            }
+            if pos < start + c.text_len() {
+                return c.phys_start;
+            }
+            delta += c.len_delta();
+        }
+        let phys = (pos as isize - delta).max(0) as usize;
+        phys / CHUNK_SIZE * CHUNK_SIZE
+    }
+
+    // 从磁盘读取原文件 phys_start 处的块
+    fn read_phys_chunk(&mut self, phys_start: usize) -> ChapResult<Option<Chunk>> {
+        if phys_start >= self.phys_size {
+            return Ok(None);
+        }
         self.file
-            .seek(std::io::SeekFrom::Start(chunk_seek as u64))?;
+            .seek(std::io::SeekFrom::Start(phys_start as u64))?;
         let mut buf = [0u8; CHUNK_SIZE];
-        let bytes_start = chunk_seek;
         let mut buffer = GapBuffer::new(CHUNK_SIZE + HEX_GAP_SIZE);
         let mut bytes_read = 0;
         while bytes_read < CHUNK_SIZE {
-            let n = self.file.read(&mut buf)?;
+            let n = self.file.read(&mut buf[..CHUNK_SIZE - bytes_read])?;
             if n == 0 {
-                //跳出 for 循环
                 break;
             }
             bytes_read += n;
             buffer.insert(buffer.text_len(), &buf[..n]);
         }
         if bytes_read == 0 {
-            return Err(ChapError::Unexpected("No data read from file".to_string()).into());
+            return Ok(None);
         }
-        return Ok(Chunk {
+        let file_start = (phys_start as isize + self.shift_before(phys_start)) as usize;
+        Ok(Some(Chunk {
             buffer: buffer,
-            file_start: bytes_start,
-            file_end: bytes_start + bytes_read,
+            file_start: file_start,
+            file_end: file_start + bytes_read,
+            phys_start: phys_start,
+            phys_end: phys_start + bytes_read,
             is_modified: false,
-        });
+        }))
     }
 
-    pub(crate) fn read_chunks(&mut self, chunk_seek: usize) -> ChapResult<()> {
-        self.file
-            .seek(std::io::SeekFrom::Start(chunk_seek as u64))?;
-        let mut chunks = RingVec::new(CHUNK_NUM);
+    // 加载块 优先取出缓存中被修改过的块
+    fn take_chunk(&mut self, phys_start: usize) -> ChapResult<Option<Chunk>> {
+        if let Some(mut c) = self.cache.remove(&phys_start) {
+            c.file_start = (phys_start as isize + self.shift_before(phys_start)) as usize;
+            c.file_end = c.file_start + c.text_len();
+            return Ok(Some(c));
+        }
+        self.read_phys_chunk(phys_start)
+    }
 
-        let mut buf = [0u8; CHUNK_SIZE];
-        let mut bytes_start = chunk_seek;
+    // 把修改过的块放入缓存
+    fn stash_chunk(&mut self, c: Chunk) {
+        if c.is_modified {
+            self.cache.insert(c.phys_start, c);
+        }
+    }
+
+    pub(crate) fn reset_chunks(&mut self, line_file_start: usize) {
+        let n = (self.phys_size + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let last_chunk_address = n.saturating_sub(CHUNK_NUM) * CHUNK_SIZE;
+        //如果没有找到块 从新重读chunks
+        //通过line_file_start 计算在哪一个块 每个块的大小是 CHUNK_SIZE
+        let chunk_start = self.locate_phys(line_file_start).min(last_chunk_address);
+        self.read_chunks(chunk_start).unwrap();
+    }
+
+    // 读取一个块的副本 不改变已加载的块
+    pub(crate) fn read_one_chunk(&mut self, phys_start: usize) -> ChapResult<Chunk> {
+        if let Some(c) = self.chunks.iter().find(|c| c.phys_start == phys_start) {
+            return Ok(c.clone());
+        }
+        if let Some(c) = self.cache.get(&phys_start) {
+            let mut c = c.clone();
+            c.file_start = (phys_start as isize + self.shift_before(phys_start)) as usize;
+            c.file_end = c.file_start + c.text_len();
+            return Ok(c);
+        }
+        self.read_phys_chunk(phys_start)?
+            .ok_or_else(|| ChapError::Unexpected("No data read from file".to_string()))
+    }
+
+    pub(crate) fn read_chunks(&mut self, chunk_seek: usize) -> ChapResult<()> {
+        let mut old = std::mem::replace(&mut self.chunks, RingVec::new(CHUNK_NUM));
+        while let Some(c) = old.remove(0) {
+            self.stash_chunk(c);
+        }
+        let mut phys_seek = chunk_seek;
         for _ in 0..CHUNK_NUM {
-            let mut buffer = GapBuffer::new(CHUNK_SIZE + HEX_GAP_SIZE);
-            let mut bytes_read = 0;
-            while bytes_read < CHUNK_SIZE {
-                let n = self.file.read(&mut buf)?;
-                if n == 0 {
-                    //跳出 for 循环
-                    break;
+            match self.take_chunk(phys_seek)? {
+                Some(c) => {
+                    phys_seek = c.phys_end;
+                    self.chunks.push(c);
                 }
-                bytes_read += n;
-                buffer.insert(buffer.text_len(), &buf[..n]);
+                None => break,
             }
-            if bytes_read == 0 {
-                break;
-            }
-            chunks.push(Chunk {
-                buffer: buffer,
-                file_start: bytes_start,
-                file_end: bytes_start + bytes_read,
-                is_modified: false,
-            });
-            bytes_start += bytes_read;
-        }
-        self.chunks = chunks;
+        }
         return Ok(());
     }
 
-    pub(crate) fn read_last_chunk(&mut self, file_seek: usize) -> ChapResult<()> {
-        if file_seek >= self.file_size {
+    pub(crate) fn read_last_chunk(&mut self, phys_seek: usize) -> ChapResult<()> {
+        let chunk = match self.take_chunk(phys_seek)? {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        //弹出最后一个块
+        if let Some(c) = self.chunks.remove_last() {
+            self.stash_chunk(c);
+        }
+        self.chunks.push_front(chunk);
+        Ok(())
+    }
+
+    pub(crate) fn read_next_chunk(&mut self, phys_seek: usize) -> ChapResult<()> {
+        let chunk = match self.take_chunk(phys_seek)? {
+            Some(c) => c,
+            None => return Ok(()),
+        };
+        //弹出第一个块
+        if let Some(c) = self.chunks.remove(0) {
+            self.stash_chunk(c);
+        }
+        self.chunks.push(chunk);
+        Ok(())
+    }
+
+    // 找到包含 pos 的块 没有则重新加载
+    fn chunk_index_at(&mut self, pos: usize) -> Option<usize> {
+        if let Some(i) = self.chunks.iter().position(|c| c.contains(pos)) {
+            return Some(i);
+        }
+        self.reset_chunks(pos);
+        self.chunks.iter().position(|c| c.contains(pos))
+    }
+
+    // 块长度变化后 修正后面块的位置和文件大小
+    fn shift_chunks_after(&mut self, index: usize, delta: isize) {
+        for i in index + 1..self.chunks.len() {
+            let c = self.chunks.get_mut(i).unwrap();
+            c.file_start = (c.file_start as isize + delta) as usize;
+            c.file_end = (c.file_end as isize + delta) as usize;
+        }
+        self.file_size = (self.file_size as isize + delta) as usize;
+    }
+
+    //覆盖写入
+    pub(crate) fn overwrite(&mut self, pos: usize, bytes: &[u8]) -> ChapResult<()> {
+        if pos + bytes.len() > self.file_size {
+            return Err(ChapError::Unexpected(format!(
+                "overwrite out of range: {}",
+                pos + bytes.len()
+            )));
+        }
+        let mut written = 0;
+        while written < bytes.len() {
+            let p = pos + written;
+            let i = self
+                .chunk_index_at(p)
+                .ok_or_else(|| ChapError::Unexpected(format!("not found chunk at {}", p)))?;
+            let c = self.chunks.get_mut(i).unwrap();
+            let n = (bytes.len() - written).min(c.file_end - p);
+            c.buffer
+                .replace(p - c.file_start, &bytes[written..written + n]);
+            c.is_modified = true;
+            written += n;
+        }
+        Ok(())
+    }
+
+    //在 pos 处插入 后面的字节后移
+    pub(crate) fn insert(&mut self, pos: usize, bytes: &[u8]) -> ChapResult<()> {
+        if pos > self.file_size {
+            return Err(ChapError::Unexpected(format!(
+                "insert out of range: {}",
+                pos
+            )));
+        }
+        if bytes.is_empty() {
             return Ok(());
         }
-        self.file.seek(std::io::SeekFrom::Start(file_seek as u64))?;
-        let mut buffer = GapBuffer::new(CHUNK_SIZE + HEX_GAP_SIZE);
-        let mut bytes_read = 0;
-        let mut buf = [0u8; 1024];
-        while bytes_read < CHUNK_SIZE {
-            let n = self.file.read(&mut buf)?;
-            if n == 0 {
-                break;
-            }
-            bytes_read += n;
-            buffer.insert(buffer.text_len(), &buf[..n]);
+        if self.chunks.is_empty() {
+            //空文件
+            self.chunks.push(Chunk::empty());
         }
+        //在文件末尾追加 写入最后一个块
+        let i = if pos == self.file_size && pos > 0 {
+            self.chunk_index_at(pos - 1)
+        } else if pos == self.file_size {
+            Some(0)
+        } else {
+            self.chunk_index_at(pos)
+        }
+        .ok_or_else(|| ChapError::Unexpected(format!("not found chunk at {}", pos)))?;
+        let c = self.chunks.get_mut(i).unwrap();
+        c.buffer.insert(pos - c.file_start, bytes);
+        c.file_end += bytes.len();
+        c.is_modified = true;
+        self.shift_chunks_after(i, bytes.len() as isize);
+        Ok(())
+    }
 
-        //弹出最后一个块
-        let chunk2 = self.chunks.remove_last();
-        if let Some(c) = chunk2 {
-            if c.is_modified {
-                self.cache.insert(c.file_start, c);
+    // 读取 pos 开始的 len 个字节 只复制需要的部分 不加载整块
+    pub(crate) fn read_bytes(&mut self, pos: usize, len: usize) -> Vec<u8> {
+        let end = pos.saturating_add(len).min(self.file_size);
+        let mut buf = Vec::with_capacity(end.saturating_sub(pos));
+        let mut p = pos;
+        while p < end {
+            let phys = self.locate_phys(p);
+            let file_start = (phys as isize + self.shift_before(phys)) as usize;
+            let c = self
+                .chunks
+                .iter()
+                .find(|c| c.phys_start == phys)
+                .or_else(|| self.cache.get(&phys));
+            let to = match c {
+                Some(c) => {
+                    let to = end.min(file_start + c.text_len());
+                    if to <= p {
+                        break;
+                    }
+                    let t = c.buffer.text(p - file_start..to - file_start);
+                    buf.extend_from_slice(t.left());
+                    buf.extend_from_slice(t.right());
+                    to
+                }
+                None => {
+                    //未修改的块 直接从磁盘读取
+                    let phys_end = (phys + CHUNK_SIZE).min(self.phys_size);
+                    let to = end.min(file_start + phys_end.saturating_sub(phys));
+                    if to <= p {
+                        break;
+                    }
+                    let n = buf.len();
+                    buf.resize(n + to - p, 0);
+                    let seek = (phys + p - file_start) as u64;
+                    if self.file.seek(std::io::SeekFrom::Start(seek)).is_err()
+                        || self.file.read_exact(&mut buf[n..]).is_err()
+                    {
+                        buf.truncate(n);
+                        break;
+                    }
+                    to
+                }
+            };
+            p = to;
+        }
+        buf
+    }
+
+    pub(crate) fn is_modified(&self) -> bool {
+        self.chunks.iter().any(|c| c.is_modified) || !self.cache.is_empty()
+    }
+
+    // 保存修改 长度不变时原地写回 否则写临时文件后替换原文件
+    pub(crate) fn save_file<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()> {
+        if !self.is_modified() {
+            return Ok(());
+        }
+        let resized = self.file_size != self.phys_size
+            || self
+                .chunks
+                .iter()
+                .chain(self.cache.values())
+                .any(|c| c.is_modified && c.len_delta() != 0);
+        if resized {
+            let backup_name = get_backup_name(&filepath)?;
+            self.make_backup(&backup_name)?;
+            rename_backup(&filepath, &backup_name)?;
+        } else {
+            self.write_in_place(&filepath)?;
+        }
+        //重新打开文件 丢弃已写回的修改
+        let pos = self.chunks.get(0).map(|c| c.file_start).unwrap_or(0);
+        self.file = File::open(&filepath)?;
+        self.phys_size = self.file.metadata()?.len() as usize;
+        self.file_size = self.phys_size;
+        self.chunks = RingVec::new(CHUNK_NUM);
+        self.cache.clear();
+        self.reset_chunks(pos);
+        self.chk_iter = self.chunks.get(0).unwrap_or(&Chunk::empty()).clone();
+        Ok(())
+    }
+
+    // 只写回修改过的块
+    fn write_in_place<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()> {
+        let mut file = fs::OpenOptions::new().write(true).open(filepath)?;
+        for c in self
+            .chunks
+            .iter()
+            .chain(self.cache.values())
+            .filter(|c| c.is_modified)
+        {
+            let txt = c.buffer.text(..);
+            file.seek(std::io::SeekFrom::Start(c.phys_start as u64))?;
+            file.write_all(txt.left())?;
+            file.write_all(txt.right())?;
+        }
+        file.sync_all()?;
+        Ok(())
+    }
+
+    fn make_backup<P: AsRef<Path>>(&mut self, backup_name: P) -> ChapResult<()> {
+        let file = File::create(backup_name)?;
+        let mut w = std::io::BufWriter::new(&file);
+        let mut phys = 0;
+        while phys < self.phys_size || phys == 0 {
+            if let Ok(c) = self.read_one_chunk(phys) {
+                let txt = c.buffer.text(..);
+                w.write_all(txt.left())?;
+                w.write_all(txt.right())?;
             }
+            phys += CHUNK_SIZE;
         }
-        self.chunks.push_front(Chunk {
-            buffer: buffer,
-            file_start: file_seek,
-            file_end: file_seek + bytes_read,
-            is_modified: false,
-        });
+        w.flush()?;
+        file.sync_all()?;
         Ok(())
     }
 
-    pub(crate) fn read_next_chunk(&mut self, file_seek: usize) -> ChapResult<()> {
-        if file_seek >= self.file_size {
-            return Ok(());
+    //删除 pos 处 len 个字节 后面的字节前移
+    pub(crate) fn delete(&mut self, pos: usize, len: usize) -> ChapResult<()> {
+        let mut remaining = len.min(self.file_size.saturating_sub(pos));
+        while remaining > 0 {
+            let i = self
+                .chunk_index_at(pos)
+                .ok_or_else(|| ChapError::Unexpected(format!("not found chunk at {}", pos)))?;
+            let c = self.chunks.get_mut(i).unwrap();
+            let n = remaining.min(c.file_end - pos);
+            c.buffer.delete(pos - c.file_start + n, n);
+            c.file_end -= n;
+            c.is_modified = true;
+            self.shift_chunks_after(i, -(n as isize));
+            remaining -= n;
         }
-        self.file.seek(std::io::SeekFrom::Start(file_seek as u64))?;
-        let mut buffer = GapBuffer::new(CHUNK_SIZE + HEX_GAP_SIZE);
-        let mut bytes_read = 0;
-        let mut buf = [0u8; 1024];
-        while bytes_read < CHUNK_SIZE {
-            let n = self.file.read(&mut buf)?;
-            if n == 0 {
-                break;
+        Ok(())
+    }
+
+    // 编辑后内容的只读快照 未修改的部分映射磁盘文件 修改过的块复制一份
+    pub(crate) fn snapshot(&self) -> ChapResult<HexSnapshot> {
+        let mmap = unsafe { Mmap::map(&self.file)? };
+        let mut dirty: Vec<&Chunk> = self
+            .chunks
+            .iter()
+            .chain(self.cache.values())
+            .filter(|c| c.is_modified)
+            .collect();
+        dirty.sort_by_key(|c| c.phys_start);
+        let mut pieces = Vec::new();
+        let (mut phys, mut pos) = (0, 0);
+        for c in dirty {
+            if c.phys_start > phys {
+                pieces.push((pos, Piece::Disk(phys, c.phys_start)));
+                pos += c.phys_start - phys;
             }
-            bytes_read += n;
-            buffer.insert(buffer.text_len(), &buf[..n]);
+            let txt = c.buffer.text(..);
+            let mut bytes = txt.left().to_vec();
+            bytes.extend_from_slice(txt.right());
+            let len = bytes.len();
+            pieces.push((pos, Piece::Mem(bytes)));
+            pos += len;
+            phys = phys.max(c.phys_end);
         }
+        if phys < self.phys_size {
+            pieces.push((pos, Piece::Disk(phys, self.phys_size)));
+        }
+        Ok(HexSnapshot {
+            mmap,
+            pieces,
+            size: self.file_size,
+            version: 0,
+        })
+    }
+}
 
-        //弹出第一个块
-        let chunk0 = self.chunks.remove(0);
-        if let Some(c) = chunk0 {
-            if c.is_modified {
-                self.cache.insert(c.file_start, c);
+enum Piece {
+    Disk(usize, usize), // 原文件中的范围
+    Mem(Vec<u8>),       // 修改过的块
+}
+
+// 某一时刻的编辑内容 可以交给后台线程读取
+pub(crate) struct HexSnapshot {
+    mmap: Mmap,
+    pieces: Vec<(usize, Piece)>, // (编辑后的起始位置, 内容) 按位置排序
+    size: usize,
+    version: usize, // 快照时的编辑次数
+}
+
+impl HexSnapshot {
+    pub(crate) fn len(&self) -> usize {
+        self.size
+    }
+
+    pub(crate) fn version(&self) -> usize {
+        self.version
+    }
+
+    fn piece(&self, i: usize) -> &[u8] {
+        match &self.pieces[i].1 {
+            Piece::Disk(start, end) => {
+                let n = self.mmap.len();
+                &self.mmap[(*start).min(n)..(*end).min(n)]
             }
+            Piece::Mem(bytes) => bytes,
+        }
+    }
+
+    // [pos, pos+len) 的内容 在一个片段内时不复制
+    pub(crate) fn slice(&self, pos: usize, len: usize) -> Cow<'_, [u8]> {
+        let end = pos.saturating_add(len).min(self.size);
+        if pos >= end {
+            return Cow::Borrowed(&[]);
+        }
+        let mut i = self.pieces.partition_point(|(start, _)| *start <= pos) - 1;
+        let from = pos - self.pieces[i].0;
+        let first = self.piece(i);
+        if from + (end - pos) <= first.len() {
+            return Cow::Borrowed(&first[from..from + end - pos]);
+        }
+        let mut buf = Vec::with_capacity(end - pos);
+        let mut p = pos;
+        while p < end && i < self.pieces.len() {
+            let bytes = self.piece(i);
+            let from = (p - self.pieces[i].0).min(bytes.len());
+            let n = (end - p).min(bytes.len() - from);
+            buf.extend_from_slice(&bytes[from..from + n]);
+            p += n;
+            i += 1;
+        }
+        Cow::Owned(buf)
+    }
+}
+
+impl ByteEdit for HexText {
+    fn apply(&mut self, op: &EditOp) -> ChapResult<()> {
+        match op {
+            EditOp::Insert { offset, bytes } => self.insert(*offset, bytes),
+            EditOp::Delete { offset, bytes } => self.delete(*offset, bytes.len()),
+            EditOp::Overwrite { offset, new, .. } => self.overwrite(*offset, new),
+            EditOp::Group(ops) => ops.iter().try_for_each(|op| self.apply(op)),
         }
-        self.chunks.push(Chunk {
-            buffer: buffer,
-            file_start: file_seek,
-            file_end: file_seek + bytes_read,
-            is_modified: false,
-        });
-        Ok(())
     }
 }
 
@@ -1207,7 +1717,7 @@ impl Text for HexText {
             if let Some(j) = j {
                 if j == 0 {
                     //读取上一个块 把最后一个块弹出
-                    let mut last_chunk = self.chunks.get(0).unwrap().file_start;
+                    let mut last_chunk = self.chunks.get(0).unwrap().phys_start;
                     if last_chunk == 0 {
                         return HexTextIter::new(
                             [self.chunks.get(0), self.chunks.get(1)],
@@ -1229,14 +1739,15 @@ impl Text for HexText {
                     //最后一个块
                     //读取下一个块 把第一个块弹出
                     let next_file_seek = self.chunks.get(j).unwrap().file_end;
-                    if next_file_seek >= self.file_size {
+                    let next_phys_seek = self.chunks.get(j).unwrap().phys_end;
+                    if next_file_seek >= self.file_size || next_phys_seek >= self.phys_size {
                         return HexTextIter::new(
                             [self.chunks.get(j), None],
                             HEX_WITH,
                             line_file_start,
                         );
                     } else {
-                        self.read_next_chunk(next_file_seek).unwrap();
+                        self.read_next_chunk(next_phys_seek).unwrap();
                         return HexTextIter::new(
                             [self.chunks.get(j - 1), self.chunks.get(j)],
                             HEX_WITH,
@@ -1287,7 +1798,7 @@ impl Text for HexText {
         // let last_chunk_address = (n - CHUNK_NUM) * CHUNK_SIZE;
         // //如果没有找到块 从新重读chunks
         // //通过line_file_start 计算在哪一个块 每个块的大小是 CHUNK_SIZE
-        let chunk_start = line_file_start / CHUNK_SIZE * CHUNK_SIZE;
+        let chunk_start = self.locate_phys(line_file_start);
         let chk_iter = self.read_one_chunk(chunk_start).unwrap();
         self.chk_iter = chk_iter;
         return HexTextU8Iter::new(self, line_file_start - self.chk_iter.file_start);
@@ -1345,7 +1856,7 @@ impl<'a> Iterator for HexTextU8Iter<'a> {
             }
             if let Ok(chk) = self
                 .hex_text
-                .read_one_chunk(self.hex_text.chk_iter.file_end)
+                .read_one_chunk(self.hex_text.chk_iter.phys_end)
             {
                 self.hex_text.chk_iter = chk;
             } else {
@@ -1649,27 +2160,96 @@ impl GapText {
         self.lines[index].text_len()
     }
 
-    fn rename_backup<P1: AsRef<Path>, P2: AsRef<Path>>(
-        filepath: P1,
-        backup_name: P2,
-    ) -> ChapResult<()> {
-        fs::rename(backup_name, filepath)?;
-        Ok(())
+    // 行列转换为文本偏移 每行末尾计一个换行符
+    fn offset_of(&self, line_index: usize, col: usize) -> usize {
+        self.lines[..line_index.min(self.lines.len())]
+            .iter()
+            .map(|l| l.text_len() + 1)
+            .sum::<usize>()
+            + col
     }
 
-    pub(crate) fn save_file<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()> {
-        let backup_name = Self::get_backup_name(&filepath)?;
-        self.make_backup(&backup_name)?;
-        Self::rename_backup(&filepath, &backup_name)?;
-        Ok(())
+    // 文本偏移转换为行列 超出时返回最后一行末尾
+    fn pos_of(&self, offset: usize) -> (usize, usize) {
+        let mut rest = offset;
+        for (i, l) in self.lines.iter().enumerate() {
+            if rest <= l.text_len() {
+                return (i, rest);
+            }
+            rest -= l.text_len() + 1;
+        }
+        let last = self.lines.len().saturating_sub(1);
+        (last, self.get_text_len(last))
     }
 
-    fn get_backup_name<P: AsRef<Path>>(filepath: P) -> ChapResult<PathBuf> {
-        let mut path = filepath.as_ref().to_path_buf();
-        if let Some(file_name) = path.file_name() {
-            path.set_file_name(format!(".{}.{}", file_name.to_string_lossy(), "chap"));
+    // 在偏移处插入 字节中的换行符会拆分行
+    pub(crate) fn insert_at(&mut self, offset: usize, bytes: &[u8]) {
+        if self.lines.is_empty() {
+            self.lines.push(GapBuffer::new(CHAR_GAP_SIZE));
         }
-        Ok(path)
+        let (line_index, col) = self.pos_of(offset);
+        let line = &mut self.lines[line_index];
+        let line_len = line.text_len();
+        let tail = line.text(col..).to_vec();
+        line.delete(line_len, line_len - col);
+        let mut parts = bytes.split(|b| *b == b'\n');
+        if let Some(first) = parts.next() {
+            line.insert(col, first);
+        }
+        let mut cur = line_index;
+        for part in parts {
+            cur += 1;
+            let mut gap_buffer = GapBuffer::new(part.len() + CHAR_GAP_SIZE);
+            gap_buffer.insert(0, part);
+            self.lines.insert(cur, gap_buffer);
+        }
+        let line = &mut self.lines[cur];
+        line.insert(line.text_len(), &tail);
+    }
+
+    // 读取偏移处 len 个字节
+    fn text_at(&self, offset: usize, len: usize) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(len);
+        let (mut line_index, mut col) = self.pos_of(offset);
+        while buf.len() < len && line_index < self.lines.len() {
+            let txt = self.lines[line_index].text(col..);
+            let n = (len - buf.len()).min(txt.len());
+            buf.extend_from_slice(&txt.text(..n).to_vec());
+            if buf.len() < len && line_index + 1 < self.lines.len() {
+                buf.push(b'\n');
+            }
+            line_index += 1;
+            col = 0;
+        }
+        buf
+    }
+
+    // 删除偏移处 len 个字节 返回被删除的字节
+    pub(crate) fn delete_at(&mut self, offset: usize, len: usize) -> Vec<u8> {
+        let removed = self.text_at(offset, len);
+        if removed.is_empty() {
+            return removed;
+        }
+        let (l1, c1) = self.pos_of(offset);
+        let (l2, c2) = self.pos_of(offset + removed.len());
+        if l1 == l2 {
+            self.lines[l1].delete(c2, c2 - c1);
+        } else {
+            let tail = self.lines[l2].text(c2..).to_vec();
+            let line = &mut self.lines[l1];
+            let line_len = line.text_len();
+            line.delete(line_len, line_len - c1);
+            line.insert(c1, &tail);
+            self.lines.drain(l1 + 1..=l2);
+        }
+        removed
+    }
+
+    pub(crate) fn save_file<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()> {
+        let backup_name = get_backup_name(&filepath)?;
+        self.make_backup(&backup_name)?;
+        rename_backup(&filepath, &backup_name)?;
+        Ok(())
     }
 
     fn make_backup<P: AsRef<Path>>(&mut self, backup_name: P) -> ChapResult<()> {
@@ -1749,87 +2329,80 @@ impl Text for GapText {
 }
 
 impl EditText for GapText {
-    fn backspace(&mut self, cursor_y: usize, cursor_x: usize, line_meta: &EditLineMeta) {
-        let (line_index, line_offset) = (
-            line_meta.get_line_index(),
-            line_meta.get_line_offset() + cursor_x,
-        );
-        if self.borrow_lines_mut()[line_index].text_len() == 0 && line_offset == 0 {
-            //删除一行
-            self.borrow_lines_mut().remove(line_index);
-            return;
-        }
-        //表示当前行和前一行合并
-        if line_offset == 0 {
-            if line_index == 0 {
-                return;
-            }
-            let (pre_lines, cur_lines) = self.borrow_lines_mut().split_at_mut(line_index);
-            let pre_line = &mut pre_lines[line_index - 1];
-            if pre_line.text_len() == 0 {
-                self.borrow_lines_mut().remove(line_index - 1);
-                return;
-            } else {
-                let cur_line = &mut cur_lines[0];
-                let cur_line_txt = cur_line.text(..);
-                pre_line.insert(pre_line.text_len(), cur_line_txt.left());
-                pre_line.insert(pre_line.text_len(), cur_line_txt.right());
-                self.borrow_lines_mut().remove(line_index);
-            }
-            return;
+    fn backspace(
+        &mut self,
+        cursor_y: usize,
+        cursor_x: usize,
+        line_meta: &EditLineMeta,
+    ) -> Option<EditOp> {
+        let line_index = line_meta.get_line_index();
+        if line_index >= self.lines.len() {
+            return None;
         }
-        self.borrow_lines_mut()[line_index].backspace(line_offset);
+        let line_offset =
+            (line_meta.get_line_offset() + cursor_x).min(self.lines[line_index].text_len());
+        let offset = self.offset_of(line_index, line_offset);
+        let start = if offset > 0 {
+            //删除前一个字节 行首时和前一行合并
+            offset - 1
+        } else if self.lines.len() > 1 && self.lines[0].text_len() == 0 {
+            //删除第一行空行
+            0
+        } else {
+            return None;
+        };
+        let bytes = self.delete_at(start, 1);
+        Some(EditOp::Delete {
+            offset: start,
+            bytes,
+        })
     }
 
-    fn insert(&mut self, cursor_y: usize, cursor_x: usize, line_meta: &EditLineMeta, c: char) {
+    fn insert(
+        &mut self,
+        cursor_y: usize,
+        cursor_x: usize,
+        line_meta: &EditLineMeta,
+        c: char,
+    ) -> Option<EditOp> {
         let (line_index, line_offset) = (
             line_meta.get_line_index(),
             line_meta.get_line_offset() + cursor_x,
         );
-
-        let mut buf = [0u8; 4]; // 一个 char 最多需要 4 个字节存储 UTF-8 编码
-        let s: &str = c.encode_utf8(&mut buf);
-        let line = &mut self.borrow_lines_mut()[line_index];
+        let line_len = self.get_text_len(line_index);
+        let mut bytes = Vec::new();
         //如果line_offset大于文本长度 要填充空格
-        if line_offset > line.text_len() {
-            let gap_len = line_offset - line.text_len();
-            line.insert(line.text_len(), " ".repeat(gap_len).as_bytes());
+        if line_offset > line_len {
+            bytes.extend_from_slice(" ".repeat(line_offset - line_len).as_bytes());
         }
-        line.insert(line_offset, s.as_bytes());
+        let mut buf = [0u8; 4]; // 一个 char 最多需要 4 个字节存储 UTF-8 编码
+        bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        let offset = self.offset_of(line_index, line_offset.min(line_len));
+        self.insert_at(offset, &bytes);
+        Some(EditOp::Insert { offset, bytes })
     }
 
-    fn insert_newline(&mut self, cursor_y: usize, cursor_x: usize, line_meta: &EditLineMeta) {
-        // let (line_index, line_offset) = self.calculate_x_y(cursor_y, cursor_x);
+    fn position(&self, offset: usize) -> (usize, usize) {
+        self.pos_of(offset)
+    }
+
+    fn insert_newline(
+        &mut self,
+        cursor_y: usize,
+        cursor_x: usize,
+        line_meta: &EditLineMeta,
+    ) -> Option<EditOp> {
         let (line_index, line_offset) = (
             line_meta.get_line_index(),
             line_meta.get_line_offset() + cursor_x,
         );
-        //    println!("line_index: {}, line_offset: {}", line_index, line_offset);
-        let line_txt = self.borrow_lines_mut()[line_index].text(..);
-        let line_len = line_txt.len();
-        {
-            if line_offset > line_len {
-                // 如果光标不在行尾，插入新行
-                let new_gap_buffer = GapBuffer::new(10);
-                self.borrow_lines_mut()
-                    .insert(line_index + 1, new_gap_buffer);
-            } else {
-                let b = &line_txt.text(line_offset..);
-                let mut new_gap_buffer = GapBuffer::new(b.len() + 5);
-                new_gap_buffer.insert(0, b.left());
-                new_gap_buffer.insert(new_gap_buffer.text_len(), b.left());
-                self.borrow_lines_mut()
-                    .insert(line_index + 1, new_gap_buffer);
-            }
-        }
-
-        {
-            if line_len > line_offset {
-                let delete_len = line_len - line_offset;
-                // 删除当前行的剩余部分
-                self.borrow_lines_mut()[line_index].delete(line_len, delete_len);
-            }
-        }
+        let line_len = self.get_text_len(line_index);
+        let offset = self.offset_of(line_index, line_offset.min(line_len));
+        self.insert_at(offset, b"\n");
+        Some(EditOp::Insert {
+            offset,
+            bytes: b"\n".to_vec(),
+        })
     }
 
     fn save<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()> {
@@ -1837,10 +2410,32 @@ impl EditText for GapText {
     }
 }
 
+impl ByteEdit for GapText {
+    fn apply(&mut self, op: &EditOp) -> ChapResult<()> {
+        match op {
+            EditOp::Insert { offset, bytes } => self.insert_at(*offset, bytes),
+            EditOp::Delete { offset, bytes } => {
+                self.delete_at(*offset, bytes.len());
+            }
+            EditOp::Overwrite { offset, new, .. } => {
+                self.delete_at(*offset, new.len());
+                self.insert_at(*offset, new);
+            }
+            EditOp::Group(ops) => {
+                for op in ops {
+                    self.apply(op)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 pub(crate) struct TextWarp<T: Text + TextIndex> {
     lines: UnsafeCell<T>,                               // 文本
     cache_lines: UnsafeCell<RingVec<CacheStr>>,         // 缓存行
     cache_line_meta: UnsafeCell<RingVec<EditLineMeta>>, // 缓存行
+    history: UnsafeCell<History>,                       // 编辑历史
     //page_offset_list: UnsafeCell<Vec<PageOffset>>,      // 每页的偏移量
     height: usize, //最大行数
     with: usize,
@@ -1858,6 +2453,7 @@ impl<T: Text + TextIndex> TextWarp<T> {
             lines: UnsafeCell::new(lines),
             cache_lines: UnsafeCell::new(RingVec::new(height)),
             cache_line_meta: UnsafeCell::new(RingVec::new(height)),
+            history: UnsafeCell::new(History::new()),
             // page_offset_list: UnsafeCell::new(vec![PageOffset {
             //     line_index: 0,
             //     line_offset: 0,
@@ -1901,6 +2497,29 @@ impl<T: Text + TextIndex> TextWarp<T> {
         unsafe { &mut *self.cache_line_meta.get() }
     }
 
+    fn borrow_history_mut(&self) -> &mut History {
+        unsafe { &mut *self.history.get() }
+    }
+
+    pub(crate) fn end_typing(&self) {
+        self.borrow_history_mut().end_typing();
+    }
+
+    // 编辑次数 撤销和重做也计入
+    pub(crate) fn version(&self) -> usize {
+        self.borrow_history_mut().version()
+    }
+
+    pub(crate) fn take_splices(&self) -> Vec<Splice> {
+        self.borrow_history_mut().take_splices()
+    }
+
+    //清空缓存行 需要重新获取一页
+    fn clear_cache(&self) {
+        self.borrow_cache_lines_mut().clear();
+        self.borrow_cache_line_meta_mut().clear();
+    }
+
     pub(crate) fn get_text_len(&self, index: usize) -> usize {
         self.borrow_lines().get_line_text_len(index, 0, 0)
     }
@@ -1922,15 +2541,13 @@ impl<T: Text + TextIndex> TextWarp<T> {
         (text_len as f64 / with as f64).ceil() as usize
     }
 
-    pub(crate) fn find(&self, pattern: &[u8], line_file_start: usize) -> Option<usize> {
+    pub(crate) fn find(
+        &self,
+        pattern: &SearchPattern,
+        line_file_start: usize,
+    ) -> Option<(usize, usize)> {
         let hex_u8_iter = self.borrow_lines_mut().iter_u8(0, 0, line_file_start);
-        let bm = BoyerMoore::new(pattern);
-        let mut j = None;
-        for i in bm.stream(hex_u8_iter) {
-            j = Some(i);
-            break;
-        }
-        j
+        pattern.find(hex_u8_iter)
     }
 
     //从当前行开始获取前面n行
@@ -2487,6 +3104,105 @@ impl<T: Text + TextIndex> TextWarp<T> {
     }
 }
 
+impl<T: Text + TextIndex + ByteEdit> TextWarp<T> {
+    //执行并记录编辑操作
+    fn exec(&self, op: EditOp) -> ChapResult<()> {
+        self.borrow_lines_mut().apply(&op)?;
+        self.borrow_history_mut().record(op);
+        self.clear_cache();
+        Ok(())
+    }
+
+    pub(crate) fn undo(&self, n: usize) -> ChapResult<Option<usize>> {
+        let cursor = self.borrow_history_mut().undo(n, self.borrow_lines_mut())?;
+        self.clear_cache();
+        Ok(cursor)
+    }
+
+    pub(crate) fn redo(&self, n: usize) -> ChapResult<Option<usize>> {
+        let cursor = self.borrow_history_mut().redo(n, self.borrow_lines_mut())?;
+        self.clear_cache();
+        Ok(cursor)
+    }
+}
+
+impl TextWarp<HexText> {
+    pub(crate) fn overwrite_bytes(&self, offset: usize, bytes: &[u8]) -> ChapResult<()> {
+        let old = self.borrow_lines_mut().read_bytes(offset, bytes.len());
+        self.exec(EditOp::Overwrite {
+            offset,
+            old,
+            new: bytes.to_vec(),
+        })
+    }
+
+    pub(crate) fn insert_bytes(&self, offset: usize, bytes: &[u8]) -> ChapResult<()> {
+        self.exec(EditOp::Insert {
+            offset,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub(crate) fn delete_bytes(&self, offset: usize, len: usize) -> ChapResult<()> {
+        let bytes = self.borrow_lines_mut().read_bytes(offset, len);
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        self.exec(EditOp::Delete { offset, bytes })
+    }
+
+    // 等长的部分覆盖 多出的插入 不足的删除 前面的替换改变长度后 后面的偏移随之移动
+    pub(crate) fn replace_bytes(&self, ranges: &[(usize, usize)], bytes: &[u8]) -> ChapResult<()> {
+        let lines = self.borrow_lines_mut();
+        let mut ops = Vec::with_capacity(ranges.len());
+        let mut shift: isize = 0;
+        // 操作还未执行 原来的字节按替换前的偏移读取
+        for &(start, len) in ranges {
+            let offset = start.checked_add_signed(shift).ok_or("bad replace range")?;
+            let common = len.min(bytes.len());
+            if common > 0 {
+                ops.push(EditOp::Overwrite {
+                    offset,
+                    old: lines.read_bytes(start, common),
+                    new: bytes[..common].to_vec(),
+                });
+            }
+            if bytes.len() > len {
+                ops.push(EditOp::Insert {
+                    offset: offset + len,
+                    bytes: bytes[len..].to_vec(),
+                });
+            } else if len > bytes.len() {
+                ops.push(EditOp::Delete {
+                    offset: offset + common,
+                    bytes: lines.read_bytes(start + common, len - common),
+                });
+            }
+            shift += bytes.len() as isize - len as isize;
+        }
+        if ops.is_empty() {
+            return Ok(());
+        }
+        self.exec(EditOp::Group(ops))
+    }
+
+    pub(crate) fn read_bytes(&self, offset: usize, len: usize) -> Vec<u8> {
+        self.borrow_lines_mut().read_bytes(offset, len)
+    }
+
+    pub(crate) fn snapshot(&self) -> ChapResult<HexSnapshot> {
+        let mut snap = self.borrow_lines().snapshot()?;
+        snap.version = self.version();
+        Ok(snap)
+    }
+
+    pub(crate) fn save<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()> {
+        self.borrow_lines_mut().save_file(filepath)?;
+        self.clear_cache();
+        Ok(())
+    }
+}
+
 pub(crate) struct EditTextWarp<T: Text + TextIndex + EditText> {
     edit_text: TextWarp<T>,
 }
@@ -2537,15 +3253,16 @@ impl<T: Text + TextIndex + EditText> EditTextWarp<T> {
         line_meta: &EditLineMeta,
         c: char,
     ) -> ChapResult<()> {
-        self.edit_text
+        let op = self
+            .edit_text
             .borrow_lines_mut()
             .insert(cursor_y, cursor_x, line_meta, c);
+        self.record(op);
         //切断page_offset_list 索引
         // todo
         // let page_offset_list = self.edit_text.borrow_page_offset_list_mut();
         // unsafe { page_offset_list.set_len(line_meta.get_page_num()) };
-        self.edit_text.borrow_cache_lines_mut().clear();
-        self.edit_text.borrow_cache_line_meta_mut().clear();
+        self.edit_text.clear_cache();
         Ok(())
     }
 
@@ -2556,14 +3273,15 @@ impl<T: Text + TextIndex + EditText> EditTextWarp<T> {
         cursor_x: usize,
         line_meta: &EditLineMeta,
     ) -> ChapResult<()> {
-        self.edit_text
+        let op = self
+            .edit_text
             .borrow_lines_mut()
             .insert_newline(cursor_y, cursor_x, line_meta);
+        self.record(op);
         // todo
         // let page_offset_list = self.edit_text.borrow_page_offset_list_mut();
         // unsafe { page_offset_list.set_len(line_meta.get_page_num()) };
-        self.edit_text.borrow_cache_lines_mut().clear();
-        self.edit_text.borrow_cache_line_meta_mut().clear();
+        self.edit_text.clear_cache();
         Ok(())
     }
 
@@ -2574,17 +3292,48 @@ impl<T: Text + TextIndex + EditText> EditTextWarp<T> {
         cursor_x: usize,
         line_meta: &EditLineMeta,
     ) -> ChapResult<()> {
-        self.edit_text
+        let op = self
+            .edit_text
             .borrow_lines_mut()
             .backspace(cursor_y, cursor_x, line_meta);
+        self.record(op);
         // todo
         //let page_offset_list = self.edit_text.borrow_page_offset_list_mut();
         //unsafe { page_offset_list.set_len(line_meta.get_page_num()) };
-        self.edit_text.borrow_cache_lines_mut().clear();
-        self.edit_text.borrow_cache_line_meta_mut().clear();
+        self.edit_text.clear_cache();
         Ok(())
     }
 
+    fn record(&self, op: Option<EditOp>) {
+        if let Some(op) = op {
+            self.edit_text.borrow_history_mut().record(op);
+        }
+    }
+
+    pub(crate) fn undo(&self, n: usize) -> ChapResult<Option<usize>> {
+        self.edit_text.undo(n)
+    }
+
+    pub(crate) fn redo(&self, n: usize) -> ChapResult<Option<usize>> {
+        self.edit_text.redo(n)
+    }
+
+    pub(crate) fn end_typing(&self) {
+        self.edit_text.end_typing();
+    }
+
+    pub(crate) fn version(&self) -> usize {
+        self.edit_text.version()
+    }
+
+    pub(crate) fn take_splices(&self) -> Vec<Splice> {
+        self.edit_text.take_splices()
+    }
+
+    pub(crate) fn position(&self, offset: usize) -> (usize, usize) {
+        self.edit_text.borrow_lines().position(offset)
+    }
+
     pub(crate) fn save<P: AsRef<Path>>(&mut self, filepath: P) -> ChapResult<()> {
         self.edit_text.borrow_lines_mut().save(filepath)
     }
@@ -3404,6 +4153,163 @@ mod tests {
     //     };
     // }
 
+    #[test]
+    fn test_hex_text_edit() {
+        let path = std::env::temp_dir().join("chap_test_hex_text_edit.bin");
+        let data: Vec<u8> = (0..CHUNK_SIZE * 8).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let mut hex_text = HexText::from_file_path(&path, 10).unwrap();
+
+        hex_text.overwrite(1, &[0xAA, 0xBB]).unwrap();
+        hex_text.insert(CHUNK_SIZE, &[1, 2, 3]).unwrap();
+        hex_text.delete(0, 1).unwrap();
+        assert_eq!(hex_text.get_file_size(), data.len() + 2);
+        assert_eq!(
+            hex_text.text_from_sel(&TextSelect::from_select(0, 1)),
+            vec![0xAA, 0xBB]
+        );
+
+        //换出已修改的块 再重新加载
+        hex_text.reset_chunks(CHUNK_SIZE * 7);
+        //已修改块在缓存中 未加载的块直接读磁盘
+        let at = CHUNK_SIZE - 1;
+        assert_eq!(
+            hex_text.read_bytes(at - 1, 5),
+            vec![data[CHUNK_SIZE - 1], 1, 2, 3, data[CHUNK_SIZE]]
+        );
+        assert_eq!(
+            hex_text.read_bytes(CHUNK_SIZE * 2, 4),
+            data[CHUNK_SIZE * 2 - 2..CHUNK_SIZE * 2 + 2].to_vec()
+        );
+        assert_eq!(
+            hex_text.read_bytes(hex_text.get_file_size() - 1, 8).len(),
+            1
+        );
+        hex_text.reset_chunks(0);
+        assert_eq!(
+            hex_text.text_from_sel(&TextSelect::from_select(at - 1, at + 3)),
+            vec![data[CHUNK_SIZE - 1], 1, 2, 3, data[CHUNK_SIZE]]
+        );
+        let end = hex_text.get_file_size() - 1;
+        hex_text.reset_chunks(end);
+        assert_eq!(
+            hex_text.text_from_sel(&TextSelect::from_select(end, end)),
+            vec![*data.last().unwrap()]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_hex_text_save() {
+        let path = std::env::temp_dir().join("chap_test_hex_text_save.bin");
+        let mut data: Vec<u8> = (0..CHUNK_SIZE * 7 + 10).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let mut hex_text = HexText::from_file_path(&path, 10).unwrap();
+
+        //长度不变 原地写回 包括被换出的块
+        hex_text.overwrite(CHUNK_SIZE * 6 + 1, &[0xEE]).unwrap();
+        hex_text.overwrite(2, &[0xFF]).unwrap();
+        hex_text.save_file(&path).unwrap();
+        data[CHUNK_SIZE * 6 + 1] = 0xEE;
+        data[2] = 0xFF;
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+
+        //长度改变 写临时文件后替换
+        hex_text.insert(CHUNK_SIZE * 7 + 10, &[1, 2]).unwrap();
+        hex_text.delete(0, 3).unwrap();
+        hex_text.save_file(&path).unwrap();
+        data.extend_from_slice(&[1, 2]);
+        data.drain(0..3);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert_eq!(hex_text.get_file_size(), data.len());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_gap_text_offset_edit() {
+        let path = std::env::temp_dir().join("chap_test_gap_text_offset_edit.txt");
+        std::fs::write(&path, "abc\ndef\n").unwrap();
+        let mut gap_text = GapText::from_file_path(&path).unwrap();
+        gap_text.insert_at(5, b"X\nY");
+        assert_eq!(gap_text.text_at(0, 11), b"abc\ndX\nYef".to_vec());
+        assert_eq!(gap_text.delete_at(2, 4), b"c\ndX".to_vec());
+        assert_eq!(gap_text.text_at(0, 6), b"ab\nYef".to_vec());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_hex_undo() {
+        let path = std::env::temp_dir().join("chap_test_hex_undo.bin");
+        let data: Vec<u8> = (0..CHUNK_SIZE * 6).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+        let tw = TextWarp::new(
+            HexText::from_file_path(&path, 10).unwrap(),
+            10,
+            80,
+            TextWarpType::NoWrap,
+        );
+        tw.overwrite_bytes(0, &[0xA0]).unwrap();
+        tw.overwrite_bytes(0, &[0xAB]).unwrap();
+        tw.overwrite_bytes(1, &[0xCD]).unwrap();
+        tw.delete_bytes(CHUNK_SIZE * 5, 2).unwrap();
+        tw.insert_bytes(3, &[9]).unwrap();
+
+        assert_eq!(tw.undo(1).unwrap(), Some(3));
+        assert_eq!(tw.undo(1).unwrap(), Some(CHUNK_SIZE * 5));
+        assert_eq!(
+            tw.borrow_lines_mut().read_bytes(CHUNK_SIZE * 5, 2),
+            data[CHUNK_SIZE * 5..CHUNK_SIZE * 5 + 2].to_vec()
+        );
+        assert_eq!(tw.borrow_lines_mut().read_bytes(0, 2), vec![0xAB, 0xCD]);
+        //连续键入的字节作为一步撤销
+        assert_eq!(tw.undo(1).unwrap(), Some(0));
+        assert_eq!(tw.borrow_lines_mut().read_bytes(0, 2), data[0..2].to_vec());
+        assert_eq!(tw.undo(1).unwrap(), None);
+        assert_eq!(tw.redo(3).unwrap(), Some(4));
+        assert_eq!(
+            tw.borrow_lines_mut().read_bytes(0, 5),
+            vec![0xAB, 0xCD, data[2], 9, data[3]]
+        );
+        assert_eq!(tw.get_file_size(), data.len() - 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_hex_replace() {
+        let path = std::env::temp_dir().join("chap_test_hex_replace.bin");
+        let mut data = vec![0u8; CHUNK_SIZE * 3];
+        for off in [10, CHUNK_SIZE - 1, CHUNK_SIZE * 2] {
+            data[off..off + 2].copy_from_slice(&[0xDE, 0xAD]);
+        }
+        std::fs::write(&path, &data).unwrap();
+        let tw = TextWarp::new(
+            HexText::from_file_path(&path, 10).unwrap(),
+            10,
+            80,
+            TextWarpType::NoWrap,
+        );
+        let pattern = SearchPattern::Bytes(vec![0xDE, 0xAD]);
+        assert_eq!(tw.find(&pattern, 11), Some((CHUNK_SIZE - 12, 2)));
+
+        //变长替换 后面的偏移随前面的替换移动
+        let ranges = [(10, 2), (CHUNK_SIZE - 1, 2), (CHUNK_SIZE * 2, 2)];
+        tw.replace_bytes(&ranges, &[1, 2, 3]).unwrap();
+        assert_eq!(tw.get_file_size(), data.len() + 3);
+        assert_eq!(tw.read_bytes(9, 5), vec![0, 1, 2, 3, 0]);
+        assert_eq!(tw.read_bytes(CHUNK_SIZE, 4), vec![1, 2, 3, 0]);
+        assert_eq!(tw.read_bytes(CHUNK_SIZE * 2 + 2, 3), vec![1, 2, 3]);
+        assert_eq!(tw.find(&pattern, 0), None);
+
+        //全部替换作为一步撤销
+        assert_eq!(tw.undo(1).unwrap(), Some(10));
+        assert_eq!(tw.read_bytes(0, data.len()), data);
+        tw.redo(1).unwrap();
+        tw.replace_bytes(&[(11, 4)], &[]).unwrap();
+        assert_eq!(tw.read_bytes(9, 3), vec![0, 1, 0]);
+        assert_eq!(tw.get_file_size(), data.len() - 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_mmap() {
         let path = "/root/aa.txt";
@@ -0,0 +1,1443 @@
+use crate::byteutil::Endian;
+use crate::pg::PageFields;
+use crate::plugin::ByteSource;
+use crate::plugin::PluginField;
+use crate::ChapResult;
+
+// 每个符号表最多显示的符号数
+const SYMBOLS_MAX: usize = 4096;
+
+// 按字节序读取整数 越界时返回 0
+struct Rd<'a> {
+    b: &'a [u8],
+    endian: Endian,
+}
+
+impl<'a> Rd<'a> {
+    fn new(b: &'a [u8], endian: &Endian) -> Rd<'a> {
+        Rd {
+            b,
+            endian: endian.clone(),
+        }
+    }
+
+    fn uint(&self, off: usize, len: usize) -> u64 {
+        let bytes = match self.b.get(off..off + len) {
+            Some(bytes) => bytes,
+            None => return 0,
+        };
+        let fold = |v: u64, b: &u8| (v << 8) | *b as u64;
+        match self.endian {
+            Endian::Little => bytes.iter().rev().fold(0, fold),
+            Endian::Big => bytes.iter().fold(0, fold),
+        }
+    }
+
+    fn u8(&self, off: usize) -> u64 {
+        self.uint(off, 1)
+    }
+
+    fn u16(&self, off: usize) -> u64 {
+        self.uint(off, 2)
+    }
+
+    fn u32(&self, off: usize) -> u64 {
+        self.uint(off, 4)
+    }
+
+    fn u64(&self, off: usize) -> u64 {
+        self.uint(off, 8)
+    }
+
+    // 32 位格式为 4 字节 64 位格式为 8 字节
+    fn word(&self, off: usize, is64: bool) -> u64 {
+        if is64 {
+            self.u64(off)
+        } else {
+            self.u32(off)
+        }
+    }
+}
+
+// 以 NUL 结尾的字符串
+fn cstr(b: &[u8]) -> String {
+    let end = b.iter().position(|&c| c == 0).unwrap_or(b.len());
+    String::from_utf8_lossy(&b[..end]).to_string()
+}
+
+fn str_at(table: &[u8], off: usize) -> String {
+    table.get(off..).map_or(String::new(), cstr)
+}
+
+fn flag_names(v: u64, flags: &[(u64, &str)]) -> String {
+    let names: Vec<&str> = flags
+        .iter()
+        .filter(|(bit, _)| v & bit != 0)
+        .map(|(_, name)| *name)
+        .collect();
+    names.join("|")
+}
+
+fn lookup(v: u64, names: &[(u64, &'static str)]) -> String {
+    names
+        .iter()
+        .find(|(n, _)| *n == v)
+        .map_or(format!("0x{:x}", v), |(_, name)| name.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ExeFormat {
+    Elf,
+    Pe,
+    MachO,
+    Fat, // mach-o universal binary
+}
+
+impl ExeFormat {
+    pub(crate) fn detect(src: &dyn ByteSource) -> Option<ExeFormat> {
+        let h = src.read(0, 8);
+        if h.len() < 8 {
+            return None;
+        }
+        match h[..4] {
+            [0x7f, b'E', b'L', b'F'] => return Some(ExeFormat::Elf),
+            [0xfe, 0xed, 0xfa, 0xce | 0xcf] | [0xce | 0xcf, 0xfa, 0xed, 0xfe] => {
+                return Some(ExeFormat::MachO)
+            }
+            //java class 文件也以 cafebabe 开头 它的版本号远大于架构数量
+            [0xca, 0xfe, 0xba, 0xbe | 0xbf] => {
+                let nfat = u32::from_be_bytes(h[4..8].try_into().unwrap());
+                if nfat > 0 && nfat < 30 {
+                    return Some(ExeFormat::Fat);
+                }
+            }
+            _ => {}
+        }
+        if h[..2] == *b"MZ" {
+            let dos = src.read(0x3c, 4);
+            if dos.len() == 4 {
+                let lfanew = u32::from_le_bytes(dos.try_into().unwrap()) as usize;
+                if src.read(lfanew, 4) == b"PE\0\0" {
+                    return Some(ExeFormat::Pe);
+                }
+            }
+        }
+        None
+    }
+}
+
+// 解析结果 fields 为全部结构 sections 为节和段在文件中的位置 用于跳转
+pub(crate) struct ExeInfo {
+    pub(crate) summary: String,
+    pub(crate) fields: Vec<PluginField>,
+    pub(crate) sections: Vec<PluginField>,
+}
+
+pub(crate) fn decode_exe(src: &dyn ByteSource) -> ChapResult<ExeInfo> {
+    match ExeFormat::detect(src) {
+        Some(ExeFormat::Elf) => decode_elf(src),
+        Some(ExeFormat::Pe) => decode_pe(src),
+        Some(ExeFormat::MachO) => decode_macho(src),
+        Some(ExeFormat::Fat) => decode_fat(src),
+        None => Err("unknown executable format, expect ELF, PE or Mach-O".into()),
+    }
+}
+
+// ---------------- ELF ----------------
+
+const ELF_MACHINES: &[(u64, &str)] = &[
+    (2, "SPARC"),
+    (3, "x86"),
+    (8, "MIPS"),
+    (20, "PowerPC"),
+    (21, "PowerPC64"),
+    (22, "S390"),
+    (40, "ARM"),
+    (62, "x86-64"),
+    (183, "AArch64"),
+    (243, "RISC-V"),
+    (258, "LoongArch"),
+];
+
+const ELF_TYPES: &[(u64, &str)] = &[
+    (0, "NONE"),
+    (1, "REL"),
+    (2, "EXEC"),
+    (3, "DYN"),
+    (4, "CORE"),
+];
+
+const PT_TYPES: &[(u64, &str)] = &[
+    (0, "PT_NULL"),
+    (1, "PT_LOAD"),
+    (2, "PT_DYNAMIC"),
+    (3, "PT_INTERP"),
+    (4, "PT_NOTE"),
+    (5, "PT_SHLIB"),
+    (6, "PT_PHDR"),
+    (7, "PT_TLS"),
+    (0x6474e550, "PT_GNU_EH_FRAME"),
+    (0x6474e551, "PT_GNU_STACK"),
+    (0x6474e552, "PT_GNU_RELRO"),
+    (0x6474e553, "PT_GNU_PROPERTY"),
+];
+
+const SHT_NULL: u64 = 0;
+const SHT_SYMTAB: u64 = 2;
+const SHT_NOBITS: u64 = 8;
+const SHT_DYNSYM: u64 = 11;
+
+const SHT_TYPES: &[(u64, &str)] = &[
+    (0, "NULL"),
+    (1, "PROGBITS"),
+    (2, "SYMTAB"),
+    (3, "STRTAB"),
+    (4, "RELA"),
+    (5, "HASH"),
+    (6, "DYNAMIC"),
+    (7, "NOTE"),
+    (8, "NOBITS"),
+    (9, "REL"),
+    (11, "DYNSYM"),
+    (14, "INIT_ARRAY"),
+    (15, "FINI_ARRAY"),
+    (16, "PREINIT_ARRAY"),
+    (17, "GROUP"),
+    (18, "SYMTAB_SHNDX"),
+    (0x6ffffff6, "GNU_HASH"),
+    (0x6ffffffd, "VERDEF"),
+    (0x6ffffffe, "VERNEED"),
+    (0x6fffffff, "VERSYM"),
+];
+
+const SHF_FLAGS: &[(u64, &str)] = &[
+    (0x1, "W"),
+    (0x2, "A"),
+    (0x4, "X"),
+    (0x10, "M"),
+    (0x20, "S"),
+    (0x40, "I"),
+    (0x80, "L"),
+    (0x100, "O"),
+    (0x200, "G"),
+    (0x400, "T"),
+    (0x800, "C"),
+];
+
+const STT_TYPES: &[(u64, &str)] = &[
+    (0, "NOTYPE"),
+    (1, "OBJECT"),
+    (2, "FUNC"),
+    (3, "SECTION"),
+    (4, "FILE"),
+    (5, "COMMON"),
+    (6, "TLS"),
+    (10, "IFUNC"),
+];
+
+const STB_BINDS: &[(u64, &str)] = &[(0, "LOCAL"), (1, "GLOBAL"), (2, "WEAK"), (10, "UNIQUE")];
+
+// p_flags PF_R PF_W PF_X
+fn rwx(flags: u64) -> String {
+    let bit = |b: u64, c: char| if flags & b != 0 { c } else { '-' };
+    format!("{}{}{}", bit(4, 'R'), bit(2, 'W'), bit(1, 'X'))
+}
+
+struct Shdr {
+    name: String,
+    typ: u64,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u64,
+    entsize: u64,
+}
+
+fn decode_elf(src: &dyn ByteSource) -> ChapResult<ExeInfo> {
+    let ident = src.read(0, 16);
+    if ident.len() < 16 {
+        return Err("truncated ELF ident".into());
+    }
+    let is64 = match ident[4] {
+        1 => false,
+        2 => true,
+        c => return Err(format!("invalid ELF class {}", c).into()),
+    };
+    let endian = match ident[5] {
+        1 => Endian::Little,
+        2 => Endian::Big,
+        d => return Err(format!("invalid ELF data encoding {}", d).into()),
+    };
+    let ehsize = if is64 { 64 } else { 52 };
+    let eh = src.read(0, ehsize);
+    if eh.len() < ehsize {
+        return Err("truncated ELF header".into());
+    }
+    let r = Rd::new(&eh, &endian);
+    let w = if is64 { 8 } else { 4 };
+    let mut out = PageFields::new(0);
+    let mut nav = PageFields::new(0);
+    out.push(
+        "e_ident",
+        0,
+        16,
+        format!(
+            "ELF{} {} version {} osabi {} abiversion {}",
+            if is64 { 64 } else { 32 },
+            if endian == Endian::Little {
+                "little-endian"
+            } else {
+                "big-endian"
+            },
+            ident[6],
+            ident[7],
+            ident[8]
+        ),
+    );
+    let e_type = r.u16(16);
+    let e_machine = r.u16(18);
+    out.push("e_type", 16, 2, lookup(e_type, ELF_TYPES));
+    out.push("e_machine", 18, 2, lookup(e_machine, ELF_MACHINES));
+    out.push("e_version", 20, 4, r.u32(20).to_string());
+    let e_entry = r.word(24, is64);
+    let (phoff_at, shoff_at, flags_at) = if is64 { (32, 40, 48) } else { (28, 32, 36) };
+    let phoff = r.word(phoff_at, is64) as usize;
+    let shoff = r.word(shoff_at, is64) as usize;
+    out.push("e_entry", 24, w, format!("0x{:x}", e_entry));
+    out.push("e_phoff", phoff_at, w, phoff.to_string());
+    out.push("e_shoff", shoff_at, w, shoff.to_string());
+    out.push("e_flags", flags_at, 4, format!("0x{:x}", r.u32(flags_at)));
+    let half = flags_at + 4;
+    let names = [
+        "e_ehsize",
+        "e_phentsize",
+        "e_phnum",
+        "e_shentsize",
+        "e_shnum",
+        "e_shstrndx",
+    ];
+    for (i, name) in names.iter().enumerate() {
+        out.push(*name, half + i * 2, 2, r.u16(half + i * 2).to_string());
+    }
+    let phentsize = r.u16(half + 2) as usize;
+    let phnum = r.u16(half + 4) as usize;
+    let shentsize = r.u16(half + 6) as usize;
+    let mut shnum = r.u16(half + 8) as usize;
+    let mut shstrndx = r.u16(half + 10) as usize;
+
+    // 程序头
+    if phoff != 0 && phnum > 0 && phentsize > 0 {
+        let tbl = src.read(phoff, phnum * phentsize);
+        for (i, ent) in tbl.chunks_exact(phentsize).enumerate() {
+            let p = Rd::new(ent, &endian);
+            let (ptype, flags, offset, vaddr, filesz, memsz) = if is64 {
+                (
+                    p.u32(0),
+                    p.u32(4),
+                    p.u64(8),
+                    p.u64(16),
+                    p.u64(32),
+                    p.u64(40),
+                )
+            } else {
+                (
+                    p.u32(0),
+                    p.u32(24),
+                    p.u32(4),
+                    p.u32(8),
+                    p.u32(16),
+                    p.u32(20),
+                )
+            };
+            let name = lookup(ptype, PT_TYPES);
+            out.push(
+                format!("phdr[{}] {}", i, name),
+                phoff + i * phentsize,
+                phentsize,
+                format!(
+                    "off=0x{:x} vaddr=0x{:x} filesz=0x{:x} memsz=0x{:x} {}",
+                    offset,
+                    vaddr,
+                    filesz,
+                    memsz,
+                    rwx(flags)
+                ),
+            );
+            if filesz > 0 {
+                nav.push(
+                    format!("segment[{}] {}", i, name),
+                    offset as usize,
+                    filesz as usize,
+                    format!("vaddr=0x{:x} {}", vaddr, rwx(flags)),
+                );
+            }
+        }
+    }
+
+    // 节头 节数量或 shstrndx 过大时保存在第0个节头中
+    let mut shdrs: Vec<Shdr> = Vec::new();
+    if shoff != 0 && shentsize > 0 {
+        let first = src.read(shoff, shentsize);
+        let s0 = Rd::new(&first, &endian);
+        if shnum == 0 {
+            shnum = s0.word(if is64 { 32 } else { 20 }, is64) as usize;
+        }
+        if shstrndx == 0xffff {
+            shstrndx = s0.u32(if is64 { 40 } else { 24 }) as usize;
+        }
+        let tbl_len = shnum
+            .checked_mul(shentsize)
+            .ok_or_else(|| format!("section header table too large: {} entries", shnum))?;
+        let tbl = src.read(shoff, tbl_len);
+        let mut name_offs = Vec::new();
+        for ent in tbl.chunks_exact(shentsize) {
+            let s = Rd::new(ent, &endian);
+            name_offs.push(s.u32(0) as usize);
+            shdrs.push(if is64 {
+                Shdr {
+                    name: String::new(),
+                    typ: s.u32(4),
+                    flags: s.u64(8),
+                    addr: s.u64(16),
+                    offset: s.u64(24),
+                    size: s.u64(32),
+                    link: s.u32(40),
+                    entsize: s.u64(56),
+                }
+            } else {
+                Shdr {
+                    name: String::new(),
+                    typ: s.u32(4),
+                    flags: s.u32(8),
+                    addr: s.u32(12),
+                    offset: s.u32(16),
+                    size: s.u32(20),
+                    link: s.u32(24),
+                    entsize: s.u32(36),
+                }
+            });
+        }
+        let shstrtab = match shdrs.get(shstrndx) {
+            Some(s) => src.read(s.offset as usize, s.size as usize),
+            None => Vec::new(),
+        };
+        for (s, off) in shdrs.iter_mut().zip(name_offs) {
+            s.name = str_at(&shstrtab, off);
+        }
+    }
+    for (i, s) in shdrs.iter().enumerate() {
+        out.push(
+            format!("shdr[{}] {}", i, s.name),
+            shoff + i * shentsize,
+            shentsize,
+            format!(
+                "{} off=0x{:x} addr=0x{:x} size=0x{:x} {}",
+                lookup(s.typ, SHT_TYPES),
+                s.offset,
+                s.addr,
+                s.size,
+                flag_names(s.flags, SHF_FLAGS)
+            ),
+        );
+        if s.typ != SHT_NULL && s.typ != SHT_NOBITS && s.size > 0 {
+            nav.push(
+                format!("section[{}] {}", i, s.name),
+                s.offset as usize,
+                s.size as usize,
+                format!("{} addr=0x{:x}", lookup(s.typ, SHT_TYPES), s.addr),
+            );
+        }
+    }
+
+    // 符号表 名字在 sh_link 指向的字符串表中
+    for s in shdrs
+        .iter()
+        .filter(|s| s.typ == SHT_SYMTAB || s.typ == SHT_DYNSYM)
+    {
+        let entsize = match s.entsize as usize {
+            0 => {
+                if is64 {
+                    24
+                } else {
+                    16
+                }
+            }
+            n => n,
+        };
+        let strtab = match shdrs.get(s.link as usize) {
+            Some(t) => src.read(t.offset as usize, t.size as usize),
+            None => Vec::new(),
+        };
+        let count = s.size as usize / entsize;
+        let tbl = src.read(s.offset as usize, count.min(SYMBOLS_MAX) * entsize);
+        for (j, ent) in tbl.chunks_exact(entsize).enumerate() {
+            let e = Rd::new(ent, &endian);
+            let (name, info, shndx, value, size) = if is64 {
+                (e.u32(0), e.u8(4), e.u16(6), e.u64(8), e.u64(16))
+            } else {
+                (e.u32(0), e.u8(12), e.u16(14), e.u32(4), e.u32(8))
+            };
+            let ndx = match shndx {
+                0 => "UND".to_string(),
+                0xfff1 => "ABS".to_string(),
+                0xfff2 => "COMMON".to_string(),
+                n => shdrs
+                    .get(n as usize)
+                    .map_or(n.to_string(), |t| t.name.clone()),
+            };
+            out.push(
+                format!("{}[{}] {}", s.name, j, str_at(&strtab, name as usize)),
+                s.offset as usize + j * entsize,
+                entsize,
+                format!(
+                    "{} {} value=0x{:x} size={} {}",
+                    lookup(info & 0xf, STT_TYPES),
+                    lookup(info >> 4, STB_BINDS),
+                    value,
+                    size,
+                    ndx
+                ),
+            );
+        }
+        if count > SYMBOLS_MAX {
+            out.push(
+                format!("{}[{}..]", s.name, SYMBOLS_MAX),
+                s.offset as usize + SYMBOLS_MAX * entsize,
+                (count - SYMBOLS_MAX) * entsize,
+                format!("{} more symbols", count - SYMBOLS_MAX),
+            );
+        }
+    }
+
+    Ok(ExeInfo {
+        summary: format!(
+            "ELF{} {} {}, {} program headers, {} sections",
+            if is64 { 64 } else { 32 },
+            lookup(e_machine, ELF_MACHINES),
+            lookup(e_type, ELF_TYPES),
+            phnum,
+            shdrs.len()
+        ),
+        fields: out.fields,
+        sections: nav.fields,
+    })
+}
+
+// ---------------- PE/COFF ----------------
+
+const PE_MACHINES: &[(u64, &str)] = &[
+    (0x14c, "i386"),
+    (0x1c0, "ARM"),
+    (0x1c4, "ARMv7"),
+    (0x200, "IA64"),
+    (0x8664, "AMD64"),
+    (0xaa64, "ARM64"),
+];
+
+const PE_CHARACTERISTICS: &[(u64, &str)] = &[
+    (0x0001, "RELOCS_STRIPPED"),
+    (0x0002, "EXECUTABLE_IMAGE"),
+    (0x0020, "LARGE_ADDRESS_AWARE"),
+    (0x0100, "32BIT_MACHINE"),
+    (0x0200, "DEBUG_STRIPPED"),
+    (0x1000, "SYSTEM"),
+    (0x2000, "DLL"),
+];
+
+const PE_SUBSYSTEMS: &[(u64, &str)] = &[
+    (1, "NATIVE"),
+    (2, "WINDOWS_GUI"),
+    (3, "WINDOWS_CUI"),
+    (9, "WINDOWS_CE_GUI"),
+    (10, "EFI_APPLICATION"),
+    (11, "EFI_BOOT_SERVICE_DRIVER"),
+    (12, "EFI_RUNTIME_DRIVER"),
+];
+
+const PE_DIRECTORIES: &[&str] = &[
+    "Export",
+    "Import",
+    "Resource",
+    "Exception",
+    "Certificate",
+    "BaseReloc",
+    "Debug",
+    "Architecture",
+    "GlobalPtr",
+    "TLS",
+    "LoadConfig",
+    "BoundImport",
+    "IAT",
+    "DelayImport",
+    "CLR",
+    "Reserved",
+];
+
+const SCN_FLAGS: &[(u64, &str)] = &[
+    (0x20, "CODE"),
+    (0x40, "INITIALIZED_DATA"),
+    (0x80, "UNINITIALIZED_DATA"),
+    (0x02000000, "DISCARDABLE"),
+    (0x10000000, "SHARED"),
+    (0x20000000, "EXECUTE"),
+    (0x40000000, "READ"),
+    (0x80000000, "WRITE"),
+];
+
+const PE_SECTION_SIZE: usize = 40;
+const PE_CERTIFICATE_DIRECTORY: usize = 4;
+
+fn decode_pe(src: &dyn ByteSource) -> ChapResult<ExeInfo> {
+    let mut out = PageFields::new(0);
+    let mut nav = PageFields::new(0);
+    let dos = src.read(0, 64);
+    let d = Rd::new(&dos, &Endian::Little);
+    let lfanew = d.u32(0x3c) as usize;
+    out.push("e_magic", 0, 2, "MZ");
+    out.push("e_lfanew", 0x3c, 4, format!("0x{:x}", lfanew));
+    out.push("signature", lfanew, 4, "PE");
+
+    let coff_off = lfanew + 4;
+    let coff = src.read(coff_off, 20);
+    if coff.len() < 20 {
+        return Err("truncated COFF header".into());
+    }
+    let c = Rd::new(&coff, &Endian::Little);
+    let machine = c.u16(0);
+    let nsections = c.u16(2) as usize;
+    let opt_size = c.u16(16) as usize;
+    out.push("Machine", coff_off, 2, lookup(machine, PE_MACHINES));
+    out.push("NumberOfSections", coff_off + 2, 2, nsections.to_string());
+    out.push(
+        "TimeDateStamp",
+        coff_off + 4,
+        4,
+        format!("{} (unix time)", c.u32(4)),
+    );
+    out.push(
+        "PointerToSymbolTable",
+        coff_off + 8,
+        4,
+        format!("0x{:x}", c.u32(8)),
+    );
+    out.push("NumberOfSymbols", coff_off + 12, 4, c.u32(12).to_string());
+    out.push(
+        "SizeOfOptionalHeader",
+        coff_off + 16,
+        2,
+        opt_size.to_string(),
+    );
+    out.push(
+        "Characteristics",
+        coff_off + 18,
+        2,
+        format!(
+            "0x{:04x} {}",
+            c.u16(18),
+            flag_names(c.u16(18), PE_CHARACTERISTICS)
+        ),
+    );
+
+    // 可选头 PE32 和 PE32+ 的 ImageBase 长度和数据目录位置不同
+    let opt_off = coff_off + 20;
+    let opt = src.read(opt_off, opt_size);
+    let o = Rd::new(&opt, &Endian::Little);
+    let magic = o.u16(0);
+    let plus = magic == 0x20b;
+    let mut dirs: Vec<(usize, u64, u64)> = Vec::new();
+    if opt.len() >= 2 {
+        out.push(
+            "Magic",
+            opt_off,
+            2,
+            match magic {
+                0x10b => "0x10b PE32".to_string(),
+                0x20b => "0x20b PE32+".to_string(),
+                m => format!("0x{:x}", m),
+            },
+        );
+        out.push(
+            "AddressOfEntryPoint",
+            opt_off + 16,
+            4,
+            format!("0x{:x}", o.u32(16)),
+        );
+        let (base_at, base_len) = if plus { (24, 8) } else { (28, 4) };
+        out.push(
+            "ImageBase",
+            opt_off + base_at,
+            base_len,
+            format!("0x{:x}", o.uint(base_at, base_len)),
+        );
+        const U32_FIELDS: &[(&str, usize)] = &[
+            ("SectionAlignment", 32),
+            ("FileAlignment", 36),
+            ("SizeOfImage", 56),
+            ("SizeOfHeaders", 60),
+            ("CheckSum", 64),
+        ];
+        for (name, off) in U32_FIELDS {
+            out.push(*name, opt_off + off, 4, format!("0x{:x}", o.u32(*off)));
+        }
+        out.push(
+            "Subsystem",
+            opt_off + 68,
+            2,
+            lookup(o.u16(68), PE_SUBSYSTEMS),
+        );
+        out.push(
+            "DllCharacteristics",
+            opt_off + 70,
+            2,
+            format!("0x{:04x}", o.u16(70)),
+        );
+        let (n_at, dir_at) = if plus { (108, 112) } else { (92, 96) };
+        let ndirs = (o.u32(n_at) as usize).min(PE_DIRECTORIES.len());
+        out.push("NumberOfRvaAndSizes", opt_off + n_at, 4, ndirs.to_string());
+        for (i, name) in PE_DIRECTORIES.iter().enumerate().take(ndirs) {
+            let at = dir_at + i * 8;
+            let (rva, size) = (o.u32(at), o.u32(at + 4));
+            if rva == 0 && size == 0 {
+                continue;
+            }
+            out.push(
+                format!("DataDirectory[{}] {}", i, name),
+                opt_off + at,
+                8,
+                format!("rva=0x{:x} size=0x{:x}", rva, size),
+            );
+            dirs.push((i, rva, size));
+        }
+    }
+
+    // 节表
+    let sec_off = opt_off + opt_size;
+    let tbl = src.read(sec_off, nsections * PE_SECTION_SIZE);
+    let mut sections = Vec::new();
+    for (i, ent) in tbl.chunks_exact(PE_SECTION_SIZE).enumerate() {
+        let s = Rd::new(ent, &Endian::Little);
+        let name = cstr(&ent[..8]);
+        let (vsize, rva, raw_size, raw_ptr, flags) =
+            (s.u32(8), s.u32(12), s.u32(16), s.u32(20), s.u32(36));
+        out.push(
+            format!("section[{}] {}", i, name),
+            sec_off + i * PE_SECTION_SIZE,
+            PE_SECTION_SIZE,
+            format!(
+                "vsize=0x{:x} rva=0x{:x} raw=0x{:x}+0x{:x} {}",
+                vsize,
+                rva,
+                raw_ptr,
+                raw_size,
+                flag_names(flags, SCN_FLAGS)
+            ),
+        );
+        if raw_ptr != 0 && raw_size > 0 {
+            nav.push(
+                format!("section[{}] {}", i, name),
+                raw_ptr as usize,
+                raw_size as usize,
+                format!("rva=0x{:x} {}", rva, flag_names(flags, SCN_FLAGS)),
+            );
+        }
+        sections.push((rva, vsize.max(raw_size), raw_ptr));
+    }
+    // 数据目录的 rva 转换为文件偏移 证书目录保存的就是文件偏移
+    for (i, rva, size) in dirs {
+        let file_off = if i == PE_CERTIFICATE_DIRECTORY {
+            Some(rva)
+        } else {
+            sections
+                .iter()
+                .find(|(va, len, _)| rva >= *va && rva < va + len)
+                .map(|(va, _, raw)| raw + rva - va)
+        };
+        if let Some(off) = file_off {
+            nav.push(
+                format!("directory {}", PE_DIRECTORIES[i]),
+                off as usize,
+                size as usize,
+                format!("rva=0x{:x}", rva),
+            );
+        }
+    }
+
+    Ok(ExeInfo {
+        summary: format!(
+            "PE{} {}, {} sections",
+            if plus { "32+" } else { "32" },
+            lookup(machine, PE_MACHINES),
+            nsections
+        ),
+        fields: out.fields,
+        sections: nav.fields,
+    })
+}
+
+// ---------------- Mach-O ----------------
+
+const CPU_TYPES: &[(u64, &str)] = &[
+    (7, "x86"),
+    (0x01000007, "x86_64"),
+    (12, "arm"),
+    (0x0100000c, "arm64"),
+    (0x0200000c, "arm64_32"),
+    (18, "ppc"),
+    (0x01000012, "ppc64"),
+];
+
+const MH_FILETYPES: &[(u64, &str)] = &[
+    (1, "OBJECT"),
+    (2, "EXECUTE"),
+    (3, "FVMLIB"),
+    (4, "CORE"),
+    (5, "PRELOAD"),
+    (6, "DYLIB"),
+    (7, "DYLINKER"),
+    (8, "BUNDLE"),
+    (9, "DYLIB_STUB"),
+    (10, "DSYM"),
+    (11, "KEXT_BUNDLE"),
+    (12, "FILESET"),
+];
+
+const LC_SEGMENT: u64 = 0x1;
+const LC_SYMTAB: u64 = 0x2;
+const LC_SEGMENT_64: u64 = 0x19;
+const LC_UUID: u64 = 0x1b;
+const LC_MAIN: u64 = 0x80000028;
+const LC_BUILD_VERSION: u64 = 0x32;
+
+const LC_NAMES: &[(u64, &str)] = &[
+    (0x1, "LC_SEGMENT"),
+    (0x2, "LC_SYMTAB"),
+    (0x4, "LC_THREAD"),
+    (0x5, "LC_UNIXTHREAD"),
+    (0xb, "LC_DYSYMTAB"),
+    (0xc, "LC_LOAD_DYLIB"),
+    (0xd, "LC_ID_DYLIB"),
+    (0xe, "LC_LOAD_DYLINKER"),
+    (0xf, "LC_ID_DYLINKER"),
+    (0x19, "LC_SEGMENT_64"),
+    (0x1b, "LC_UUID"),
+    (0x1d, "LC_CODE_SIGNATURE"),
+    (0x1e, "LC_SEGMENT_SPLIT_INFO"),
+    (0x21, "LC_ENCRYPTION_INFO"),
+    (0x22, "LC_DYLD_INFO"),
+    (0x24, "LC_VERSION_MIN_MACOSX"),
+    (0x25, "LC_VERSION_MIN_IPHONEOS"),
+    (0x26, "LC_FUNCTION_STARTS"),
+    (0x29, "LC_DATA_IN_CODE"),
+    (0x2a, "LC_SOURCE_VERSION"),
+    (0x2c, "LC_ENCRYPTION_INFO_64"),
+    (0x2d, "LC_LINKER_OPTION"),
+    (0x32, "LC_BUILD_VERSION"),
+    (0x80000018, "LC_LOAD_WEAK_DYLIB"),
+    (0x8000001c, "LC_RPATH"),
+    (0x8000001f, "LC_REEXPORT_DYLIB"),
+    (0x80000022, "LC_DYLD_INFO_ONLY"),
+    (0x80000028, "LC_MAIN"),
+    (0x80000033, "LC_DYLD_EXPORTS_TRIE"),
+    (0x80000034, "LC_DYLD_CHAINED_FIXUPS"),
+];
+
+// 以 lc_str 保存路径的命令 (dylib dylinker rpath)
+const LC_WITH_PATH: &[u64] = &[0xc, 0xd, 0xe, 0xf, 0x80000018, 0x8000001c, 0x8000001f];
+
+// 没有文件数据的节类型
+const S_ZEROFILL: u64 = 0x1;
+const S_GB_ZEROFILL: u64 = 0xc;
+const S_THREAD_LOCAL_ZEROFILL: u64 = 0x12;
+
+// vm_prot_t
+fn prot(p: u64) -> String {
+    let bit = |b: u64, c: char| if p & b != 0 { c } else { '-' };
+    format!("{}{}{}", bit(1, 'r'), bit(2, 'w'), bit(4, 'x'))
+}
+
+// xxxx.yy.zz
+fn macho_version(v: u64) -> String {
+    format!("{}.{}.{}", v >> 16, (v >> 8) & 0xff, v & 0xff)
+}
+
+fn decode_macho(src: &dyn ByteSource) -> ChapResult<ExeInfo> {
+    let m = src.read(0, 4);
+    if m.len() < 4 {
+        return Err("truncated Mach-O header".into());
+    }
+    let (is64, endian) = match u32::from_le_bytes(m[..4].try_into().unwrap()) {
+        0xfeedface => (false, Endian::Little),
+        0xfeedfacf => (true, Endian::Little),
+        0xcefaedfe => (false, Endian::Big),
+        0xcffaedfe => (true, Endian::Big),
+        _ => return Err("invalid Mach-O magic".into()),
+    };
+    let hsize = if is64 { 32 } else { 28 };
+    let hdr = src.read(0, hsize);
+    if hdr.len() < hsize {
+        return Err("truncated Mach-O header".into());
+    }
+    let h = Rd::new(&hdr, &endian);
+    let mut out = PageFields::new(0);
+    let mut nav = PageFields::new(0);
+    let cputype = h.u32(4);
+    let filetype = h.u32(12);
+    let ncmds = h.u32(16) as usize;
+    let sizeofcmds = h.u32(20) as usize;
+    out.push(
+        "magic",
+        0,
+        4,
+        format!(
+            "0x{:08x} {}-bit {}",
+            h.u32(0),
+            if is64 { 64 } else { 32 },
+            if endian == Endian::Little {
+                "little-endian"
+            } else {
+                "big-endian"
+            }
+        ),
+    );
+    out.push("cputype", 4, 4, lookup(cputype, CPU_TYPES));
+    out.push("cpusubtype", 8, 4, format!("0x{:x}", h.u32(8)));
+    out.push("filetype", 12, 4, lookup(filetype, MH_FILETYPES));
+    out.push("ncmds", 16, 4, ncmds.to_string());
+    out.push("sizeofcmds", 20, 4, sizeofcmds.to_string());
+    out.push("flags", 24, 4, format!("0x{:08x}", h.u32(24)));
+
+    let cmds = src.read(hsize, sizeofcmds);
+    let mut pos = 0;
+    let mut symtab = None;
+    for i in 0..ncmds {
+        let c = Rd::new(&cmds[pos.min(cmds.len())..], &endian);
+        let cmd = c.u32(0);
+        let cmdsize = c.u32(4) as usize;
+        let off = hsize + pos;
+        if cmdsize < 8 || pos + cmdsize > cmds.len() {
+            out.push(
+                format!("lc[{}]", i),
+                off,
+                0,
+                format!("invalid cmdsize {}", cmdsize),
+            );
+            break;
+        }
+        let data = &cmds[pos..pos + cmdsize];
+        let c = Rd::new(data, &endian);
+        let name = lookup(cmd, LC_NAMES);
+        match cmd {
+            LC_SEGMENT | LC_SEGMENT_64 => {
+                let seg64 = cmd == LC_SEGMENT_64;
+                let segname = cstr(&data[8..24.min(cmdsize)]);
+                let w = if seg64 { 8 } else { 4 };
+                let vmaddr = c.word(24, seg64);
+                let vmsize = c.word(24 + w, seg64);
+                let fileoff = c.word(24 + 2 * w, seg64);
+                let filesize = c.word(24 + 3 * w, seg64);
+                let p = 24 + 4 * w;
+                let (maxprot, initprot, nsects) = (c.u32(p), c.u32(p + 4), c.u32(p + 8));
+                out.push(
+                    format!("lc[{}] {} {}", i, name, segname),
+                    off,
+                    cmdsize,
+                    format!(
+                        "vmaddr=0x{:x} vmsize=0x{:x} fileoff=0x{:x} filesize=0x{:x} {}/{}",
+                        vmaddr,
+                        vmsize,
+                        fileoff,
+                        filesize,
+                        prot(initprot),
+                        prot(maxprot)
+                    ),
+                );
+                if filesize > 0 {
+                    nav.push(
+                        format!("segment {}", segname),
+                        fileoff as usize,
+                        filesize as usize,
+                        format!("vmaddr=0x{:x} {}", vmaddr, prot(initprot)),
+                    );
+                }
+                let (sect_start, sect_size) = if seg64 { (72, 80) } else { (56, 68) };
+                for j in 0..nsects as usize {
+                    let at = sect_start + j * sect_size;
+                    if at + sect_size > cmdsize {
+                        break;
+                    }
+                    let sect = &data[at..at + sect_size];
+                    let s = Rd::new(sect, &endian);
+                    let sectname = format!("{},{}", cstr(&sect[16..32]), cstr(&sect[..16]));
+                    let addr = s.word(32, seg64);
+                    let size = s.word(32 + w, seg64);
+                    let offset = s.u32(32 + 2 * w);
+                    let flags = s.u32(32 + 2 * w + 16);
+                    out.push(
+                        format!("lc[{}].sect[{}] {}", i, j, sectname),
+                        off + at,
+                        sect_size,
+                        format!(
+                            "addr=0x{:x} size=0x{:x} offset=0x{:x} flags=0x{:08x}",
+                            addr, size, offset, flags
+                        ),
+                    );
+                    let zerofill = matches!(
+                        flags & 0xff,
+                        S_ZEROFILL | S_GB_ZEROFILL | S_THREAD_LOCAL_ZEROFILL
+                    );
+                    if offset != 0 && size > 0 && !zerofill {
+                        nav.push(
+                            format!("section {}", sectname),
+                            offset as usize,
+                            size as usize,
+                            format!("addr=0x{:x}", addr),
+                        );
+                    }
+                }
+            }
+            LC_SYMTAB => {
+                let (symoff, nsyms, stroff, strsize) = (c.u32(8), c.u32(12), c.u32(16), c.u32(20));
+                out.push(
+                    format!("lc[{}] {}", i, name),
+                    off,
+                    cmdsize,
+                    format!(
+                        "symoff=0x{:x} nsyms={} stroff=0x{:x} strsize={}",
+                        symoff, nsyms, stroff, strsize
+                    ),
+                );
+                symtab = Some((
+                    symoff as usize,
+                    nsyms as usize,
+                    stroff as usize,
+                    strsize as usize,
+                ));
+            }
+            LC_UUID => {
+                let uuid = hex::encode(data.get(8..24).unwrap_or_default());
+                out.push(format!("lc[{}] {}", i, name), off, cmdsize, uuid);
+            }
+            LC_MAIN => {
+                out.push(
+                    format!("lc[{}] {}", i, name),
+                    off,
+                    cmdsize,
+                    format!("entryoff=0x{:x} stacksize={}", c.u64(8), c.u64(16)),
+                );
+            }
+            LC_BUILD_VERSION => {
+                out.push(
+                    format!("lc[{}] {}", i, name),
+                    off,
+                    cmdsize,
+                    format!(
+                        "platform {} minos {} sdk {}",
+                        c.u32(8),
+                        macho_version(c.u32(12)),
+                        macho_version(c.u32(16))
+                    ),
+                );
+            }
+            cmd if LC_WITH_PATH.contains(&cmd) => {
+                let path = str_at(data, c.u32(8) as usize);
+                out.push(format!("lc[{}] {}", i, name), off, cmdsize, path);
+            }
+            _ => {
+                out.push(
+                    format!("lc[{}] {}", i, name),
+                    off,
+                    cmdsize,
+                    format!("{} bytes", cmdsize),
+                );
+            }
+        }
+        pos += cmdsize;
+    }
+
+    // nlist 符号表
+    if let Some((symoff, nsyms, stroff, strsize)) = symtab {
+        let entsize = if is64 { 16 } else { 12 };
+        let strtab = src.read(stroff, strsize);
+        let tbl = src.read(symoff, nsyms.min(SYMBOLS_MAX) * entsize);
+        for (j, ent) in tbl.chunks_exact(entsize).enumerate() {
+            let e = Rd::new(ent, &endian);
+            let (strx, ntype, nsect, value) = (e.u32(0), e.u8(4), e.u8(5), e.word(8, is64));
+            out.push(
+                format!("sym[{}] {}", j, str_at(&strtab, strx as usize)),
+                symoff + j * entsize,
+                entsize,
+                format!(
+                    "type=0x{:02x}{} sect={} value=0x{:x}",
+                    ntype,
+                    if ntype & 0x01 != 0 { " EXT" } else { "" },
+                    nsect,
+                    value
+                ),
+            );
+        }
+        if nsyms > SYMBOLS_MAX {
+            out.push(
+                format!("sym[{}..]", SYMBOLS_MAX),
+                symoff + SYMBOLS_MAX * entsize,
+                (nsyms - SYMBOLS_MAX) * entsize,
+                format!("{} more symbols", nsyms - SYMBOLS_MAX),
+            );
+        }
+        nav.push(
+            "symtab",
+            symoff,
+            nsyms * entsize,
+            format!("{} symbols", nsyms),
+        );
+        nav.push("strtab", stroff, strsize, format!("{} bytes", strsize));
+    }
+
+    Ok(ExeInfo {
+        summary: format!(
+            "Mach-O {} {}, {} load commands",
+            lookup(cputype, CPU_TYPES),
+            lookup(filetype, MH_FILETYPES),
+            ncmds
+        ),
+        fields: out.fields,
+        sections: nav.fields,
+    })
+}
+
+// universal binary 中的一个架构 偏移相对于 base
+struct Slice<'a> {
+    src: &'a dyn ByteSource,
+    base: usize,
+    size: usize,
+}
+
+impl ByteSource for Slice<'_> {
+    fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+        match self.base.checked_add(offset) {
+            Some(start) if offset < self.size => self.src.read(start, len.min(self.size - offset)),
+            _ => Vec::new(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+}
+
+// fat_header 和 fat_arch 总是大端
+fn decode_fat(src: &dyn ByteSource) -> ChapResult<ExeInfo> {
+    let hdr = src.read(0, 8);
+    let h = Rd::new(&hdr, &Endian::Big);
+    let fat64 = h.u32(0) == 0xcafebabf;
+    let nfat = h.u32(4) as usize;
+    let entsize = if fat64 { 32 } else { 20 };
+    let mut out = PageFields::new(0);
+    let mut nav = PageFields::new(0);
+    out.push(
+        "magic",
+        0,
+        4,
+        if fat64 {
+            "0xcafebabf FAT_MAGIC_64"
+        } else {
+            "0xcafebabe FAT_MAGIC"
+        },
+    );
+    out.push("nfat_arch", 4, 4, nfat.to_string());
+    let tbl = src.read(8, nfat * entsize);
+    let mut archs = Vec::new();
+    for (i, ent) in tbl.chunks_exact(entsize).enumerate() {
+        let a = Rd::new(ent, &Endian::Big);
+        let cpu = lookup(a.u32(0), CPU_TYPES);
+        let (offset, size, align) = if fat64 {
+            (a.u64(8), a.u64(16), a.u32(24))
+        } else {
+            (a.u32(8), a.u32(12), a.u32(16))
+        };
+        out.push(
+            format!("fat_arch[{}] {}", i, cpu),
+            8 + i * entsize,
+            entsize,
+            format!("offset=0x{:x} size=0x{:x} align=2^{}", offset, size, align),
+        );
+        nav.push(
+            format!("arch {}", cpu),
+            offset as usize,
+            size as usize,
+            format!("{} bytes", size),
+        );
+        archs.push((cpu, offset as usize, size as usize));
+    }
+    // 解析每个架构 字段偏移转换为文件偏移
+    for (cpu, base, size) in archs {
+        let slice = Slice { src, base, size };
+        let info = match decode_macho(&slice) {
+            Ok(info) => info,
+            Err(e) => {
+                out.push(format!("[{}]", cpu), base, 0, e.to_string());
+                continue;
+            }
+        };
+        let shift = |fields: Vec<PluginField>| -> ChapResult<Vec<PluginField>> {
+            fields
+                .into_iter()
+                .map(|f| {
+                    let offset = f
+                        .offset
+                        .checked_add(base)
+                        .ok_or_else(|| format!("[{}] offset overflow", cpu))?;
+                    Ok(PluginField {
+                        name: format!("[{}] {}", cpu, f.name),
+                        offset,
+                        ..f
+                    })
+                })
+                .collect()
+        };
+        out.fields.extend(shift(info.fields)?);
+        nav.fields.extend(shift(info.sections)?);
+    }
+    Ok(ExeInfo {
+        summary: format!("Mach-O universal binary, {} architectures", nfat),
+        fields: out.fields,
+        sections: nav.fields,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(buf: &mut [u8], off: usize, bytes: &[u8]) {
+        buf[off..off + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn field<'a>(fields: &'a [PluginField], name: &str) -> &'a PluginField {
+        fields
+            .iter()
+            .find(|f| f.name == name)
+            .unwrap_or_else(|| panic!("field {} not found", name))
+    }
+
+    // .text .shstrtab .symtab .strtab 四个节
+    fn elf64() -> Vec<u8> {
+        let mut b = vec![0u8; 0x1e0];
+        put(&mut b, 0, b"\x7fELF\x02\x01\x01");
+        put(&mut b, 16, &3u16.to_le_bytes());
+        put(&mut b, 18, &62u16.to_le_bytes());
+        put(&mut b, 20, &1u32.to_le_bytes());
+        put(&mut b, 24, &0x40u64.to_le_bytes());
+        put(&mut b, 40, &0xa0u64.to_le_bytes());
+        put(&mut b, 52, &64u16.to_le_bytes());
+        put(&mut b, 58, &64u16.to_le_bytes());
+        put(&mut b, 60, &5u16.to_le_bytes());
+        put(&mut b, 62, &2u16.to_le_bytes());
+        put(&mut b, 0x40, b"\x90\x90\xc3");
+        put(&mut b, 0x44, b"\0.text\0.shstrtab\0.symtab\0.strtab\0");
+        put(&mut b, 0x68, b"\0main\0");
+        // main GLOBAL FUNC
+        put(&mut b, 0x88, &1u32.to_le_bytes());
+        put(&mut b, 0x8c, &[0x12, 0]);
+        put(&mut b, 0x8e, &1u16.to_le_bytes());
+        put(&mut b, 0x90, &0x40u64.to_le_bytes());
+        put(&mut b, 0x98, &3u64.to_le_bytes());
+        // name type flags offset size link entsize
+        let shdrs: [(u32, u32, u64, u64, u64, u32, u64); 4] = [
+            (1, 1, 0x6, 0x40, 3, 0, 0),
+            (7, 3, 0, 0x44, 33, 0, 0),
+            (17, 2, 0, 0x70, 48, 4, 24),
+            (25, 3, 0, 0x68, 6, 0, 0),
+        ];
+        for (i, (name, typ, flags, offset, size, link, entsize)) in shdrs.iter().enumerate() {
+            let at = 0xa0 + (i + 1) * 64;
+            put(&mut b, at, &name.to_le_bytes());
+            put(&mut b, at + 4, &typ.to_le_bytes());
+            put(&mut b, at + 8, &flags.to_le_bytes());
+            put(&mut b, at + 24, &offset.to_le_bytes());
+            put(&mut b, at + 32, &size.to_le_bytes());
+            put(&mut b, at + 40, &link.to_le_bytes());
+            put(&mut b, at + 56, &entsize.to_le_bytes());
+        }
+        b
+    }
+
+    fn macho64() -> Vec<u8> {
+        let mut b = vec![0u8; 0x200];
+        put(&mut b, 0, &0xfeedfacfu32.to_le_bytes());
+        put(&mut b, 4, &0x01000007u32.to_le_bytes());
+        put(&mut b, 12, &2u32.to_le_bytes());
+        put(&mut b, 16, &2u32.to_le_bytes());
+        put(&mut b, 20, &176u32.to_le_bytes());
+        // LC_SEGMENT_64 __TEXT 带一个 __text 节
+        put(&mut b, 32, &0x19u32.to_le_bytes());
+        put(&mut b, 36, &152u32.to_le_bytes());
+        put(&mut b, 40, b"__TEXT");
+        put(&mut b, 56, &0x1000u64.to_le_bytes());
+        put(&mut b, 64, &0x1000u64.to_le_bytes());
+        put(&mut b, 80, &0x200u64.to_le_bytes());
+        put(&mut b, 88, &5u32.to_le_bytes());
+        put(&mut b, 92, &5u32.to_le_bytes());
+        put(&mut b, 96, &1u32.to_le_bytes());
+        put(&mut b, 104, b"__text");
+        put(&mut b, 120, b"__TEXT");
+        put(&mut b, 136, &0x1100u64.to_le_bytes());
+        put(&mut b, 144, &0x10u64.to_le_bytes());
+        put(&mut b, 152, &0x100u32.to_le_bytes());
+        // LC_SYMTAB
+        put(&mut b, 184, &0x2u32.to_le_bytes());
+        put(&mut b, 188, &24u32.to_le_bytes());
+        put(&mut b, 192, &0x180u32.to_le_bytes());
+        put(&mut b, 196, &1u32.to_le_bytes());
+        put(&mut b, 200, &0x190u32.to_le_bytes());
+        put(&mut b, 204, &8u32.to_le_bytes());
+        put(&mut b, 0x180, &1u32.to_le_bytes());
+        put(&mut b, 0x184, &[0x0f, 1]);
+        put(&mut b, 0x188, &0x1100u64.to_le_bytes());
+        put(&mut b, 0x190, b"\0_main\0");
+        b
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(ExeFormat::detect(&elf64()), Some(ExeFormat::Elf));
+        assert_eq!(ExeFormat::detect(&macho64()), Some(ExeFormat::MachO));
+        // java class: cafebabe 后是版本号
+        let class = b"\xca\xfe\xba\xbe\x00\x00\x00\x34".to_vec();
+        assert_eq!(ExeFormat::detect(&class), None);
+        assert!(decode_exe(&class).is_err());
+    }
+
+    #[test]
+    fn test_decode_elf() {
+        let info = decode_exe(&elf64()).unwrap();
+        assert_eq!(
+            info.summary,
+            "ELF64 x86-64 DYN, 0 program headers, 5 sections"
+        );
+        assert_eq!(field(&info.fields, "e_entry").value, "0x40");
+        let text = field(&info.fields, "shdr[1] .text");
+        assert_eq!(text.offset, 0xa0 + 64);
+        assert_eq!(text.value, "PROGBITS off=0x40 addr=0x0 size=0x3 A|X");
+        let main = field(&info.fields, ".symtab[1] main");
+        assert_eq!(main.offset, 0x88);
+        assert_eq!(main.value, "FUNC GLOBAL value=0x40 size=3 .text");
+        let names: Vec<_> = info.sections.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "section[1] .text",
+                "section[2] .shstrtab",
+                "section[3] .symtab",
+                "section[4] .strtab"
+            ]
+        );
+        assert_eq!(
+            (info.sections[0].offset, info.sections[0].length),
+            (0x40, 3)
+        );
+    }
+
+    #[test]
+    fn test_decode_pe() {
+        let mut b = vec![0u8; 0x400];
+        put(&mut b, 0, b"MZ");
+        put(&mut b, 0x3c, &0x40u32.to_le_bytes());
+        put(&mut b, 0x40, b"PE\0\0");
+        put(&mut b, 0x44, &0x8664u16.to_le_bytes());
+        put(&mut b, 0x46, &1u16.to_le_bytes());
+        put(&mut b, 0x54, &0xf0u16.to_le_bytes());
+        put(&mut b, 0x56, &0x22u16.to_le_bytes());
+        let opt = 0x58;
+        put(&mut b, opt, &0x20bu16.to_le_bytes());
+        put(&mut b, opt + 16, &0x1000u32.to_le_bytes());
+        put(&mut b, opt + 108, &16u32.to_le_bytes());
+        put(&mut b, opt + 120, &0x1010u32.to_le_bytes());
+        put(&mut b, opt + 124, &0x10u32.to_le_bytes());
+        let sec = opt + 0xf0;
+        put(&mut b, sec, b".text");
+        put(&mut b, sec + 8, &0x100u32.to_le_bytes());
+        put(&mut b, sec + 12, &0x1000u32.to_le_bytes());
+        put(&mut b, sec + 16, &0x200u32.to_le_bytes());
+        put(&mut b, sec + 20, &0x200u32.to_le_bytes());
+        put(&mut b, sec + 36, &0x60000020u32.to_le_bytes());
+
+        assert_eq!(ExeFormat::detect(&b), Some(ExeFormat::Pe));
+        let info = decode_exe(&b).unwrap();
+        assert_eq!(info.summary, "PE32+ AMD64, 1 sections");
+        assert_eq!(
+            field(&info.fields, "Characteristics").value,
+            "0x0022 EXECUTABLE_IMAGE|LARGE_ADDRESS_AWARE"
+        );
+        assert_eq!(
+            field(&info.fields, "DataDirectory[1] Import").value,
+            "rva=0x1010 size=0x10"
+        );
+        assert_eq!(
+            field(&info.fields, "section[0] .text").value,
+            "vsize=0x100 rva=0x1000 raw=0x200+0x200 CODE|EXECUTE|READ"
+        );
+        let text = field(&info.sections, "section[0] .text");
+        assert_eq!((text.offset, text.length), (0x200, 0x200));
+        // rva 0x1010 位于 .text 中
+        let import = field(&info.sections, "directory Import");
+        assert_eq!((import.offset, import.length), (0x210, 0x10));
+    }
+
+    #[test]
+    fn test_decode_macho() {
+        let info = decode_exe(&macho64()).unwrap();
+        assert_eq!(info.summary, "Mach-O x86_64 EXECUTE, 2 load commands");
+        assert_eq!(
+            field(&info.fields, "lc[0] LC_SEGMENT_64 __TEXT").value,
+            "vmaddr=0x1000 vmsize=0x1000 fileoff=0x0 filesize=0x200 r-x/r-x"
+        );
+        let sect = field(&info.fields, "lc[0].sect[0] __TEXT,__text");
+        assert_eq!(sect.offset, 104);
+        assert_eq!(
+            sect.value,
+            "addr=0x1100 size=0x10 offset=0x100 flags=0x00000000"
+        );
+        assert_eq!(
+            field(&info.fields, "sym[0] _main").value,
+            "type=0x0f EXT sect=1 value=0x1100"
+        );
+        let text = field(&info.sections, "section __TEXT,__text");
+        assert_eq!((text.offset, text.length), (0x100, 0x10));
+    }
+
+    #[test]
+    fn test_decode_fat() {
+        let macho = macho64();
+        let mut b = vec![0u8; 0x1000];
+        put(&mut b, 0, &0xcafebabeu32.to_be_bytes());
+        put(&mut b, 4, &1u32.to_be_bytes());
+        put(&mut b, 8, &0x01000007u32.to_be_bytes());
+        put(&mut b, 16, &0x1000u32.to_be_bytes());
+        put(&mut b, 20, &(macho.len() as u32).to_be_bytes());
+        put(&mut b, 24, &12u32.to_be_bytes());
+        b.extend(macho);
+
+        assert_eq!(ExeFormat::detect(&b), Some(ExeFormat::Fat));
+        let info = decode_exe(&b).unwrap();
+        assert_eq!(
+            field(&info.fields, "fat_arch[0] x86_64").value,
+            "offset=0x1000 size=0x200 align=2^12"
+        );
+        let sym = field(&info.fields, "[x86_64] sym[0] _main");
+        assert_eq!(sym.offset, 0x1180);
+        let text = field(&info.sections, "[x86_64] section __TEXT,__text");
+        assert_eq!(text.offset, 0x1100);
+
+        // 架构偏移超出文件或大小不足4字节时不能 panic
+        put(&mut b, 16, &0xffff_0000u32.to_be_bytes());
+        let info = decode_exe(&b).unwrap();
+        assert_eq!(
+            field(&info.fields, "[x86_64]").value,
+            "Unexpected: truncated Mach-O header"
+        );
+        put(&mut b, 16, &0x1000u32.to_be_bytes());
+        put(&mut b, 20, &2u32.to_be_bytes());
+        assert!(decode_exe(&b).is_ok());
+    }
+}
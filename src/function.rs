@@ -1,9 +1,17 @@
 use crate::pg::format_item_ids;
 use crate::pg::format_va_extinfo;
 use crate::pg::format_varatt_external;
+use crate::pg::parse_btree_page;
 use crate::pg::parse_heap_tuple_header;
 use crate::pg::parse_pg_page_header;
+use crate::pgfile::parse_control;
+use crate::pgfile::parse_fsm;
+use crate::pgfile::parse_vm;
+use crate::plugin::ByteSource;
 use crate::plugin::Plugin;
+use crate::plugin::PluginOutput;
+use crate::plugin::PluginResult;
+use crate::tui::TextSelect;
 use crate::ChapResult;
 use once_cell::sync::Lazy;
 use std::collections::HashMap;
@@ -18,6 +26,10 @@ impl FunctionPlugin {
         let mut function_registry: HashMap<String, FunctionFn> = HashMap::new();
         function_registry.insert("hello".to_string(), hello);
         function_registry.insert("pg_page_header".to_string(), parse_pg_page_header);
+        function_registry.insert("pg_btree_page".to_string(), parse_btree_page);
+        function_registry.insert("pg_fsm".to_string(), parse_fsm);
+        function_registry.insert("pg_vm".to_string(), parse_vm);
+        function_registry.insert("pg_control".to_string(), parse_control);
         function_registry.insert("item_data".to_string(), format_item_ids);
         function_registry.insert("pg_heap_tuple".to_string(), parse_heap_tuple_header);
         function_registry.insert("format_va_extinfo".to_string(), format_va_extinfo);
@@ -33,11 +45,32 @@ impl FunctionPlugin {
 }
 
 impl Plugin for FunctionPlugin {
-    fn eval(&self, name: &str, buf: &[u8]) -> ChapResult<String> {
-        if let Some(func) = self.function_registry.get(name) {
-            return Ok(func(buf));
-        }
-        Ok("no function call".to_string())
+    fn eval(
+        &self,
+        name: &str,
+        _args: &[(String, String)],
+        src: &dyn ByteSource,
+        sel: &TextSelect,
+    ) -> ChapResult<PluginResult> {
+        let output = match self.function_registry.get(name) {
+            Some(func) => {
+                let buf = src.read(sel.get_start(), sel.get_end() + 1 - sel.get_start());
+                func(&buf)
+            }
+            None => "no function call".to_string(),
+        };
+        Ok(PluginResult {
+            output: PluginOutput::Text(output),
+            actions: Vec::new(),
+        })
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.function_registry.contains_key(name)
+    }
+
+    fn reload(&self) -> ChapResult<usize> {
+        Ok(self.function_registry.len())
     }
 
     fn list(&self) -> ChapResult<String> {
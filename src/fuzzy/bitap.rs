@@ -59,33 +59,102 @@ pub(crate) fn remove_overlapping(matches: Vec<Match>) -> Vec<Match> {
     result
 }
 
-pub(crate) struct Bitap {
-    length: usize,
-    masks: HashMap<char, usize>,
+// 模式中的符号 每个符号对应一个位掩码 字符用 HashMap 字节用 256 项的表
+pub(crate) trait Symbol: Copy {
+    type Masks;
+    fn masks(pattern: &[Self]) -> Self::Masks;
+    fn mask(masks: &Self::Masks, s: Self) -> usize;
 }
 
-impl Bitap {
-    pub(crate) fn new(pattern: &str) -> Bitap {
+impl Symbol for char {
+    type Masks = HashMap<char, usize>;
+
+    fn masks(pattern: &[char]) -> HashMap<char, usize> {
         let mut masks = HashMap::new();
-        let mut length = 0;
-        for (i, b) in pattern.chars().enumerate() {
+        for (i, &c) in pattern.iter().enumerate() {
             masks
-                .entry(b)
+                .entry(c)
                 .and_modify(|mask| *mask &= !(1usize << i))
                 .or_insert(!(1usize << i));
-            length += 1;
         }
+        masks
+    }
+
+    fn mask(masks: &HashMap<char, usize>, c: char) -> usize {
+        *masks.get(&c).unwrap_or(&!0usize)
+    }
+}
+
+impl Symbol for u8 {
+    type Masks = [usize; 256];
+
+    fn masks(pattern: &[u8]) -> [usize; 256] {
+        let mut masks = [!0usize; 256];
+        for (i, &b) in pattern.iter().enumerate() {
+            masks[b as usize] &= !(1usize << i);
+        }
+        masks
+    }
+
+    fn mask(masks: &[usize; 256], b: u8) -> usize {
+        masks[b as usize]
+    }
+}
+
+// 模式最长为 usize 的位数减一
+pub(crate) struct Bitap<T: Symbol> {
+    length: usize,
+    masks: T::Masks,
+}
+
+impl<T: Symbol> Bitap<T> {
+    pub(crate) fn new(pattern: &[T]) -> Bitap<T> {
         Bitap {
-            length: length,
-            masks: masks,
+            length: pattern.len(),
+            masks: T::masks(pattern),
         }
     }
 
+    // 编辑距离不超过 max_distance 的匹配 返回结束位置 (不含) 和最小的编辑距离
+    pub(crate) fn fuzzy_ends<'a>(
+        &'a self,
+        text: impl Iterator<Item = T> + 'a,
+        max_distance: usize,
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let m = self.length;
+        let max_distance = std::cmp::min(max_distance, m);
+        let mut r: Vec<usize> = (0..=max_distance).map(|i| !1usize << i).collect();
+        text.enumerate().filter_map(move |(i, b)| {
+            let mask = T::mask(&self.masks, b);
+            let mut prev_parent = r[0];
+            r[0] |= mask;
+            r[0] <<= 1;
+            for j in 1..r.len() {
+                let prev = r[j];
+                let current = (prev | mask) << 1;
+                let replace = prev_parent << 1;
+                let delete = r[j - 1] << 1;
+                let insert = prev_parent;
+                r[j] = current & insert & delete & replace;
+                prev_parent = prev;
+            }
+            r.iter()
+                .position(|rv| rv & (1usize << m) == 0)
+                .map(|k| (i + 1, k))
+        })
+    }
+}
+
+impl Bitap<char> {
+    pub(crate) fn for_str(pattern: &str) -> Bitap<char> {
+        Bitap::new(&pattern.chars().collect::<Vec<char>>())
+    }
+
     pub(crate) fn search<'a>(&'a self, text: &'a str) -> impl Iterator<Item = usize> + 'a {
         let m = self.length;
         let mut r: usize = !1usize; // Ini
         let matches = text.chars().enumerate().filter_map(move |(i, b)| {
-            r |= self.masks.get(&b).unwrap_or(&!0usize);
+            r |= char::mask(&self.masks, b);
             r <<= 1;
             if (r & (1usize << m)) == 0 {
                 return Some(i + 1 - m); // Return the start index of the match
@@ -95,48 +164,24 @@ impl Bitap {
         matches
     }
 
+    // 匹配的起止位置是字节偏移
     pub(crate) fn fuzzy_search<'a>(
         &'a self,
         text: &'a str,
         max_distance: usize,
     ) -> impl Iterator<Item = Match> + 'a {
         let m = self.length;
-        let max_distance = std::cmp::min(max_distance, m);
-        let mut r: Vec<usize> = (0..=max_distance).map(|i| !1usize << i).collect();
-        let matches = text
+        let offsets: Vec<usize> = text
             .char_indices()
-            .enumerate()
-            .filter_map(move |(i, (bytes_i, b))| {
-                let mask = self.masks.get(&b).unwrap_or(&!0usize);
-                let mut prev_parent = r[0];
-                r[0] |= mask;
-                r[0] <<= 1;
-                for j in 1..r.len() {
-                    let prev = r[j];
-                    let current = (prev | mask) << 1;
-                    let replace = prev_parent << 1;
-                    let delete = r[j - 1] << 1;
-                    let insert = prev_parent;
-                    r[j] = current & insert & delete & replace;
-                    prev_parent = prev;
-                }
-                for (k, rv) in r.iter().enumerate() {
-                    if rv & (1usize << m) == 0 {
-                        let start = if i >= m { i - m + 1 } else { 0 };
-                        return Some(Match {
-                            distance: k,
-                            start: text.char_indices().nth(start).map(|(i, _)| i).unwrap_or(0),
-                            end: text
-                                .char_indices()
-                                .nth(i + 1)
-                                .map(|(i, _)| i)
-                                .unwrap_or(text.len()), //i,
-                        });
-                    }
-                }
-                None
-            });
-        matches
+            .map(|(i, _)| i)
+            .chain(std::iter::once(text.len()))
+            .collect();
+        self.fuzzy_ends(text.chars(), max_distance)
+            .map(move |(end, distance)| Match {
+                distance,
+                start: offsets[end.saturating_sub(m)],
+                end: offsets[end],
+            })
     }
 }
 
@@ -148,7 +193,9 @@ mod tests {
         let p = "use";
         let distance = std::cmp::min(p.chars().count() / 3, 5);
         let t = "use super过前几个字如果你想从一个字符串中并从之后的位置开始截取";
-        let actual = Bitap::new(p).fuzzy_search(t, distance).collect::<Vec<_>>();
+        let actual = Bitap::for_str(p)
+            .fuzzy_search(t, distance)
+            .collect::<Vec<_>>();
         let cleaned_matches = remove_overlapping(actual);
         for v in cleaned_matches {
             println!("{:?}: find({:?}, {:?})", v, p, &t[v.start..v.end]);
@@ -171,11 +218,22 @@ mod tests {
         ];
 
         for (text, pattern, k, expected) in test_cases {
-            for i in Bitap::new(pattern).fuzzy_search(text, k) {
+            for i in Bitap::for_str(pattern).fuzzy_search(text, k) {
                 println!("text:{},pattern:{},{:?}", text, pattern, i);
             }
         }
 
         //println!("{:?}", actual);
     }
+
+    #[test]
+    fn test_byte_bitap() {
+        let text = b"\x00PK\x03\x04\x00PK\x07\x04\x00";
+        let bitap = Bitap::new(b"PK\x03\x04");
+        let ends: Vec<(usize, usize)> = bitap.fuzzy_ends(text.iter().copied(), 1).collect();
+        assert!(ends.contains(&(5, 0)));
+        assert!(ends.contains(&(10, 1)));
+        assert!(ends.iter().all(|&(_, k)| k <= 1));
+        assert_eq!(bitap.fuzzy_ends(text.iter().copied(), 0).count(), 1);
+    }
 }
@@ -119,7 +119,8 @@ impl<'a> BoyerMoore<'a> {
                     // Compute shift
                     let bad = self.bad_char_skip[window[j as usize] as usize];
                     let good = self.good_suffix_skip[j as usize];
-                    let shift = bad.max(good);
+                    // 跳跃距离是相对于失配位置的 窗口要减去已经匹配的后缀长度
+                    let shift = bad.max(good) - (m - 1 - j as usize);
                     // Slide window by shift
                     for _ in 0..shift {
                         window.remove(0);
@@ -182,6 +183,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_boyermoore_stream_suffix() {
+        // 后缀部分匹配后失配 跳跃距离要减去已匹配的长度 否则会跳过后面的匹配
+        let bm = BoyerMoore::new("abab".as_bytes());
+        let i: Vec<usize> = bm.stream("xbabab".bytes()).collect();
+        assert_eq!(i, [2]);
+        // 不会自身重叠的模式 与 find 的结果一致
+        let text = "aabbabaababbababbabaabab".as_bytes();
+        for pattern in ["ab", "aab", "abb", "aabab"] {
+            let bm = BoyerMoore::new(pattern.as_bytes());
+            let found: Vec<usize> = bm.find(text).collect();
+            let streamed: Vec<usize> = bm.stream(text.iter().copied()).collect();
+            assert_eq!(streamed, found, "{}", pattern);
+        }
+    }
+
     #[test]
     fn test_boyermoore_stream() {
         let pattern = "英文";
@@ -1,16 +1,16 @@
 use std::cmp::min;
 
-// 计算两个字符串的编辑距离，适合相似度比较
+// 计算两个序列的编辑距离，适合相似度比较 字节搜索时用来对齐匹配区域
 // 由一个转换成另一个所需的最少编辑操作次数
 // 将其中一个字符替换成另一个字符（Substitutions）。
 // 插入一个字符（Insertions）。
 // 删除一个字符（Deletions）。
-fn levenshtein(a: &str, b: &str) -> usize {
-    let mut column: Vec<usize> = (0..=a.chars().count()).collect();
-    for (x, ch2) in b.chars().enumerate() {
+pub(crate) fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut column: Vec<usize> = (0..=a.len()).collect();
+    for (x, ch2) in b.iter().enumerate() {
         let mut last_diag = x;
         column[0] = x + 1;
-        for (y, ch1) in a.chars().enumerate() {
+        for (y, ch1) in a.iter().enumerate() {
             let old_diag = column[y + 1];
             let cost = if ch1 == ch2 { 0 } else { 1 };
             column[y + 1] = min3(
@@ -21,7 +21,7 @@ fn levenshtein(a: &str, b: &str) -> usize {
             last_diag = old_diag;
         }
     }
-    column[a.chars().count()]
+    column[a.len()]
 }
 
 #[inline]
@@ -38,6 +38,12 @@ mod tests {
         wanted: usize,
     }
 
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        edit_distance(&a, &b)
+    }
+
     #[test]
     fn test_levenshtein() {
         let test_cases = vec![
@@ -106,5 +112,6 @@ mod tests {
                 distance, test.wanted, test.s, test.t
             );
         }
+        assert_eq!(edit_distance(b"\x7fELF\x02", b"\x7fEF\x02\x01"), 2);
     }
 }
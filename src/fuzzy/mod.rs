@@ -3,6 +3,7 @@ use std::vec;
 use crate::fuzzy::smithwaterman::MAXDIMS;
 use bitap::{remove_overlapping, Bitap};
 use boyermoore::BoyerMoore;
+use levenshtein::edit_distance;
 use smithwaterman::SmithWaterman;
 mod bitap;
 pub(crate) mod boyermoore;
@@ -36,6 +37,68 @@ pub(crate) struct ByteMatch {
     pub end: usize,
 }
 
+// 近似字节搜索的模式最大长度
+pub(crate) const APPROX_MAX: usize = usize::BITS as usize - 1;
+
+// 结束位置已知 在 min_start 之后找编辑距离最小的起点 长度接近模式的优先
+fn align(pattern: &[u8], text: &[u8], end: usize, distance: usize, min_start: usize) -> ByteMatch {
+    let m = pattern.len();
+    let mut best = ByteMatch {
+        distance: usize::MAX,
+        start: min_start.max(end.saturating_sub(m)),
+        end,
+    };
+    for delta in 0..=distance {
+        for len in [m.saturating_sub(delta), m + delta] {
+            if len == 0 || len > end - min_start {
+                continue;
+            }
+            let d = edit_distance(pattern, &text[end - len..end]);
+            if d < best.distance {
+                best.distance = d;
+                best.start = end - len;
+            }
+        }
+        if best.distance <= distance {
+            break;
+        }
+    }
+    if best.distance == usize::MAX {
+        best.distance = edit_distance(pattern, &text[best.start..end]);
+    }
+    best
+}
+
+// 与 pattern 编辑距离不超过 k 的字节区域 重叠的候选只保留距离最小的
+pub(crate) fn approx_find(pattern: &[u8], text: &[u8], k: usize) -> Vec<ByteMatch> {
+    let m = pattern.len();
+    let mut matches: Vec<ByteMatch> = Vec::new();
+    let mut best: Option<(usize, usize)> = None;
+    let mut emit = |(end, distance): (usize, usize), matches: &mut Vec<ByteMatch>| {
+        let min_start = matches.last().map_or(0, |l| l.end);
+        matches.push(align(pattern, text, end, distance, min_start));
+    };
+    for (end, distance) in Bitap::new(pattern).fuzzy_ends(text.iter().copied(), k) {
+        match best {
+            // 与当前候选重叠
+            Some((e, d)) if end < e + m => {
+                if distance < d {
+                    best = Some((end, distance));
+                }
+            }
+            Some(b) => {
+                emit(b, &mut matches);
+                best = Some((end, distance));
+            }
+            None => best = Some((end, distance)),
+        }
+    }
+    if let Some(b) = best {
+        emit(b, &mut matches);
+    }
+    matches
+}
+
 pub(crate) struct FuzzySearch {
     cache: Vec<i16>,
 }
@@ -66,7 +129,7 @@ impl FuzzySearch {
             );
         } else {
             if pattern_bytes.len() * text_bytes.len() > MAXDIMS {
-                let bitap = Bitap::new(pattern);
+                let bitap = Bitap::for_str(pattern);
                 let distance = std::cmp::min(pattern.chars().count() / 4, 5);
                 let m: Vec<bitap::Match> = bitap.fuzzy_search(text, distance).collect();
                 let m1 = remove_overlapping(m);
@@ -135,4 +198,21 @@ mod tests {
 
         println!("{:?}", spans);
     }
+
+    #[test]
+    fn test_approx_find() {
+        let text = b"..\x7fELF\x02\x01..\x7fEF\x02\x01..\x7fELX\x02\x01..AAAAAAAAAA";
+        let found: Vec<(usize, usize, usize)> = approx_find(b"\x7fELF\x02", text, 1)
+            .iter()
+            .map(|m| (m.start, m.end, m.distance))
+            .collect();
+        // 完全匹配 少一个字节 替换一个字节
+        assert_eq!(found, [(2, 7, 0), (10, 14, 1), (17, 22, 1)]);
+        let runs: Vec<usize> = approx_find(b"AAAA", text, 1)
+            .iter()
+            .map(|m| m.start)
+            .collect();
+        assert_eq!(runs, [25, 29]);
+        assert!(approx_find(b"ZZZZ", text, 1).is_empty());
+    }
 }
@@ -288,6 +288,22 @@ impl GapBuffer {
         self.gap_start += text_len;
     }
 
+    /// 从index处开始覆盖写入 不改变文本长度
+    pub(crate) fn replace(&mut self, index: usize, text: &[u8]) {
+        for (i, b) in text.iter().enumerate() {
+            let pos = index + i;
+            if pos >= self.text_len() {
+                break;
+            }
+            let j = if pos < self.gap_start {
+                pos
+            } else {
+                pos + self.gap_size()
+            };
+            self.buffer[j] = *b;
+        }
+    }
+
     // Backspace 删除光标前一个字符
     /// [H][e][l][l][o][ ][ ][ ][ ][ ][W][o][r][l][d]
     pub(crate) fn backspace(&mut self, index: usize) {
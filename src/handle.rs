@@ -1,5 +1,6 @@
+use crate::bookmark::Bookmark;
+use crate::bookmark::SESSION_DIR;
 use crate::command::Command;
-use crate::command::FindValue;
 use crate::editor::EditLineMeta;
 use crate::editor::RingVec;
 use crate::editor::TextDisplay;
@@ -7,13 +8,44 @@ use crate::editor::TextOper;
 use crate::editor::TextWarpType;
 use crate::editor::HEX_WITH;
 use crate::error::ChapResult;
+use crate::exe::decode_exe;
 use crate::execute;
+use crate::function::FunctionPlugin;
 use crate::lua::LuaPlugin;
+use crate::pg::check_pages;
+use crate::pg::decode_btree_page;
+use crate::pg::decode_heap_page;
+use crate::pg::decode_heap_tuple;
+use crate::pg::page_stored_checksum;
+use crate::pg::pg_checksum_page;
+use crate::pg::BLCKSZ;
+use crate::pg::RELSEG_SIZE;
+use crate::pgfile::decode_pg_file;
+use crate::pgfile::PgFileKind;
+use crate::pgtuple::parse_type_list;
+use crate::plugin::ByteSource;
 use crate::plugin::Plugin;
+use crate::plugin::PluginAction;
+use crate::plugin::PluginOutput;
+use crate::search::PendingReplace;
+use crate::search::SearchPattern;
+use crate::sqlite::SqlitePlugin;
+use crate::template::apply_template;
+use crate::template::find_template;
+use crate::template::parse_template;
+use crate::template::TEMPLATE_DIR;
+use crate::toast::decompress_varlena;
+use crate::tui::HexEditMode;
+use crate::tui::HexInput;
+use crate::tui::Highlight;
 use crate::tui::TextSelect;
+use crate::tui::CMD_INPUT_MAX;
+use crate::wal::segment_len;
+use crate::wal::walk_segment;
 use crate::ChapTui;
 use crossterm::cursor::Show;
 use ratatui::restore;
+use std::fs;
 use std::fs::File;
 use std::io::stdout;
 use std::io::Write;
@@ -169,6 +201,46 @@ impl Handle for HandleImpl {
             HandleImpl::Hex(h) => h.handle_char(chap_tui, line_meta, td, c),
         }
     }
+
+    fn handle_tab(&self, chap_tui: &mut ChapTui) -> ChapResult<()> {
+        match self {
+            HandleImpl::Edit(h) => h.handle_tab(chap_tui),
+            HandleImpl::Hex(h) => h.handle_tab(chap_tui),
+        }
+    }
+
+    fn handle_ctrl_z(&self, chap_tui: &mut ChapTui, td: &TextDisplay) -> ChapResult<()> {
+        match self {
+            HandleImpl::Edit(h) => h.handle_ctrl_z(chap_tui, td),
+            HandleImpl::Hex(h) => h.handle_ctrl_z(chap_tui, td),
+        }
+    }
+
+    fn handle_ctrl_y(&self, chap_tui: &mut ChapTui, td: &TextDisplay) -> ChapResult<()> {
+        match self {
+            HandleImpl::Edit(h) => h.handle_ctrl_y(chap_tui, td),
+            HandleImpl::Hex(h) => h.handle_ctrl_y(chap_tui, td),
+        }
+    }
+
+    fn handle_insert(&self, chap_tui: &mut ChapTui) -> ChapResult<()> {
+        match self {
+            HandleImpl::Edit(h) => h.handle_insert(chap_tui),
+            HandleImpl::Hex(h) => h.handle_insert(chap_tui),
+        }
+    }
+
+    fn handle_delete<'a>(
+        &self,
+        chap_tui: &mut ChapTui,
+        line_meta: &'a RingVec<EditLineMeta>,
+        td: &'a TextDisplay,
+    ) -> ChapResult<()> {
+        match self {
+            HandleImpl::Edit(h) => h.handle_delete(chap_tui, line_meta, td),
+            HandleImpl::Hex(h) => h.handle_delete(chap_tui, line_meta, td),
+        }
+    }
 }
 
 pub(crate) fn tui_retore() -> ChapResult<()> {
@@ -185,7 +257,12 @@ pub(crate) trait Handle {
         chap_tui.elem.cmd_inp.clear();
         chap_tui.elem.navi.clear();
         chap_tui.assist_tv2_data.clear();
+        chap_tui.set_fields(Vec::new());
+        chap_tui.field_sel = 0;
         chap_tui.txt_sel.reset_to_start();
+        chap_tui.set_hex_input(HexInput::Cmd);
+        chap_tui.hex_nibble = false;
+        chap_tui.replace = None;
         Ok(())
     }
 
@@ -278,6 +355,23 @@ pub(crate) trait Handle {
         td: &'a TextDisplay,
         c: char,
     ) -> ChapResult<()>;
+
+    fn handle_tab(&self, chap_tui: &mut ChapTui) -> ChapResult<()>;
+
+    //撤销
+    fn handle_ctrl_z(&self, chap_tui: &mut ChapTui, td: &TextDisplay) -> ChapResult<()>;
+
+    //重做
+    fn handle_ctrl_y(&self, chap_tui: &mut ChapTui, td: &TextDisplay) -> ChapResult<()>;
+
+    fn handle_insert(&self, chap_tui: &mut ChapTui) -> ChapResult<()>;
+
+    fn handle_delete<'a>(
+        &self,
+        chap_tui: &mut ChapTui,
+        line_meta: &'a RingVec<EditLineMeta>,
+        td: &'a TextDisplay,
+    ) -> ChapResult<()>;
 }
 
 pub(crate) struct HandleEdit;
@@ -286,6 +380,43 @@ impl HandleEdit {
     pub(crate) fn new() -> Self {
         HandleEdit {}
     }
+
+    // 光标移到文本偏移处 不在当前页时跳到所在行
+    fn move_cursor_to(
+        &self,
+        chap_tui: &mut ChapTui,
+        td: &TextDisplay,
+        offset: usize,
+    ) -> ChapResult<()> {
+        let (line_index, col) = td.text_position(offset);
+        let on_page = |line_meta: &RingVec<EditLineMeta>| {
+            (0..line_meta.len()).rev().find(|&i| {
+                let meta = line_meta.get(i).unwrap();
+                meta.get_line_index() == line_index && col >= meta.get_line_offset()
+            })
+        };
+        let mut line_meta = td.get_one_page(chap_tui.start_line_num.max(1))?.1;
+        let mut row = on_page(line_meta);
+        if row.is_none() {
+            line_meta = td.get_one_page(line_index + 1)?.1;
+            row = on_page(line_meta);
+        }
+        chap_tui.is_last_line = false;
+        match row {
+            Some(y) => {
+                let x = col - line_meta.get(y).unwrap().get_line_offset();
+                chap_tui.cursor_y = y;
+                chap_tui.cursor_x = x.min(chap_tui.elem.tv.get_width());
+                chap_tui.offset = x;
+            }
+            None => {
+                chap_tui.cursor_y = 0;
+                chap_tui.cursor_x = 0;
+                chap_tui.offset = 0;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Handle for HandleEdit {
@@ -580,15 +711,56 @@ impl Handle for HandleEdit {
     ) -> ChapResult<()> {
         Ok(())
     }
+
+    fn handle_tab(&self, chap_tui: &mut ChapTui) -> ChapResult<()> {
+        Ok(())
+    }
+
+    fn handle_ctrl_z(&self, chap_tui: &mut ChapTui, td: &TextDisplay) -> ChapResult<()> {
+        match td.undo(1)? {
+            Some(offset) => self.move_cursor_to(chap_tui, td, offset),
+            None => {
+                td.get_one_page(chap_tui.start_line_num.max(1))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_ctrl_y(&self, chap_tui: &mut ChapTui, td: &TextDisplay) -> ChapResult<()> {
+        match td.redo(1)? {
+            Some(offset) => self.move_cursor_to(chap_tui, td, offset),
+            None => {
+                td.get_one_page(chap_tui.start_line_num.max(1))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_insert(&self, chap_tui: &mut ChapTui) -> ChapResult<()> {
+        Ok(())
+    }
+
+    fn handle_delete<'a>(
+        &self,
+        chap_tui: &mut ChapTui,
+        line_meta: &'a RingVec<EditLineMeta>,
+        td: &'a TextDisplay,
+    ) -> ChapResult<()> {
+        Ok(())
+    }
 }
 
 pub(crate) struct HandleHex<T: Plugin> {
     plugin: T,
+    functions: FunctionPlugin, // 内置的解析函数 插件中没有同名函数时使用
 }
 
 impl<T: Plugin> HandleHex<T> {
     pub(crate) fn new(plugin: T) -> Self {
-        HandleHex { plugin }
+        HandleHex {
+            plugin,
+            functions: FunctionPlugin::new(),
+        }
     }
 
     fn jump_to_address(
@@ -620,20 +792,359 @@ impl<T: Plugin> HandleHex<T> {
         line_meta: &RingVec<EditLineMeta>,
         td: &TextDisplay,
         seek_start: usize,
-        pattern: &[u8],
+        pattern: &SearchPattern,
     ) -> ChapResult<()> {
         if pattern.is_empty() {
             return Ok(());
         }
-        let seek_start = seek_start + pattern.len();
-        if let Some(addr) = td.find(pattern, seek_start) {
+        let seek_start = seek_start + pattern.skip_len();
+        if let Some((addr, len)) = td.find(pattern, seek_start) {
             self.jump_to_address(chap_tui, line_meta, addr + seek_start, td)?;
             chap_tui
                 .txt_sel
-                .set_select(addr + seek_start, addr + seek_start + pattern.len() - 1);
+                .set_select(addr + seek_start, addr + seek_start + len - 1);
+        }
+        Ok(())
+    }
+
+    // 光标所在的文件偏移
+    fn cursor_offset(
+        &self,
+        chap_tui: &ChapTui,
+        line_meta: &RingVec<EditLineMeta>,
+    ) -> Option<usize> {
+        line_meta
+            .get(chap_tui.cursor_y)
+            .map(|m| m.get_line_file_start() + chap_tui.cursor_x)
+    }
+
+    // 读取 offset 处的一个字节
+    fn byte_at(&self, td: &TextDisplay, offset: usize) -> u8 {
+        td.get_text_from_sel(&TextSelect::from_select(offset, offset))
+            .first()
+            .copied()
+            .unwrap_or(0)
+    }
+
+    // 修改后重新获取当前页 并把光标移动到 offset 处
+    fn move_cursor_to(
+        &self,
+        chap_tui: &mut ChapTui,
+        td: &TextDisplay,
+        offset: usize,
+    ) -> ChapResult<()> {
+        let file_size = td.get_file_size();
+        //最后一行已满时 光标不能停在文件末尾
+        let max = if file_size % HEX_WITH == 0 {
+            file_size.saturating_sub(1)
+        } else {
+            file_size
+        };
+        let offset = offset.min(max);
+        let (_, mut line_meta) = td.get_one_page(chap_tui.start_line_num.max(1))?;
+        if line_meta.is_empty() {
+            chap_tui.cursor_x = 0;
+            chap_tui.cursor_y = 0;
+            return Ok(());
+        }
+        let first = line_meta.get(0).unwrap();
+        let first_start = first.get_line_file_start();
+        if offset + HEX_WITH < first_start
+            || offset >= first_start + (line_meta.len() + 1) * HEX_WITH
+        {
+            //不在当前页附近 直接跳转
+            line_meta = td.get_one_page(offset / HEX_WITH + 1)?.1;
+        } else if offset < first_start {
+            td.scroll_pre_one_line(first)?;
+            line_meta = td.get_current_line_meta()?;
+        } else if (offset - first_start) / HEX_WITH >= line_meta.len() {
+            td.scroll_next_one_line(line_meta.last().unwrap())?;
+            line_meta = td.get_current_line_meta()?;
+        }
+        if line_meta.is_empty() {
+            return Ok(());
+        }
+        let rel = offset.saturating_sub(line_meta.get(0).unwrap().get_line_file_start());
+        chap_tui.cursor_y = (rel / HEX_WITH).min(line_meta.len() - 1);
+        chap_tui.cursor_x = offset
+            - line_meta
+                .get(chap_tui.cursor_y)
+                .unwrap()
+                .get_line_file_start();
+        chap_tui.txt_sel.set_pos(offset);
+        Ok(())
+    }
+
+    fn undo(&self, chap_tui: &mut ChapTui, td: &TextDisplay, n: usize) -> ChapResult<()> {
+        chap_tui.hex_nibble = false;
+        match td.undo(n)? {
+            Some(offset) => self.move_cursor_to(chap_tui, td, offset),
+            None => {
+                chap_tui.assist_tv2_data = "nothing to undo".to_string();
+                Ok(())
+            }
+        }
+    }
+
+    fn redo(&self, chap_tui: &mut ChapTui, td: &TextDisplay, n: usize) -> ChapResult<()> {
+        chap_tui.hex_nibble = false;
+        match td.redo(n)? {
+            Some(offset) => self.move_cursor_to(chap_tui, td, offset),
+            None => {
+                chap_tui.assist_tv2_data = "nothing to redo".to_string();
+                Ok(())
+            }
+        }
+    }
+
+    // 执行插件记录的操作
+    fn apply_actions(
+        &self,
+        chap_tui: &mut ChapTui,
+        line_meta: &RingVec<EditLineMeta>,
+        td: &TextDisplay,
+        actions: Vec<PluginAction>,
+    ) -> ChapResult<()> {
+        for action in actions {
+            match action {
+                PluginAction::Jump(addr) => {
+                    self.jump_to_address(chap_tui, line_meta, addr, td)?;
+                }
+                PluginAction::Select(start, end) => {
+                    chap_tui.txt_sel.set_select(start, end);
+                }
+                PluginAction::Highlight(offset, length, color) => {
+                    chap_tui.highlights.push(Highlight {
+                        start: offset,
+                        end: offset + length,
+                        color,
+                    });
+                }
+                PluginAction::ClearHighlights => chap_tui.highlights.clear(),
+                PluginAction::Message(msg) => {
+                    chap_tui.elem.cmd_inp.clear();
+                    chap_tui.elem.cmd_inp.push_str(&msg);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 书签改变后立即写入会话目录
+    fn save_session(&self, chap_tui: &ChapTui) -> ChapResult<()> {
+        let home = dirs::home_dir().ok_or("failed to get home directory")?;
+        chap_tui.session.save(&home.join(SESSION_DIR))
+    }
+
+    // cut 命令的保存 新建一个文件 把bytes 保存到文件
+    fn save_cut(&self, chap_tui: &mut ChapTui, filepath: &str, bytes: &[u8]) -> ChapResult<()> {
+        let mut file = File::create(filepath)?;
+        // 写入字节数组
+        chap_tui.elem.cmd_inp.clear();
+        if file.write_all(bytes).is_ok() {
+            chap_tui.elem.cmd_inp.push_str("save file success");
+        } else {
+            chap_tui.elem.cmd_inp.push_str("save file failed");
+        }
+        Ok(())
+    }
+
+    // from 之后所有不重叠的匹配 (偏移, 长度)
+    fn find_hits(
+        &self,
+        td: &TextDisplay,
+        pattern: &SearchPattern,
+        from: usize,
+    ) -> Vec<(usize, usize)> {
+        let mut hits = Vec::new();
+        let mut pos = from;
+        while let Some((rel, len)) = td.find(pattern, pos) {
+            hits.push((pos + rel, len));
+            pos += rel + len;
+        }
+        hits
+    }
+
+    // 选中 from 之后的下一个匹配等待确认 没有了就结束替换
+    fn replace_next(
+        &self,
+        chap_tui: &mut ChapTui,
+        td: &TextDisplay,
+        from: usize,
+    ) -> ChapResult<()> {
+        let pending = match &mut chap_tui.replace {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        match td.find(&pending.pattern, from) {
+            Some((rel, len)) => {
+                let offset = from + rel;
+                pending.hit = (offset, len);
+                self.move_cursor_to(chap_tui, td, offset)?;
+                chap_tui.elem.navi.set_cur_line(chap_tui.cursor_y);
+                chap_tui.txt_sel.set_select(offset, offset + len - 1);
+                chap_tui.elem.cmd_inp.clear();
+                chap_tui
+                    .elem
+                    .cmd_inp
+                    .push_str(&format!("replace at {}? (y/n/a/q)", offset));
+            }
+            None => self.finish_replace(chap_tui, 0),
+        }
+        Ok(())
+    }
+
+    fn finish_replace(&self, chap_tui: &mut ChapTui, more: usize) {
+        let count = chap_tui.replace.take().map_or(0, |p| p.count) + more;
+        chap_tui.elem.cmd_inp.clear();
+        chap_tui
+            .elem
+            .cmd_inp
+            .push_str(&format!("replaced {} occurrences", count));
+    }
+
+    // y 替换当前匹配 n 跳过 a 替换剩下的全部 q 结束
+    fn confirm_replace(&self, chap_tui: &mut ChapTui, td: &TextDisplay, c: char) -> ChapResult<()> {
+        let pending = match &mut chap_tui.replace {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let (offset, len) = pending.hit;
+        match c {
+            'y' => {
+                td.replace_bytes(&[(offset, len)], &pending.replacement)?;
+                pending.count += 1;
+                let next = offset + pending.replacement.len();
+                self.replace_next(chap_tui, td, next)
+            }
+            'n' => self.replace_next(chap_tui, td, offset + len),
+            'a' => {
+                let hits = self.find_hits(td, &pending.pattern, offset);
+                td.replace_bytes(&hits, &pending.replacement)?;
+                self.finish_replace(chap_tui, hits.len());
+                self.move_cursor_to(chap_tui, td, offset)
+            }
+            'q' => {
+                self.finish_replace(chap_tui, 0);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // 跳到选中位置之后或之前的搜索匹配 到头后从另一头继续
+    fn goto_hit(&self, chap_tui: &mut ChapTui, td: &TextDisplay, forward: bool) -> ChapResult<()> {
+        let hits = chap_tui.find_all.hits();
+        if hits.is_empty() {
+            return Err("no search hits".into());
+        }
+        let pos = chap_tui.txt_sel.get_start();
+        let (i, wrapped) = if forward {
+            chap_tui
+                .find_all
+                .next(pos)
+                .map_or((0, true), |i| (i, false))
+        } else {
+            chap_tui
+                .find_all
+                .prev(pos)
+                .map_or((hits.len() - 1, true), |i| (i, false))
+        };
+        let (offset, len) = (hits[i].offset, hits[i].len);
+        let total = hits.len();
+        self.move_cursor_to(chap_tui, td, offset)?;
+        chap_tui.elem.navi.set_cur_line(chap_tui.cursor_y);
+        chap_tui.txt_sel.set_select(offset, offset + len - 1);
+        let row = chap_tui.find_all.row(i);
+        if chap_tui.shows_results() && row < chap_tui.assist_fields.len() {
+            chap_tui.field_sel = row;
+            chap_tui.field_tree.reveal(row);
+        }
+        chap_tui.elem.cmd_inp.clear();
+        chap_tui.elem.cmd_inp.push_str(&format!(
+            "hit {} of {}{}",
+            i + 1,
+            total,
+            if wrapped { ", wrapped" } else { "" }
+        ));
+        Ok(())
+    }
+
+    // 选中结构树的节点 并跳转到字段所在位置
+    fn select_field(&self, chap_tui: &mut ChapTui, td: &TextDisplay, idx: usize) -> ChapResult<()> {
+        if chap_tui.assist_fields.is_empty() {
+            return Ok(());
+        }
+        chap_tui.field_sel = idx.min(chap_tui.assist_fields.len() - 1);
+        chap_tui.field_tree.reveal(chap_tui.field_sel);
+        let field = &chap_tui.assist_fields[chap_tui.field_sel];
+        let (offset, length) = (field.offset, field.length);
+        self.move_cursor_to(chap_tui, td, offset)?;
+        chap_tui.elem.navi.set_cur_line(chap_tui.cursor_y);
+        if length > 0 {
+            chap_tui.txt_sel.set_select(offset, offset + length - 1);
         }
         Ok(())
     }
+
+    // 在16进制列输入一个半字节
+    fn edit_hex(
+        &self,
+        chap_tui: &mut ChapTui,
+        line_meta: &RingVec<EditLineMeta>,
+        td: &TextDisplay,
+        c: char,
+    ) -> ChapResult<()> {
+        let nibble = match c.to_digit(16) {
+            Some(n) => n as u8,
+            None => return Ok(()),
+        };
+        let offset = match self.cursor_offset(chap_tui, line_meta) {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+        let at_end = offset >= td.get_file_size();
+        if !chap_tui.hex_nibble {
+            //高4位
+            if chap_tui.hex_edit_mode == HexEditMode::Insert || at_end {
+                td.insert_bytes(offset, &[nibble << 4])?;
+            } else {
+                let old = self.byte_at(td, offset);
+                td.overwrite_bytes(offset, &[(nibble << 4) | (old & 0x0f)])?;
+            }
+            chap_tui.hex_nibble = true;
+            self.move_cursor_to(chap_tui, td, offset)
+        } else {
+            //低4位
+            let old = self.byte_at(td, offset);
+            td.overwrite_bytes(offset, &[(old & 0xf0) | nibble])?;
+            chap_tui.hex_nibble = false;
+            self.move_cursor_to(chap_tui, td, offset + 1)
+        }
+    }
+
+    // 在ascii列输入一个字符
+    fn edit_ascii(
+        &self,
+        chap_tui: &mut ChapTui,
+        line_meta: &RingVec<EditLineMeta>,
+        td: &TextDisplay,
+        c: char,
+    ) -> ChapResult<()> {
+        if !c.is_ascii() || c.is_ascii_control() {
+            return Ok(());
+        }
+        let offset = match self.cursor_offset(chap_tui, line_meta) {
+            Some(offset) => offset,
+            None => return Ok(()),
+        };
+        if chap_tui.hex_edit_mode == HexEditMode::Insert || offset >= td.get_file_size() {
+            td.insert_bytes(offset, &[c as u8])?;
+        } else {
+            td.overwrite_bytes(offset, &[c as u8])?;
+        }
+        self.move_cursor_to(chap_tui, td, offset + 1)
+    }
 }
 
 impl<T: Plugin> Handle for HandleHex<T> {
@@ -643,6 +1154,21 @@ impl<T: Plugin> Handle for HandleHex<T> {
         p: P,
         td: &mut TextDisplay,
     ) -> ChapResult<()> {
+        chap_tui.elem.cmd_inp.clear();
+        chap_tui.set_hex_input(HexInput::Cmd);
+        chap_tui.hex_nibble = false;
+        //保存
+        match td.save(&p) {
+            Ok(_) => {
+                chap_tui.elem.cmd_inp.push_str("saved");
+                //书签偏移跟着移动过 和保存后的文件一致 一起写入
+                chap_tui.poll_splices(td);
+                chap_tui.session.file_saved();
+                self.save_session(chap_tui)?;
+            }
+            Err(e) => chap_tui.elem.cmd_inp.push_str(&format!("save fail: {}", e)),
+        }
+        td.get_one_page(chap_tui.start_line_num.max(1))?;
         Ok(())
     }
 
@@ -652,6 +1178,10 @@ impl<T: Plugin> Handle for HandleHex<T> {
         mut line_meta: &'a RingVec<EditLineMeta>,
         td: &'a TextDisplay,
     ) -> ChapResult<()> {
+        if chap_tui.hex_input == HexInput::Fields {
+            let idx = chap_tui.field_tree.prev(chap_tui.field_sel);
+            return self.select_field(chap_tui, td, idx);
+        }
         if line_meta.is_empty() {
             return Ok(());
         }
@@ -691,6 +1221,10 @@ impl<T: Plugin> Handle for HandleHex<T> {
         mut line_meta: &'a RingVec<EditLineMeta>,
         td: &'a TextDisplay,
     ) -> ChapResult<()> {
+        if chap_tui.hex_input == HexInput::Fields {
+            let idx = chap_tui.field_tree.next(chap_tui.field_sel);
+            return self.select_field(chap_tui, td, idx);
+        }
         if line_meta.is_empty() {
             return Ok(());
         }
@@ -731,6 +1265,11 @@ impl<T: Plugin> Handle for HandleHex<T> {
         mut line_meta: &'a RingVec<EditLineMeta>,
         td: &'a TextDisplay,
     ) -> ChapResult<()> {
+        if chap_tui.hex_input == HexInput::Fields {
+            //折叠 已折叠时回到父节点
+            let idx = chap_tui.field_tree.collapse(chap_tui.field_sel);
+            return self.select_field(chap_tui, td, idx);
+        }
         if line_meta.is_empty() {
             return Ok(());
         }
@@ -782,6 +1321,11 @@ impl<T: Plugin> Handle for HandleHex<T> {
         line_meta: &'a RingVec<EditLineMeta>,
         td: &'a TextDisplay,
     ) -> ChapResult<()> {
+        if chap_tui.hex_input == HexInput::Fields {
+            //展开 已展开时进入第一个子节点
+            let idx = chap_tui.field_tree.expand(chap_tui.field_sel);
+            return self.select_field(chap_tui, td, idx);
+        }
         if line_meta.is_empty() {
             return Ok(());
         }
@@ -818,6 +1362,8 @@ impl<T: Plugin> Handle for HandleHex<T> {
         //todo!("Handle enter in hex mode");
         let cmd_inp = chap_tui.elem.cmd_inp.get_inp();
         let cmd = Command::parse(cmd_inp);
+        //新的命令替换辅助窗口的16进制视图
+        chap_tui.assist_hex.clear();
         match cmd {
             Command::Back => {
                 if let Some(line_num) = chap_tui.back_linenum.pop() {
@@ -847,13 +1393,47 @@ impl<T: Plugin> Handle for HandleHex<T> {
                     .unwrap()
                     .get_line_file_start()
                     + chap_tui.cursor_x;
-                match value {
-                    FindValue::Hex(pattern) => {
-                        self.find_jump(chap_tui, line_meta, td, seek_start, pattern.as_slice())?;
-                    }
-                    FindValue::Ascii(pattern) => {
-                        self.find_jump(chap_tui, line_meta, td, seek_start, pattern.as_bytes())?;
-                    }
+                let pattern = SearchPattern::new(value)?;
+                //近似匹配的最佳结果要扫描完才知道 不在前台查找第一个
+                if !matches!(pattern, SearchPattern::Fuzzy(..)) {
+                    self.find_jump(chap_tui, line_meta, td, seek_start, &pattern)?;
+                }
+                //在后台查找全部匹配 结果显示在结构树窗口
+                chap_tui.find_all.start(td.snapshot()?, pattern);
+                chap_tui.set_result_fields();
+            }
+            Command::Replace(value, replacement, confirm) => {
+                let pattern = SearchPattern::new(value)?;
+                if pattern.is_empty() {
+                    return Err("empty replace pattern".into());
+                }
+                if confirm {
+                    //从光标处开始 逐个确认
+                    chap_tui.replace = Some(PendingReplace {
+                        pattern,
+                        replacement,
+                        hit: (0, 0),
+                        count: 0,
+                    });
+                    self.replace_next(chap_tui, td, chap_tui.txt_sel.get_start())?;
+                } else {
+                    let hits = self.find_hits(td, &pattern, 0);
+                    td.replace_bytes(&hits, &replacement)?;
+                    self.move_cursor_to(chap_tui, td, chap_tui.txt_sel.get_start())?;
+                    chap_tui.elem.cmd_inp.clear();
+                    chap_tui
+                        .elem
+                        .cmd_inp
+                        .push_str(&format!("replaced {} occurrences", hits.len()));
+                }
+            }
+            Command::NextHit => self.goto_hit(chap_tui, td, true)?,
+            Command::PrevHit => self.goto_hit(chap_tui, td, false)?,
+            Command::Results => {
+                chap_tui.set_result_fields();
+                if chap_tui.has_fields() {
+                    chap_tui.set_hex_input(HexInput::Fields);
+                    self.select_field(chap_tui, td, 0)?;
                 }
             }
             Command::Cut(c) => {
@@ -866,38 +1446,294 @@ impl<T: Plugin> Handle for HandleHex<T> {
                     seek_start,
                     seek_start + c.get_count(),
                 ));
-                // 新建一个文件 把bytes 保存到文件
-                let mut file = File::create(c.get_filepath())?;
-                // 写入字节数组
-                chap_tui.elem.cmd_inp.clear();
-                if let Ok(_) = file.write_all(&bytes) {
-                    chap_tui.elem.cmd_inp.push_str("save file success");
-                } else {
-                    chap_tui.elem.cmd_inp.push_str("save file failed");
-                }
+                self.save_cut(chap_tui, c.get_filepath(), &bytes)?;
             }
 
             Command::CutSel(c) => {
                 let bytes =
                     td.get_text_from_sel(&TextSelect::from_select(c.get_start(), c.get_end()));
-                // 新建一个文件 把bytes 保存到文件
-                let mut file = File::create(c.get_filepath())?;
-                // 写入字节数组
+                self.save_cut(chap_tui, c.get_filepath(), &bytes)?;
+            }
+            Command::Call(function, args) => {
+                chap_tui.set_fields(Vec::new());
+                chap_tui.field_sel = 0;
+                let result = if !self.plugin.has(&function) && self.functions.has(&function) {
+                    self.functions
+                        .eval(&function, &args, td, &chap_tui.txt_sel)?
+                } else {
+                    self.plugin.eval(&function, &args, td, &chap_tui.txt_sel)?
+                };
+                match result.output {
+                    PluginOutput::Text(a) => chap_tui.assist_tv2_data = a,
+                    PluginOutput::Fields(fields) => {
+                        chap_tui.assist_tv2_data.clear();
+                        chap_tui.set_fields(fields);
+                    }
+                }
+                self.apply_actions(chap_tui, line_meta, td, result.actions)?;
+            }
+            Command::ListFunc => {
+                chap_tui.set_fields(Vec::new());
+                chap_tui.assist_tv2_data =
+                    format!("{}\n{}", self.plugin.list()?, self.functions.list()?);
+            }
+            Command::Undo(n) => {
+                self.undo(chap_tui, td, n)?;
+            }
+            Command::Redo(n) => {
+                self.redo(chap_tui, td, n)?;
+            }
+            Command::NoHighlight => {
+                chap_tui.highlights.clear();
+                chap_tui.template_fields.clear();
+                chap_tui.find_all.clear();
+                if chap_tui.shows_results() {
+                    chap_tui.set_fields(Vec::new());
+                }
+            }
+            Command::Reload => {
+                let n = self.plugin.reload()?;
+                chap_tui.set_fields(Vec::new());
+                chap_tui.assist_tv2_data = format!("reloaded {} plugins", n);
+            }
+            Command::PgPage(blkno, types) => {
+                let types = match types {
+                    Some(types) => parse_type_list(&types)?,
+                    None => Vec::new(),
+                };
+                let base = blkno * BLCKSZ;
+                let page = td.read(base, BLCKSZ);
+                if page.is_empty() {
+                    return Err(format!("block {} out of range", blkno).into());
+                }
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_fields(decode_heap_page(&page, base, &types));
+                //直接进入字段列表 上下键选择字段
+                chap_tui.set_hex_input(HexInput::Fields);
+                self.select_field(chap_tui, td, 0)?;
+            }
+            Command::PgBtree(blkno) => {
+                let base = blkno * BLCKSZ;
+                let page = td.read(base, BLCKSZ);
+                if page.is_empty() {
+                    return Err(format!("block {} out of range", blkno).into());
+                }
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_fields(decode_btree_page(&page, base));
+                chap_tui.set_hex_input(HexInput::Fields);
+                self.select_field(chap_tui, td, 0)?;
+            }
+            Command::PgTuple(types) => {
+                let types = parse_type_list(&types)?;
+                //元组不会跨页 读到所在页面的末尾
+                let start = chap_tui.txt_sel.get_start();
+                let tup = td.read(start, BLCKSZ - start % BLCKSZ);
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_fields(decode_heap_tuple(&tup, start, &types));
+                chap_tui.set_hex_input(HexInput::Fields);
+                self.select_field(chap_tui, td, 0)?;
+            }
+            Command::PgCheck(segno) => {
+                let (pages, bad) = check_pages(td, segno);
+                chap_tui.elem.cmd_inp.clear();
+                chap_tui.elem.cmd_inp.push_str(&format!(
+                    "{} of {} pages mismatch",
+                    bad.len(),
+                    pages
+                ));
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_fields(bad);
+                if chap_tui.has_fields() {
+                    chap_tui.set_hex_input(HexInput::Fields);
+                    self.select_field(chap_tui, td, 0)?;
+                }
+            }
+            Command::PgFix(segno) => {
+                let offset = match self.cursor_offset(chap_tui, line_meta) {
+                    Some(offset) => offset,
+                    None => return Ok(()),
+                };
+                let base = offset / BLCKSZ * BLCKSZ;
+                let page = td.read(base, BLCKSZ);
+                if page.len() < BLCKSZ {
+                    return Err(format!("incomplete page at 0x{:x}", base).into());
+                }
+                let blkno = segno * RELSEG_SIZE + base / BLCKSZ;
+                let stored = page_stored_checksum(&page);
+                let checksum = pg_checksum_page(&page, blkno as u32);
+                //通过编辑写入 可以撤销 ctrl+s 保存
+                td.overwrite_bytes(base + 8, &checksum.to_le_bytes())?;
+                self.move_cursor_to(chap_tui, td, offset)?;
+                chap_tui.elem.cmd_inp.clear();
+                chap_tui.elem.cmd_inp.push_str(&format!(
+                    "blk {} checksum 0x{:04x} -> 0x{:04x}",
+                    blkno, stored, checksum
+                ));
+            }
+            Command::Detoast(filepath) => {
+                let start = chap_tui.txt_sel.get_start();
+                //先读 header 得到 varlena 的长度
+                let header = td.read(start, 4);
+                let len = match header.as_slice() {
+                    [b0, b1, b2, b3] if b0 & 0x01 == 0 => {
+                        (u32::from_le_bytes([*b0, *b1, *b2, *b3]) >> 2) as usize
+                    }
+                    _ => header.len(),
+                };
+                let d = decompress_varlena(&td.read(start, len))?;
+                let summary = format!(
+                    "{} {} -> {} bytes",
+                    d.method.name(),
+                    d.compressed_len,
+                    d.data.len()
+                );
+                chap_tui.set_fields(Vec::new());
+                match filepath {
+                    Some(filepath) => {
+                        self.save_cut(chap_tui, &filepath, &d.data)?;
+                        chap_tui.assist_tv2_data = format!("{}\nsaved to {}", summary, filepath);
+                    }
+                    None => {
+                        chap_tui.elem.cmd_inp.clear();
+                        chap_tui.elem.cmd_inp.push_str(&summary);
+                        chap_tui.assist_tv2_data.clear();
+                        //在辅助窗口的16进制视图中显示解压后的数据
+                        chap_tui.assist_hex = d.data;
+                    }
+                }
+            }
+            Command::Wal => {
+                let seg = walk_segment(&td.read(0, segment_len(td)))?;
+                chap_tui.elem.cmd_inp.clear();
+                chap_tui.elem.cmd_inp.push_str(&seg.summary());
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_fields(seg.fields());
+                if chap_tui.has_fields() {
+                    //从光标所在的记录开始 上下键切换记录
+                    let cursor = self.cursor_offset(chap_tui, line_meta).unwrap_or(0);
+                    let idx = seg.records.iter().position(|r| r.end > cursor).unwrap_or(0);
+                    chap_tui.set_hex_input(HexInput::Fields);
+                    self.select_field(chap_tui, td, idx)?;
+                }
+            }
+            Command::PgFile => {
+                let kind = PgFileKind::detect(&chap_tui.file_path)
+                    .ok_or("unknown postgres file, expect pg_control, *_fsm, *_vm or WAL")?;
+                let (summary, fields) = decode_pg_file(kind, td)?;
                 chap_tui.elem.cmd_inp.clear();
-                if let Ok(_) = file.write_all(&bytes) {
-                    chap_tui.elem.cmd_inp.push_str("save file success");
+                chap_tui.elem.cmd_inp.push_str(&summary);
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_fields(fields);
+                if chap_tui.has_fields() {
+                    chap_tui.set_hex_input(HexInput::Fields);
+                    self.select_field(chap_tui, td, 0)?;
+                }
+            }
+            Command::SqlitePage(pgno) => {
+                let args = vec![("page".to_string(), pgno.to_string())];
+                let result = SqlitePlugin.eval("sqlite_page", &args, td, &chap_tui.txt_sel)?;
+                chap_tui.assist_tv2_data.clear();
+                if let PluginOutput::Fields(fields) = result.output {
+                    chap_tui.set_fields(fields);
+                }
+                self.apply_actions(chap_tui, line_meta, td, result.actions)?;
+                if chap_tui.has_fields() {
+                    chap_tui.set_hex_input(HexInput::Fields);
+                    self.select_field(chap_tui, td, 0)?;
+                }
+            }
+            Command::Exe(sections_only) => {
+                let info = decode_exe(td)?;
+                chap_tui.elem.cmd_inp.clear();
+                chap_tui.elem.cmd_inp.push_str(&info.summary);
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_fields(if sections_only {
+                    info.sections
                 } else {
-                    chap_tui.elem.cmd_inp.push_str("save file failed");
+                    info.fields
+                });
+                if chap_tui.has_fields() {
+                    chap_tui.set_hex_input(HexInput::Fields);
+                    self.select_field(chap_tui, td, 0)?;
                 }
             }
-            Command::Call(function) => {
-                let b = td.get_text_from_sel(&chap_tui.txt_sel);
-                // let a = function.call(ByteView::new(b, chap_tui.endian.clone()));
-                let a = self.plugin.eval(&function, &b)?;
-                chap_tui.assist_tv2_data = a;
+            Command::Apply(name, addr) => {
+                let home = dirs::home_dir().ok_or("failed to get home directory")?;
+                let path = find_template(&name, &home.join(TEMPLATE_DIR))
+                    .ok_or_else(|| format!("template {} not found", name))?;
+                let tpl = parse_template(&fs::read_to_string(&path)?)?;
+                let base = addr.unwrap_or(chap_tui.txt_sel.get_start());
+                let applied = apply_template(&tpl, td, base, chap_tui.endian.clone())?;
+                //替换上一次的标注
+                let mut actions = vec![PluginAction::ClearHighlights];
+                actions.extend(applied.highlights);
+                actions.push(PluginAction::Message(format!(
+                    "applied {} at {}, {} fields",
+                    name,
+                    base,
+                    applied.fields.len()
+                )));
+                self.apply_actions(chap_tui, line_meta, td, actions)?;
+                chap_tui.template_fields = applied.fields.clone();
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_fields(applied.fields);
+                if chap_tui.has_fields() {
+                    chap_tui.set_hex_input(HexInput::Fields);
+                    self.select_field(chap_tui, td, 0)?;
+                }
             }
-            Command::ListFunc => {
-                chap_tui.assist_tv2_data = self.plugin.list()?;
+            Command::Mark(name, comment) => {
+                //选中范围 没有选中时为光标处的一个字节
+                let start = chap_tui.txt_sel.get_start();
+                let end = chap_tui.txt_sel.get_end().max(start) + 1;
+                chap_tui.session.add(Bookmark {
+                    name: name.clone(),
+                    start,
+                    end,
+                    comment,
+                });
+                self.save_session(chap_tui)?;
+                chap_tui.elem.cmd_inp.clear();
+                chap_tui.elem.cmd_inp.push_str(&format!(
+                    "marked {} at {}, {} bytes",
+                    name,
+                    start,
+                    end - start
+                ));
+            }
+            Command::Unmark(name) => {
+                chap_tui
+                    .session
+                    .remove(&name)
+                    .ok_or_else(|| format!("bookmark {} not found", name))?;
+                self.save_session(chap_tui)?;
+                chap_tui.elem.cmd_inp.clear();
+                chap_tui
+                    .elem
+                    .cmd_inp
+                    .push_str(&format!("unmarked {}", name));
+            }
+            Command::Goto(name) => {
+                let mark = chap_tui
+                    .session
+                    .get(&name)
+                    .cloned()
+                    .ok_or_else(|| format!("bookmark {} not found", name))?;
+                self.move_cursor_to(chap_tui, td, mark.start)?;
+                chap_tui.elem.navi.set_cur_line(chap_tui.cursor_y);
+                chap_tui.txt_sel.set_select(mark.start, mark.end - 1);
+                chap_tui.elem.cmd_inp.clear();
+                chap_tui.elem.cmd_inp.push_str(&mark.comment);
+            }
+            Command::Marks => {
+                chap_tui.assist_tv2_data.clear();
+                chap_tui.set_bookmark_fields();
+                if chap_tui.has_fields() {
+                    chap_tui.set_hex_input(HexInput::Fields);
+                    self.select_field(chap_tui, td, 0)?;
+                } else {
+                    chap_tui.assist_tv2_data = "no bookmarks".to_string();
+                }
             }
             Command::Unknown(cmd) => {}
         }
@@ -1086,8 +1922,24 @@ impl<T: Plugin> Handle for HandleHex<T> {
         line_meta: &'a RingVec<EditLineMeta>,
         td: &'a TextDisplay,
     ) -> ChapResult<()> {
-        chap_tui.elem.cmd_inp.pop();
-        Ok(())
+        match chap_tui.hex_input {
+            HexInput::Cmd => {
+                chap_tui.elem.cmd_inp.pop();
+                return Ok(());
+            }
+            HexInput::Fields => return Ok(()),
+            _ => {}
+        }
+        chap_tui.hex_nibble = false;
+        let offset = match self.cursor_offset(chap_tui, line_meta) {
+            Some(offset) if offset > 0 => offset,
+            _ => return Ok(()),
+        };
+        //插入模式删除光标前一个字节 覆盖模式只左移光标
+        if chap_tui.hex_edit_mode == HexEditMode::Insert {
+            td.delete_bytes(offset - 1, 1)?;
+        }
+        self.move_cursor_to(chap_tui, td, offset - 1)
     }
 
     fn handle_char<'a>(
@@ -1097,12 +1949,80 @@ impl<T: Plugin> Handle for HandleHex<T> {
         td: &'a TextDisplay,
         c: char,
     ) -> ChapResult<()> {
-        if chap_tui.elem.cmd_inp.len() >= 50 {
-            return Ok(()); // 限制输入长度为16
+        if chap_tui.replace.is_some() {
+            return self.confirm_replace(chap_tui, td, c);
         }
-        chap_tui.elem.cmd_inp.push(c);
+        match chap_tui.hex_input {
+            HexInput::Cmd => {
+                if chap_tui.elem.cmd_inp.len() + c.len_utf8() > CMD_INPUT_MAX {
+                    // 限制输入长度 超出时提示
+                    chap_tui.assist_tv2_data =
+                        format!("command too long, at most {} bytes", CMD_INPUT_MAX);
+                    return Ok(());
+                }
+                chap_tui.elem.cmd_inp.push(c);
+                Ok(())
+            }
+            HexInput::Hex => self.edit_hex(chap_tui, line_meta, td, c),
+            HexInput::Ascii => self.edit_ascii(chap_tui, line_meta, td, c),
+            HexInput::Fields => {
+                match c {
+                    //空格展开或折叠
+                    ' ' if chap_tui.has_fields() => {
+                        chap_tui.field_tree.toggle(chap_tui.field_sel);
+                    }
+                    'n' if chap_tui.shows_results() => self.goto_hit(chap_tui, td, true)?,
+                    'N' if chap_tui.shows_results() => self.goto_hit(chap_tui, td, false)?,
+                    _ => {}
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_tab(&self, chap_tui: &mut ChapTui) -> ChapResult<()> {
+        chap_tui.next_hex_input();
+        chap_tui.hex_nibble = false;
         Ok(())
     }
+
+    fn handle_ctrl_z(&self, chap_tui: &mut ChapTui, td: &TextDisplay) -> ChapResult<()> {
+        self.undo(chap_tui, td, 1)
+    }
+
+    fn handle_ctrl_y(&self, chap_tui: &mut ChapTui, td: &TextDisplay) -> ChapResult<()> {
+        self.redo(chap_tui, td, 1)
+    }
+
+    fn handle_insert(&self, chap_tui: &mut ChapTui) -> ChapResult<()> {
+        chap_tui.hex_edit_mode = match chap_tui.hex_edit_mode {
+            HexEditMode::Overwrite => HexEditMode::Insert,
+            HexEditMode::Insert => HexEditMode::Overwrite,
+        };
+        chap_tui.hex_nibble = false;
+        Ok(())
+    }
+
+    fn handle_delete<'a>(
+        &self,
+        chap_tui: &mut ChapTui,
+        line_meta: &'a RingVec<EditLineMeta>,
+        td: &'a TextDisplay,
+    ) -> ChapResult<()> {
+        //只在插入模式下删除光标处字节
+        if !matches!(chap_tui.hex_input, HexInput::Hex | HexInput::Ascii)
+            || chap_tui.hex_edit_mode != HexEditMode::Insert
+        {
+            return Ok(());
+        }
+        chap_tui.hex_nibble = false;
+        let offset = match self.cursor_offset(chap_tui, line_meta) {
+            Some(offset) if offset < td.get_file_size() => offset,
+            _ => return Ok(()),
+        };
+        td.delete_bytes(offset, 1)?;
+        self.move_cursor_to(chap_tui, td, offset)
+    }
 }
 
 // impl Handle for HandleHex {
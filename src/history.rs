@@ -0,0 +1,420 @@
+use crate::error::ChapResult;
+
+//编辑操作 offset 为文本中的字节偏移
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum EditOp {
+    Insert {
+        offset: usize,
+        bytes: Vec<u8>,
+    },
+    Delete {
+        offset: usize,
+        bytes: Vec<u8>,
+    },
+    Overwrite {
+        offset: usize,
+        old: Vec<u8>,
+        new: Vec<u8>,
+    },
+    //按顺序执行的多个操作 作为一步撤销 比如全部替换
+    Group(Vec<EditOp>),
+}
+
+impl EditOp {
+    //反向操作
+    pub(crate) fn invert(&self) -> EditOp {
+        match self {
+            EditOp::Insert { offset, bytes } => EditOp::Delete {
+                offset: *offset,
+                bytes: bytes.clone(),
+            },
+            EditOp::Delete { offset, bytes } => EditOp::Insert {
+                offset: *offset,
+                bytes: bytes.clone(),
+            },
+            EditOp::Overwrite { offset, old, new } => EditOp::Overwrite {
+                offset: *offset,
+                old: new.clone(),
+                new: old.clone(),
+            },
+            EditOp::Group(ops) => EditOp::Group(ops.iter().rev().map(|op| op.invert()).collect()),
+        }
+    }
+
+    pub(crate) fn offset(&self) -> usize {
+        match self {
+            EditOp::Insert { offset, .. }
+            | EditOp::Delete { offset, .. }
+            | EditOp::Overwrite { offset, .. } => *offset,
+            EditOp::Group(ops) => ops.first().map_or(0, |op| op.offset()),
+        }
+    }
+
+    //执行后光标所在位置
+    pub(crate) fn cursor(&self) -> usize {
+        match self {
+            EditOp::Insert { offset, bytes } => offset + bytes.len(),
+            EditOp::Delete { offset, .. } => *offset,
+            EditOp::Overwrite { offset, new, .. } => offset + new.len(),
+            EditOp::Group(ops) => ops.last().map_or(0, |op| op.cursor()),
+        }
+    }
+
+    //改变长度的部分 按执行顺序追加到 out
+    fn splices(&self, out: &mut Vec<Splice>) {
+        let (offset, removed, inserted) = match self {
+            EditOp::Insert { offset, bytes } => (*offset, 0, bytes.len()),
+            EditOp::Delete { offset, bytes } => (*offset, bytes.len(), 0),
+            EditOp::Overwrite { offset, old, new } => (*offset, old.len(), new.len()),
+            EditOp::Group(ops) => {
+                for op in ops {
+                    op.splices(out);
+                }
+                return;
+            }
+        };
+        if removed != inserted {
+            out.push(Splice {
+                offset,
+                removed,
+                inserted,
+            });
+        }
+    }
+
+    //单个键入的字节 可以合并
+    fn is_typed(&self) -> bool {
+        match self {
+            EditOp::Insert { bytes, .. } => bytes.len() == 1,
+            EditOp::Overwrite { new, .. } => new.len() == 1,
+            EditOp::Delete { .. } | EditOp::Group(_) => false,
+        }
+    }
+
+    //把紧接着的键入合并到当前操作
+    fn merge(&mut self, next: &EditOp) -> bool {
+        match (self, next) {
+            (
+                EditOp::Insert { offset, bytes },
+                EditOp::Insert {
+                    offset: o,
+                    bytes: b,
+                },
+            ) if *o == *offset + bytes.len() => {
+                bytes.extend_from_slice(b);
+                true
+            }
+            // 修改刚插入的字节 比如16进制输入低4位
+            (EditOp::Insert { offset, bytes }, EditOp::Overwrite { offset: o, new, .. })
+                if *o >= *offset && *o + new.len() <= *offset + bytes.len() =>
+            {
+                let i = *o - *offset;
+                bytes[i..i + new.len()].copy_from_slice(new);
+                true
+            }
+            (
+                EditOp::Overwrite { offset, old, new },
+                EditOp::Overwrite {
+                    offset: o,
+                    old: o_old,
+                    new: o_new,
+                },
+            ) if *o >= *offset && *o <= *offset + new.len() => {
+                let i = *o - *offset;
+                for (k, b) in o_new.iter().enumerate() {
+                    if i + k < new.len() {
+                        new[i + k] = *b;
+                    } else {
+                        new.push(*b);
+                        old.push(o_old[k]);
+                    }
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// 一次编辑对后面偏移的影响: offset 开始的 removed 个字节换成了 inserted 个字节
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Splice {
+    pub(crate) offset: usize,
+    pub(crate) removed: usize,
+    pub(crate) inserted: usize,
+}
+
+impl Splice {
+    //编辑前的区间 [start, end) 在编辑后的位置 被删除的部分收缩到替换内容上
+    pub(crate) fn map_range(&self, start: usize, end: usize) -> (usize, usize) {
+        let removed_end = self.offset + self.removed;
+        let start = if start >= removed_end {
+            start - self.removed + self.inserted
+        } else if start > self.offset {
+            self.offset
+        } else {
+            start
+        };
+        let end = if end > removed_end {
+            end - self.removed + self.inserted
+        } else if end > self.offset {
+            self.offset + self.inserted
+        } else {
+            end
+        };
+        (start, end.max(start))
+    }
+}
+
+// 可以回放编辑操作的文本
+pub(crate) trait ByteEdit {
+    fn apply(&mut self, op: &EditOp) -> ChapResult<()>;
+}
+
+// 操作日志 支持多级撤销/重做
+pub(crate) struct History {
+    undo: Vec<EditOp>,
+    redo: Vec<EditOp>,
+    merging: bool,        //上一次记录的是键入 后续键入可以合并
+    version: usize,       //每次修改内容加一
+    splices: Vec<Splice>, //还没取走的长度变化 书签等按偏移保存的数据据此移动
+}
+
+impl History {
+    pub(crate) fn new() -> History {
+        History {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            merging: false,
+            version: 0,
+            splices: Vec::new(),
+        }
+    }
+
+    pub(crate) fn version(&self) -> usize {
+        self.version
+    }
+
+    //取走上次之后执行过的长度变化 包括撤销和重做
+    pub(crate) fn take_splices(&mut self) -> Vec<Splice> {
+        std::mem::take(&mut self.splices)
+    }
+
+    pub(crate) fn record(&mut self, op: EditOp) {
+        self.redo.clear();
+        self.version += 1;
+        op.splices(&mut self.splices);
+        let typed = op.is_typed();
+        if self.merging && typed {
+            if let Some(last) = self.undo.last_mut() {
+                if last.merge(&op) {
+                    return;
+                }
+            }
+        }
+        self.undo.push(op);
+        self.merging = typed;
+    }
+
+    //光标移走后 后续键入作为新的一步
+    pub(crate) fn end_typing(&mut self) {
+        self.merging = false;
+    }
+
+    //撤销n步 返回光标位置
+    pub(crate) fn undo<T: ByteEdit>(
+        &mut self,
+        n: usize,
+        text: &mut T,
+    ) -> ChapResult<Option<usize>> {
+        self.merging = false;
+        let mut cursor = None;
+        for _ in 0..n {
+            let op = match self.undo.pop() {
+                Some(op) => op,
+                None => break,
+            };
+            let inv = op.invert();
+            text.apply(&inv)?;
+            inv.splices(&mut self.splices);
+            self.version += 1;
+            cursor = Some(op.offset());
+            self.redo.push(op);
+        }
+        Ok(cursor)
+    }
+
+    //重做n步 返回光标位置
+    pub(crate) fn redo<T: ByteEdit>(
+        &mut self,
+        n: usize,
+        text: &mut T,
+    ) -> ChapResult<Option<usize>> {
+        self.merging = false;
+        let mut cursor = None;
+        for _ in 0..n {
+            let op = match self.redo.pop() {
+                Some(op) => op,
+                None => break,
+            };
+            text.apply(&op)?;
+            op.splices(&mut self.splices);
+            self.version += 1;
+            cursor = Some(op.cursor());
+            self.undo.push(op);
+        }
+        Ok(cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl ByteEdit for Vec<u8> {
+        fn apply(&mut self, op: &EditOp) -> ChapResult<()> {
+            match op {
+                EditOp::Insert { offset, bytes } => {
+                    self.splice(*offset..*offset, bytes.iter().copied());
+                }
+                EditOp::Delete { offset, bytes } => {
+                    self.drain(*offset..*offset + bytes.len());
+                }
+                EditOp::Overwrite { offset, new, .. } => {
+                    self[*offset..*offset + new.len()].copy_from_slice(new);
+                }
+                EditOp::Group(ops) => {
+                    for op in ops {
+                        self.apply(op)?;
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn exec(h: &mut History, text: &mut Vec<u8>, op: EditOp) {
+        text.apply(&op).unwrap();
+        h.record(op);
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut text = b"hello".to_vec();
+        let mut h = History::new();
+        for (i, c) in b"abc".iter().enumerate() {
+            exec(
+                &mut h,
+                &mut text,
+                EditOp::Insert {
+                    offset: 5 + i,
+                    bytes: vec![*c],
+                },
+            );
+        }
+        exec(
+            &mut h,
+            &mut text,
+            EditOp::Delete {
+                offset: 0,
+                bytes: b"he".to_vec(),
+            },
+        );
+        assert_eq!(text, b"lloabc");
+        //连续键入合并为一步
+        assert_eq!(h.undo(1, &mut text).unwrap(), Some(0));
+        assert_eq!(text, b"helloabc");
+        assert_eq!(h.undo(5, &mut text).unwrap(), Some(5));
+        assert_eq!(text, b"hello");
+        assert_eq!(h.redo(2, &mut text).unwrap(), Some(0));
+        assert_eq!(text, b"lloabc");
+    }
+
+    #[test]
+    fn test_merge_nibble() {
+        let mut text = vec![0x00, 0x11];
+        let mut h = History::new();
+        let ops = [
+            (0, 0x00, 0xA0),
+            (0, 0xA0, 0xAB),
+            (1, 0x11, 0xC1),
+            (1, 0xC1, 0xCD),
+        ];
+        for (offset, old, new) in ops {
+            exec(
+                &mut h,
+                &mut text,
+                EditOp::Overwrite {
+                    offset,
+                    old: vec![old],
+                    new: vec![new],
+                },
+            );
+        }
+        assert_eq!(text, vec![0xAB, 0xCD]);
+        h.undo(1, &mut text).unwrap();
+        assert_eq!(text, vec![0x00, 0x11]);
+
+        //光标移开后再回来键入 不和之前的合并
+        h.redo(1, &mut text).unwrap();
+        h.end_typing();
+        exec(
+            &mut h,
+            &mut text,
+            EditOp::Overwrite {
+                offset: 1,
+                old: vec![0xCD],
+                new: vec![0xEF],
+            },
+        );
+        h.undo(1, &mut text).unwrap();
+        assert_eq!(text, vec![0xAB, 0xCD]);
+    }
+
+    #[test]
+    fn test_splices() {
+        let mut text = b"0123456789".to_vec();
+        let mut h = History::new();
+        exec(
+            &mut h,
+            &mut text,
+            EditOp::Insert {
+                offset: 2,
+                bytes: b"ab".to_vec(),
+            },
+        );
+        //长度不变的覆盖不影响后面的偏移
+        exec(
+            &mut h,
+            &mut text,
+            EditOp::Overwrite {
+                offset: 0,
+                old: b"0".to_vec(),
+                new: b"x".to_vec(),
+            },
+        );
+        h.undo(2, &mut text).unwrap();
+        let insert = Splice {
+            offset: 2,
+            removed: 0,
+            inserted: 2,
+        };
+        let delete = Splice {
+            offset: 2,
+            removed: 2,
+            inserted: 0,
+        };
+        assert_eq!(h.take_splices(), vec![insert, delete]);
+        assert!(h.take_splices().is_empty());
+
+        //插入点之前结束的区间不动 之后的后移
+        assert_eq!(insert.map_range(0, 2), (0, 2));
+        assert_eq!(insert.map_range(2, 4), (4, 6));
+        assert_eq!(insert.map_range(1, 3), (1, 5));
+        //被删除的部分收缩到删除位置
+        assert_eq!(delete.map_range(5, 8), (3, 6));
+        assert_eq!(delete.map_range(3, 6), (2, 4));
+        assert_eq!(delete.map_range(2, 4), (2, 2));
+        assert_eq!(delete.map_range(0, 3), (0, 2));
+    }
+}
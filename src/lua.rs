@@ -1,81 +1,291 @@
-use crate::{error::ChapResult, plugin::Plugin};
+use crate::plugin::{
+    ByteSource, Manifest, PluginAction, PluginArg, PluginField, PluginOutput, PluginResult,
+};
+use crate::tui::TextSelect;
+use crate::{
+    error::{ChapError, ChapResult},
+    plugin::Plugin,
+};
 use mlua::prelude::*;
+use mlua::StdLib;
+use ratatui::style::Color;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 pub(crate) struct LuaScript {
     desc: PathBuf,
     script: PathBuf,
 }
 
+impl LuaScript {
+    // 读取插件描述 没有desc.txt时使用默认值
+    fn manifest(&self) -> ChapResult<Manifest> {
+        if !self.desc.exists() {
+            return Ok(Manifest::default());
+        }
+        let content = fs::read_to_string(&self.desc)
+            .map_err(|e| format!("Failed to read manifest: {}", e))?;
+        Manifest::parse(&content)
+    }
+}
+
 pub(crate) struct LuaPlugin {
-    lua: Lua,
-    plugin_path: PathBuf,
-    scripts_registry: HashMap<String, LuaScript>,
+    lua: RefCell<Lua>,
+    budget: Rc<Budget>,
+    plugin_paths: Vec<PathBuf>,
+    scripts_registry: RefCell<HashMap<String, LuaScript>>,
 }
 
-impl LuaPlugin {
-    pub(crate) fn new<P: AsRef<Path>>(plugin_path: P) -> LuaPlugin {
-        let mut scripts_registry: HashMap<String, LuaScript> = HashMap::new();
+// 单次执行最多的指令数
+const PLUGIN_MAX_INSTRUCTIONS: u64 = 100_000_000;
+// 单次执行最长时间
+const PLUGIN_TIMEOUT: Duration = Duration::from_secs(3);
+// 每执行多少条指令检查一次预算
+const HOOK_INSTRUCTIONS: u32 = 10_000;
+// lua 最多使用的内存
+const PLUGIN_MEMORY_LIMIT: usize = 256 * 1024 * 1024;
+
+// 插件执行预算 超出后中止脚本
+struct Budget {
+    max_instructions: u64,
+    timeout: Duration,
+    start: Cell<Instant>,
+    instructions: Cell<u64>,
+    aborted: RefCell<Option<String>>, //中止原因
+}
+
+impl Budget {
+    fn new(max_instructions: u64, timeout: Duration) -> Budget {
+        Budget {
+            max_instructions,
+            timeout,
+            start: Cell::new(Instant::now()),
+            instructions: Cell::new(0),
+            aborted: RefCell::new(None),
+        }
+    }
+
+    fn reset(&self) {
+        self.start.set(Instant::now());
+        self.instructions.set(0);
+        self.aborted.replace(None);
+    }
+
+    // 超出预算后一直返回错误 直到下一次执行
+    fn check(&self) -> LuaResult<()> {
+        if self.aborted.borrow().is_none() {
+            let reason = if self.instructions.get() > self.max_instructions {
+                format!("exceeded {} instructions", self.max_instructions)
+            } else if self.start.get().elapsed() > self.timeout {
+                format!("exceeded {:?} time budget", self.timeout)
+            } else {
+                return Ok(());
+            };
+            self.aborted.replace(Some(reason));
+        }
+        Err(LuaError::RuntimeError(format!(
+            "plugin aborted: {}",
+            self.aborted.borrow().as_deref().unwrap_or_default()
+        )))
+    }
+}
 
+// 插件运行环境 不加载 io package debug os只保留时间函数
+// 通过指令计数钩子限制执行时间 pcall 不能捕获超时错误
+fn new_sandbox(budget: &Rc<Budget>) -> LuaResult<Lua> {
+    let lua = Lua::new_with(
+        StdLib::TABLE
+            | StdLib::STRING
+            | StdLib::MATH
+            | StdLib::UTF8
+            | StdLib::COROUTINE
+            | StdLib::OS,
+        LuaOptions::default(),
+    )?;
+    lua.set_memory_limit(PLUGIN_MEMORY_LIMIT)?;
+    let globals = lua.globals();
+    let os: LuaTable = globals.get("os")?;
+    let safe_os = lua.create_table()?;
+    for name in ["clock", "date", "difftime", "time"] {
+        safe_os.set(name, os.get::<LuaValue>(name)?)?;
+    }
+    globals.set("os", safe_os)?;
+    globals.set("dofile", LuaNil)?;
+    globals.set("loadfile", LuaNil)?;
+    lua.load(
+        r#"
+        local load = load
+        -- 只允许加载文本代码
+        _G.load = function(chunk, name, _, env) return load(chunk, name, "t", env) end
+        -- 输出会破坏终端界面
+        _G.print = function() end
+        "#,
+    )
+    .exec()?;
+    for name in ["pcall", "xpcall"] {
+        let f: LuaFunction = globals.get(name)?;
+        let b = budget.clone();
+        globals.set(
+            name,
+            lua.create_function(move |_, args: LuaMultiValue| {
+                let ret: LuaMultiValue = f.call(args)?;
+                if b.aborted.borrow().is_some() {
+                    b.check()?;
+                }
+                Ok(ret)
+            })?,
+        )?;
+    }
+    let b = budget.clone();
+    lua.set_hook(
+        LuaHookTriggers::new().every_nth_instruction(HOOK_INSTRUCTIONS),
+        move |_, _| {
+            b.instructions
+                .set(b.instructions.get() + HOOK_INSTRUCTIONS as u64);
+            b.check()?;
+            Ok(LuaVmState::Continue)
+        },
+    );
+    Ok(lua)
+}
+
+// 扫描插件目录 前面目录中的同名插件优先
+fn scan_plugins(plugin_paths: &[PathBuf]) -> HashMap<String, LuaScript> {
+    let mut scripts_registry: HashMap<String, LuaScript> = HashMap::new();
+    for plugin_path in plugin_paths {
         // 读取plugin_path下的所有子目录
-        if let Ok(entries) = fs::read_dir(&plugin_path) {
-            for entry in entries {
-                if let Ok(entry) = entry {
-                    // 只处理子目录
-                    if let Ok(file_type) = entry.file_type() {
-                        if file_type.is_dir() {
-                            let dir_name = entry.file_name();
-                            let dir_name_str = dir_name.to_string_lossy().to_string();
-                            // 构建desc.txt的路径
-                            let desc_path = entry.path().join("desc.txt");
-                            // 构建lua的路径
-                            let expected_lua_name = dir_name_str.clone() + ".lua";
-                            let script_path = entry.path().join(&expected_lua_name);
-                            let lua_script = LuaScript {
-                                desc: desc_path,
-                                script: script_path,
-                            };
-                            scripts_registry.insert(dir_name_str, lua_script);
+        if let Ok(entries) = fs::read_dir(plugin_path) {
+            for entry in entries.flatten() {
+                // 只处理子目录
+                if let Ok(file_type) = entry.file_type() {
+                    if file_type.is_dir() {
+                        let dir_name = entry.file_name();
+                        let dir_name_str = dir_name.to_string_lossy().to_string();
+                        if scripts_registry.contains_key(&dir_name_str) {
+                            continue;
                         }
+                        // 构建desc.txt的路径
+                        let desc_path = entry.path().join("desc.txt");
+                        // 构建lua的路径
+                        let expected_lua_name = dir_name_str.clone() + ".lua";
+                        let script_path = entry.path().join(&expected_lua_name);
+                        let lua_script = LuaScript {
+                            desc: desc_path,
+                            script: script_path,
+                        };
+                        scripts_registry.insert(dir_name_str, lua_script);
                     }
                 }
             }
         }
+    }
+    scripts_registry
+}
 
-        Self {
-            lua: Lua::new(),
-            plugin_path: plugin_path.as_ref().to_path_buf(),
-            scripts_registry,
-        }
+impl LuaPlugin {
+    pub(crate) fn new<P: AsRef<Path>>(plugin_paths: &[P]) -> ChapResult<LuaPlugin> {
+        Self::with_budget(plugin_paths, PLUGIN_MAX_INSTRUCTIONS, PLUGIN_TIMEOUT)
     }
 
-    fn eval_lua_script(&self, name: &str, buf: &[u8]) -> ChapResult<String> {
-        let lua_scr = self
-            .scripts_registry
-            .get(name)
-            .ok_or_else(|| format!("Script '{}' not found in registry", name))?;
+    fn with_budget<P: AsRef<Path>>(
+        plugin_paths: &[P],
+        max_instructions: u64,
+        timeout: Duration,
+    ) -> ChapResult<LuaPlugin> {
+        let plugin_paths: Vec<PathBuf> = plugin_paths
+            .iter()
+            .map(|p| p.as_ref().to_path_buf())
+            .collect();
+        let budget = Rc::new(Budget::new(max_instructions, timeout));
+        Ok(Self {
+            lua: RefCell::new(new_sandbox(&budget)?),
+            budget,
+            scripts_registry: RefCell::new(scan_plugins(&plugin_paths)),
+            plugin_paths,
+        })
+    }
 
-        let lua_table = self
-            .lua
-            .create_table_from(buf.iter().enumerate().map(|(i, &b)| (i + 1, b)))?;
-        self.lua.globals().set("lua_table", lua_table)?;
-        let script_content = fs::read_to_string(lua_scr.script.as_path())
+    fn eval_lua_script(
+        &self,
+        name: &str,
+        args: &[(String, String)],
+        src: &dyn ByteSource,
+        sel: &TextSelect,
+    ) -> ChapResult<PluginResult> {
+        let (manifest, script) = {
+            let registry = self.scripts_registry.borrow();
+            let lua_scr = registry
+                .get(name)
+                .ok_or_else(|| format!("Script '{}' not found in registry", name))?;
+            (lua_scr.manifest()?, lua_scr.script.clone())
+        };
+        let sel_len = sel.get_end() + 1 - sel.get_start();
+        if sel_len < manifest.min_sel {
+            return Err(format!(
+                "'{}' needs at least {} selected bytes, got {}",
+                name, manifest.min_sel, sel_len
+            )
+            .into());
+        }
+        let args = manifest.bind(args)?;
+        let script_content = fs::read_to_string(script.as_path())
             .map_err(|e| format!("Failed to read Lua script: {}", e))?;
-        let result: String = self.lua.load(script_content).eval()?;
-        Ok(result)
+        let actions = RefCell::new(Vec::new());
+        let lua = self.lua.borrow();
+        self.budget.reset();
+        let output = lua.scope(|scope| {
+            let chap = chap_module(&lua, scope, src, sel, &actions)?;
+            chap.set("args", args_table(&lua, &args)?)?;
+            lua.globals().set("chap", chap)?;
+            let result: LuaValue = lua.load(script_content).set_name(name).eval()?;
+            to_plugin_output(result)
+        });
+        //超出预算被中止 只显示中止原因
+        if let Some(reason) = self.budget.aborted.borrow().as_ref() {
+            return Err(ChapError::LuaFail(LuaError::RuntimeError(format!(
+                "plugin '{}' aborted: {}",
+                name, reason
+            ))));
+        }
+        let output = output?;
+        Ok(PluginResult {
+            output,
+            actions: actions.into_inner(),
+        })
     }
 
     fn list_registered(&self) -> Vec<String> {
-        self.scripts_registry.keys().cloned().collect()
+        self.scripts_registry.borrow().keys().cloned().collect()
     }
 }
 
 impl Plugin for LuaPlugin {
-    fn eval(&self, name: &str, buf: &[u8]) -> ChapResult<String> {
-        self.eval_lua_script(name, buf)
+    fn eval(
+        &self,
+        name: &str,
+        args: &[(String, String)],
+        src: &dyn ByteSource,
+        sel: &TextSelect,
+    ) -> ChapResult<PluginResult> {
+        self.eval_lua_script(name, args, src, sel)
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.scripts_registry.borrow().contains_key(name)
     }
+    // 重新扫描插件目录 并重置lua状态
+    fn reload(&self) -> ChapResult<usize> {
+        let registry = scan_plugins(&self.plugin_paths);
+        let n = registry.len();
+        *self.scripts_registry.borrow_mut() = registry;
+        *self.lua.borrow_mut() = new_sandbox(&self.budget)?;
+        Ok(n)
+    }
+
     fn list(&self) -> ChapResult<String> {
         let mut names = self.list_registered();
         if names.is_empty() {
@@ -85,13 +295,199 @@ impl Plugin for LuaPlugin {
             names.sort();
             Ok(names
                 .iter()
-                .map(|name| format!("* {}", name))
+                .map(
+                    |name| match self.scripts_registry.borrow()[name].manifest() {
+                        Ok(m) if m == Manifest::default() => format!("* {}", name),
+                        Ok(m) => format!("* {} - {}", name, m.summary()),
+                        Err(e) => format!("* {} - manifest error: {}", name, e),
+                    },
+                )
                 .collect::<Vec<_>>()
                 .join("\n"))
         }
     }
 }
 
+// cstring 最多读取的字节数
+const CSTRING_MAX: usize = 4096;
+
+// chap.highlight 默认颜色
+const HIGHLIGHT_COLOR: &str = "yellow";
+
+// 读取 off 处 len 个字节 不足时报错
+fn read_exact(src: &dyn ByteSource, off: usize, len: usize) -> LuaResult<Vec<u8>> {
+    let buf = src.read(off, len);
+    if buf.len() < len {
+        return Err(LuaError::RuntimeError(format!(
+            "read {} bytes at {} out of range",
+            len, off
+        )));
+    }
+    Ok(buf)
+}
+
+// 注册定长数值读取函数 如 chap.u16le(off)
+macro_rules! set_reader {
+    ($scope:expr, $chap:expr, $src:expr, $name:literal, $t:ty, $from:ident) => {
+        $chap.set(
+            $name,
+            $scope.create_function(move |_, off: usize| {
+                let buf = read_exact($src, off, std::mem::size_of::<$t>())?;
+                Ok(<$t>::$from(buf.try_into().unwrap()))
+            })?,
+        )?;
+    };
+}
+
+// 构建 chap 模块 所有读取函数使用文件绝对偏移 按需从文件读取
+// 跳转 选择 标注等操作记录到 actions 脚本结束后由编辑器执行
+fn chap_module<'scope, 'env: 'scope>(
+    lua: &Lua,
+    scope: &'scope mlua::Scope<'scope, 'env>,
+    src: &'env dyn ByteSource,
+    sel: &TextSelect,
+    actions: &'env RefCell<Vec<PluginAction>>,
+) -> LuaResult<LuaTable> {
+    let chap = lua.create_table()?;
+    set_reader!(scope, chap, src, "u8", u8, from_le_bytes);
+    set_reader!(scope, chap, src, "i8", i8, from_le_bytes);
+    set_reader!(scope, chap, src, "u16le", u16, from_le_bytes);
+    set_reader!(scope, chap, src, "u16be", u16, from_be_bytes);
+    set_reader!(scope, chap, src, "i16le", i16, from_le_bytes);
+    set_reader!(scope, chap, src, "i16be", i16, from_be_bytes);
+    set_reader!(scope, chap, src, "u32le", u32, from_le_bytes);
+    set_reader!(scope, chap, src, "u32be", u32, from_be_bytes);
+    set_reader!(scope, chap, src, "i32le", i32, from_le_bytes);
+    set_reader!(scope, chap, src, "i32be", i32, from_be_bytes);
+    set_reader!(scope, chap, src, "u64le", u64, from_le_bytes);
+    set_reader!(scope, chap, src, "u64be", u64, from_be_bytes);
+    set_reader!(scope, chap, src, "i64le", i64, from_le_bytes);
+    set_reader!(scope, chap, src, "i64be", i64, from_be_bytes);
+    set_reader!(scope, chap, src, "f32le", f32, from_le_bytes);
+    set_reader!(scope, chap, src, "f32be", f32, from_be_bytes);
+    set_reader!(scope, chap, src, "f64le", f64, from_le_bytes);
+    set_reader!(scope, chap, src, "f64be", f64, from_be_bytes);
+    chap.set(
+        "bytes",
+        scope.create_function(move |lua, (off, len): (usize, usize)| {
+            lua.create_string(read_exact(src, off, len)?)
+        })?,
+    )?;
+    chap.set(
+        "cstring",
+        scope.create_function(move |lua, (off, max): (usize, Option<usize>)| {
+            let max = max.unwrap_or(CSTRING_MAX);
+            let mut s = Vec::new();
+            //分段读取 直到遇到\0
+            while s.len() < max {
+                let buf = src.read(off + s.len(), 64.min(max - s.len()));
+                if buf.is_empty() {
+                    break;
+                }
+                match buf.iter().position(|&b| b == 0) {
+                    Some(i) => {
+                        s.extend_from_slice(&buf[..i]);
+                        break;
+                    }
+                    None => s.extend_from_slice(&buf),
+                }
+            }
+            lua.create_string(s)
+        })?,
+    )?;
+    chap.set("size", scope.create_function(move |_, ()| Ok(src.size()))?)?;
+    chap.set(
+        "jump",
+        scope.create_function(move |_, addr: usize| {
+            actions.borrow_mut().push(PluginAction::Jump(addr));
+            Ok(())
+        })?,
+    )?;
+    chap.set(
+        "select",
+        scope.create_function(move |_, (off, len): (usize, usize)| {
+            let end = off + len.max(1) - 1;
+            actions.borrow_mut().push(PluginAction::Select(off, end));
+            Ok(())
+        })?,
+    )?;
+    chap.set(
+        "highlight",
+        scope.create_function(
+            move |_, (off, len, color): (usize, usize, Option<String>)| {
+                let color = color.as_deref().unwrap_or(HIGHLIGHT_COLOR);
+                let color = color
+                    .parse::<Color>()
+                    .map_err(|_| LuaError::RuntimeError(format!("unknown color '{}'", color)))?;
+                actions
+                    .borrow_mut()
+                    .push(PluginAction::Highlight(off, len, color));
+                Ok(())
+            },
+        )?,
+    )?;
+    chap.set(
+        "clear_highlights",
+        scope.create_function(move |_, ()| {
+            actions.borrow_mut().push(PluginAction::ClearHighlights);
+            Ok(())
+        })?,
+    )?;
+    chap.set(
+        "message",
+        scope.create_function(move |_, msg: String| {
+            actions.borrow_mut().push(PluginAction::Message(msg));
+            Ok(())
+        })?,
+    )?;
+    chap.set("sel_start", sel.get_start())?;
+    chap.set("sel_len", sel.get_end() + 1 - sel.get_start())?;
+    Ok(chap)
+}
+
+// call 传入的参数 脚本中通过 chap.args.<name> 读取
+fn args_table(lua: &Lua, args: &[(String, PluginArg)]) -> LuaResult<LuaTable> {
+    let table = lua.create_table()?;
+    for (name, arg) in args {
+        match arg {
+            PluginArg::Int(v) => table.set(name.as_str(), *v)?,
+            PluginArg::Bool(v) => table.set(name.as_str(), *v)?,
+            PluginArg::Str(v) => table.set(name.as_str(), v.as_str())?,
+        }
+    }
+    Ok(table)
+}
+
+fn value_to_string(v: &LuaValue) -> String {
+    match v {
+        LuaValue::Nil => String::new(),
+        LuaValue::Boolean(b) => b.to_string(),
+        LuaValue::Integer(i) => i.to_string(),
+        LuaValue::Number(n) => n.to_string(),
+        LuaValue::String(s) => s.to_string_lossy(),
+        v => format!("{:?}", v),
+    }
+}
+
+// 脚本返回字符串 或者 {name, offset, length, value} 字段表
+fn to_plugin_output(result: LuaValue) -> LuaResult<PluginOutput> {
+    let table = match result {
+        LuaValue::Table(t) => t,
+        v => return Ok(PluginOutput::Text(value_to_string(&v))),
+    };
+    let mut fields = Vec::new();
+    for item in table.sequence_values::<LuaTable>() {
+        let item = item?;
+        fields.push(PluginField {
+            name: value_to_string(&item.get::<LuaValue>("name")?),
+            offset: item.get::<Option<usize>>("offset")?.unwrap_or(0),
+            length: item.get::<Option<usize>>("length")?.unwrap_or(0),
+            value: value_to_string(&item.get::<LuaValue>("value")?),
+        });
+    }
+    Ok(PluginOutput::Fields(fields))
+}
+
 fn lua_vec() -> ChapResult<()> {
     let data: Vec<u8> = b"hello world".to_vec();
     let lua = Lua::new();
@@ -137,4 +533,198 @@ mod tests {
     fn test_lua_hello() {
         lua_vec().unwrap();
     }
+
+    impl ByteSource for Vec<u8> {
+        fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+            let start = offset.min(self.len());
+            let end = offset.saturating_add(len).min(self.len());
+            self[start..end].to_vec()
+        }
+
+        fn size(&self) -> usize {
+            self.len()
+        }
+    }
+
+    #[test]
+    fn test_lua_chap_module() {
+        let dir = std::env::temp_dir().join("chap_test_lua_plugin");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("hdr")).unwrap();
+        fs::write(
+            dir.join("hdr/hdr.lua"),
+            r#"
+            local off = chap.sel_start
+            return {
+                { name = "magic", offset = off, length = 2, value = chap.u16le(off) },
+                { name = "size", offset = off + 2, length = 4, value = chap.u32be(off + 2) },
+                { name = "name", offset = off + 6, length = 3, value = chap.cstring(off + 6) },
+                { name = "raw", offset = off, length = 2, value = #chap.bytes(off, 2) },
+            }
+            "#,
+        )
+        .unwrap();
+        fs::create_dir_all(dir.join("oob")).unwrap();
+        fs::write(
+            dir.join("oob/oob.lua"),
+            "return chap.u64le(chap.size() - 4)",
+        )
+        .unwrap();
+
+        let plugin = LuaPlugin::new(&[&dir]).unwrap();
+        let data: Vec<u8> = vec![0xff, 0x34, 0x12, 0, 0, 1, 0, b'a', b'b', 0, b'c'];
+        let sel = TextSelect::from_select(1, 4);
+        let out = plugin.eval("hdr", &[], &data, &sel).unwrap().output;
+        let field = |name: &str, offset, length, value: &str| PluginField {
+            name: name.to_string(),
+            offset,
+            length,
+            value: value.to_string(),
+        };
+        assert_eq!(
+            out,
+            PluginOutput::Fields(vec![
+                field("magic", 1, 2, "4660"),
+                field("size", 3, 4, "256"),
+                field("name", 7, 3, "ab"),
+                field("raw", 1, 2, "2"),
+            ])
+        );
+        //越界读取报错
+        assert!(plugin.eval("oob", &[], &data, &sel).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_lua_args() {
+        let plugin = LuaPlugin::new(&["plugin"]).unwrap();
+        let mut tuple = vec![0u8; 32];
+        tuple[0..4].copy_from_slice(&7u32.to_be_bytes());
+        tuple[18..20].copy_from_slice(&3u16.to_be_bytes());
+        tuple[20..22].copy_from_slice(&1u16.to_be_bytes());
+        tuple[23] = 0x05;
+        let sel = TextSelect::from_select(0, 22);
+        let args = vec![
+            ("natts".to_string(), "9".to_string()),
+            ("endian".to_string(), "big".to_string()),
+        ];
+        let value = |out: &PluginOutput, name: &str| match out {
+            PluginOutput::Fields(fields) => fields
+                .iter()
+                .find(|f| f.name == name)
+                .map(|f| f.value.clone()),
+            _ => None,
+        };
+        let out = plugin
+            .eval("pg_heap_tuple", &args, &tuple, &sel)
+            .unwrap()
+            .output;
+        assert_eq!(value(&out, "t_xmin").as_deref(), Some("7"));
+        assert_eq!(value(&out, "natts").as_deref(), Some("9"));
+        assert_eq!(value(&out, "t_bits").as_deref(), Some("05 00"));
+        //未传入 natts 时使用 t_infomask2 中的列数
+        let args = vec![("endian".to_string(), "big".to_string())];
+        let out = plugin
+            .eval("pg_heap_tuple", &args, &tuple, &sel)
+            .unwrap()
+            .output;
+        assert_eq!(value(&out, "natts").as_deref(), Some("3"));
+        //参数错误 选区太短
+        let args = vec![("natts".to_string(), "x".to_string())];
+        assert!(plugin.eval("pg_heap_tuple", &args, &tuple, &sel).is_err());
+        let short = TextSelect::from_select(0, 3);
+        assert!(plugin.eval("pg_heap_tuple", &[], &tuple, &short).is_err());
+        assert!(plugin.list().unwrap().contains("natts: int = 0"));
+    }
+
+    #[test]
+    fn test_lua_reload() {
+        let dir = std::env::temp_dir().join("chap_test_lua_reload");
+        let _ = fs::remove_dir_all(&dir);
+        let (dir1, dir2) = (dir.join("a"), dir.join("b"));
+        fs::create_dir_all(dir1.join("p1")).unwrap();
+        fs::create_dir_all(dir2.join("p1")).unwrap();
+        fs::write(dir1.join("p1/p1.lua"), "return 'a'").unwrap();
+        fs::write(dir2.join("p1/p1.lua"), "return 'b'").unwrap();
+        let data: Vec<u8> = vec![0; 4];
+        let sel = TextSelect::from_select(0, 0);
+
+        //前面目录中的同名插件优先
+        let plugin = LuaPlugin::new(&[&dir1, &dir2]).unwrap();
+        let out = plugin.eval("p1", &[], &data, &sel).unwrap().output;
+        assert_eq!(out, PluginOutput::Text("a".to_string()));
+        assert!(plugin.eval("p2", &[], &data, &sel).is_err());
+
+        fs::create_dir_all(dir2.join("p2")).unwrap();
+        fs::write(dir2.join("p2/p2.lua"), "return 'p2'").unwrap();
+        assert_eq!(plugin.reload().unwrap(), 2);
+        let out = plugin.eval("p2", &[], &data, &sel).unwrap().output;
+        assert_eq!(out, PluginOutput::Text("p2".to_string()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_lua_sandbox() {
+        let dir = std::env::temp_dir().join("chap_test_lua_sandbox");
+        let _ = fs::remove_dir_all(&dir);
+        let scripts = [
+            (
+                "env",
+                "return tostring(io == nil and require == nil and dofile == nil \
+                 and os.execute == nil and os.time ~= nil)",
+            ),
+            ("spin", "while true do end"),
+            (
+                "catch",
+                "while true do pcall(function() while true do end end) end",
+            ),
+        ];
+        for (name, script) in scripts {
+            fs::create_dir_all(dir.join(name)).unwrap();
+            fs::write(dir.join(name).join(format!("{}.lua", name)), script).unwrap();
+        }
+        let plugin = LuaPlugin::with_budget(&[&dir], 1_000_000, Duration::from_secs(5)).unwrap();
+        let data: Vec<u8> = vec![0; 4];
+        let sel = TextSelect::from_select(0, 0);
+        let out = plugin.eval("env", &[], &data, &sel).unwrap().output;
+        assert_eq!(out, PluginOutput::Text("true".to_string()));
+        //死循环被中止 pcall 不能捕获
+        for name in ["spin", "catch"] {
+            let err = plugin.eval(name, &[], &data, &sel).err().unwrap();
+            assert!(matches!(err, ChapError::LuaFail(_)));
+            assert!(err.to_string().contains("aborted"), "{}", err);
+        }
+        //中止后可以继续执行其他插件
+        assert!(plugin.eval("env", &[], &data, &sel).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_lua_actions() {
+        let plugin = LuaPlugin::new(&["plugin"]).unwrap();
+        let mut page = vec![0u8; 128];
+        page[12..14].copy_from_slice(&32u16.to_le_bytes());
+        let lp = |off: u32, flags: u32, len: u32| (off | flags << 15 | len << 17).to_le_bytes();
+        page[24..28].copy_from_slice(&lp(64, 1, 10));
+        page[28..32].copy_from_slice(&lp(80, 1, 6));
+        let result = plugin
+            .eval("pg_tuples", &[], &page, &TextSelect::from_select(0, 0))
+            .unwrap();
+        assert_eq!(
+            result.actions,
+            vec![
+                PluginAction::ClearHighlights,
+                PluginAction::Highlight(24, 4, Color::Yellow),
+                PluginAction::Highlight(64, 10, Color::Cyan),
+                PluginAction::Highlight(28, 4, Color::Yellow),
+                PluginAction::Highlight(80, 6, Color::Magenta),
+                PluginAction::Select(24, 31),
+                PluginAction::Message("2 line pointers".to_string()),
+            ]
+        );
+        match result.output {
+            PluginOutput::Fields(fields) => assert_eq!(fields.len(), 2),
+            _ => panic!("expect fields"),
+        }
+    }
 }
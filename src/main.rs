@@ -3,23 +3,35 @@
 #![feature(let_chains)]
 #![feature(trait_alias)]
 //mod chatapi;
+mod bookmark;
 mod byteutil;
 mod chap;
 mod cli;
 mod command;
+mod diff;
 mod editor;
 mod error;
+mod exe;
 mod function;
 mod fuzzy;
 mod gap_buffer;
 mod handle;
+mod history;
 mod lua;
 mod pg;
+mod pgfile;
+mod pgtuple;
 mod plugin;
+mod search;
+mod sqlite;
+mod template;
 mod textwarp;
+mod toast;
+mod tree;
 mod tui;
 mod util;
 mod vb;
+mod wal;
 use crate::cli::Cli;
 use crate::handle::tui_retore;
 use crate::util::mmap_file;
@@ -36,8 +48,14 @@ fn main() -> Result<(), Box<dyn Error>> {
     if !std::path::Path::new(filename).exists() {
         return Err(error::ChapError::FileNotFound(filename.to_string()).into());
     }
+    let other = cli.get_diff_path()?;
+    if let Some(other) = other {
+        if !std::path::Path::new(other).exists() {
+            return Err(error::ChapError::FileNotFound(other.to_string()).into());
+        }
+    }
     if atty::is(atty::Stream::Stdin) {
-        if let Err(e) = run_app(&cli, filename) {
+        if let Err(e) = run_app(&cli, filename, other) {
             println!("chap error: {}", e);
         }
     }
@@ -45,8 +63,15 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-fn run_app(cli: &Cli, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn run_app(
+    cli: &Cli,
+    filename: &str,
+    other: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
     let mut chap = Chappie::new(&cli)?;
-    chap.run(filename)?;
+    match other {
+        Some(other) => chap.run_diff(filename, other, cli.get_align())?,
+        None => chap.run(filename)?,
+    }
     Ok(())
 }
@@ -1,4 +1,9 @@
 use crate::byteutil::ByteView;
+use crate::pgtuple::tuple_attr_fields;
+use crate::pgtuple::PgType;
+use crate::plugin::ByteSource;
+use crate::plugin::PluginField;
+use crate::wal::format_lsn;
 use std::fmt;
 #[repr(C)]
 #[derive(Debug)]
@@ -155,25 +160,11 @@ impl fmt::Display for HeapTupleHeader {
         writeln!(f, "    → HEAP_ONLY_TUPLE flag: {}", heap_only)?;
         writeln!(f, "  infomask:  0x{:04x}", self.infomask)?;
         // 解析 infomask 标志
-        let mut flags = Vec::new();
-        macro_rules! chk {
-            ($mask:expr, $name:expr) => {
-                if self.infomask & $mask != 0 {
-                    flags.push($name);
-                }
-            };
-        };
-        chk!(0x0001, "HASNULL");
-        chk!(0x0002, "HASVARWIDTH");
-        chk!(0x0004, "HASEXTERNAL");
-        chk!(0x0008, "HASOID");
-        chk!(0x0100, "XMIN_COMMITTED");
-        chk!(0x0200, "XMIN_INVALID");
-        chk!(0x0400, "XMAX_COMMITTED");
-        chk!(0x0800, "XMAX_INVALID");
-        chk!(0x2000, "UPDATED");
-        // …根据需要可添加更多宏表位…
-        writeln!(f, "    → flags: {:?}", flags)?;
+        writeln!(
+            f,
+            "    → flags: {:?}",
+            flag_names(self.infomask, INFOMASK_FLAGS)
+        )?;
         writeln!(f, "  t_hoff: {} bytes", self.t_hoff)?;
         writeln!(f, "  has_nulls: {}", self.has_nulls)?;
         writeln!(f, "  null_bitmap_bytes: {}", self.null_bitmap_bytes)?;
@@ -205,7 +196,7 @@ pub(crate) fn parse_heap_tuple_header(buf: &[u8]) -> String {
     let natts = infomask2 & 0x07ff;
     let null_bitmap_bytes = if has_nulls {
         // 位图按位对齐到字节，位数 = natts
-        ((natts as usize + 7) / 8)
+        (natts as usize).div_ceil(8)
     } else {
         0
     };
@@ -322,3 +313,909 @@ pub fn format_varatt_external(attr: &[u8]) -> String {
     }
     .to_string()
 }
+
+// 默认页大小 BLCKSZ
+pub(crate) const BLCKSZ: usize = 8192;
+pub(crate) const PAGE_HEADER_SIZE: usize = 24;
+const ITEM_ID_SIZE: usize = 4;
+pub(crate) const HEAP_TUPLE_HEADER_SIZE: usize = 23;
+
+const PD_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "PD_HAS_FREE_LINES"),
+    (0x0002, "PD_PAGE_FULL"),
+    (0x0004, "PD_ALL_VISIBLE"),
+];
+
+const INFOMASK_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "HASNULL"),
+    (0x0002, "HASVARWIDTH"),
+    (0x0004, "HASEXTERNAL"),
+    (0x0008, "HASOID"),
+    (0x0010, "XMAX_KEYSHR_LOCK"),
+    (0x0020, "COMBOCID"),
+    (0x0040, "XMAX_EXCL_LOCK"),
+    (0x0080, "XMAX_LOCK_ONLY"),
+    (0x0100, "XMIN_COMMITTED"),
+    (0x0200, "XMIN_INVALID"),
+    (0x0400, "XMAX_COMMITTED"),
+    (0x0800, "XMAX_INVALID"),
+    (0x1000, "XMAX_IS_MULTI"),
+    (0x2000, "UPDATED"),
+    (0x4000, "MOVED_OFF"),
+    (0x8000, "MOVED_IN"),
+];
+
+const INFOMASK2_FLAGS: &[(u16, &str)] = &[
+    (0x2000, "KEYS_UPDATED"),
+    (0x4000, "HOT_UPDATED"),
+    (0x8000, "HEAP_ONLY_TUPLE"),
+];
+
+// 按位列出标志名
+fn flag_names(v: u16, flags: &[(u16, &'static str)]) -> Vec<&'static str> {
+    flags
+        .iter()
+        .filter(|(mask, _)| v & mask != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+fn flags_value(v: u16, flags: &[(u16, &'static str)]) -> String {
+    let names = flag_names(v, flags);
+    if names.is_empty() {
+        format!("0x{:04x}", v)
+    } else {
+        format!("0x{:04x} {}", v, names.join("|"))
+    }
+}
+
+pub(crate) fn u16_at(page: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes(page[off..off + 2].try_into().unwrap())
+}
+
+pub(crate) fn u32_at(page: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(page[off..off + 4].try_into().unwrap())
+}
+
+// 页面字段列表 offset 相对页面 保存时加上页面在文件中的偏移
+pub(crate) struct PageFields {
+    base: usize,
+    pub(crate) fields: Vec<PluginField>,
+}
+
+impl PageFields {
+    pub(crate) fn new(base: usize) -> PageFields {
+        PageFields {
+            base,
+            fields: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push<N: Into<String>, V: Into<String>>(
+        &mut self,
+        name: N,
+        offset: usize,
+        length: usize,
+        value: V,
+    ) {
+        self.fields.push(PluginField {
+            name: name.into(),
+            offset: self.base + offset,
+            length,
+            value: value.into(),
+        })
+    }
+}
+
+// 行指针
+struct LinePointer {
+    n: usize, // OffsetNumber 从1开始
+    flags: u32,
+    off: usize,
+    len: usize,
+}
+
+const LP_NORMAL: u32 = 1;
+const LP_REDIRECT: u32 = 2;
+const LP_DEAD: u32 = 3;
+
+// 页头 PageHeaderData
+fn page_header_fields(page: &[u8], out: &mut PageFields) {
+    let pd_pagesize_version = u16_at(page, 18);
+    //PageXLogRecPtr 高32位在前
+    let lsn = (u32_at(page, 0) as u64) << 32 | u32_at(page, 4) as u64;
+    out.push("pd_lsn", 0, 8, format_lsn(lsn));
+    out.push("pd_checksum", 8, 2, u16_at(page, 8).to_string());
+    out.push("pd_flags", 10, 2, flags_value(u16_at(page, 10), PD_FLAGS));
+    out.push("pd_lower", 12, 2, u16_at(page, 12).to_string());
+    out.push("pd_upper", 14, 2, u16_at(page, 14).to_string());
+    out.push("pd_special", 16, 2, u16_at(page, 16).to_string());
+    out.push(
+        "pd_pagesize_version",
+        18,
+        2,
+        format!(
+            "{} bytes, version {}",
+            pd_pagesize_version & 0xFF00,
+            pd_pagesize_version & 0x00FF
+        ),
+    );
+    out.push("pd_prune_xid", 20, 4, u32_at(page, 20).to_string());
+}
+
+// 行指针数组 ItemIdData 和空闲空间
+fn line_pointer_fields(page: &[u8], out: &mut PageFields) -> Vec<LinePointer> {
+    let size = page.len();
+    let pd_upper = u16_at(page, 14) as usize;
+    let lower = (u16_at(page, 12) as usize).clamp(PAGE_HEADER_SIZE, size);
+    let item_count = (lower - PAGE_HEADER_SIZE) / ITEM_ID_SIZE;
+    let mut items = Vec::with_capacity(item_count);
+    for i in 0..item_count {
+        let off = PAGE_HEADER_SIZE + i * ITEM_ID_SIZE;
+        let raw = u32_at(page, off);
+        let lp = LinePointer {
+            n: i + 1,
+            flags: (raw >> 15) & 0x3,
+            off: (raw & 0x7FFF) as usize,
+            len: ((raw >> 17) & 0x7FFF) as usize,
+        };
+        let flags = match lp.flags {
+            LP_NORMAL => "LP_NORMAL",
+            LP_REDIRECT => "LP_REDIRECT",
+            LP_DEAD => "LP_DEAD",
+            _ => "LP_UNUSED",
+        };
+        let value = if lp.flags == LP_REDIRECT {
+            format!("{} -> lp {}", flags, lp.off)
+        } else {
+            format!("{} off={} len={}", flags, lp.off, lp.len)
+        };
+        out.push(format!("lp[{}]", lp.n), off, ITEM_ID_SIZE, value);
+        items.push(lp);
+    }
+    if pd_upper >= lower && pd_upper <= size {
+        out.push(
+            "free space",
+            lower,
+            pd_upper - lower,
+            format!("{} bytes", pd_upper - lower),
+        );
+    }
+    items
+}
+
+// 解析整个堆页面 base 为页面在文件中的偏移
+// 每个字段对应页面中的一段字节 用于在16进制视图中选中
+pub(crate) fn decode_heap_page(page: &[u8], base: usize, types: &[PgType]) -> Vec<PluginField> {
+    let mut out = PageFields::new(base);
+    if page.len() < PAGE_HEADER_SIZE {
+        out.push(
+            "page",
+            0,
+            page.len(),
+            format!("page < {}", PAGE_HEADER_SIZE),
+        );
+        return out.fields;
+    }
+    let size = page.len();
+    let pd_special = u16_at(page, 16) as usize;
+    page_header_fields(page, &mut out);
+    let items = line_pointer_fields(page, &mut out);
+
+    for lp in items
+        .iter()
+        .filter(|lp| lp.flags == LP_NORMAL && lp.len > 0)
+    {
+        let (lp_off, lp_len) = (lp.off, lp.len);
+        let t = format!("tup[{}]", lp.n);
+        if lp_off + lp_len > size || lp_len < HEAP_TUPLE_HEADER_SIZE {
+            out.push(
+                t,
+                lp_off.min(size),
+                0,
+                format!("invalid item off={} len={}", lp_off, lp_len),
+            );
+            continue;
+        }
+        heap_tuple_fields(&page[lp_off..lp_off + lp_len], lp_off, &t, types, &mut out);
+    }
+
+    if pd_special < size && pd_special >= PAGE_HEADER_SIZE {
+        out.push(
+            "special",
+            pd_special,
+            size - pd_special,
+            format!("{} bytes", size - pd_special),
+        );
+    }
+    out.fields
+}
+
+// 元组头部字段 types 不为空时按列类型解析数据部分
+fn heap_tuple_fields(tup: &[u8], off: usize, t: &str, types: &[PgType], out: &mut PageFields) {
+    let infomask2 = u16_at(tup, 18);
+    let infomask = u16_at(tup, 20);
+    let t_hoff = tup[22] as usize;
+    let natts = (infomask2 & 0x07FF) as usize;
+    out.push(format!("{}.t_xmin", t), off, 4, u32_at(tup, 0).to_string());
+    out.push(
+        format!("{}.t_xmax", t),
+        off + 4,
+        4,
+        u32_at(tup, 4).to_string(),
+    );
+    out.push(
+        format!("{}.t_cid", t),
+        off + 8,
+        4,
+        u32_at(tup, 8).to_string(),
+    );
+    out.push(
+        format!("{}.t_ctid", t),
+        off + 12,
+        6,
+        format_tid(&tup[12..18]),
+    );
+    out.push(
+        format!("{}.t_infomask2", t),
+        off + 18,
+        2,
+        format!(
+            "natts={} {}",
+            natts,
+            flags_value(infomask2 & !0x07FF, INFOMASK2_FLAGS)
+        ),
+    );
+    out.push(
+        format!("{}.t_infomask", t),
+        off + 20,
+        2,
+        flags_value(infomask, INFOMASK_FLAGS),
+    );
+    out.push(format!("{}.t_hoff", t), off + 22, 1, t_hoff.to_string());
+    if infomask & 0x0001 != 0 {
+        let bitmap = &tup[HEAP_TUPLE_HEADER_SIZE..];
+        out.push(
+            format!("{}.t_bits", t),
+            off + HEAP_TUPLE_HEADER_SIZE,
+            natts.div_ceil(8),
+            format_null_bitmap(bitmap, natts),
+        );
+    }
+    if t_hoff > tup.len() {
+        return;
+    }
+    if !types.is_empty() {
+        tuple_attr_fields(tup, off, t, types, out);
+    } else {
+        out.push(
+            format!("{}.data", t),
+            off + t_hoff,
+            tup.len() - t_hoff,
+            format!("{} bytes", tup.len() - t_hoff),
+        );
+    }
+}
+
+// 解析选区开始处的单个堆元组
+pub(crate) fn decode_heap_tuple(tup: &[u8], base: usize, types: &[PgType]) -> Vec<PluginField> {
+    let mut out = PageFields::new(base);
+    if tup.len() < HEAP_TUPLE_HEADER_SIZE {
+        out.push(
+            "tup",
+            0,
+            tup.len(),
+            format!("heap tuple < {}", HEAP_TUPLE_HEADER_SIZE),
+        );
+        return out.fields;
+    }
+    heap_tuple_fields(tup, 0, "tup", types, &mut out);
+    out.fields
+}
+
+// ItemPointerData (bi_hi, bi_lo, ip_posid)
+pub(crate) fn format_tid(b: &[u8]) -> String {
+    let block = (u16_at(b, 0) as u32) << 16 | u16_at(b, 2) as u32;
+    format!("({},{})", block, u16_at(b, 4))
+}
+
+// 空值位图 位为1表示不为空
+fn format_null_bitmap(bitmap: &[u8], natts: usize) -> String {
+    let bits: String = (0..natts)
+        .map(|i| match bitmap.get(i / 8) {
+            Some(b) if b & (1 << (i % 8)) != 0 => '1',
+            Some(_) => '0',
+            None => '?',
+        })
+        .collect();
+    format!("{} (0=null)", bits)
+}
+
+// BTPageOpaqueData 位于页面的特殊空间
+const BT_OPAQUE_SIZE: usize = 16;
+const BTP_LEAF: u16 = 0x0001;
+const BTP_META: u16 = 0x0008;
+const BTREE_MAGIC: u32 = 0x053162;
+
+const BTP_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "LEAF"),
+    (0x0002, "ROOT"),
+    (0x0004, "DELETED"),
+    (0x0008, "META"),
+    (0x0010, "HALF_DEAD"),
+    (0x0020, "SPLIT_END"),
+    (0x0040, "HAS_GARBAGE"),
+    (0x0080, "INCOMPLETE_SPLIT"),
+    (0x0100, "HAS_FULLXID"),
+];
+
+// IndexTupleData.t_info 高3位
+const INDEX_SIZE_MASK: u16 = 0x1FFF;
+const INDEX_ALT_TID_MASK: u16 = 0x2000;
+const INDEX_NULL_MASK: u16 = 0x8000;
+const INDEX_TUPLE_HEADER_SIZE: usize = 8;
+const INDEX_NULL_BITMAP_SIZE: usize = 4;
+const INDEX_MAX_KEYS: usize = 32;
+
+const INDEX_TINFO_FLAGS: &[(u16, &str)] = &[
+    (0x2000, "ALT_TID"),
+    (0x4000, "VARWIDTH"),
+    (0x8000, "HASNULL"),
+];
+
+// ALT_TID 时 ip_posid 的含义
+const BT_OFFSET_MASK: u16 = 0x0FFF;
+const BT_PIVOT_HEAP_TID_ATTR: u16 = 0x1000;
+const BT_IS_POSTING: u16 = 0x2000;
+const TID_SIZE: usize = 6;
+// 倒排列表最多显示的 tid 数
+const POSTING_SHOW: usize = 8;
+
+// 解析整个 nbtree 索引页面
+// 元页面解析 BTMetaPageData 其他页面解析高键 pivot 和 posting list 元组
+pub(crate) fn decode_btree_page(page: &[u8], base: usize) -> Vec<PluginField> {
+    let mut out = PageFields::new(base);
+    if page.len() < PAGE_HEADER_SIZE {
+        out.push(
+            "page",
+            0,
+            page.len(),
+            format!("page < {}", PAGE_HEADER_SIZE),
+        );
+        return out.fields;
+    }
+    let size = page.len();
+    page_header_fields(page, &mut out);
+
+    let special = u16_at(page, 16) as usize;
+    let opaque = if special >= PAGE_HEADER_SIZE && special + BT_OPAQUE_SIZE <= size {
+        Some(special)
+    } else {
+        out.push(
+            "special",
+            special.min(size),
+            0,
+            format!("invalid btree special {}", special),
+        );
+        None
+    };
+    let (btpo_next, btpo_flags) = match opaque {
+        Some(sp) => (u32_at(page, sp + 4), u16_at(page, sp + 12)),
+        None => (0, BTP_LEAF),
+    };
+
+    if btpo_flags & BTP_META != 0 {
+        btree_meta_fields(page, &mut out);
+    } else {
+        let items = line_pointer_fields(page, &mut out);
+        //最右页面没有高键
+        let has_high_key = btpo_next != 0;
+        let leaf = btpo_flags & BTP_LEAF != 0;
+        for lp in items
+            .iter()
+            .filter(|lp| (lp.flags == LP_NORMAL || lp.flags == LP_DEAD) && lp.len > 0)
+        {
+            let pos = ItupPos {
+                leaf,
+                high_key: has_high_key && lp.n == 1,
+                //内部页面的第一个数据项没有键 表示负无穷
+                first_data: lp.n == if has_high_key { 2 } else { 1 },
+            };
+            index_tuple_fields(page, lp, &pos, &mut out);
+        }
+    }
+
+    if let Some(sp) = opaque {
+        out.push("btpo_prev", sp, 4, u32_at(page, sp).to_string());
+        out.push("btpo_next", sp + 4, 4, btpo_next.to_string());
+        out.push("btpo_level", sp + 8, 4, u32_at(page, sp + 8).to_string());
+        out.push("btpo_flags", sp + 12, 2, flags_value(btpo_flags, BTP_FLAGS));
+        out.push(
+            "btpo_cycleid",
+            sp + 14,
+            2,
+            u16_at(page, sp + 14).to_string(),
+        );
+    }
+    out.fields
+}
+
+// BTMetaPageData 紧跟在页头之后
+fn btree_meta_fields(page: &[u8], out: &mut PageFields) {
+    let m = PAGE_HEADER_SIZE;
+    if page.len() < m + 41 {
+        out.push("btm", m, 0, "metapage too short");
+        return;
+    }
+    let magic = u32_at(page, m);
+    let magic_value = if magic == BTREE_MAGIC {
+        format!("0x{:06x}", magic)
+    } else {
+        format!("0x{:06x} (expected 0x{:06x})", magic, BTREE_MAGIC)
+    };
+    out.push("btm_magic", m, 4, magic_value);
+    for (i, name) in [
+        "btm_version",
+        "btm_root",
+        "btm_level",
+        "btm_fastroot",
+        "btm_fastlevel",
+        "btm_last_cleanup_num_delpages",
+    ]
+    .iter()
+    .enumerate()
+    {
+        let off = m + 4 + i * 4;
+        out.push(*name, off, 4, u32_at(page, off).to_string());
+    }
+    let tuples = f64::from_le_bytes(page[m + 32..m + 40].try_into().unwrap());
+    out.push(
+        "btm_last_cleanup_num_heap_tuples",
+        m + 32,
+        8,
+        tuples.to_string(),
+    );
+    out.push(
+        "btm_allequalimage",
+        m + 40,
+        1,
+        (page[m + 40] != 0).to_string(),
+    );
+}
+
+// 元组在页面中的位置 决定 pivot 的类型
+struct ItupPos {
+    leaf: bool,
+    high_key: bool,
+    first_data: bool,
+}
+
+// IndexTupleData
+fn index_tuple_fields(page: &[u8], lp: &LinePointer, pos: &ItupPos, out: &mut PageFields) {
+    let ItupPos {
+        leaf,
+        high_key,
+        first_data,
+    } = *pos;
+    let t = format!("itup[{}]", lp.n);
+    if lp.off + lp.len > page.len() || lp.len < INDEX_TUPLE_HEADER_SIZE {
+        out.push(
+            t,
+            lp.off.min(page.len()),
+            0,
+            format!("invalid item off={} len={}", lp.off, lp.len),
+        );
+        return;
+    }
+    let tup = &page[lp.off..lp.off + lp.len];
+    let block = (u16_at(tup, 0) as usize) << 16 | u16_at(tup, 2) as usize;
+    let posid = u16_at(tup, 4);
+    let t_info = u16_at(tup, 6);
+    let size = ((t_info & INDEX_SIZE_MASK) as usize).min(tup.len());
+    let alt = t_info & INDEX_ALT_TID_MASK != 0;
+    let data_off = if t_info & INDEX_NULL_MASK != 0 {
+        INDEX_TUPLE_HEADER_SIZE + INDEX_NULL_BITMAP_SIZE + 4 //MAXALIGN
+    } else {
+        INDEX_TUPLE_HEADER_SIZE
+    };
+    let posting = alt && posid & BT_IS_POSTING != 0;
+    //旧版本的 pivot 元组没有 ALT_TID 标志
+    let pivot = !posting && (alt || high_key || !leaf);
+
+    let (kind, tid) = if posting {
+        let n = (posid & BT_OFFSET_MASK) as usize;
+        ("posting list", format!("posting off={} n={}", block, n))
+    } else if pivot {
+        //截断后的 pivot 在 ip_posid 中保存键的数量
+        let natts = if alt {
+            format!("natts={}", posid & BT_OFFSET_MASK)
+        } else {
+            "untruncated".to_string()
+        };
+        let kind = if high_key {
+            "high key"
+        } else if first_data && !leaf {
+            "pivot (minus infinity)"
+        } else {
+            "pivot"
+        };
+        if leaf {
+            (kind, natts)
+        } else {
+            (kind, format!("downlink blk {} {}", block, natts))
+        }
+    } else {
+        (
+            "non-pivot",
+            format!("heap {}", format_tid(&tup[0..TID_SIZE])),
+        )
+    };
+    out.push(t.clone(), lp.off, lp.len, kind);
+    out.push(format!("{}.t_tid", t), lp.off, TID_SIZE, tid);
+    out.push(
+        format!("{}.t_info", t),
+        lp.off + TID_SIZE,
+        2,
+        format!(
+            "size={} {}",
+            t_info & INDEX_SIZE_MASK,
+            flags_value(t_info & !INDEX_SIZE_MASK, INDEX_TINFO_FLAGS)
+        ),
+    );
+    if t_info & INDEX_NULL_MASK != 0 {
+        let natts = if alt && !posting {
+            (posid & BT_OFFSET_MASK) as usize
+        } else {
+            INDEX_MAX_KEYS
+        };
+        out.push(
+            format!("{}.t_bits", t),
+            lp.off + INDEX_TUPLE_HEADER_SIZE,
+            INDEX_NULL_BITMAP_SIZE,
+            format_null_bitmap(&tup[INDEX_TUPLE_HEADER_SIZE..], natts),
+        );
+    }
+
+    //键值之后可能跟着 posting list 或 pivot 的堆 tid
+    let mut key_end = size;
+    if posting {
+        let n = (posid & BT_OFFSET_MASK) as usize;
+        let (start, end) = (block, block + n * TID_SIZE);
+        if start >= data_off && end <= size {
+            let mut tids: Vec<String> = tup[start..end]
+                .chunks_exact(TID_SIZE)
+                .take(POSTING_SHOW)
+                .map(format_tid)
+                .collect();
+            if n > POSTING_SHOW {
+                tids.push("...".to_string());
+            }
+            out.push(
+                format!("{}.posting", t),
+                lp.off + start,
+                end - start,
+                format!("{} tids {}", n, tids.join(" ")),
+            );
+            key_end = start;
+        } else {
+            out.push(
+                format!("{}.posting", t),
+                lp.off + size,
+                0,
+                format!("invalid posting off={} n={}", start, n),
+            );
+        }
+    } else if alt && posid & BT_PIVOT_HEAP_TID_ATTR != 0 && size >= data_off + TID_SIZE {
+        key_end = size - TID_SIZE;
+        out.push(
+            format!("{}.heap_tid", t),
+            lp.off + key_end,
+            TID_SIZE,
+            format_tid(&tup[key_end..size]),
+        );
+    }
+    if key_end > data_off {
+        out.push(
+            format!("{}.key", t),
+            lp.off + data_off,
+            key_end - data_off,
+            format!("{} bytes", key_end - data_off),
+        );
+    }
+}
+
+// 字段列表转为文本 每行一个字段
+pub(crate) fn format_fields(fields: &[PluginField]) -> String {
+    fields
+        .iter()
+        .map(|f| format!("{}: {}", f.name, f.value))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 从选区起始位置解析 nbtree 页面
+pub(crate) fn parse_btree_page(buf: &[u8]) -> String {
+    format_fields(&decode_btree_page(buf, 0))
+}
+
+// 每个段文件的页数 1GB / BLCKSZ
+pub(crate) const RELSEG_SIZE: usize = 131072;
+
+// 页面校验和 与 postgres checksum_impl.h 一致
+const N_SUMS: usize = 32;
+const FNV_PRIME: u32 = 16777619;
+const CHECKSUM_BASE_OFFSETS: [u32; N_SUMS] = [
+    0x5B1F36E9, 0xB8525960, 0x02AB50AA, 0x1DE66D2A, 0x79FF467A, 0x9BB9F8A3, 0x217E7CD2, 0x83E13D2C,
+    0xF8D4474F, 0xE39EB970, 0x42C6AE16, 0x993216FA, 0x7B093B5D, 0x98DAFF3C, 0xF718902A, 0x0B1C9CDB,
+    0xE58F764B, 0x187636BC, 0x5D7B3BB1, 0xE73DE7DE, 0x92BEC979, 0xCCA6C0B2, 0x304A0979, 0x85AA43D4,
+    0x783125BB, 0x6CA8EAA2, 0xE407EAC6, 0x4B5CFC3E, 0x9FBF8C76, 0x15CA20BE, 0xF2CA9FD3, 0x959BD756,
+];
+
+fn checksum_comp(checksum: u32, value: u32) -> u32 {
+    let tmp = checksum ^ value;
+    tmp.wrapping_mul(FNV_PRIME) ^ (tmp >> 17)
+}
+
+// pd_checksum 视为 0 计算整个页面的校验和
+fn pg_checksum_block(page: &[u8]) -> u32 {
+    let mut sums = CHECKSUM_BASE_OFFSETS;
+    for (i, chunk) in page.chunks_exact(4).enumerate() {
+        let mut value = u32::from_le_bytes(chunk.try_into().unwrap());
+        if i == 2 {
+            //pd_checksum 在第3个 u32 的低16位
+            value &= 0xFFFF_0000;
+        }
+        let j = i % N_SUMS;
+        sums[j] = checksum_comp(sums[j], value);
+    }
+    //再做两轮0 让每个值都影响最终结果
+    for _ in 0..2 {
+        for sum in sums.iter_mut() {
+            *sum = checksum_comp(*sum, 0);
+        }
+    }
+    sums.iter().fold(0, |acc, s| acc ^ s)
+}
+
+// blkno 为页面在整个关系中的块号 (segno * RELSEG_SIZE + 段内块号)
+pub(crate) fn pg_checksum_page(page: &[u8], blkno: u32) -> u16 {
+    let checksum = pg_checksum_block(page) ^ blkno;
+    ((checksum % 65535) + 1) as u16
+}
+
+// 未初始化的页面没有校验和
+pub(crate) fn page_is_new(page: &[u8]) -> bool {
+    page.len() >= PAGE_HEADER_SIZE && u16_at(page, 14) == 0
+}
+
+// 页面中保存的校验和
+pub(crate) fn page_stored_checksum(page: &[u8]) -> u16 {
+    u16_at(page, 8)
+}
+
+// 检查文件中的所有页面 返回校验和不一致的页面
+// 每个字段选中对应页面的 pd_checksum
+pub(crate) fn check_pages(src: &dyn ByteSource, segno: usize) -> (usize, Vec<PluginField>) {
+    let pages = src.size() / BLCKSZ;
+    let mut bad = Vec::new();
+    for i in 0..pages {
+        let base = i * BLCKSZ;
+        let page = src.read(base, BLCKSZ);
+        if page.len() < BLCKSZ || page_is_new(&page) {
+            continue;
+        }
+        let blkno = segno * RELSEG_SIZE + i;
+        let stored = page_stored_checksum(&page);
+        let expected = pg_checksum_page(&page, blkno as u32);
+        if stored != expected {
+            bad.push(PluginField {
+                name: format!("blk {}", blkno),
+                offset: base + 8,
+                length: 2,
+                value: format!("stored 0x{:04x} expected 0x{:04x}", stored, expected),
+            });
+        }
+    }
+    (pages, bad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_heap_page() {
+        let mut page = vec![0u8; BLCKSZ];
+        page[12..14].copy_from_slice(&32u16.to_le_bytes());
+        page[14..16].copy_from_slice(&8160u16.to_le_bytes());
+        page[16..18].copy_from_slice(&8192u16.to_le_bytes());
+        page[18..20].copy_from_slice(&0x2004u16.to_le_bytes());
+        let lp = |off: u32, flags: u32, len: u32| (off | flags << 15 | len << 17).to_le_bytes();
+        page[24..28].copy_from_slice(&lp(8160, 1, 32));
+        page[28..32].copy_from_slice(&lp(1, 2, 0));
+        let tup = &mut page[8160..8192];
+        tup[0..4].copy_from_slice(&100u32.to_le_bytes());
+        tup[18..20].copy_from_slice(&(0x4000u16 | 3).to_le_bytes());
+        tup[20..22].copy_from_slice(&0x0901u16.to_le_bytes());
+        tup[22] = 24;
+        tup[23] = 0b101;
+
+        let base = BLCKSZ * 2;
+        let fields = decode_heap_page(&page, base, &[]);
+        let get = |name: &str| fields.iter().find(|f| f.name == name).unwrap();
+        assert_eq!(get("pd_pagesize_version").value, "8192 bytes, version 4");
+        assert_eq!(get("lp[1]").value, "LP_NORMAL off=8160 len=32");
+        assert_eq!(get("lp[2]").value, "LP_REDIRECT -> lp 1");
+        assert_eq!(get("lp[2]").offset, base + 28);
+        assert_eq!(get("free space").length, 8160 - 32);
+        assert_eq!(get("tup[1].t_xmin").value, "100");
+        assert_eq!(get("tup[1].t_xmin").offset, base + 8160);
+        assert_eq!(
+            get("tup[1].t_infomask").value,
+            "0x0901 HASNULL|XMIN_COMMITTED|XMAX_INVALID"
+        );
+        assert_eq!(
+            get("tup[1].t_infomask2").value,
+            "natts=3 0x4000 HOT_UPDATED"
+        );
+        assert_eq!(get("tup[1].t_bits").value, "101 (0=null)");
+        assert_eq!(get("tup[1].data").length, 8);
+        assert!(fields.iter().all(|f| !f.name.starts_with("tup[2]")));
+
+        //损坏的行指针不会越界
+        page[24..28].copy_from_slice(&lp(8180, 1, 100));
+        let fields = decode_heap_page(&page, 0, &[]);
+        assert!(fields
+            .iter()
+            .any(|f| f.name == "tup[1]" && f.value.starts_with("invalid")));
+    }
+
+    // PostgreSQL 15 开启 data_checksums 后 t(a int, b text) 插入 (1,'chappie') 写盘的第0页
+    // 中间的字节都是0
+    const PG15_PAGE_HEAD: &str = "0000000070dd7b01ca9700001c00d81f0020042000000000d89f4800";
+    const PG15_PAGE_TAIL: &str =
+        "d4020000000000004e00000000000000010002000208180001000000116368617070696500000000";
+
+    #[test]
+    fn test_pg_checksum_page() {
+        //与 PostgreSQL 写入的校验和一致
+        let head = hex::decode(PG15_PAGE_HEAD).unwrap();
+        let tail = hex::decode(PG15_PAGE_TAIL).unwrap();
+        let mut page = vec![0u8; BLCKSZ];
+        page[..head.len()].copy_from_slice(&head);
+        page[BLCKSZ - tail.len()..].copy_from_slice(&tail);
+        assert_eq!(page_stored_checksum(&page), 0x97CA);
+        assert_eq!(pg_checksum_page(&page, 0), 0x97CA);
+
+        let mut page = vec![0u8; BLCKSZ];
+        page[14..16].copy_from_slice(&8192u16.to_le_bytes());
+        page[18..20].copy_from_slice(&0x2004u16.to_le_bytes());
+        page[100] = 0x42;
+        let c = pg_checksum_page(&page, 0);
+        assert_eq!(c, 0x9E73);
+        assert_eq!(pg_checksum_page(&page, 7), 0x9E78);
+        //结果与保存的校验和无关
+        page[8..10].copy_from_slice(&c.to_le_bytes());
+        assert_eq!(pg_checksum_page(&page, 0), c);
+        //块号参与计算
+        assert_ne!(pg_checksum_page(&page, 1), c);
+        page[101] = 1;
+        assert_ne!(pg_checksum_page(&page, 0), c);
+        assert!(!page_is_new(&page));
+        assert!(page_is_new(&vec![0u8; BLCKSZ]));
+
+        struct Pages(Vec<u8>);
+        impl ByteSource for Pages {
+            fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+                self.0[offset.min(self.0.len())..(offset + len).min(self.0.len())].to_vec()
+            }
+            fn size(&self) -> usize {
+                self.0.len()
+            }
+        }
+        let mut file = vec![0u8; BLCKSZ * 3];
+        //blk 0 正确 blk 1 错误 blk 2 未初始化
+        let c = pg_checksum_page(&page, 0);
+        page[8..10].copy_from_slice(&c.to_le_bytes());
+        file[..BLCKSZ].copy_from_slice(&page);
+        file[BLCKSZ..BLCKSZ * 2].copy_from_slice(&page);
+        let (pages, bad) = check_pages(&Pages(file.clone()), 0);
+        assert_eq!(pages, 3);
+        assert_eq!(bad.len(), 1);
+        assert_eq!(bad[0].name, "blk 1");
+        assert_eq!(bad[0].offset, BLCKSZ + 8);
+        //段号影响块号
+        let (_, bad) = check_pages(&Pages(file), 1);
+        assert_eq!(bad.len(), 2);
+        assert_eq!(bad[0].name, format!("blk {}", RELSEG_SIZE));
+    }
+
+    #[test]
+    fn test_decode_btree_page() {
+        let special = BLCKSZ - BT_OPAQUE_SIZE;
+        let mut meta = vec![0u8; BLCKSZ];
+        meta[16..18].copy_from_slice(&(special as u16).to_le_bytes());
+        meta[24..28].copy_from_slice(&BTREE_MAGIC.to_le_bytes());
+        meta[28..32].copy_from_slice(&4u32.to_le_bytes());
+        meta[32..36].copy_from_slice(&3u32.to_le_bytes());
+        meta[special + 12..special + 14].copy_from_slice(&BTP_META.to_le_bytes());
+        let fields = decode_btree_page(&meta, 0);
+        let get = |name: &str| fields.iter().find(|f| f.name == name).unwrap().clone();
+        assert_eq!(get("btm_magic").value, "0x053162");
+        assert_eq!(get("btm_root").value, "3");
+        assert_eq!(get("btpo_flags").value, "0x0008 META");
+        assert!(fields.iter().all(|f| !f.name.starts_with("lp[")));
+
+        //叶子页面: 高键 posting list 含空值的普通元组
+        let mut page = vec![0u8; BLCKSZ];
+        page[12..14].copy_from_slice(&36u16.to_le_bytes());
+        page[14..16].copy_from_slice(&8000u16.to_le_bytes());
+        page[16..18].copy_from_slice(&(special as u16).to_le_bytes());
+        page[special + 4..special + 8].copy_from_slice(&9u32.to_le_bytes());
+        page[special + 12..special + 14].copy_from_slice(&BTP_LEAF.to_le_bytes());
+        let lp = |off: u32, len: u32| (off | 1 << 15 | len << 17).to_le_bytes();
+        let tid = |blk: u32, pos: u16| {
+            let mut b = [0u8; 6];
+            b[0..2].copy_from_slice(&((blk >> 16) as u16).to_le_bytes());
+            b[2..4].copy_from_slice(&(blk as u16).to_le_bytes());
+            b[4..6].copy_from_slice(&pos.to_le_bytes());
+            b
+        };
+        let mut item = |n: usize, off: usize, t: [u8; 6], info: u16, rest: &[u8]| {
+            page[24 + (n - 1) * 4..24 + n * 4]
+                .copy_from_slice(&lp(off as u32, info as u32 & 0x1FFF));
+            page[off..off + 6].copy_from_slice(&t);
+            page[off + 6..off + 8].copy_from_slice(&info.to_le_bytes());
+            page[off + 8..off + 8 + rest.len()].copy_from_slice(rest);
+        };
+        //高键 1个键 + 堆 tid
+        let mut hk = vec![7u8, 0, 0, 0, 0, 0, 0, 0];
+        hk.extend_from_slice(&tid(5, 3));
+        item(
+            1,
+            8000,
+            tid(0, 1 | BT_PIVOT_HEAP_TID_ATTR),
+            22 | INDEX_ALT_TID_MASK,
+            &hk,
+        );
+        //posting list 2个 tid 从偏移 16 开始
+        let mut pl = vec![1u8, 0, 0, 0, 0, 0, 0, 0];
+        pl.extend_from_slice(&tid(0, 1));
+        pl.extend_from_slice(&tid(0, 2));
+        item(
+            2,
+            8040,
+            tid(16, 2 | BT_IS_POSTING),
+            28 | INDEX_ALT_TID_MASK,
+            &pl,
+        );
+        //第一个属性为空
+        item(3, 8080, tid(2, 4), 24 | INDEX_NULL_MASK, &[0b10]);
+
+        let base = BLCKSZ;
+        let fields = decode_btree_page(&page, base);
+        let get = |name: &str| fields.iter().find(|f| f.name == name).unwrap().clone();
+        assert_eq!(get("btpo_flags").value, "0x0001 LEAF");
+        assert_eq!(get("itup[1]").value, "high key");
+        assert_eq!(get("itup[1].t_tid").value, "natts=1");
+        assert_eq!(get("itup[1].heap_tid").value, "(5,3)");
+        assert_eq!(get("itup[1].key").length, 8);
+        assert_eq!(get("itup[2]").value, "posting list");
+        assert_eq!(get("itup[2].posting").value, "2 tids (0,1) (0,2)");
+        assert_eq!(get("itup[2].posting").offset, base + 8040 + 16);
+        assert_eq!(get("itup[2].key").length, 8);
+        assert_eq!(get("itup[3]").value, "non-pivot");
+        assert_eq!(get("itup[3].t_tid").value, "heap (2,4)");
+        assert_eq!(get("itup[3].t_info").value, "size=24 0x8000 HASNULL");
+        assert!(get("itup[3].t_bits").value.starts_with("01"));
+        assert_eq!(get("itup[3].key").offset, base + 8080 + 16);
+
+        //内部页面中没有 ALT_TID 的元组是旧格式的 pivot
+        page[special + 12..special + 14].copy_from_slice(&0u16.to_le_bytes());
+        let fields = decode_btree_page(&page, 0);
+        let get = |name: &str| fields.iter().find(|f| f.name == name).unwrap().clone();
+        assert_eq!(get("itup[2]").value, "posting list");
+        assert_eq!(get("itup[3]").value, "pivot");
+        assert_eq!(get("itup[3].t_tid").value, "downlink blk 2 untruncated");
+    }
+}
@@ -0,0 +1,578 @@
+use crate::pg::format_fields;
+use crate::pg::u16_at;
+use crate::pg::u32_at;
+use crate::pg::PageFields;
+use crate::pg::BLCKSZ;
+use crate::pg::PAGE_HEADER_SIZE;
+use crate::pg::RELSEG_SIZE;
+use crate::plugin::ByteSource;
+use crate::plugin::PluginField;
+use crate::wal::crc32c;
+use crate::wal::format_lsn;
+use crate::wal::segment_len;
+use crate::wal::walk_segment;
+use std::path::Path;
+
+// 根据文件名识别的 postgres 文件类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PgFileKind {
+    Fsm(usize), // 段号
+    Vm(usize),
+    Control,
+    Wal,
+}
+
+impl PgFileKind {
+    pub(crate) fn detect<P: AsRef<Path>>(p: P) -> Option<PgFileKind> {
+        let name = p.as_ref().file_name()?.to_str()?;
+        if name == "pg_control" {
+            return Some(PgFileKind::Control);
+        }
+        if name.len() == 24 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Some(PgFileKind::Wal);
+        }
+        // 16384_fsm 16384_fsm.1
+        let (base, segno) = match name.split_once('.') {
+            Some((base, segno)) => (base, segno.parse().ok()?),
+            None => (name, 0),
+        };
+        let (relfilenode, fork) = base.split_once('_')?;
+        if relfilenode.is_empty() || !relfilenode.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        match fork {
+            "fsm" => Some(PgFileKind::Fsm(segno)),
+            "vm" => Some(PgFileKind::Vm(segno)),
+            _ => None,
+        }
+    }
+}
+
+// 解析文件 返回说明和字段
+// 逐页读取 fsm vm 只读 pg_control 的 ControlFileData 和 WAL 的一个段
+pub(crate) fn decode_pg_file(
+    kind: PgFileKind,
+    src: &dyn ByteSource,
+) -> Result<(String, Vec<PluginField>), String> {
+    match kind {
+        PgFileKind::Fsm(segno) => {
+            let fields = decode_fsm(src, segno * RELSEG_SIZE);
+            Ok((format!("fsm fork, {} pages", src.size() / BLCKSZ), fields))
+        }
+        PgFileKind::Vm(segno) => {
+            let fields = decode_vm(src, segno * RELSEG_SIZE);
+            Ok((
+                format!("visibility map, {} pages", src.size() / BLCKSZ),
+                fields,
+            ))
+        }
+        PgFileKind::Control => {
+            let fields = decode_control(&src.read(0, CONTROL_CRC_OFFSET + 4));
+            Ok(("pg_control".to_string(), fields))
+        }
+        PgFileKind::Wal => {
+            let seg = walk_segment(&src.read(0, segment_len(src)))?;
+            Ok((seg.summary(), seg.fields()))
+        }
+    }
+}
+
+// FSM 页面: 页头之后是 FSMPageData { fp_next_slot, fp_nodes[] }
+// fp_nodes 是一棵完全二叉树 叶子保存下一层页面或堆页面的空闲空间等级
+const FSM_NODES_OFFSET: usize = PAGE_HEADER_SIZE + 4;
+const FSM_NODES_PER_PAGE: usize = BLCKSZ - FSM_NODES_OFFSET;
+const FSM_NON_LEAF_NODES: usize = BLCKSZ / 2 - 1;
+pub(crate) const FSM_SLOTS_PER_PAGE: usize = FSM_NODES_PER_PAGE - FSM_NON_LEAF_NODES;
+const FSM_TREE_DEPTH: usize = 3;
+// 空闲空间等级 每级 BLCKSZ / 256 字节
+const FSM_CAT_STEP: usize = BLCKSZ / 256;
+
+// 与 freespace.c fsm_logical_to_physical 一致
+fn fsm_logical_to_physical(level: usize, logpageno: usize) -> usize {
+    let mut leafno = logpageno;
+    for _ in 0..level {
+        leafno *= FSM_SLOTS_PER_PAGE;
+    }
+    let mut pages = 0;
+    for _ in 0..FSM_TREE_DEPTH {
+        pages += leafno + 1;
+        leafno /= FSM_SLOTS_PER_PAGE;
+    }
+    pages - level - 1
+}
+
+// 物理页号对应的 (层, 逻辑页号) 0 层的叶子对应堆页面
+fn fsm_page_addresses(npages: usize) -> Vec<Option<(usize, usize)>> {
+    let mut addrs = vec![None; npages];
+    for level in 0..FSM_TREE_DEPTH {
+        let mut logpageno = 0;
+        loop {
+            let phys = fsm_logical_to_physical(level, logpageno);
+            if phys >= npages {
+                break;
+            }
+            addrs[phys] = Some((level, logpageno));
+            logpageno += 1;
+            //根页面只有一个
+            if level == FSM_TREE_DEPTH - 1 {
+                break;
+            }
+        }
+    }
+    addrs
+}
+
+// 解析 FSM fork first_blk 为第一个页面的块号 (段号 * RELSEG_SIZE)
+pub(crate) fn decode_fsm(src: &dyn ByteSource, first_blk: usize) -> Vec<PluginField> {
+    let npages = src.size() / BLCKSZ;
+    let addrs = fsm_page_addresses(first_blk + npages);
+    let mut out = PageFields::new(0);
+    for i in 0..npages {
+        let base = i * BLCKSZ;
+        let page = src.read(base, BLCKSZ);
+        if page.len() < BLCKSZ {
+            break;
+        }
+        let blkno = first_blk + i;
+        let (level, logpageno) = match addrs[blkno] {
+            Some(addr) => addr,
+            None => continue,
+        };
+        if u16_at(&page, 14) == 0 {
+            out.push(format!("blk {}", blkno), base, BLCKSZ, "new page");
+            continue;
+        }
+        let nodes = &page[FSM_NODES_OFFSET..];
+        out.push(
+            format!("blk {}", blkno),
+            base + FSM_NODES_OFFSET,
+            1,
+            format!(
+                "level {} page {} root cat {} next_slot {}",
+                level,
+                logpageno,
+                nodes[0],
+                u32_at(&page, PAGE_HEADER_SIZE)
+            ),
+        );
+        for slot in 0..FSM_SLOTS_PER_PAGE {
+            let cat = nodes[FSM_NON_LEAF_NODES + slot];
+            if cat == 0 {
+                continue;
+            }
+            let child = logpageno * FSM_SLOTS_PER_PAGE + slot;
+            let name = if level == 0 {
+                format!("  heap blk {}", child)
+            } else {
+                format!("  level {} page {}", level - 1, child)
+            };
+            out.push(
+                name,
+                base + FSM_NODES_OFFSET + FSM_NON_LEAF_NODES + slot,
+                1,
+                format!(
+                    "cat {} (>= {} bytes free)",
+                    cat,
+                    cat as usize * FSM_CAT_STEP
+                ),
+            );
+        }
+    }
+    out.fields
+}
+
+// VM 页面: 页头之后每个堆页面 2 位 all-visible 和 all-frozen
+const VM_MAP_OFFSET: usize = PAGE_HEADER_SIZE;
+const VM_MAPSIZE: usize = BLCKSZ - VM_MAP_OFFSET;
+const VM_HEAPBLOCKS_PER_BYTE: usize = 4;
+pub(crate) const VM_HEAPBLOCKS_PER_PAGE: usize = VM_MAPSIZE * VM_HEAPBLOCKS_PER_BYTE;
+const VISIBILITYMAP_ALL_VISIBLE: u8 = 0x01;
+const VISIBILITYMAP_ALL_FROZEN: u8 = 0x02;
+
+fn vm_status(bits: u8) -> &'static str {
+    match bits {
+        VISIBILITYMAP_ALL_VISIBLE => "all-visible",
+        VISIBILITYMAP_ALL_FROZEN => "all-frozen without all-visible (invalid)",
+        _ => "all-visible all-frozen",
+    }
+}
+
+// 解析 VM fork 相同状态的连续堆页面合并为一个字段
+pub(crate) fn decode_vm(src: &dyn ByteSource, first_blk: usize) -> Vec<PluginField> {
+    let npages = src.size() / BLCKSZ;
+    let mut out = PageFields::new(0);
+    for i in 0..npages {
+        let base = i * BLCKSZ;
+        let page = src.read(base, BLCKSZ);
+        if page.len() < BLCKSZ {
+            break;
+        }
+        let blkno = first_blk + i;
+        let first_heap = blkno * VM_HEAPBLOCKS_PER_PAGE;
+        let bits_at = |j: usize| {
+            (page[VM_MAP_OFFSET + j / VM_HEAPBLOCKS_PER_BYTE] >> (2 * (j % VM_HEAPBLOCKS_PER_BYTE)))
+                & 0x03
+        };
+        let mut visible = 0;
+        let mut frozen = 0;
+        let mut runs = Vec::new();
+        let mut j = 0;
+        while j < VM_HEAPBLOCKS_PER_PAGE {
+            let bits = bits_at(j);
+            let start = j;
+            while j < VM_HEAPBLOCKS_PER_PAGE && bits_at(j) == bits {
+                j += 1;
+            }
+            if bits & VISIBILITYMAP_ALL_VISIBLE != 0 {
+                visible += j - start;
+            }
+            if bits & VISIBILITYMAP_ALL_FROZEN != 0 {
+                frozen += j - start;
+            }
+            if bits != 0 {
+                runs.push((start, j, bits));
+            }
+        }
+        out.push(
+            format!("blk {}", blkno),
+            base,
+            BLCKSZ,
+            format!(
+                "heap blk {}-{}: {} all-visible, {} all-frozen",
+                first_heap,
+                first_heap + VM_HEAPBLOCKS_PER_PAGE - 1,
+                visible,
+                frozen
+            ),
+        );
+        for (start, end, bits) in runs {
+            let first_byte = VM_MAP_OFFSET + start / VM_HEAPBLOCKS_PER_BYTE;
+            let last_byte = VM_MAP_OFFSET + (end - 1) / VM_HEAPBLOCKS_PER_BYTE;
+            let name = if end - start == 1 {
+                format!("  heap blk {}", first_heap + start)
+            } else {
+                format!("  heap blk {}-{}", first_heap + start, first_heap + end - 1)
+            };
+            out.push(
+                name,
+                base + first_byte,
+                last_byte - first_byte + 1,
+                vm_status(bits),
+            );
+        }
+    }
+    out.fields
+}
+
+// ControlFileData (PG_CONTROL_VERSION 1300, postgres 13-16)
+const PG_CONTROL_VERSION: u32 = 1300;
+const CONTROL_CRC_OFFSET: usize = 288;
+const CHECKPOINT_OFFSET: usize = 40; // checkPointCopy
+
+const DB_STATES: &[&str] = &[
+    "starting up",
+    "shut down",
+    "shut down in recovery",
+    "shutting down",
+    "in crash recovery",
+    "in archive recovery",
+    "in production",
+];
+
+const WAL_LEVELS: &[&str] = &["minimal", "replica", "logical"];
+
+// 字段类型 用于按偏移读取
+enum Ctl {
+    U32,
+    I32,
+    U64,
+    Lsn,
+    Time,
+    Bool,
+    F64,
+    Xid64, // FullTransactionId epoch:xid
+    State,
+    WalLevel,
+    Nonce,
+    Version,
+}
+
+const CONTROL_FIELDS: &[(&str, usize, Ctl)] = &[
+    ("system_identifier", 0, Ctl::U64),
+    ("pg_control_version", 8, Ctl::Version),
+    ("catalog_version_no", 12, Ctl::U32),
+    ("state", 16, Ctl::State),
+    ("time", 24, Ctl::Time),
+    ("checkPoint", 32, Ctl::Lsn),
+    ("checkPointCopy.redo", CHECKPOINT_OFFSET, Ctl::Lsn),
+    (
+        "checkPointCopy.ThisTimeLineID",
+        CHECKPOINT_OFFSET + 8,
+        Ctl::U32,
+    ),
+    (
+        "checkPointCopy.PrevTimeLineID",
+        CHECKPOINT_OFFSET + 12,
+        Ctl::U32,
+    ),
+    (
+        "checkPointCopy.fullPageWrites",
+        CHECKPOINT_OFFSET + 16,
+        Ctl::Bool,
+    ),
+    ("checkPointCopy.nextXid", CHECKPOINT_OFFSET + 24, Ctl::Xid64),
+    ("checkPointCopy.nextOid", CHECKPOINT_OFFSET + 32, Ctl::U32),
+    ("checkPointCopy.nextMulti", CHECKPOINT_OFFSET + 36, Ctl::U32),
+    (
+        "checkPointCopy.nextMultiOffset",
+        CHECKPOINT_OFFSET + 40,
+        Ctl::U32,
+    ),
+    ("checkPointCopy.oldestXid", CHECKPOINT_OFFSET + 44, Ctl::U32),
+    (
+        "checkPointCopy.oldestXidDB",
+        CHECKPOINT_OFFSET + 48,
+        Ctl::U32,
+    ),
+    (
+        "checkPointCopy.oldestMulti",
+        CHECKPOINT_OFFSET + 52,
+        Ctl::U32,
+    ),
+    (
+        "checkPointCopy.oldestMultiDB",
+        CHECKPOINT_OFFSET + 56,
+        Ctl::U32,
+    ),
+    ("checkPointCopy.time", CHECKPOINT_OFFSET + 64, Ctl::Time),
+    (
+        "checkPointCopy.oldestCommitTsXid",
+        CHECKPOINT_OFFSET + 72,
+        Ctl::U32,
+    ),
+    (
+        "checkPointCopy.newestCommitTsXid",
+        CHECKPOINT_OFFSET + 76,
+        Ctl::U32,
+    ),
+    (
+        "checkPointCopy.oldestActiveXid",
+        CHECKPOINT_OFFSET + 80,
+        Ctl::U32,
+    ),
+    ("unloggedLSN", 128, Ctl::Lsn),
+    ("minRecoveryPoint", 136, Ctl::Lsn),
+    ("minRecoveryPointTLI", 144, Ctl::U32),
+    ("backupStartPoint", 152, Ctl::Lsn),
+    ("backupEndPoint", 160, Ctl::Lsn),
+    ("backupEndRequired", 168, Ctl::Bool),
+    ("wal_level", 172, Ctl::WalLevel),
+    ("wal_log_hints", 176, Ctl::Bool),
+    ("MaxConnections", 180, Ctl::I32),
+    ("max_worker_processes", 184, Ctl::I32),
+    ("max_wal_senders", 188, Ctl::I32),
+    ("max_prepared_xacts", 192, Ctl::I32),
+    ("max_locks_per_xact", 196, Ctl::I32),
+    ("track_commit_timestamp", 200, Ctl::Bool),
+    ("maxAlign", 204, Ctl::U32),
+    ("floatFormat", 208, Ctl::F64),
+    ("blcksz", 216, Ctl::U32),
+    ("relseg_size", 220, Ctl::U32),
+    ("xlog_blcksz", 224, Ctl::U32),
+    ("xlog_seg_size", 228, Ctl::U32),
+    ("nameDataLen", 232, Ctl::U32),
+    ("indexMaxKeys", 236, Ctl::U32),
+    ("toast_max_chunk_size", 240, Ctl::U32),
+    ("loblksize", 244, Ctl::U32),
+    ("float8ByVal", 248, Ctl::Bool),
+    ("data_checksum_version", 252, Ctl::U32),
+    ("mock_authentication_nonce", 256, Ctl::Nonce),
+];
+
+// 解析 global/pg_control 并检查 crc
+pub(crate) fn decode_control(data: &[u8]) -> Vec<PluginField> {
+    let mut out = PageFields::new(0);
+    if data.len() < CONTROL_CRC_OFFSET + 4 {
+        out.push(
+            "pg_control",
+            0,
+            data.len(),
+            format!("pg_control < {}", CONTROL_CRC_OFFSET + 4),
+        );
+        return out.fields;
+    }
+    let u64_at = |off: usize| u64::from_le_bytes(data[off..off + 8].try_into().unwrap());
+    for (name, off, ty) in CONTROL_FIELDS {
+        let off = *off;
+        let (len, value) = match ty {
+            Ctl::U32 => (4, u32_at(data, off).to_string()),
+            Ctl::I32 => (4, (u32_at(data, off) as i32).to_string()),
+            Ctl::U64 => (8, u64_at(off).to_string()),
+            Ctl::Lsn => (8, format_lsn(u64_at(off))),
+            Ctl::Time => (8, format!("{} (unix time)", u64_at(off) as i64)),
+            Ctl::Bool => (1, (data[off] != 0).to_string()),
+            Ctl::F64 => (
+                8,
+                f64::from_le_bytes(data[off..off + 8].try_into().unwrap()).to_string(),
+            ),
+            Ctl::Xid64 => {
+                let v = u64_at(off);
+                (8, format!("{}:{}", v >> 32, v as u32))
+            }
+            Ctl::State => {
+                let v = u32_at(data, off);
+                (
+                    4,
+                    DB_STATES
+                        .get(v as usize)
+                        .map_or(format!("unknown {}", v), |s| s.to_string()),
+                )
+            }
+            Ctl::WalLevel => {
+                let v = u32_at(data, off);
+                (
+                    4,
+                    WAL_LEVELS
+                        .get(v as usize)
+                        .map_or(format!("unknown {}", v), |s| s.to_string()),
+                )
+            }
+            Ctl::Nonce => (32, hex::encode(&data[off..off + 32])),
+            Ctl::Version => match u32_at(data, off) {
+                PG_CONTROL_VERSION => (4, PG_CONTROL_VERSION.to_string()),
+                v => (4, format!("{} (decoded as {})", v, PG_CONTROL_VERSION)),
+            },
+        };
+        out.push(*name, off, len, value);
+    }
+    let stored = u32_at(data, CONTROL_CRC_OFFSET);
+    let calc = crc32c(&data[..CONTROL_CRC_OFFSET]);
+    let value = if stored == calc {
+        format!("0x{:08X} ok", stored)
+    } else {
+        format!("0x{:08X} BAD (calculated 0x{:08X})", stored, calc)
+    };
+    out.push("crc", CONTROL_CRC_OFFSET, 4, value);
+    out.fields
+}
+
+// FunctionPlugin 使用 选区视为从第0页开始
+pub(crate) fn parse_fsm(buf: &[u8]) -> String {
+    format_fields(&decode_fsm(&buf, 0))
+}
+
+pub(crate) fn parse_vm(buf: &[u8]) -> String {
+    format_fields(&decode_vm(&buf, 0))
+}
+
+pub(crate) fn parse_control(buf: &[u8]) -> String {
+    format_fields(&decode_control(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            PgFileKind::detect("base/5/16384_fsm"),
+            Some(PgFileKind::Fsm(0))
+        );
+        assert_eq!(PgFileKind::detect("16384_vm.2"), Some(PgFileKind::Vm(2)));
+        assert_eq!(
+            PgFileKind::detect("global/pg_control"),
+            Some(PgFileKind::Control)
+        );
+        assert_eq!(
+            PgFileKind::detect("pg_wal/000000010000000000000001"),
+            Some(PgFileKind::Wal)
+        );
+        assert_eq!(PgFileKind::detect("16384"), None);
+        assert_eq!(PgFileKind::detect("16384_init"), None);
+        assert_eq!(PgFileKind::detect("abc_fsm"), None);
+    }
+
+    #[test]
+    fn test_decode_fsm() {
+        //根页面 中间页面 叶子页面
+        let mut data = vec![0u8; 3 * BLCKSZ];
+        for i in 0..3 {
+            data[i * BLCKSZ + 14..i * BLCKSZ + 16].copy_from_slice(&8192u16.to_le_bytes());
+        }
+        let leaf = 2 * BLCKSZ + FSM_NODES_OFFSET;
+        data[leaf] = 2;
+        data[leaf + FSM_NON_LEAF_NODES + 5] = 2;
+        let fields = decode_fsm(&data.as_slice(), 0);
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["blk 0", "blk 1", "blk 2", "  heap blk 5"]);
+        assert_eq!(fields[0].value, "level 2 page 0 root cat 0 next_slot 0");
+        assert_eq!(fields[2].value, "level 0 page 0 root cat 2 next_slot 0");
+        assert_eq!(fields[3].value, "cat 2 (>= 64 bytes free)");
+        assert_eq!(fields[3].offset, leaf + FSM_NON_LEAF_NODES + 5);
+    }
+
+    #[test]
+    fn test_decode_vm() {
+        let mut data = vec![0u8; BLCKSZ];
+        //堆页面 0-2 all-visible all-frozen, 3 all-visible
+        data[VM_MAP_OFFSET] = 0x7f;
+        let fields = decode_vm(&data.as_slice(), 0);
+        assert_eq!(fields.len(), 3);
+        assert_eq!(
+            fields[0].value,
+            "heap blk 0-32671: 4 all-visible, 3 all-frozen"
+        );
+        assert_eq!(fields[1].name, "  heap blk 0-2");
+        assert_eq!(fields[1].value, "all-visible all-frozen");
+        assert_eq!(fields[2].name, "  heap blk 3");
+        assert_eq!(fields[2].value, "all-visible");
+    }
+
+    #[test]
+    fn test_decode_control() {
+        let mut data = vec![0u8; BLCKSZ];
+        data[8..12].copy_from_slice(&PG_CONTROL_VERSION.to_le_bytes());
+        let crc = crc32c(&data[..CONTROL_CRC_OFFSET]);
+        data[CONTROL_CRC_OFFSET..CONTROL_CRC_OFFSET + 4].copy_from_slice(&crc.to_le_bytes());
+        let fields = decode_control(&data);
+        let get = |fields: &[PluginField], name: &str| {
+            fields
+                .iter()
+                .find(|f| f.name == name)
+                .unwrap()
+                .value
+                .clone()
+        };
+        assert_eq!(get(&fields, "pg_control_version"), "1300");
+        assert!(get(&fields, "crc").ends_with("ok"));
+
+        data[0] = 1;
+        let fields = decode_control(&data);
+        assert!(get(&fields, "crc").contains("BAD"));
+        assert_eq!(decode_control(&data[..100]).len(), 1);
+    }
+
+    #[test]
+    fn test_decode_pg_file_reads() {
+        //1GB 的文件 只有第一页有数据 记录最大的一次读取
+        struct Big(Vec<u8>, std::cell::Cell<usize>);
+        impl ByteSource for Big {
+            fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+                self.1.set(self.1.get().max(len));
+                (&self.0[..]).read(offset, len)
+            }
+            fn size(&self) -> usize {
+                RELSEG_SIZE * BLCKSZ
+            }
+        }
+        let src = Big(vec![0u8; BLCKSZ], std::cell::Cell::new(0));
+        decode_pg_file(PgFileKind::Control, &src).unwrap();
+        assert_eq!(src.1.get(), CONTROL_CRC_OFFSET + 4);
+        let (_, fields) = decode_pg_file(PgFileKind::Vm(0), &src).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(src.1.get(), BLCKSZ);
+        decode_pg_file(PgFileKind::Fsm(0), &src).unwrap();
+        assert_eq!(src.1.get(), BLCKSZ);
+    }
+}
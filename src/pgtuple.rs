@@ -0,0 +1,638 @@
+use crate::byteutil::parse_varlena_header;
+use crate::byteutil::VarlenaData;
+use crate::byteutil::VarlenaType;
+use crate::pg::format_tid;
+use crate::pg::format_varatt_external;
+use crate::pg::u16_at;
+use crate::pg::u32_at;
+use crate::pg::PageFields;
+use crate::pg::HEAP_TUPLE_HEADER_SIZE;
+use crate::toast::decompress_varlena;
+
+// 列类型 对应 pg_type 的 typlen 和 typalign
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum PgType {
+    Bool,
+    Char, // "char"
+    Int2,
+    Int4,
+    Int8,
+    Oid,
+    Float4,
+    Float8,
+    Money,
+    Date,
+    Time,
+    Timestamp,
+    Timestamptz,
+    Interval,
+    Uuid,
+    Name,
+    Tid,
+    Text,
+    Varchar,
+    Bpchar,
+    Bytea,
+    Json,
+    Jsonb,
+    Numeric,
+}
+
+impl PgType {
+    fn parse(name: &str) -> Option<PgType> {
+        let typ = match name {
+            "bool" | "boolean" => PgType::Bool,
+            "\"char\"" => PgType::Char,
+            "int2" | "smallint" => PgType::Int2,
+            "int4" | "int" | "integer" => PgType::Int4,
+            "int8" | "bigint" => PgType::Int8,
+            "oid" | "xid" | "cid" | "regclass" => PgType::Oid,
+            "float4" | "real" => PgType::Float4,
+            "float8" | "float" | "double" => PgType::Float8,
+            "money" => PgType::Money,
+            "date" => PgType::Date,
+            "time" => PgType::Time,
+            "timestamp" => PgType::Timestamp,
+            "timestamptz" => PgType::Timestamptz,
+            "interval" => PgType::Interval,
+            "uuid" => PgType::Uuid,
+            "name" => PgType::Name,
+            "tid" => PgType::Tid,
+            "text" => PgType::Text,
+            "varchar" => PgType::Varchar,
+            "bpchar" | "char" | "character" => PgType::Bpchar,
+            "bytea" => PgType::Bytea,
+            "json" => PgType::Json,
+            "jsonb" => PgType::Jsonb,
+            "numeric" | "decimal" => PgType::Numeric,
+            _ => return None,
+        };
+        Some(typ)
+    }
+
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            PgType::Bool => "bool",
+            PgType::Char => "\"char\"",
+            PgType::Int2 => "int2",
+            PgType::Int4 => "int4",
+            PgType::Int8 => "int8",
+            PgType::Oid => "oid",
+            PgType::Float4 => "float4",
+            PgType::Float8 => "float8",
+            PgType::Money => "money",
+            PgType::Date => "date",
+            PgType::Time => "time",
+            PgType::Timestamp => "timestamp",
+            PgType::Timestamptz => "timestamptz",
+            PgType::Interval => "interval",
+            PgType::Uuid => "uuid",
+            PgType::Name => "name",
+            PgType::Tid => "tid",
+            PgType::Text => "text",
+            PgType::Varchar => "varchar",
+            PgType::Bpchar => "bpchar",
+            PgType::Bytea => "bytea",
+            PgType::Json => "json",
+            PgType::Jsonb => "jsonb",
+            PgType::Numeric => "numeric",
+        }
+    }
+
+    // typlen -1 表示 varlena
+    fn len(&self) -> isize {
+        match self {
+            PgType::Bool | PgType::Char => 1,
+            PgType::Int2 => 2,
+            PgType::Int4 | PgType::Oid | PgType::Float4 | PgType::Date => 4,
+            PgType::Tid => 6,
+            PgType::Int8
+            | PgType::Float8
+            | PgType::Money
+            | PgType::Time
+            | PgType::Timestamp
+            | PgType::Timestamptz => 8,
+            PgType::Interval | PgType::Uuid => 16,
+            PgType::Name => 64,
+            _ => -1,
+        }
+    }
+
+    // typalign c s i d
+    fn align(&self) -> usize {
+        match self {
+            PgType::Bool | PgType::Char | PgType::Uuid | PgType::Name => 1,
+            PgType::Int2 | PgType::Tid => 2,
+            PgType::Int8
+            | PgType::Float8
+            | PgType::Money
+            | PgType::Time
+            | PgType::Timestamp
+            | PgType::Timestamptz
+            | PgType::Interval => 8,
+            _ => 4,
+        }
+    }
+}
+
+// 解析逗号分隔的类型列表 如 int4,text,timestamptz
+pub(crate) fn parse_type_list(s: &str) -> Result<Vec<PgType>, String> {
+    s.split(',')
+        .map(|name| {
+            let name = name.trim().to_lowercase();
+            PgType::parse(&name).ok_or_else(|| format!("unknown type {}", name))
+        })
+        .collect()
+}
+
+// 文本和二进制值在辅助窗口中最多显示的长度
+const VALUE_DISPLAY_MAX: usize = 64;
+
+// 解析元组数据部分的每一列 off 为元组在 out 中的偏移
+// 按 typalign 对齐 varlena 为 1 字节 header 时不对齐 (与 att_align_pointer 一致)
+pub(crate) fn tuple_attr_fields(
+    tup: &[u8],
+    off: usize,
+    prefix: &str,
+    types: &[PgType],
+    out: &mut PageFields,
+) {
+    let infomask2 = u16_at(tup, 18);
+    let infomask = u16_at(tup, 20);
+    let t_hoff = tup[22] as usize;
+    let natts = (infomask2 & 0x07FF) as usize;
+    let has_nulls = infomask & 0x0001 != 0;
+    let is_null = |i: usize| {
+        has_nulls
+            && tup
+                .get(HEAP_TUPLE_HEADER_SIZE + i / 8)
+                .is_none_or(|b| b & (1 << (i % 8)) == 0)
+    };
+    let mut pos = t_hoff;
+    for (i, typ) in types.iter().enumerate() {
+        let name = format!("{}.attr{} {}", prefix, i + 1, typ.name());
+        if i >= natts {
+            //ALTER TABLE ADD COLUMN 之后插入的元组 列不在元组中
+            out.push(
+                name,
+                off + pos,
+                0,
+                format!("NULL (not in tuple, natts={})", natts),
+            );
+            continue;
+        }
+        if is_null(i) {
+            out.push(name, off + pos, 0, "NULL");
+            continue;
+        }
+        let short_varlena = typ.len() < 0 && tup.get(pos).is_some_and(|&b| b != 0);
+        if !short_varlena {
+            pos = pos.next_multiple_of(typ.align());
+        }
+        match decode_attr(&tup[pos.min(tup.len())..], *typ) {
+            Ok((len, value)) => {
+                out.push(name, off + pos, len, value);
+                pos += len;
+            }
+            Err(e) => {
+                out.push(name, off + pos.min(tup.len()), 0, e);
+                return;
+            }
+        }
+    }
+    if natts > types.len() && pos < tup.len() {
+        out.push(
+            format!("{}.rest", prefix),
+            off + pos,
+            tup.len() - pos,
+            format!(
+                "{} bytes, {} attributes not decoded",
+                tup.len() - pos,
+                natts - types.len()
+            ),
+        );
+    }
+}
+
+// 解析一个值 返回占用的字节数和显示的值
+fn decode_attr(data: &[u8], typ: PgType) -> Result<(usize, String), String> {
+    if typ.len() < 0 {
+        return decode_varlena_attr(data, typ);
+    }
+    let len = typ.len() as usize;
+    if data.len() < len {
+        return Err(format!("truncated {} < {}", data.len(), len));
+    }
+    let d = &data[..len];
+    let i64_at = |o: usize| i64::from_le_bytes(d[o..o + 8].try_into().unwrap());
+    let value = match typ {
+        PgType::Bool => (d[0] != 0).to_string(),
+        PgType::Char => format!("{:?}", d[0] as char),
+        PgType::Int2 => (u16_at(d, 0) as i16).to_string(),
+        PgType::Int4 => (u32_at(d, 0) as i32).to_string(),
+        PgType::Oid => u32_at(d, 0).to_string(),
+        PgType::Int8 => i64_at(0).to_string(),
+        PgType::Float4 => format_float(f32::from_le_bytes(d.try_into().unwrap()) as f64),
+        PgType::Float8 => format_float(f64::from_le_bytes(d.try_into().unwrap())),
+        PgType::Money => {
+            let v = i64_at(0);
+            let sign = if v < 0 { "-" } else { "" };
+            format!(
+                "{}{}.{:02}",
+                sign,
+                v.unsigned_abs() / 100,
+                v.unsigned_abs() % 100
+            )
+        }
+        PgType::Date => format_date(u32_at(d, 0) as i32),
+        PgType::Time => format_time(i64_at(0)),
+        PgType::Timestamp => format_timestamp(i64_at(0)),
+        PgType::Timestamptz => match i64_at(0) {
+            i64::MIN | i64::MAX => format_timestamp(i64_at(0)),
+            v => format!("{}+00", format_timestamp(v)),
+        },
+        PgType::Interval => format_interval(i64_at(0), u32_at(d, 8) as i32, u32_at(d, 12) as i32),
+        PgType::Uuid => {
+            let h = hex::encode(d);
+            format!(
+                "{}-{}-{}-{}-{}",
+                &h[0..8],
+                &h[8..12],
+                &h[12..16],
+                &h[16..20],
+                &h[20..32]
+            )
+        }
+        PgType::Name => {
+            let end = d.iter().position(|&b| b == 0).unwrap_or(len);
+            format!("{:?}", String::from_utf8_lossy(&d[..end]))
+        }
+        PgType::Tid => format_tid(d),
+        _ => unreachable!(),
+    };
+    Ok((len, value))
+}
+
+fn decode_varlena_attr(data: &[u8], typ: PgType) -> Result<(usize, String), String> {
+    let VarlenaData(vtyp, total_len) =
+        parse_varlena_header(data).ok_or("invalid varlena header")?;
+    match vtyp {
+        VarlenaType::ToastPointer => {
+            // varattrib_1b_e: header tag 和 va_tag 决定的数据
+            let tag = *data.get(1).ok_or("truncated toast pointer")?;
+            let len = match tag {
+                18 => 2 + 16, // VARTAG_ONDISK varatt_external
+                1..=3 => 2 + 8,
+                _ => return Err(format!("unknown vartag {}", tag)),
+            };
+            if data.len() < len {
+                return Err(format!("truncated toast pointer {} < {}", data.len(), len));
+            }
+            let value = match tag {
+                18 => format_varatt_external(&data[..len]).replace('\n', ", "),
+                _ => format!("in-memory pointer vartag {}", tag),
+            };
+            Ok((len, format!("toast pointer: {}", value)))
+        }
+        VarlenaType::ShortInline | VarlenaType::FourByteUncompressed => {
+            let header_len = if vtyp == VarlenaType::ShortInline {
+                1
+            } else {
+                4
+            };
+            let len = total_len as usize;
+            if len < header_len || len > data.len() {
+                return Err(format!("invalid varlena length {}", len));
+            }
+            Ok((len, format_varlena_value(&data[header_len..len], typ)?))
+        }
+        VarlenaType::FourByteCompressed => {
+            let len = total_len as usize;
+            if len > data.len() {
+                return Err(format!("invalid varlena length {}", len));
+            }
+            let d = decompress_varlena(&data[..len]).map_err(|e| e.to_string())?;
+            Ok((
+                len,
+                format!(
+                    "({} {} -> {} bytes) {}",
+                    d.method.name(),
+                    len,
+                    d.data.len(),
+                    format_varlena_value(&d.data, typ)?
+                ),
+            ))
+        }
+        VarlenaType::Unknown => Err("unknown varlena".to_string()),
+    }
+}
+
+// varlena header 之后的数据
+fn format_varlena_value(payload: &[u8], typ: PgType) -> Result<String, String> {
+    let value = match typ {
+        PgType::Text | PgType::Varchar | PgType::Bpchar | PgType::Json => {
+            let s = String::from_utf8_lossy(payload);
+            if s.chars().count() > VALUE_DISPLAY_MAX {
+                let s: String = s.chars().take(VALUE_DISPLAY_MAX).collect();
+                format!("{:?}... ({} bytes)", s, payload.len())
+            } else {
+                format!("{:?}", s)
+            }
+        }
+        PgType::Bytea => {
+            let n = payload.len().min(VALUE_DISPLAY_MAX / 2);
+            let more = if payload.len() > n {
+                format!("... ({} bytes)", payload.len())
+            } else {
+                String::new()
+            };
+            format!("\\x{}{}", hex::encode(&payload[..n]), more)
+        }
+        PgType::Jsonb => format!("jsonb {} bytes", payload.len()),
+        PgType::Numeric => format_numeric(payload)?,
+        _ => unreachable!(),
+    };
+    Ok(value)
+}
+
+// NumericData 与 numeric.c 一致 数字以 NBASE=10000 保存
+const NUMERIC_SIGN_MASK: u16 = 0xC000;
+const NUMERIC_NEG: u16 = 0x4000;
+const NUMERIC_SHORT: u16 = 0x8000;
+const NUMERIC_SPECIAL: u16 = 0xC000;
+const NUMERIC_SHORT_SIGN_MASK: u16 = 0x2000;
+const NUMERIC_SHORT_DSCALE_MASK: u16 = 0x1F80;
+const NUMERIC_SHORT_DSCALE_SHIFT: u16 = 7;
+const NUMERIC_SHORT_WEIGHT_SIGN_MASK: u16 = 0x0040;
+const NUMERIC_SHORT_WEIGHT_MASK: u16 = 0x003F;
+const NUMERIC_DSCALE_MASK: u16 = 0x3FFF;
+
+fn format_numeric(payload: &[u8]) -> Result<String, String> {
+    if payload.len() < 2 {
+        return Err("numeric < 2".to_string());
+    }
+    let header = u16_at(payload, 0);
+    let (neg, dscale, weight, digits) = match header & NUMERIC_SIGN_MASK {
+        NUMERIC_SPECIAL => {
+            return Ok(match header & 0xF000 {
+                0xC000 => "NaN",
+                0xD000 => "Infinity",
+                0xF000 => "-Infinity",
+                _ => "invalid special numeric",
+            }
+            .to_string())
+        }
+        NUMERIC_SHORT => {
+            let mut weight = (header & NUMERIC_SHORT_WEIGHT_MASK) as i32;
+            if header & NUMERIC_SHORT_WEIGHT_SIGN_MASK != 0 {
+                weight -= 64;
+            }
+            (
+                header & NUMERIC_SHORT_SIGN_MASK != 0,
+                ((header & NUMERIC_SHORT_DSCALE_MASK) >> NUMERIC_SHORT_DSCALE_SHIFT) as i32,
+                weight,
+                &payload[2..],
+            )
+        }
+        sign => {
+            if payload.len() < 4 {
+                return Err("numeric < 4".to_string());
+            }
+            (
+                sign == NUMERIC_NEG,
+                (header & NUMERIC_DSCALE_MASK) as i32,
+                u16_at(payload, 2) as i16 as i32,
+                &payload[4..],
+            )
+        }
+    };
+    let digits: Vec<i32> = digits
+        .chunks_exact(2)
+        .map(|c| i16::from_le_bytes([c[0], c[1]]) as i32)
+        .collect();
+    //digits[i] 的权重为 10000^(weight - i)
+    let digit = |i: i32| {
+        if i >= 0 {
+            digits.get(i as usize).copied().unwrap_or(0)
+        } else {
+            0
+        }
+    };
+    let mut s = String::new();
+    if neg {
+        s.push('-');
+    }
+    if weight < 0 {
+        s.push('0');
+    } else {
+        s.push_str(&digit(0).to_string());
+        for i in 1..=weight {
+            s.push_str(&format!("{:04}", digit(i)));
+        }
+    }
+    if dscale > 0 {
+        let mut frac = String::new();
+        let mut i = weight + 1;
+        while (frac.len() as i32) < dscale {
+            frac.push_str(&format!("{:04}", digit(i)));
+            i += 1;
+        }
+        s.push('.');
+        s.push_str(&frac[..dscale as usize]);
+    }
+    Ok(s)
+}
+
+// 很大或很小的数用科学计数法
+fn format_float(v: f64) -> String {
+    if v != 0.0 && v.is_finite() && (v.abs() >= 1e16 || v.abs() < 1e-4) {
+        format!("{:e}", v)
+    } else {
+        v.to_string()
+    }
+}
+
+// 日期和时间以 2000-01-01 为起点
+const POSTGRES_EPOCH_UNIX_DAYS: i64 = 10957;
+const USECS_PER_DAY: i64 = 86_400_000_000;
+
+// 与 Howard Hinnant 的 civil_from_days 一致
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + POSTGRES_EPOCH_UNIX_DAYS + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y, m, d)
+}
+
+fn format_ymd(days: i64) -> String {
+    let (y, m, d) = civil_from_days(days);
+    if y <= 0 {
+        format!("{:04}-{:02}-{:02} BC", 1 - y, m, d)
+    } else {
+        format!("{:04}-{:02}-{:02}", y, m, d)
+    }
+}
+
+fn format_date(days: i32) -> String {
+    match days {
+        i32::MIN => "-infinity".to_string(),
+        i32::MAX => "infinity".to_string(),
+        days => format_ymd(days as i64),
+    }
+}
+
+// HH:MM:SS[.ffffff] 去掉小数末尾的0
+fn format_time(usecs: i64) -> String {
+    let secs = usecs / 1_000_000;
+    let frac = usecs % 1_000_000;
+    let mut s = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+    if frac != 0 {
+        s.push_str(format!(".{:06}", frac).trim_end_matches('0'));
+    }
+    s
+}
+
+fn format_timestamp(usecs: i64) -> String {
+    match usecs {
+        i64::MIN => "-infinity".to_string(),
+        i64::MAX => "infinity".to_string(),
+        usecs => format!(
+            "{} {}",
+            format_ymd(usecs.div_euclid(USECS_PER_DAY)),
+            format_time(usecs.rem_euclid(USECS_PER_DAY))
+        ),
+    }
+}
+
+fn format_interval(time: i64, day: i32, month: i32) -> String {
+    let mut parts = Vec::new();
+    let (years, mons) = (month / 12, month % 12);
+    let unit = |n: i32, name: &str| {
+        let plural = if n == 1 { "" } else { "s" };
+        format!("{} {}{}", n, name, plural)
+    };
+    if years != 0 {
+        parts.push(unit(years, "year"));
+    }
+    if mons != 0 {
+        parts.push(unit(mons, "mon"));
+    }
+    if day != 0 {
+        parts.push(unit(day, "day"));
+    }
+    if time != 0 || parts.is_empty() {
+        let sign = if time < 0 { "-" } else { "" };
+        parts.push(format!("{}{}", sign, format_time(time.abs())));
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pg::decode_heap_tuple;
+
+    // postgres 15 写入的元组 (2,null,null,-0.00012,false,null,'2000-01-01',null...)
+    const TUPLE: &str = "e902000000000000060000000000000002000f0003082059000000000000\
+                         0000020000000fffa20100d0070000000000";
+
+    #[test]
+    fn test_parse_type_list() {
+        assert_eq!(
+            parse_type_list("int4, TEXT,timestamptz").unwrap(),
+            vec![PgType::Int4, PgType::Text, PgType::Timestamptz]
+        );
+        assert!(parse_type_list("int4,foo").is_err());
+    }
+
+    #[test]
+    fn test_tuple_attr_fields() {
+        let tup = hex::decode(TUPLE).unwrap();
+        let types = parse_type_list(
+            "int4,text,timestamptz,numeric,bool,int8,date,interval,uuid,float8,int2,bytea,varchar,name,time",
+        )
+        .unwrap();
+        let fields = decode_heap_tuple(&tup, 100, &types);
+        let get = |name: &str| fields.iter().find(|f| f.name == name).unwrap();
+        assert_eq!(get("tup.attr1 int4").value, "2");
+        assert_eq!(get("tup.attr1 int4").offset, 132);
+        assert_eq!(get("tup.attr2 text").value, "NULL");
+        //1 字节 header 的 varlena 不对齐
+        assert_eq!(get("tup.attr4 numeric").value, "-0.00012");
+        assert_eq!(get("tup.attr4 numeric").offset, 136);
+        assert_eq!(get("tup.attr4 numeric").length, 7);
+        assert_eq!(get("tup.attr5 bool").value, "false");
+        assert_eq!(get("tup.attr6 int8").value, "NULL");
+        assert_eq!(get("tup.attr7 date").value, "2000-01-01");
+        assert_eq!(get("tup.attr7 date").offset, 144);
+        assert_eq!(get("tup.attr15 time").value, "NULL");
+
+        //类型比 natts 多
+        let types = parse_type_list(&"int4,".repeat(16).trim_end_matches(',')).unwrap();
+        let fields = decode_heap_tuple(&tup, 0, &types);
+        assert_eq!(
+            fields.last().unwrap().value,
+            "NULL (not in tuple, natts=15)"
+        );
+        //类型比 natts 少
+        let fields = decode_heap_tuple(&tup, 0, &[PgType::Int4]);
+        assert_eq!(fields.last().unwrap().name, "tup.rest");
+    }
+
+    #[test]
+    fn test_decode_varlena_attr() {
+        let (len, v) = decode_attr(b"\x0dhello", PgType::Text).unwrap();
+        assert_eq!((len, v.as_str()), (6, "\"hello\""));
+        let (len, v) = decode_attr(b"\x1c\x00\x00\x00abc", PgType::Bytea).unwrap();
+        assert_eq!((len, v.as_str()), (7, "\\x616263"));
+        let mut ptr = vec![0x01, 18];
+        ptr.extend_from_slice(&6404i32.to_le_bytes());
+        ptr.extend_from_slice(&6400u32.to_le_bytes());
+        ptr.extend_from_slice(&16473u32.to_le_bytes());
+        ptr.extend_from_slice(&16471u32.to_le_bytes());
+        let (len, v) = decode_attr(&ptr, PgType::Text).unwrap();
+        assert_eq!(len, 18);
+        assert!(v.starts_with("toast pointer: va_rawsize = 6404"));
+        assert!(decode_attr(&ptr[..10], PgType::Text).is_err());
+        assert!(decode_attr(b"\x40\x00\x00\x00ab", PgType::Text).is_err());
+    }
+
+    #[test]
+    fn test_format_numeric() {
+        let n = hex::decode("84800f270f270f270f270f278813").unwrap();
+        assert_eq!(format_numeric(&n).unwrap(), "99999999999999999999.5");
+        let n = hex::decode("81810c00800dd21e").unwrap();
+        assert_eq!(format_numeric(&n).unwrap(), "123456.789");
+        //长格式 -12.34
+        let n = [0x02, 0x40, 0x00, 0x00, 12, 0, 0x48, 0x0d];
+        assert_eq!(format_numeric(&n).unwrap(), "-12.34");
+        assert_eq!(format_numeric(&[0x00, 0xC0]).unwrap(), "NaN");
+        assert_eq!(format_numeric(&[0x00, 0xF0]).unwrap(), "-Infinity");
+    }
+
+    #[test]
+    fn test_format_datetime() {
+        assert_eq!(format_date(-1), "1999-12-31");
+        assert_eq!(format_date(-746_117), "0044-03-15 BC");
+        assert_eq!(format_date(i32::MAX), "infinity");
+        assert_eq!(
+            format_timestamp(762_529_506_123_000),
+            "2024-02-29 13:45:06.123"
+        );
+        assert_eq!(format_time(1), "00:00:00.000001");
+        assert_eq!(
+            format_interval(14_706_500_000, 3, 14),
+            "1 year 2 mons 3 days 04:05:06.5"
+        );
+        assert_eq!(format_interval(0, -1, 0), "-1 days");
+        assert_eq!(format_interval(0, 0, 0), "00:00:00");
+        assert_eq!(format_float(1e300), "1e300");
+        assert_eq!(format_float(3.25), "3.25");
+    }
+}
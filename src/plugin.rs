@@ -1,5 +1,284 @@
+use crate::error::ChapError;
+use crate::tui::TextSelect;
 use crate::ChapResult;
+use ratatui::style::Color;
+
+// 插件读取文件数据 按需读取 不复制整个文件
+pub(crate) trait ByteSource {
+    fn read(&self, offset: usize, len: usize) -> Vec<u8>;
+    fn size(&self) -> usize;
+}
+
+// 内存中的字节 FunctionPlugin 的解析函数使用
+impl ByteSource for &[u8] {
+    fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+        let start = offset.min(self.len());
+        self[start..start + len.min(self.len() - start)].to_vec()
+    }
+
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
+
+// 插件解析出的一个字段
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PluginField {
+    pub(crate) name: String,
+    pub(crate) offset: usize,
+    pub(crate) length: usize,
+    pub(crate) value: String,
+}
+
+// 插件返回结果
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PluginOutput {
+    Text(String),
+    Fields(Vec<PluginField>),
+}
+
+// 插件对编辑器的操作 按调用顺序执行
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PluginAction {
+    Jump(usize),                    //跳转到地址
+    Select(usize, usize),           //选中 [start, end]
+    Highlight(usize, usize, Color), //标注 offset length color
+    ClearHighlights,                //清除所有标注
+    Message(String),                //在命令行显示消息
+}
+
+pub(crate) struct PluginResult {
+    pub(crate) output: PluginOutput,
+    pub(crate) actions: Vec<PluginAction>,
+}
+
+// 插件参数类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ParamType {
+    Int,
+    Bool,
+    Str,
+}
+
+impl ParamType {
+    fn parse(s: &str) -> ChapResult<ParamType> {
+        match s {
+            "int" => Ok(ParamType::Int),
+            "bool" => Ok(ParamType::Bool),
+            "string" => Ok(ParamType::Str),
+            _ => Err(ChapError::Unexpected(format!("unknown param type '{}'", s))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            ParamType::Int => "int",
+            ParamType::Bool => "bool",
+            ParamType::Str => "string",
+        }
+    }
+}
+
+// 解析后的参数值
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum PluginArg {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+}
+
+impl PluginArg {
+    fn parse(ty: ParamType, name: &str, value: &str) -> ChapResult<PluginArg> {
+        let arg = match ty {
+            ParamType::Int => {
+                let v = match value.strip_prefix("0x") {
+                    Some(hex) => i64::from_str_radix(hex, 16).ok(),
+                    None => value.parse::<i64>().ok(),
+                };
+                v.map(PluginArg::Int)
+            }
+            ParamType::Bool => match value {
+                "true" | "1" | "yes" => Some(PluginArg::Bool(true)),
+                "false" | "0" | "no" => Some(PluginArg::Bool(false)),
+                _ => None,
+            },
+            ParamType::Str => Some(PluginArg::Str(value.to_string())),
+        };
+        arg.ok_or_else(|| {
+            ChapError::Unexpected(format!(
+                "param '{}' expects {}, got '{}'",
+                name,
+                ty.name(),
+                value
+            ))
+        })
+    }
+}
+
+// 插件声明的参数
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ParamSpec {
+    pub(crate) name: String,
+    pub(crate) ty: ParamType,
+    pub(crate) default: Option<String>,
+}
+
+// 插件描述文件 每行一个 key: value
+//   description: Parse heap tuple header
+//   author: someone
+//   min_sel: 23
+//   param: natts int 0
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Manifest {
+    pub(crate) description: String,
+    pub(crate) author: String,
+    pub(crate) min_sel: usize,
+    pub(crate) params: Vec<ParamSpec>,
+}
+
+impl Manifest {
+    pub(crate) fn parse(content: &str) -> ChapResult<Manifest> {
+        let mut manifest = Manifest::default();
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("invalid manifest line '{}'", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "description" => manifest.description = value.to_string(),
+                "author" => manifest.author = value.to_string(),
+                "min_sel" => {
+                    manifest.min_sel = value
+                        .parse()
+                        .map_err(|_| format!("invalid min_sel '{}'", value))?
+                }
+                "param" => {
+                    let parts: Vec<&str> = value.split_whitespace().collect();
+                    let (name, ty, default) = match parts.as_slice() {
+                        [name, ty] => (name, ty, None),
+                        [name, ty, default] => (name, ty, Some(default.to_string())),
+                        _ => return Err(format!("invalid param '{}'", value).into()),
+                    };
+                    let spec = ParamSpec {
+                        name: name.to_string(),
+                        ty: ParamType::parse(ty)?,
+                        default,
+                    };
+                    //默认值也要符合类型
+                    if let Some(d) = &spec.default {
+                        PluginArg::parse(spec.ty, &spec.name, d)?;
+                    }
+                    manifest.params.push(spec);
+                }
+                key => return Err(format!("unknown manifest key '{}'", key).into()),
+            }
+        }
+        Ok(manifest)
+    }
+
+    // 检查并转换 call 传入的参数 未传入的参数使用默认值
+    pub(crate) fn bind(&self, args: &[(String, String)]) -> ChapResult<Vec<(String, PluginArg)>> {
+        if let Some((name, _)) = args
+            .iter()
+            .find(|(name, _)| !self.params.iter().any(|p| &p.name == name))
+        {
+            return Err(format!("unknown param '{}'", name).into());
+        }
+        let mut bound = Vec::new();
+        for p in self.params.iter() {
+            let value = args
+                .iter()
+                .rev()
+                .find(|(name, _)| name == &p.name)
+                .map(|(_, v)| v.as_str())
+                .or(p.default.as_deref());
+            if let Some(value) = value {
+                bound.push((p.name.clone(), PluginArg::parse(p.ty, &p.name, value)?));
+            }
+        }
+        Ok(bound)
+    }
+
+    // lf 中显示的插件说明
+    pub(crate) fn summary(&self) -> String {
+        let mut s = self.description.clone();
+        if !self.author.is_empty() {
+            s.push_str(&format!(" ({})", self.author));
+        }
+        if self.min_sel > 0 {
+            s.push_str(&format!("\n    min selection: {} bytes", self.min_sel));
+        }
+        for p in self.params.iter() {
+            s.push_str(&format!("\n    {}: {}", p.name, p.ty.name()));
+            if let Some(d) = &p.default {
+                s.push_str(&format!(" = {}", d));
+            }
+        }
+        s
+    }
+}
+
 pub(crate) trait Plugin {
-    fn eval(&self, name: &str, buf: &[u8]) -> ChapResult<String>;
+    fn eval(
+        &self,
+        name: &str,
+        args: &[(String, String)],
+        src: &dyn ByteSource,
+        sel: &TextSelect,
+    ) -> ChapResult<PluginResult>;
     fn list(&self) -> ChapResult<String>;
+    // 是否提供名为 name 的函数
+    fn has(&self, name: &str) -> bool;
+    // 重新加载插件 返回插件数量
+    fn reload(&self) -> ChapResult<usize>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let m = Manifest::parse(
+            "description: Parse heap tuple header\n\
+             author: chap\n\
+             # comment\n\
+             min_sel: 23\n\
+             param: natts int 0\n\
+             param: endian string little\n\
+             param: verbose bool\n",
+        )
+        .unwrap();
+        assert_eq!(m.min_sel, 23);
+        assert_eq!(m.params.len(), 3);
+
+        let args = vec![
+            ("natts".to_string(), "0x10".to_string()),
+            ("endian".to_string(), "big".to_string()),
+        ];
+        assert_eq!(
+            m.bind(&args).unwrap(),
+            vec![
+                ("natts".to_string(), PluginArg::Int(16)),
+                ("endian".to_string(), PluginArg::Str("big".to_string())),
+            ]
+        );
+        assert_eq!(
+            m.bind(&[]).unwrap(),
+            vec![
+                ("natts".to_string(), PluginArg::Int(0)),
+                ("endian".to_string(), PluginArg::Str("little".to_string())),
+            ]
+        );
+        assert!(m
+            .bind(&[("natts".to_string(), "five".to_string())])
+            .is_err());
+        assert!(m.bind(&[("nattr".to_string(), "5".to_string())]).is_err());
+        assert!(Manifest::parse("param: natts float").is_err());
+        assert!(Manifest::parse("param: natts int x").is_err());
+    }
 }
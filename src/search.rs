@@ -0,0 +1,620 @@
+use crate::byteutil::Endian;
+use crate::chap::RUNTIME;
+use crate::command::FindValue;
+use crate::editor::HexSnapshot;
+use crate::fuzzy::approx_find;
+use crate::fuzzy::boyermoore::BoyerMoore;
+use crate::fuzzy::APPROX_MAX;
+use crate::plugin::PluginField;
+use crate::ChapResult;
+use regex::bytes::Regex;
+use regex::bytes::RegexBuilder;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+// 正则和近似匹配按块查找 正则块之间重叠的字节数 更长的匹配跨块时可能被截断
+const REGEX_CHUNK: usize = 1 << 20;
+const REGEX_OVERLAP: usize = 4096;
+// 后台搜索每次扫描的块大小
+const SCAN_CHUNK: usize = 4 << 20;
+// 保存的匹配数上限 超过后只计数
+const HITS_MAX: usize = 1_000_000;
+// 结果列表最多显示的匹配数
+const RESULTS_MAX: usize = 10_000;
+// 结果列表中匹配前后显示的字节数 以及匹配本身最多显示的字节数
+const CONTEXT: usize = 4;
+const MATCH_SHOWN: usize = 16;
+// 近似匹配默认允许的编辑距离 模式长度的四分之一 最多 5
+const FUZZY_DEFAULT_DIV: usize = 4;
+const FUZZY_DEFAULT_MAX: usize = 5;
+
+// 搜索模式 在字节流中查找第一个匹配
+#[derive(Debug, Clone)]
+pub(crate) enum SearchPattern {
+    Bytes(Vec<u8>),        // 字面量
+    Masked(Vec<(u8, u8)>), // (值, 掩码) 掩码为 0 的半字节是通配
+    NoCase(Vec<u8>),       // 忽略大小写的 ASCII
+    Regex(Regex),          // 字节正则
+    Fuzzy(Vec<u8>, usize), // 近似匹配 最多 k 个字节的编辑
+}
+
+impl SearchPattern {
+    pub(crate) fn new(value: FindValue) -> ChapResult<SearchPattern> {
+        let pattern = match value {
+            FindValue::Hex(bytes) => SearchPattern::Bytes(bytes),
+            FindValue::Ascii(s) => SearchPattern::Bytes(s.into_bytes()),
+            FindValue::Masked(bytes) => SearchPattern::Masked(bytes),
+            FindValue::NoCase(s) => SearchPattern::NoCase(s.to_ascii_lowercase().into_bytes()),
+            FindValue::Utf16(s, endian) => SearchPattern::Bytes(
+                s.encode_utf16()
+                    .flat_map(|u| match endian {
+                        Endian::Little => u.to_le_bytes(),
+                        Endian::Big => u.to_be_bytes(),
+                    })
+                    .collect(),
+            ),
+            // 关闭 unicode 后 . 和 \xNN 按单个字节匹配
+            FindValue::Regex(s) => SearchPattern::Regex(
+                RegexBuilder::new(&s)
+                    .unicode(false)
+                    .build()
+                    .map_err(|e| format!("invalid regex: {}", e))?,
+            ),
+            FindValue::Fuzzy(p, k) => {
+                if p.len() > APPROX_MAX {
+                    return Err(format!("fuzzy pattern longer than {} bytes", APPROX_MAX).into());
+                }
+                let k = k.unwrap_or((p.len() / FUZZY_DEFAULT_DIV).min(FUZZY_DEFAULT_MAX));
+                if !p.is_empty() && k >= p.len() {
+                    return Err(format!("edit distance must be less than {}", p.len()).into());
+                }
+                SearchPattern::Fuzzy(p, k)
+            }
+        };
+        Ok(pattern)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            SearchPattern::Bytes(p) | SearchPattern::NoCase(p) => p.is_empty(),
+            SearchPattern::Masked(p) => p.is_empty(),
+            SearchPattern::Regex(_) => false,
+            SearchPattern::Fuzzy(p, _) => p.is_empty(),
+        }
+    }
+
+    // 从光标处跳过的字节数 避免再次找到光标处的匹配
+    pub(crate) fn skip_len(&self) -> usize {
+        match self {
+            SearchPattern::Bytes(p) | SearchPattern::NoCase(p) => p.len(),
+            SearchPattern::Masked(p) => p.len(),
+            SearchPattern::Regex(_) | SearchPattern::Fuzzy(..) => 1,
+        }
+    }
+
+    // 返回第一个匹配相对于流开头的偏移和长度
+    pub(crate) fn find<I: Iterator<Item = u8>>(&self, text: I) -> Option<(usize, usize)> {
+        match self {
+            SearchPattern::Bytes(p) => BoyerMoore::new(p).stream(text).next().map(|i| (i, p.len())),
+            SearchPattern::NoCase(p) => BoyerMoore::new(p)
+                .stream(text.map(|b| b.to_ascii_lowercase()))
+                .next()
+                .map(|i| (i, p.len())),
+            SearchPattern::Masked(p) => find_masked(p, text).map(|i| (i, p.len())),
+            SearchPattern::Regex(re) => find_chunked(text, REGEX_OVERLAP, |buf| {
+                re.find_iter(buf)
+                    .find(|m| !m.is_empty())
+                    .map(|m| (m.start(), m.len()))
+            }),
+            SearchPattern::Fuzzy(p, k) => find_chunked(text, p.len() + k, |buf| {
+                approx_find(p, buf, *k)
+                    .first()
+                    .map(|m| (m.start, m.end - m.start))
+            }),
+        }
+    }
+
+    // 匹配可能的最大长度减一 分块扫描时块之间要重叠这么多字节
+    fn overlap(&self) -> usize {
+        match self {
+            SearchPattern::Regex(_) => REGEX_OVERLAP,
+            SearchPattern::Fuzzy(p, k) => p.len() + k,
+            p => p.skip_len().saturating_sub(1),
+        }
+    }
+
+    // buf 中所有不重叠的匹配 (偏移, 长度, 编辑距离)
+    fn find_all(&self, buf: &[u8], mut f: impl FnMut(usize, usize, usize)) {
+        // BoyerMoore 返回重叠的匹配 跳过和上一个重叠的
+        let mut literal = |p: &[u8], text: &[u8]| {
+            let mut next = 0;
+            for i in BoyerMoore::new(p).find(text) {
+                if i >= next {
+                    f(i, p.len(), 0);
+                    next = i + p.len();
+                }
+            }
+        };
+        match self {
+            SearchPattern::Bytes(p) => literal(p, buf),
+            SearchPattern::NoCase(p) => literal(p, &buf.to_ascii_lowercase()),
+            SearchPattern::Masked(p) => {
+                let mut i = 0;
+                while i + p.len() <= buf.len() {
+                    if buf[i..]
+                        .iter()
+                        .zip(p)
+                        .all(|(b, (value, mask))| b & mask == *value)
+                    {
+                        f(i, p.len(), 0);
+                        i += p.len();
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            SearchPattern::Regex(re) => re
+                .find_iter(buf)
+                .filter(|m| !m.is_empty())
+                .for_each(|m| f(m.start(), m.len(), 0)),
+            SearchPattern::Fuzzy(p, k) => approx_find(p, buf, *k)
+                .iter()
+                .for_each(|m| f(m.start, m.end - m.start, m.distance)),
+        }
+    }
+}
+
+// 逐个确认的替换 等待按 y/n/a/q
+pub(crate) struct PendingReplace {
+    pub(crate) pattern: SearchPattern,
+    pub(crate) replacement: Vec<u8>,
+    pub(crate) hit: (usize, usize), // 等待确认的匹配 (偏移, 长度)
+    pub(crate) count: usize,        // 已替换的个数
+}
+
+// 后台搜索找到的一个匹配
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Hit {
+    pub(crate) offset: usize,
+    pub(crate) len: usize,
+    distance: usize, // 近似匹配的编辑距离
+    context: String, // 匹配前后的字节 匹配部分用方括号标出
+}
+
+enum FindMsg {
+    Hits(Vec<Hit>, usize), // 保存的匹配 和这一块的匹配总数
+    Progress(usize),       // 已扫描的字节数
+    Done,
+}
+
+// 在整个文件中查找所有匹配 结果通过通道陆续送回界面
+#[derive(Default)]
+pub(crate) struct FindAll {
+    hits: Vec<Hit>,
+    count: usize,   // 全部匹配数 可能多于保存的
+    scanned: usize, // 已扫描的字节数
+    size: usize,    // 文件大小
+    ranked: bool,   // 近似匹配 结果列表按编辑距离排序
+    pattern: Option<SearchPattern>,
+    version: usize, // 扫描的内容的编辑次数 内容改变后重新搜索
+    rx: Option<mpsc::UnboundedReceiver<FindMsg>>,
+    cancel: Arc<AtomicBool>,
+}
+
+fn context(buf: &[u8], off: usize, len: usize) -> String {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let before = &buf[off.saturating_sub(CONTEXT)..off];
+    let shown = &buf[off..off + len.min(MATCH_SHOWN)];
+    let after = &buf[off + len..(off + len + CONTEXT).min(buf.len())];
+    let ascii: String = buf[off.saturating_sub(CONTEXT)..off + len.min(MATCH_SHOWN)]
+        .iter()
+        .chain(after)
+        .map(|&b| if b.is_ascii_graphic() { b as char } else { '.' })
+        .collect();
+    let more = if len > MATCH_SHOWN { " .." } else { "" };
+    format!(
+        "{} [{}{}] {} |{}|",
+        hex(before),
+        hex(shown),
+        more,
+        hex(after),
+        ascii
+    )
+}
+
+// 分块扫描 块之间重叠 只保留起点在本块内的匹配
+fn scan(
+    snap: &HexSnapshot,
+    pattern: &SearchPattern,
+    tx: mpsc::UnboundedSender<FindMsg>,
+    cancel: &AtomicBool,
+) {
+    let overlap = pattern.overlap();
+    let size = snap.len();
+    let mut saved = 0;
+    let mut start = 0;
+    while start < size {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let end = (start + SCAN_CHUNK).min(size);
+        // 前后多读一些字节 用于显示匹配的上下文
+        let lo = start.saturating_sub(CONTEXT);
+        let hi = (end + overlap).min(size);
+        let window = snap.slice(lo, hi + CONTEXT - lo);
+        let chunk = &window[start - lo..hi - lo];
+        let mut hits = Vec::new();
+        let mut count = 0;
+        pattern.find_all(chunk, |i, len, distance| {
+            if start + i < end {
+                count += 1;
+                if saved + hits.len() < HITS_MAX {
+                    hits.push(Hit {
+                        offset: start + i,
+                        len,
+                        distance,
+                        context: context(&window, start - lo + i, len),
+                    });
+                }
+            }
+        });
+        saved += hits.len();
+        if tx.send(FindMsg::Hits(hits, count)).is_err() || tx.send(FindMsg::Progress(end)).is_err()
+        {
+            return;
+        }
+        start = end;
+    }
+    let _ = tx.send(FindMsg::Done);
+}
+
+impl FindAll {
+    // 停止上一次搜索 在后台线程中扫描编辑后内容的快照
+    pub(crate) fn start(&mut self, snap: HexSnapshot, pattern: SearchPattern) {
+        self.clear();
+        if pattern.is_empty() {
+            return;
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.size = snap.len();
+        self.version = snap.version();
+        self.ranked = matches!(pattern, SearchPattern::Fuzzy(..));
+        self.pattern = Some(pattern.clone());
+        self.rx = Some(rx);
+        self.cancel = cancel.clone();
+        RUNTIME.spawn_blocking(move || scan(&snap, &pattern, tx, &cancel));
+    }
+
+    // 搜索后内容被修改 结果已经过期 需要重新搜索
+    pub(crate) fn is_stale(&self, version: usize) -> bool {
+        self.pattern.is_some() && self.version != version
+    }
+
+    pub(crate) fn pattern(&self) -> Option<&SearchPattern> {
+        self.pattern.as_ref()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        *self = FindAll::default();
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.rx.is_some()
+    }
+
+    // 取出后台送回的结果 返回新保存的匹配数
+    pub(crate) fn poll(&mut self) -> usize {
+        let before = self.hits.len();
+        while let Some(rx) = &mut self.rx {
+            match rx.try_recv() {
+                Ok(FindMsg::Hits(hits, count)) => {
+                    self.hits.extend(hits);
+                    self.count += count;
+                }
+                Ok(FindMsg::Progress(scanned)) => self.scanned = scanned,
+                Ok(FindMsg::Done) | Err(mpsc::error::TryRecvError::Disconnected) => {
+                    self.rx = None;
+                }
+                Err(mpsc::error::TryRecvError::Empty) => break,
+            }
+        }
+        self.hits.len() - before
+    }
+
+    pub(crate) fn hits(&self) -> &[Hit] {
+        &self.hits
+    }
+
+    // 起点在 [start, end) 中的匹配 以及之前开始但延伸进来的匹配
+    pub(crate) fn visible(&self, start: usize, end: usize) -> &[Hit] {
+        let from = self.hits.partition_point(|h| h.offset + h.len <= start);
+        let to = self.hits.partition_point(|h| h.offset < end);
+        &self.hits[from..to.max(from)]
+    }
+
+    // pos 之后或之前最近的匹配 返回下标
+    pub(crate) fn next(&self, pos: usize) -> Option<usize> {
+        let i = self.hits.partition_point(|h| h.offset <= pos);
+        (i < self.hits.len()).then_some(i)
+    }
+
+    pub(crate) fn prev(&self, pos: usize) -> Option<usize> {
+        self.hits.partition_point(|h| h.offset < pos).checked_sub(1)
+    }
+
+    // 结果列表中的顺序 近似匹配按编辑距离排序 距离相同的按偏移
+    fn order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.hits.len()).collect();
+        if self.ranked {
+            order.sort_by_key(|&i| self.hits[i].distance);
+        }
+        order
+    }
+
+    // 第 i 个匹配在结果列表中的行
+    pub(crate) fn row(&self, i: usize) -> usize {
+        if !self.ranked {
+            return i;
+        }
+        let key = |h: &Hit| (h.distance, h.offset);
+        let hit = key(&self.hits[i]);
+        self.hits.iter().filter(|h| key(h) < hit).count()
+    }
+
+    // 结果列表
+    pub(crate) fn fields(&self) -> Vec<PluginField> {
+        self.order()
+            .into_iter()
+            .take(RESULTS_MAX)
+            .enumerate()
+            .map(|(row, i)| {
+                let h = &self.hits[i];
+                PluginField {
+                    name: format!("#{}", row + 1),
+                    offset: h.offset,
+                    length: h.len,
+                    value: if self.ranked {
+                        format!("d={} {}", h.distance, h.context)
+                    } else {
+                        h.context.clone()
+                    },
+                }
+            })
+            .collect()
+    }
+
+    // 结果窗口的标题 搜索中显示进度
+    pub(crate) fn title(&self) -> String {
+        let saved = if self.count > self.hits.len() {
+            format!(", {} saved", self.hits.len())
+        } else {
+            String::new()
+        };
+        let name = if self.ranked {
+            "Fuzzy results"
+        } else {
+            "Results"
+        };
+        if self.is_running() {
+            let percent = self.scanned * 100 / self.size.max(1);
+            format!("{} ({} hits{}, {}%)", name, self.count, saved, percent)
+        } else {
+            format!("{} ({} hits{})", name, self.count, saved)
+        }
+    }
+}
+
+fn find_masked<I: Iterator<Item = u8>>(pattern: &[(u8, u8)], text: I) -> Option<usize> {
+    let mut window = VecDeque::with_capacity(pattern.len());
+    for (i, b) in text.enumerate() {
+        if window.len() == pattern.len() {
+            window.pop_front();
+        }
+        window.push_back(b);
+        if window.len() == pattern.len()
+            && window
+                .iter()
+                .zip(pattern)
+                .all(|(b, (value, mask))| b & mask == *value)
+        {
+            return Some(i + 1 - pattern.len());
+        }
+    }
+    None
+}
+
+// 按块查找第一个匹配 匹配到块末尾且可能更长时 留下重叠部分读下一块再找
+fn find_chunked<I: Iterator<Item = u8>>(
+    mut text: I,
+    overlap: usize,
+    find: impl Fn(&[u8]) -> Option<(usize, usize)>,
+) -> Option<(usize, usize)> {
+    let mut buf: Vec<u8> = Vec::with_capacity(REGEX_CHUNK);
+    let mut base = 0;
+    loop {
+        let mut done = false;
+        while buf.len() < REGEX_CHUNK {
+            match text.next() {
+                Some(b) => buf.push(b),
+                None => {
+                    done = true;
+                    break;
+                }
+            }
+        }
+        let cut = buf.len().saturating_sub(overlap);
+        match find(&buf) {
+            Some((start, len)) if done || start + len < buf.len() || start < cut => {
+                return Some((base + start, len));
+            }
+            _ if done => return None,
+            _ => {
+                buf.drain(..cut);
+                base += cut;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::HexText;
+
+    fn wait(all: &mut FindAll) {
+        while all.is_running() {
+            all.poll();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+    }
+
+    fn find(value: FindValue, text: &[u8]) -> Option<(usize, usize)> {
+        SearchPattern::new(value)
+            .unwrap()
+            .find(text.iter().copied())
+    }
+
+    #[test]
+    fn test_find_patterns() {
+        let text = b"xxMZ\x90\x00PE\x00\x00Hello hELLO";
+        assert_eq!(find(FindValue::Hex(b"PE\0\0".to_vec()), text), Some((6, 4)));
+        // 4D ?? 9? 00
+        let masked = vec![(0x4d, 0xff), (0, 0), (0x90, 0xf0), (0, 0xff)];
+        assert_eq!(find(FindValue::Masked(masked), text), Some((2, 4)));
+        assert_eq!(
+            find(FindValue::NoCase("HELLO".to_string()), &text[10..]),
+            Some((0, 5))
+        );
+        assert_eq!(
+            find(FindValue::NoCase("hello".to_string()), &text[11..]),
+            Some((5, 5))
+        );
+        assert_eq!(
+            find(FindValue::Regex(r"PE\x00\x00|h[A-Z]+".to_string()), text),
+            Some((6, 4))
+        );
+        assert_eq!(
+            find(FindValue::Regex(r"\x90.".to_string()), text),
+            Some((4, 2))
+        );
+        assert_eq!(find(FindValue::Regex("z*".to_string()), text), None);
+        assert!(SearchPattern::new(FindValue::Regex("(".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_find_utf16() {
+        let text = b"..C\0:\0\\\0\0W\0i\0n";
+        assert_eq!(
+            find(FindValue::Utf16("C:\\".to_string(), Endian::Little), text),
+            Some((2, 6))
+        );
+        assert_eq!(
+            find(FindValue::Utf16("Win".to_string(), Endian::Big), text),
+            Some((8, 6))
+        );
+    }
+
+    #[test]
+    fn test_find_all() {
+        let path = std::env::temp_dir().join("chap_test_find_all.bin");
+        let mut data = vec![0u8; SCAN_CHUNK + 64];
+        for off in [3, SCAN_CHUNK - 2, SCAN_CHUNK + 10] {
+            data[off..off + 4].copy_from_slice(b"PK\x03\x04");
+        }
+        std::fs::write(&path, &data).unwrap();
+        let mut all = FindAll::default();
+        let pattern = SearchPattern::new(FindValue::Hex(b"PK\x03\x04".to_vec())).unwrap();
+        let mut text = HexText::from_file_path(&path, 10).unwrap();
+        all.start(text.snapshot().unwrap(), pattern.clone());
+        wait(&mut all);
+        let offsets: Vec<usize> = all.hits().iter().map(|h| h.offset).collect();
+        assert_eq!(offsets, [3, SCAN_CHUNK - 2, SCAN_CHUNK + 10]);
+        assert_eq!(all.title(), "Results (3 hits)");
+        assert_eq!(
+            all.hits()[0].context,
+            "00 00 00 [50 4B 03 04] 00 00 00 00 |...PK......|"
+        );
+        assert_eq!(all.next(3), Some(1));
+        assert_eq!(all.prev(3), None);
+        assert_eq!(all.prev(SCAN_CHUNK), Some(1));
+        assert_eq!(all.visible(5, SCAN_CHUNK), &all.hits()[..2]);
+        assert_eq!(all.visible(7, 8).len(), 0);
+        assert_eq!(all.fields()[1].name, "#2");
+
+        // 扫描未保存的修改 插入后的匹配位置后移
+        text.insert(0, b"PK\x03\x04").unwrap();
+        text.overwrite(SCAN_CHUNK + 60, b"PK\x03\x04").unwrap();
+        all.start(text.snapshot().unwrap(), pattern);
+        wait(&mut all);
+        let offsets: Vec<usize> = all.hits().iter().map(|h| h.offset).collect();
+        assert_eq!(
+            offsets,
+            [0, 7, SCAN_CHUNK + 2, SCAN_CHUNK + 14, SCAN_CHUNK + 60]
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_find_all_overlap() {
+        let mut offsets = Vec::new();
+        let pattern = SearchPattern::new(FindValue::Ascii("aa".to_string())).unwrap();
+        pattern.find_all(b"aaaaa", |i, _, _| offsets.push(i));
+        assert_eq!(offsets, [0, 2]);
+    }
+
+    #[test]
+    fn test_find_fuzzy() {
+        let path = std::env::temp_dir().join("chap_test_find_fuzzy.bin");
+        let mut data = vec![0u8; 256];
+        data[16..21].copy_from_slice(b"\x7fEXF\x02"); // 替换一个字节
+        data[64..69].copy_from_slice(b"\x7fELF\x02");
+        data[128..132].copy_from_slice(b"\x7fEF\x02"); // 少一个字节
+        std::fs::write(&path, &data).unwrap();
+        let fuzzy = FindValue::Fuzzy(b"\x7fELF\x02".to_vec(), Some(1));
+        assert_eq!(find(fuzzy, &data), Some((16, 5)));
+        assert!(SearchPattern::new(FindValue::Fuzzy(b"ab".to_vec(), Some(2))).is_err());
+        assert!(SearchPattern::new(FindValue::Fuzzy(vec![1; 64], None)).is_err());
+
+        let mut all = FindAll::default();
+        let pattern = SearchPattern::new(FindValue::Fuzzy(b"\x7fELF\x02".to_vec(), Some(1)));
+        let text = HexText::from_file_path(&path, 10).unwrap();
+        all.start(text.snapshot().unwrap(), pattern.unwrap());
+        wait(&mut all);
+        let offsets: Vec<usize> = all.hits().iter().map(|h| h.offset).collect();
+        assert_eq!(offsets, [16, 64, 128]);
+        // 结果列表按编辑距离排序
+        let fields = all.fields();
+        let ranked: Vec<(usize, usize)> = fields.iter().map(|f| (f.offset, f.length)).collect();
+        assert_eq!(ranked, [(64, 5), (16, 5), (128, 4)]);
+        assert!(fields[0].value.starts_with("d=0 "));
+        assert_eq!((all.row(0), all.row(1), all.row(2)), (1, 0, 2));
+        assert_eq!(all.title(), "Fuzzy results (3 hits)");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_find_regex_chunks() {
+        // 跨块的匹配 以及块末尾还在继续的匹配
+        let mut text = vec![0u8; REGEX_CHUNK + 100];
+        text[REGEX_CHUNK - 2..REGEX_CHUNK + 3].copy_from_slice(b"ABCDE");
+        assert_eq!(
+            find(FindValue::Regex("[A-Z]+".to_string()), &text),
+            Some((REGEX_CHUNK - 2, 5))
+        );
+        assert_eq!(
+            find(FindValue::Regex("E".to_string()), &text),
+            Some((REGEX_CHUNK + 2, 1))
+        );
+    }
+}
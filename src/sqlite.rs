@@ -0,0 +1,727 @@
+use crate::pg::PageFields;
+use crate::plugin::ByteSource;
+use crate::plugin::Plugin;
+use crate::plugin::PluginAction;
+use crate::plugin::PluginField;
+use crate::plugin::PluginOutput;
+use crate::plugin::PluginResult;
+use crate::tui::TextSelect;
+use crate::ChapResult;
+
+// sqlite 文件格式 https://www.sqlite.org/fileformat.html
+// 所有整数都是大端
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+const FILE_HEADER_SIZE: usize = 100;
+
+const PAGE_INTERIOR_INDEX: u8 = 2;
+const PAGE_INTERIOR_TABLE: u8 = 5;
+const PAGE_LEAF_INDEX: u8 = 10;
+const PAGE_LEAF_TABLE: u8 = 13;
+
+// 文本和 blob 在辅助窗口中最多显示的长度
+const VALUE_DISPLAY_MAX: usize = 64;
+
+fn be16(b: &[u8], off: usize) -> usize {
+    u16::from_be_bytes([b[off], b[off + 1]]) as usize
+}
+
+fn be32(b: &[u8], off: usize) -> usize {
+    u32::from_be_bytes(b[off..off + 4].try_into().unwrap()) as usize
+}
+
+// 大端 varint 每字节7位 第9个字节使用全部8位 返回值和长度
+pub(crate) fn read_varint(b: &[u8]) -> Option<(u64, usize)> {
+    let mut v: u64 = 0;
+    for i in 0..9 {
+        let byte = *b.get(i)?;
+        if i == 8 {
+            return Some(((v << 8) | byte as u64, 9));
+        }
+        v = (v << 7) | (byte & 0x7f) as u64;
+        if byte & 0x80 == 0 {
+            return Some((v, i + 1));
+        }
+    }
+    None
+}
+
+// 文件头中解析页面需要的信息
+struct DbHeader {
+    page_size: usize,
+    usable_size: usize, // 去掉每页末尾的保留空间
+    encoding: u32,
+    page_count: usize,
+}
+
+impl DbHeader {
+    fn read(src: &dyn ByteSource) -> ChapResult<DbHeader> {
+        let h = src.read(0, FILE_HEADER_SIZE);
+        if h.len() < FILE_HEADER_SIZE || &h[..16] != SQLITE_MAGIC {
+            return Err("not a sqlite database file".into());
+        }
+        let page_size = match be16(&h, 16) {
+            1 => 65536,
+            n => n,
+        };
+        if page_size < 512 || !page_size.is_power_of_two() {
+            return Err(format!("invalid page size {}", page_size).into());
+        }
+        Ok(DbHeader {
+            page_size,
+            usable_size: page_size - h[20] as usize,
+            encoding: be32(&h, 56) as u32,
+            page_count: src.size() / page_size,
+        })
+    }
+
+    fn page_offset(&self, pgno: usize) -> usize {
+        (pgno - 1) * self.page_size
+    }
+}
+
+fn encoding_name(enc: u32) -> &'static str {
+    match enc {
+        1 => "UTF-8",
+        2 => "UTF-16le",
+        3 => "UTF-16be",
+        _ => "unknown",
+    }
+}
+
+// 100 字节的文件头
+fn file_header_fields(h: &[u8], out: &mut PageFields) {
+    out.push(
+        "magic",
+        0,
+        16,
+        format!("{:?}", String::from_utf8_lossy(&h[..15])),
+    );
+    let page_size = match be16(h, 16) {
+        1 => 65536,
+        n => n,
+    };
+    out.push("page_size", 16, 2, page_size.to_string());
+    let version = |v: u8| match v {
+        1 => "1 legacy",
+        2 => "2 WAL",
+        _ => "unknown",
+    };
+    out.push("write_version", 18, 1, version(h[18]));
+    out.push("read_version", 19, 1, version(h[19]));
+    out.push("reserved_space", 20, 1, h[20].to_string());
+    out.push("max_payload_fraction", 21, 1, h[21].to_string());
+    out.push("min_payload_fraction", 22, 1, h[22].to_string());
+    out.push("leaf_payload_fraction", 23, 1, h[23].to_string());
+    const U32_FIELDS: &[(&str, usize)] = &[
+        ("file_change_counter", 24),
+        ("database_size", 28),
+        ("first_freelist_trunk", 32),
+        ("freelist_count", 36),
+        ("schema_cookie", 40),
+        ("schema_format", 44),
+        ("default_cache_size", 48),
+        ("largest_root_page", 52),
+    ];
+    for (name, off) in U32_FIELDS {
+        out.push(*name, *off, 4, be32(h, *off).to_string());
+    }
+    let enc = be32(h, 56) as u32;
+    out.push(
+        "text_encoding",
+        56,
+        4,
+        format!("{} {}", enc, encoding_name(enc)),
+    );
+    out.push("user_version", 60, 4, be32(h, 60).to_string());
+    out.push("incremental_vacuum", 64, 4, be32(h, 64).to_string());
+    out.push("application_id", 68, 4, format!("0x{:08x}", be32(h, 68)));
+    out.push("version_valid_for", 92, 4, be32(h, 92).to_string());
+    let v = be32(h, 96);
+    out.push(
+        "sqlite_version",
+        96,
+        4,
+        format!("{} ({}.{}.{})", v, v / 1000000, v / 1000 % 1000, v % 1000),
+    );
+}
+
+fn page_type_name(t: u8) -> &'static str {
+    match t {
+        PAGE_INTERIOR_INDEX => "interior index",
+        PAGE_INTERIOR_TABLE => "interior table",
+        PAGE_LEAF_INDEX => "leaf index",
+        PAGE_LEAF_TABLE => "leaf table",
+        _ => "unknown",
+    }
+}
+
+// 解析第 pgno 页 (从1开始) 第1页包括文件头
+pub(crate) fn decode_page(
+    src: &dyn ByteSource,
+    pgno: usize,
+) -> ChapResult<(String, Vec<PluginField>)> {
+    let db = DbHeader::read(src)?;
+    if pgno == 0 || pgno > db.page_count {
+        return Err(format!("page {} out of range 1-{}", pgno, db.page_count).into());
+    }
+    let base = db.page_offset(pgno);
+    let page = src.read(base, db.page_size);
+    let mut out = PageFields::new(base);
+    let hdr = if pgno == 1 {
+        file_header_fields(&page, &mut out);
+        FILE_HEADER_SIZE
+    } else {
+        0
+    };
+    let page_type = page[hdr];
+    if !matches!(
+        page_type,
+        PAGE_INTERIOR_INDEX | PAGE_INTERIOR_TABLE | PAGE_LEAF_INDEX | PAGE_LEAF_TABLE
+    ) {
+        //溢出页 空闲页等没有 b-tree 页头
+        out.push(
+            "page_type",
+            hdr,
+            1,
+            format!("{} not a b-tree page", page_type),
+        );
+        return Ok((format!("page {}, not a b-tree page", pgno), out.fields));
+    }
+    let interior = page_type == PAGE_INTERIOR_INDEX || page_type == PAGE_INTERIOR_TABLE;
+    let header_size = if interior { 12 } else { 8 };
+    let ncells = be16(&page, hdr + 3);
+    let content_start = match be16(&page, hdr + 5) {
+        0 => 65536,
+        n => n,
+    };
+    out.push(
+        "page_type",
+        hdr,
+        1,
+        format!("{} {}", page_type, page_type_name(page_type)),
+    );
+    out.push(
+        "first_freeblock",
+        hdr + 1,
+        2,
+        be16(&page, hdr + 1).to_string(),
+    );
+    out.push("cell_count", hdr + 3, 2, ncells.to_string());
+    out.push("cell_content_start", hdr + 5, 2, content_start.to_string());
+    out.push("fragmented_bytes", hdr + 7, 1, page[hdr + 7].to_string());
+    if interior {
+        out.push("right_child", hdr + 8, 4, be32(&page, hdr + 8).to_string());
+    }
+
+    let ptr_start = hdr + header_size;
+    let ptr_end = ptr_start + ncells * 2;
+    if ptr_end > db.usable_size {
+        out.push(
+            "cell_ptr",
+            ptr_start,
+            0,
+            format!("invalid cell count {}", ncells),
+        );
+        return Ok((format!("page {}, invalid cell count", pgno), out.fields));
+    }
+    let cells: Vec<usize> = (0..ncells)
+        .map(|i| be16(&page, ptr_start + i * 2))
+        .collect();
+    for (i, off) in cells.iter().enumerate() {
+        out.push(
+            format!("cell_ptr[{}]", i),
+            ptr_start + i * 2,
+            2,
+            off.to_string(),
+        );
+    }
+    if content_start > ptr_end && content_start <= db.usable_size {
+        out.push(
+            "unallocated",
+            ptr_end,
+            content_start - ptr_end,
+            format!("{} bytes", content_start - ptr_end),
+        );
+    }
+    freeblock_fields(&page, be16(&page, hdr + 1), db.usable_size, &mut out);
+    let ctx = PageCtx {
+        src,
+        db: &db,
+        page: &page,
+        base,
+        page_type,
+    };
+    for (i, off) in cells.iter().enumerate() {
+        if *off < ptr_end || *off >= db.usable_size {
+            out.push(
+                format!("cell[{}]", i),
+                ptr_start + i * 2,
+                2,
+                format!("invalid cell offset {}", off),
+            );
+            continue;
+        }
+        if let Err(e) = cell_fields(&ctx, i, *off, &mut out) {
+            out.push(format!("cell[{}]", i), *off, 0, e);
+        }
+    }
+    Ok((
+        format!(
+            "page {}, {}, {} cells",
+            pgno,
+            page_type_name(page_type),
+            ncells
+        ),
+        out.fields,
+    ))
+}
+
+// 空闲块链表 每个空闲块: 下一个空闲块偏移(2) 大小(2)
+fn freeblock_fields(page: &[u8], mut off: usize, usable: usize, out: &mut PageFields) {
+    let mut n = 0;
+    while off != 0 && off + 4 <= usable && n < usable / 4 {
+        let next = be16(page, off);
+        let size = be16(page, off + 2);
+        out.push(
+            format!("freeblock[{}]", n),
+            off,
+            size.clamp(4, usable - off),
+            format!("size {} next {}", size, next),
+        );
+        //链表按偏移递增 防止循环
+        if next <= off {
+            break;
+        }
+        off = next;
+        n += 1;
+    }
+}
+
+// 单元格的 payload 可能跨越溢出页 segs 记录每一段在文件中的位置
+struct Payload {
+    data: Vec<u8>,
+    segs: Vec<(usize, usize)>, // 文件偏移 长度
+}
+
+impl Payload {
+    // payload 中 [pos, pos+len) 对应的文件范围 跨页时只返回第一段
+    fn file_range(&self, pos: usize, len: usize) -> (usize, usize) {
+        let mut seg_start = 0;
+        for (off, seg_len) in self.segs.iter() {
+            if pos < seg_start + seg_len {
+                let start = pos - seg_start;
+                return (off + start, len.min(seg_len - start));
+            }
+            seg_start += seg_len;
+        }
+        let last = self.segs.last().map_or(0, |(off, len)| off + len);
+        (last, 0)
+    }
+}
+
+// 页面中保存的 payload 长度 其余部分在溢出页中
+fn local_payload_size(usable: usize, page_type: u8, payload: usize) -> usize {
+    let max_local = if page_type == PAGE_LEAF_TABLE {
+        usable - 35
+    } else {
+        (usable - 12) * 64 / 255 - 23
+    };
+    if payload <= max_local {
+        return payload;
+    }
+    let min_local = (usable - 12) * 32 / 255 - 23;
+    let k = min_local + (payload - min_local) % (usable - 4);
+    if k <= max_local {
+        k
+    } else {
+        min_local
+    }
+}
+
+// 解析单元格时所在页的信息
+struct PageCtx<'a> {
+    src: &'a dyn ByteSource,
+    db: &'a DbHeader,
+    page: &'a [u8],
+    base: usize, // 页在文件中的偏移
+    page_type: u8,
+}
+
+fn cell_fields(ctx: &PageCtx, i: usize, off: usize, out: &mut PageFields) -> Result<(), String> {
+    let PageCtx {
+        src,
+        db,
+        page,
+        base,
+        page_type,
+    } = *ctx;
+    let c = format!("cell[{}]", i);
+    let cell = &page[off..db.usable_size];
+    let mut pos = 0;
+    if page_type == PAGE_INTERIOR_INDEX || page_type == PAGE_INTERIOR_TABLE {
+        if cell.len() < 4 {
+            return Err("truncated left child".to_string());
+        }
+        out.push(
+            format!("{}.left_child", c),
+            off,
+            4,
+            be32(cell, 0).to_string(),
+        );
+        pos = 4;
+    }
+    if page_type == PAGE_INTERIOR_TABLE {
+        let (rowid, n) = read_varint(&cell[pos..]).ok_or("truncated rowid")?;
+        out.push(format!("{}.rowid", c), off + pos, n, rowid.to_string());
+        return Ok(());
+    }
+    let (payload_size, n) = read_varint(&cell[pos..]).ok_or("truncated payload size")?;
+    let payload_size = payload_size as usize;
+    out.push(
+        format!("{}.payload_size", c),
+        off + pos,
+        n,
+        payload_size.to_string(),
+    );
+    pos += n;
+    if page_type == PAGE_LEAF_TABLE {
+        let (rowid, n) = read_varint(&cell[pos..]).ok_or("truncated rowid")?;
+        out.push(format!("{}.rowid", c), off + pos, n, rowid.to_string());
+        pos += n;
+    }
+    let local = local_payload_size(db.usable_size, page_type, payload_size);
+    if pos + local > cell.len() {
+        return Err(format!("payload {} exceeds page", local));
+    }
+    let mut payload = Payload {
+        data: cell[pos..pos + local].to_vec(),
+        segs: vec![(base + off + pos, local)],
+    };
+    if local < payload_size {
+        if pos + local + 4 > cell.len() {
+            return Err("truncated overflow page number".to_string());
+        }
+        let first = be32(cell, pos + local);
+        out.push(
+            format!("{}.overflow", c),
+            off + pos + local,
+            4,
+            format!("page {}", first),
+        );
+        overflow_fields(src, db, &c, first, payload_size, &mut payload, out);
+    }
+    record_fields(&payload, db.encoding, &c, out);
+    Ok(())
+}
+
+// 溢出页: 下一页页号(4) 之后是 payload 数据
+fn overflow_fields(
+    src: &dyn ByteSource,
+    db: &DbHeader,
+    c: &str,
+    mut pgno: usize,
+    payload_size: usize,
+    payload: &mut Payload,
+    out: &mut PageFields,
+) {
+    let mut n = 0;
+    while pgno != 0 && payload.data.len() < payload_size {
+        if pgno > db.page_count || n >= db.page_count {
+            out.push(
+                format!("{}.overflow[{}]", c, n),
+                0,
+                0,
+                format!("invalid overflow page {}", pgno),
+            );
+            return;
+        }
+        let base = db.page_offset(pgno);
+        let page = src.read(base, db.usable_size);
+        let len = (payload_size - payload.data.len()).min(db.usable_size - 4);
+        payload.data.extend_from_slice(&page[4..4 + len]);
+        payload.segs.push((base + 4, len));
+        let next = be32(&page, 0);
+        //溢出页不在当前页面中 直接使用文件偏移
+        out.fields.push(PluginField {
+            name: format!("{}.overflow[{}]", c, n),
+            offset: base,
+            length: 4,
+            value: format!("page {} next {}, {} bytes", pgno, next, len),
+        });
+        pgno = next;
+        n += 1;
+    }
+}
+
+fn serial_type_name(t: u64) -> String {
+    match t {
+        0 => "NULL".to_string(),
+        1..=6 => format!("int{}", serial_type_size(t) * 8),
+        7 => "float".to_string(),
+        8 => "0".to_string(),
+        9 => "1".to_string(),
+        10 | 11 => "reserved".to_string(),
+        t if t % 2 == 0 => format!("blob({})", (t - 12) / 2),
+        t => format!("text({})", (t - 13) / 2),
+    }
+}
+
+fn serial_type_size(t: u64) -> usize {
+    match t {
+        0 | 8..=11 => 0,
+        1..=4 => t as usize,
+        5 => 6,
+        6 | 7 => 8,
+        t if t % 2 == 0 => (t as usize - 12) / 2,
+        t => (t as usize - 13) / 2,
+    }
+}
+
+// 记录格式: header 长度 varint, 每列的 serial type varint, 之后是每列的值
+fn record_fields(payload: &Payload, encoding: u32, c: &str, out: &mut PageFields) {
+    let data = &payload.data;
+    let (hsize, n) = match read_varint(data) {
+        Some(v) => v,
+        None => return,
+    };
+    let hsize = (hsize as usize).min(data.len());
+    let mut types = Vec::new();
+    let mut pos = n;
+    while pos < hsize {
+        match read_varint(&data[pos..hsize]) {
+            Some((t, n)) => {
+                types.push(t);
+                pos += n;
+            }
+            None => break,
+        }
+    }
+    let (off, len) = payload.file_range(0, hsize);
+    let names: Vec<String> = types.iter().map(|t| serial_type_name(*t)).collect();
+    out.fields.push(PluginField {
+        name: format!("{}.header", c),
+        offset: off,
+        length: len,
+        value: format!("{} bytes: {}", hsize, names.join(", ")),
+    });
+    let mut pos = hsize;
+    for (j, t) in types.iter().enumerate() {
+        let size = serial_type_size(*t);
+        let name = format!("{}.col{}", c, j);
+        if pos + size > data.len() {
+            out.fields.push(PluginField {
+                name,
+                offset: payload.file_range(pos, 0).0,
+                length: 0,
+                value: format!("truncated {} at {}", serial_type_name(*t), pos),
+            });
+            return;
+        }
+        let (off, len) = payload.file_range(pos, size);
+        out.fields.push(PluginField {
+            name,
+            offset: off,
+            length: len,
+            value: format_value(*t, &data[pos..pos + size], encoding),
+        });
+        pos += size;
+    }
+}
+
+fn format_value(t: u64, v: &[u8], encoding: u32) -> String {
+    match t {
+        0 => "NULL".to_string(),
+        1..=6 => {
+            //符号扩展
+            let mut n: i64 = if v[0] & 0x80 != 0 { -1 } else { 0 };
+            for b in v {
+                n = (n << 8) | *b as i64;
+            }
+            n.to_string()
+        }
+        7 => f64::from_be_bytes(v.try_into().unwrap()).to_string(),
+        8 => "0".to_string(),
+        9 => "1".to_string(),
+        10 | 11 => "reserved".to_string(),
+        t if t % 2 == 0 => {
+            let n = v.len().min(VALUE_DISPLAY_MAX / 2);
+            let more = if v.len() > n {
+                format!("... ({} bytes)", v.len())
+            } else {
+                String::new()
+            };
+            format!("x'{}'{}", hex::encode(&v[..n]), more)
+        }
+        _ => {
+            let s = match encoding {
+                2 | 3 => {
+                    let units: Vec<u16> = v
+                        .chunks_exact(2)
+                        .map(|c| match encoding {
+                            2 => u16::from_le_bytes([c[0], c[1]]),
+                            _ => u16::from_be_bytes([c[0], c[1]]),
+                        })
+                        .collect();
+                    String::from_utf16_lossy(&units)
+                }
+                _ => String::from_utf8_lossy(v).to_string(),
+            };
+            if s.chars().count() > VALUE_DISPLAY_MAX {
+                let s: String = s.chars().take(VALUE_DISPLAY_MAX).collect();
+                format!("{:?}... ({} bytes)", s, v.len())
+            } else {
+                format!("{:?}", s)
+            }
+        }
+    }
+}
+
+// 内置的 sqlite 解析插件
+//   sqlite_header        解析 100 字节文件头
+//   sqlite_page page=N   解析第 N 页 不指定时为选区所在的页面
+pub(crate) struct SqlitePlugin;
+
+impl Plugin for SqlitePlugin {
+    fn eval(
+        &self,
+        name: &str,
+        args: &[(String, String)],
+        src: &dyn ByteSource,
+        sel: &TextSelect,
+    ) -> ChapResult<PluginResult> {
+        let db = DbHeader::read(src)?;
+        let (summary, fields, jump) = match name {
+            "sqlite_header" => {
+                let mut out = PageFields::new(0);
+                file_header_fields(&src.read(0, FILE_HEADER_SIZE), &mut out);
+                (
+                    format!(
+                        "sqlite {} pages of {} bytes, {}",
+                        db.page_count,
+                        db.page_size,
+                        encoding_name(db.encoding)
+                    ),
+                    out.fields,
+                    0,
+                )
+            }
+            "sqlite_page" => {
+                let pgno = match args.iter().find(|(k, _)| k == "page") {
+                    Some((_, v)) => v
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid page '{}'", v))?,
+                    None => sel.get_start() / db.page_size + 1,
+                };
+                let (summary, fields) = decode_page(src, pgno)?;
+                (summary, fields, db.page_offset(pgno))
+            }
+            _ => return Err(format!("unknown sqlite function '{}'", name).into()),
+        };
+        Ok(PluginResult {
+            output: PluginOutput::Fields(fields),
+            actions: vec![PluginAction::Jump(jump), PluginAction::Message(summary)],
+        })
+    }
+
+    fn list(&self) -> ChapResult<String> {
+        Ok("* sqlite_header\n* sqlite_page page=N".to_string())
+    }
+
+    fn has(&self, name: &str) -> bool {
+        name == "sqlite_header" || name == "sqlite_page"
+    }
+
+    fn reload(&self) -> ChapResult<usize> {
+        Ok(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 两个 512 字节的页面 第2页保存一行 (rowid 5: 7, 'hi', NULL)
+    fn test_db() -> Vec<u8> {
+        let mut db = vec![0u8; 1024];
+        db[..16].copy_from_slice(SQLITE_MAGIC);
+        db[16..18].copy_from_slice(&512u16.to_be_bytes());
+        db[18] = 1;
+        db[19] = 1;
+        db[21..24].copy_from_slice(&[64, 32, 32]);
+        db[28..32].copy_from_slice(&2u32.to_be_bytes());
+        db[56..60].copy_from_slice(&1u32.to_be_bytes());
+        db[100] = PAGE_LEAF_TABLE;
+        db[105..107].copy_from_slice(&512u16.to_be_bytes());
+        let page = &mut db[512..];
+        page[0] = PAGE_LEAF_TABLE;
+        page[3..5].copy_from_slice(&1u16.to_be_bytes());
+        page[5..7].copy_from_slice(&503u16.to_be_bytes());
+        page[8..10].copy_from_slice(&503u16.to_be_bytes());
+        page[503..512].copy_from_slice(&[0x07, 0x05, 0x04, 0x01, 0x11, 0x00, 0x07, b'h', b'i']);
+        db
+    }
+
+    #[test]
+    fn test_read_varint() {
+        assert_eq!(read_varint(&[0x7f]), Some((127, 1)));
+        assert_eq!(read_varint(&[0x81, 0x00]), Some((128, 2)));
+        assert_eq!(read_varint(&[0xff; 9]), Some((u64::MAX, 9)));
+        assert_eq!(read_varint(&[0x81]), None);
+    }
+
+    #[test]
+    fn test_local_payload_size() {
+        //与 sqlite 3.40 写入的 1024 字节页面一致
+        assert_eq!(local_payload_size(1024, PAGE_LEAF_TABLE, 100), 100);
+        assert_eq!(local_payload_size(1024, PAGE_LEAF_TABLE, 3017), 977);
+        assert_eq!(local_payload_size(1024, PAGE_LEAF_INDEX, 3005), 103);
+    }
+
+    #[test]
+    fn test_decode_page() {
+        let db = test_db();
+        let (summary, fields) = decode_page(&db, 1).unwrap();
+        assert_eq!(summary, "page 1, leaf table, 0 cells");
+        let get = |fields: &[PluginField], name: &str| {
+            fields.iter().find(|f| f.name == name).unwrap().clone()
+        };
+        assert_eq!(get(&fields, "page_size").value, "512");
+        assert_eq!(get(&fields, "text_encoding").value, "1 UTF-8");
+        assert_eq!(get(&fields, "page_type").offset, 100);
+
+        let (_, fields) = decode_page(&db, 2).unwrap();
+        assert_eq!(get(&fields, "cell_ptr[0]").value, "503");
+        assert_eq!(get(&fields, "unallocated").length, 503 - 10);
+        assert_eq!(get(&fields, "cell[0].rowid").value, "5");
+        assert_eq!(
+            get(&fields, "cell[0].header").value,
+            "4 bytes: int8, text(2), NULL"
+        );
+        let col = get(&fields, "cell[0].col1");
+        assert_eq!((col.offset, col.length), (512 + 510, 2));
+        assert_eq!(col.value, "\"hi\"");
+        assert_eq!(get(&fields, "cell[0].col2").value, "NULL");
+
+        assert!(decode_page(&db, 3).is_err());
+        assert!(decode_page(&vec![0u8; 1024], 1).is_err());
+    }
+
+    #[test]
+    fn test_sqlite_plugin() {
+        let db = test_db();
+        let args = vec![("page".to_string(), "2".to_string())];
+        let r = SqlitePlugin
+            .eval("sqlite_page", &args, &db, &TextSelect::from_select(0, 0))
+            .unwrap();
+        assert!(matches!(r.output, PluginOutput::Fields(_)));
+        assert_eq!(r.actions[0], PluginAction::Jump(512));
+        //不指定页号时使用选区所在的页面
+        let r = SqlitePlugin
+            .eval("sqlite_page", &[], &db, &TextSelect::from_select(600, 600))
+            .unwrap();
+        assert_eq!(r.actions[0], PluginAction::Jump(512));
+        assert!(SqlitePlugin
+            .eval("sqlite_foo", &[], &db, &TextSelect::from_select(0, 0))
+            .is_err());
+    }
+}
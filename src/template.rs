@@ -0,0 +1,915 @@
+use crate::byteutil::Endian;
+use crate::plugin::ByteSource;
+use crate::plugin::PluginAction;
+use crate::plugin::PluginField;
+use crate::ChapResult;
+use ratatui::style::Color;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+// 模板目录 相对于 home 目录
+pub(crate) const TEMPLATE_DIR: &str = ".chap/templates";
+// 模板文件扩展名
+pub(crate) const TEMPLATE_EXT: &str = "tpl";
+// 展开后的最大字段数和结构体嵌套深度
+const FIELDS_MAX: usize = 100_000;
+const DEPTH_MAX: usize = 64;
+// u8 和 char 数组最多显示的字节数
+const BYTES_SHOWN: usize = 32;
+// 叶子字段轮流使用的标注颜色
+const COLORS: [Color; 4] = [Color::Blue, Color::Green, Color::Magenta, Color::Cyan];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Num(u64),
+    Punct(char),
+}
+
+// 词法分析 返回 token 和所在行号
+fn tokenize(text: &str) -> ChapResult<Vec<(Tok, usize)>> {
+    let mut toks = Vec::new();
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            prev = c;
+                        }
+                        None => return Err(format!("line {}: unterminated comment", line).into()),
+                    }
+                }
+            }
+            c if c.is_ascii_digit() => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                }
+                let s = s.replace('_', "");
+                let n = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => s.parse::<u64>(),
+                }
+                .map_err(|_| format!("line {}: invalid number {}", line, s))?;
+                toks.push((Tok::Num(n), line));
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                }
+                toks.push((Tok::Ident(s), line));
+            }
+            '{' | '}' | '[' | ']' | '(' | ')' | ';' | ':' | ',' | '=' | '.' | '+' | '-' | '*'
+            | '/' => toks.push((Tok::Punct(c), line)),
+            c => return Err(format!("line {}: unexpected character '{}'", line, c).into()),
+        }
+    }
+    Ok(toks)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Prim {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    Char,
+}
+
+impl Prim {
+    // u16be u32le 这样的后缀指定字节序
+    fn parse(name: &str) -> Option<(Prim, Option<Endian>)> {
+        let (base, endian) = match name.len() > 2 {
+            true if name.ends_with("le") => (&name[..name.len() - 2], Some(Endian::Little)),
+            true if name.ends_with("be") => (&name[..name.len() - 2], Some(Endian::Big)),
+            _ => (name, None),
+        };
+        let prim = match base {
+            "u8" => Prim::U8,
+            "u16" => Prim::U16,
+            "u32" => Prim::U32,
+            "u64" => Prim::U64,
+            "i8" => Prim::I8,
+            "i16" => Prim::I16,
+            "i32" => Prim::I32,
+            "i64" => Prim::I64,
+            "f32" => Prim::F32,
+            "f64" => Prim::F64,
+            "char" => Prim::Char,
+            _ => return None,
+        };
+        Some((prim, endian))
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Prim::U8 | Prim::I8 | Prim::Char => 1,
+            Prim::U16 | Prim::I16 => 2,
+            Prim::U32 | Prim::I32 | Prim::F32 => 4,
+            Prim::U64 | Prim::I64 | Prim::F64 => 8,
+        }
+    }
+
+    fn is_integer(self) -> bool {
+        !matches!(self, Prim::F32 | Prim::F64)
+    }
+
+    // 返回原始整数和显示的值
+    fn decode(self, b: &[u8], endian: &Endian) -> (u64, String) {
+        let fold = |v: u64, b: &u8| (v << 8) | *b as u64;
+        let raw = match endian {
+            Endian::Little => b.iter().rev().fold(0, fold),
+            Endian::Big => b.iter().fold(0, fold),
+        };
+        let shift = 64 - self.size() as u32 * 8;
+        let signed = ((raw << shift) as i64) >> shift;
+        let value = match self {
+            Prim::U8 | Prim::U16 | Prim::U32 | Prim::U64 => raw.to_string(),
+            Prim::I8 | Prim::I16 | Prim::I32 | Prim::I64 => signed.to_string(),
+            Prim::F32 => f32::from_bits(raw as u32).to_string(),
+            Prim::F64 => f64::from_bits(raw).to_string(),
+            Prim::Char => format!("'{}'", (raw as u8).escape_ascii()),
+        };
+        match self {
+            Prim::I8 | Prim::I16 | Prim::I32 | Prim::I64 => (signed as u64, value),
+            _ => (raw, value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(u64),
+    Path(Vec<String>),
+    Bin(Box<Expr>, char, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+    Endian(Endian),
+    Field {
+        ty: String,
+        name: String,
+        count: Option<Expr>,
+        line: usize,
+    },
+}
+
+#[derive(Debug)]
+struct EnumDef {
+    prim: Prim,
+    endian: Option<Endian>,
+    items: Vec<(String, u64)>,
+}
+
+// 位域从最低位开始分配
+#[derive(Debug)]
+struct BitfieldDef {
+    prim: Prim,
+    endian: Option<Endian>,
+    bits: Vec<(String, u32)>,
+}
+
+// 解析后的模板 顶层字段从模板的起始地址开始展开
+#[derive(Debug, Default)]
+pub(crate) struct Template {
+    structs: HashMap<String, Vec<Stmt>>,
+    enums: HashMap<String, EnumDef>,
+    bitfields: HashMap<String, BitfieldDef>,
+    root: Vec<Stmt>,
+}
+
+struct Parser {
+    toks: Vec<(Tok, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.toks
+            .get(self.pos)
+            .or(self.toks.last())
+            .map_or(1, |(_, l)| *l)
+    }
+
+    fn err<T>(&self, msg: String) -> ChapResult<T> {
+        Err(format!("line {}: {}", self.line(), msg).into())
+    }
+
+    fn next(&mut self) -> ChapResult<Tok> {
+        match self.toks.get(self.pos) {
+            Some((t, _)) => {
+                self.pos += 1;
+                Ok(t.clone())
+            }
+            None => self.err("unexpected end of template".to_string()),
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Tok::Punct(c)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> ChapResult<()> {
+        if self.eat(c) {
+            return Ok(());
+        }
+        self.err(format!("expected '{}'", c))
+    }
+
+    fn ident(&mut self) -> ChapResult<String> {
+        match self.peek() {
+            Some(Tok::Ident(s)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Ok(s)
+            }
+            _ => self.err("expected identifier".to_string()),
+        }
+    }
+
+    fn number(&mut self) -> ChapResult<u64> {
+        match self.peek() {
+            Some(Tok::Num(n)) => {
+                let n = *n;
+                self.pos += 1;
+                Ok(n)
+            }
+            _ => self.err("expected number".to_string()),
+        }
+    }
+
+    fn prim(&mut self) -> ChapResult<(Prim, Option<Endian>)> {
+        let name = self.ident()?;
+        match Prim::parse(&name) {
+            Some((prim, endian)) if prim.is_integer() => Ok((prim, endian)),
+            _ => self.err(format!("{} is not an integer type", name)),
+        }
+    }
+
+    fn endian(&mut self) -> ChapResult<Endian> {
+        let e = match self.ident()?.as_str() {
+            "little" => Endian::Little,
+            "big" => Endian::Big,
+            e => return self.err(format!("unknown endian {}, expect little or big", e)),
+        };
+        self.expect(';')?;
+        Ok(e)
+    }
+
+    // expr := term (('+'|'-') term)*
+    fn expr(&mut self) -> ChapResult<Expr> {
+        let mut lhs = self.term()?;
+        while let Some(Tok::Punct(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            lhs = Expr::Bin(Box::new(lhs), op, Box::new(self.term()?));
+        }
+        Ok(lhs)
+    }
+
+    // term := atom (('*'|'/') atom)*
+    fn term(&mut self) -> ChapResult<Expr> {
+        let mut lhs = self.atom()?;
+        while let Some(Tok::Punct(op @ ('*' | '/'))) = self.peek().cloned() {
+            self.pos += 1;
+            lhs = Expr::Bin(Box::new(lhs), op, Box::new(self.atom()?));
+        }
+        Ok(lhs)
+    }
+
+    // atom := number | path | '(' expr ')'
+    fn atom(&mut self) -> ChapResult<Expr> {
+        match self.next()? {
+            Tok::Num(n) => Ok(Expr::Num(n)),
+            Tok::Ident(s) => {
+                let mut path = vec![s];
+                while self.eat('.') {
+                    path.push(self.ident()?);
+                }
+                Ok(Expr::Path(path))
+            }
+            Tok::Punct('(') => {
+                let e = self.expr()?;
+                self.expect(')')?;
+                Ok(e)
+            }
+            t => {
+                self.pos -= 1;
+                self.err(format!("unexpected {:?} in expression", t))
+            }
+        }
+    }
+
+    // stmt := 'endian' (little|big) ';' | type name ['[' expr ']'] ';'
+    fn stmt(&mut self) -> ChapResult<Stmt> {
+        let line = self.line();
+        let ty = self.ident()?;
+        if ty == "endian" {
+            return Ok(Stmt::Endian(self.endian()?));
+        }
+        let name = self.ident()?;
+        let count = if self.eat('[') {
+            let e = self.expr()?;
+            self.expect(']')?;
+            Some(e)
+        } else {
+            None
+        };
+        self.expect(';')?;
+        Ok(Stmt::Field {
+            ty,
+            name,
+            count,
+            line,
+        })
+    }
+
+    fn template(&mut self) -> ChapResult<Template> {
+        let mut tpl = Template::default();
+        while let Some(tok) = self.peek().cloned() {
+            let kw = match tok {
+                Tok::Ident(kw) => kw,
+                t => return self.err(format!("unexpected {:?}", t)),
+            };
+            match kw.as_str() {
+                "struct" => {
+                    self.pos += 1;
+                    let name = self.ident()?;
+                    self.expect('{')?;
+                    let mut body = Vec::new();
+                    while !self.eat('}') {
+                        body.push(self.stmt()?);
+                    }
+                    self.eat(';');
+                    if tpl.structs.insert(name.clone(), body).is_some() {
+                        return self.err(format!("struct {} defined twice", name));
+                    }
+                }
+                // enum Name : u8 { A = 1, B, C = 0x10 }
+                "enum" => {
+                    self.pos += 1;
+                    let name = self.ident()?;
+                    self.expect(':')?;
+                    let (prim, endian) = self.prim()?;
+                    self.expect('{')?;
+                    let mut items = Vec::new();
+                    let mut next = 0u64;
+                    while !self.eat('}') {
+                        let item = self.ident()?;
+                        if self.eat('=') {
+                            next = self.number()?;
+                        }
+                        items.push((item, next));
+                        next = next.wrapping_add(1);
+                        if !self.eat(',') {
+                            self.expect('}')?;
+                            break;
+                        }
+                    }
+                    self.eat(';');
+                    tpl.enums.insert(
+                        name,
+                        EnumDef {
+                            prim,
+                            endian,
+                            items,
+                        },
+                    );
+                }
+                // bitfield Name : u16 { a : 1; b : 3; }
+                "bitfield" => {
+                    self.pos += 1;
+                    let name = self.ident()?;
+                    self.expect(':')?;
+                    let (prim, endian) = self.prim()?;
+                    self.expect('{')?;
+                    let mut bits = Vec::new();
+                    let mut total = 0;
+                    while !self.eat('}') {
+                        let bit = self.ident()?;
+                        self.expect(':')?;
+                        let width = self.number()? as u32;
+                        self.expect(';')?;
+                        total += width;
+                        if width == 0 || total > prim.size() as u32 * 8 {
+                            return self
+                                .err(format!("bitfield {} does not fit in {:?}", name, prim));
+                        }
+                        bits.push((bit, width));
+                    }
+                    self.eat(';');
+                    tpl.bitfields
+                        .insert(name, BitfieldDef { prim, endian, bits });
+                }
+                _ => {
+                    let stmt = self.stmt()?;
+                    tpl.root.push(stmt);
+                }
+            }
+        }
+        Ok(tpl)
+    }
+}
+
+pub(crate) fn parse_template(text: &str) -> ChapResult<Template> {
+    let mut parser = Parser {
+        toks: tokenize(text)?,
+        pos: 0,
+    };
+    let tpl = parser.template()?;
+    if !tpl.root.iter().any(|s| matches!(s, Stmt::Field { .. })) {
+        return Err("template has no top-level fields".into());
+    }
+    // 检查所有类型都已定义
+    for stmt in tpl.structs.values().flatten().chain(tpl.root.iter()) {
+        if let Stmt::Field { ty, line, .. } = stmt {
+            if Prim::parse(ty).is_none()
+                && !tpl.structs.contains_key(ty)
+                && !tpl.enums.contains_key(ty)
+                && !tpl.bitfields.contains_key(ty)
+            {
+                return Err(format!("line {}: unknown type {}", line, ty).into());
+            }
+        }
+    }
+    Ok(tpl)
+}
+
+// 模板名字可以是文件路径 或者模板目录中的文件名 扩展名可以省略
+pub(crate) fn find_template(name: &str, dir: &Path) -> Option<PathBuf> {
+    let path = PathBuf::from(name);
+    if path.is_file() {
+        return Some(path);
+    }
+    [dir.join(name), dir.join(name).with_extension(TEMPLATE_EXT)]
+        .into_iter()
+        .find(|p| p.is_file())
+}
+
+// 已解析字段的值 数组大小可以引用前面的字段
+#[derive(Debug, Clone)]
+enum Value {
+    Num(u64),
+    Struct(Vec<(String, Value)>),
+    Other,
+}
+
+// 从内层作用域向外查找第一个名字 再逐级进入结构体
+fn lookup(scopes: &[Vec<(String, Value)>], path: &[String]) -> Option<u64> {
+    let mut value = scopes
+        .iter()
+        .rev()
+        .find_map(|s| s.iter().rev().find(|(n, _)| *n == path[0]))
+        .map(|(_, v)| v)?;
+    for name in &path[1..] {
+        value = match value {
+            Value::Struct(members) => members
+                .iter()
+                .rev()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v)?,
+            _ => return None,
+        };
+    }
+    match value {
+        Value::Num(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn eval_expr(expr: &Expr, scopes: &[Vec<(String, Value)>]) -> Result<u64, String> {
+    match expr {
+        Expr::Num(n) => Ok(*n),
+        Expr::Path(path) => lookup(scopes, path)
+            .ok_or_else(|| format!("{} is not an integer field", path.join("."))),
+        Expr::Bin(lhs, op, rhs) => {
+            let (l, r) = (eval_expr(lhs, scopes)?, eval_expr(rhs, scopes)?);
+            match op {
+                '+' => l.checked_add(r),
+                '-' => l.checked_sub(r),
+                '*' => l.checked_mul(r),
+                _ => l.checked_div(r),
+            }
+            .ok_or_else(|| format!("overflow in {} {} {}", l, op, r))
+        }
+    }
+}
+
+// 模板展开结果 fields 为树形字段 highlights 为叶子字段的标注
+pub(crate) struct Applied {
+    pub(crate) fields: Vec<PluginField>,
+    pub(crate) highlights: Vec<PluginAction>,
+}
+
+struct Eval<'a> {
+    tpl: &'a Template,
+    src: &'a dyn ByteSource,
+    scopes: Vec<Vec<(String, Value)>>, // 正在展开的结构体 内层在后
+    out: Applied,
+}
+
+// 数组元素共用的展开信息
+struct ArrayCtx<'a> {
+    endian: &'a Endian,
+    depth: usize,
+    line: usize, // 模板中的行号 用于错误信息
+}
+
+impl Eval<'_> {
+    fn push(&mut self, name: &str, offset: usize, length: usize, value: String) -> ChapResult<()> {
+        if self.out.fields.len() >= FIELDS_MAX {
+            return Err(format!("template expands to more than {} fields", FIELDS_MAX).into());
+        }
+        self.out.fields.push(PluginField {
+            name: name.to_string(),
+            offset,
+            length,
+            value,
+        });
+        Ok(())
+    }
+
+    // 叶子字段 轮流着色
+    fn leaf(&mut self, name: &str, offset: usize, length: usize, value: String) -> ChapResult<()> {
+        let color = COLORS[self.out.highlights.len() % COLORS.len()];
+        self.out
+            .highlights
+            .push(PluginAction::Highlight(offset, length, color));
+        self.push(name, offset, length, value)
+    }
+
+    fn check(&self, path: &str, off: usize, len: usize, line: usize) -> ChapResult<()> {
+        if off.checked_add(len).is_none_or(|end| end > self.src.size()) {
+            return Err(format!(
+                "line {}: {} at {} (+{}) runs past end of data",
+                line, path, off, len
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    fn read_prim(
+        &self,
+        prim: Prim,
+        endian: &Endian,
+        path: &str,
+        off: usize,
+        line: usize,
+    ) -> ChapResult<(u64, String)> {
+        self.check(path, off, prim.size(), line)?;
+        Ok(prim.decode(&self.src.read(off, prim.size()), endian))
+    }
+
+    // 展开结构体成员 返回结束偏移和成员的值
+    fn body(
+        &mut self,
+        stmts: &[Stmt],
+        prefix: &str,
+        mut off: usize,
+        mut endian: Endian,
+        depth: usize,
+    ) -> ChapResult<(usize, Vec<(String, Value)>)> {
+        self.scopes.push(Vec::new());
+        for stmt in stmts {
+            let (ty, name, count, line) = match stmt {
+                Stmt::Endian(e) => {
+                    endian = e.clone();
+                    continue;
+                }
+                Stmt::Field {
+                    ty,
+                    name,
+                    count,
+                    line,
+                } => (ty, name, count, *line),
+            };
+            let path = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            let value = match count {
+                None => {
+                    let (value, end) = self.item(ty, &path, off, &endian, depth, line)?;
+                    off = end;
+                    value
+                }
+                Some(expr) => {
+                    let n = eval_expr(expr, &self.scopes)
+                        .map_err(|e| format!("line {}: {}", line, e))?
+                        as usize;
+                    let ctx = ArrayCtx {
+                        endian: &endian,
+                        depth,
+                        line,
+                    };
+                    off = self.array(ty, &path, n, off, &ctx)?;
+                    Value::Other
+                }
+            };
+            self.scopes.last_mut().unwrap().push((name.clone(), value));
+        }
+        Ok((off, self.scopes.pop().unwrap()))
+    }
+
+    // 单个字段 返回值和结束偏移
+    fn item(
+        &mut self,
+        ty: &str,
+        path: &str,
+        off: usize,
+        endian: &Endian,
+        depth: usize,
+        line: usize,
+    ) -> ChapResult<(Value, usize)> {
+        let tpl = self.tpl;
+        if let Some((prim, e)) = Prim::parse(ty) {
+            let (raw, value) =
+                self.read_prim(prim, e.as_ref().unwrap_or(endian), path, off, line)?;
+            self.leaf(path, off, prim.size(), value)?;
+            let value = if prim.is_integer() {
+                Value::Num(raw)
+            } else {
+                Value::Other
+            };
+            return Ok((value, off + prim.size()));
+        }
+        if let Some(def) = tpl.enums.get(ty) {
+            let e = def.endian.as_ref().unwrap_or(endian);
+            let (raw, value) = self.read_prim(def.prim, e, path, off, line)?;
+            let value = match def.items.iter().find(|(_, v)| *v == raw) {
+                Some((name, _)) => format!("{} ({})", name, value),
+                None => format!("{} (unknown {})", value, ty),
+            };
+            self.leaf(path, off, def.prim.size(), value)?;
+            return Ok((Value::Num(raw), off + def.prim.size()));
+        }
+        if let Some(def) = tpl.bitfields.get(ty) {
+            let e = def.endian.as_ref().unwrap_or(endian);
+            let (raw, _) = self.read_prim(def.prim, e, path, off, line)?;
+            let size = def.prim.size();
+            let mut shift = 0;
+            let mut members = Vec::new();
+            for (name, width) in &def.bits {
+                let v = (raw >> shift) & (u64::MAX >> (64 - width));
+                shift += width;
+                members.push((name.clone(), Value::Num(v)));
+            }
+            let summary: Vec<String> = members
+                .iter()
+                .map(|(name, v)| match v {
+                    Value::Num(v) => format!("{}={}", name, v),
+                    _ => unreachable!(),
+                })
+                .collect();
+            self.leaf(
+                path,
+                off,
+                size,
+                format!("0x{:0w$x} {}", raw, summary.join(" "), w = size * 2),
+            )?;
+            for (name, v) in &members {
+                if let Value::Num(v) = v {
+                    self.push(&format!("{}.{}", path, name), off, size, v.to_string())?;
+                }
+            }
+            return Ok((Value::Struct(members), off + size));
+        }
+        if let Some(body) = tpl.structs.get(ty) {
+            if depth >= DEPTH_MAX {
+                return Err(format!(
+                    "line {}: structs nested deeper than {} at {}",
+                    line, DEPTH_MAX, path
+                )
+                .into());
+            }
+            let index = self.out.fields.len();
+            self.push(path, off, 0, ty.to_string())?;
+            let (end, members) = self.body(body, path, off, endian.clone(), depth + 1)?;
+            self.out.fields[index].length = end - off;
+            return Ok((Value::Struct(members), end));
+        }
+        Err(format!("line {}: unknown type {}", line, ty).into())
+    }
+
+    // 数组 char 显示为字符串 u8 显示为十六进制 其他类型逐个展开
+    fn array(
+        &mut self,
+        ty: &str,
+        path: &str,
+        n: usize,
+        off: usize,
+        ctx: &ArrayCtx,
+    ) -> ChapResult<usize> {
+        let ArrayCtx {
+            endian,
+            depth,
+            line,
+        } = *ctx;
+        if ty == "char" || ty == "u8" {
+            self.check(path, off, n, line)?;
+            let shown = self.src.read(off, n.min(BYTES_SHOWN));
+            let more = if n > BYTES_SHOWN { "..." } else { "" };
+            let value = if ty == "char" {
+                let end = shown.iter().position(|&c| c == 0).unwrap_or(shown.len());
+                format!("\"{}\"{}", shown[..end].escape_ascii(), more)
+            } else {
+                format!("{}{}", hex::encode(&shown), more)
+            };
+            self.leaf(path, off, n, value)?;
+            return Ok(off + n);
+        }
+        let index = self.out.fields.len();
+        self.push(path, off, 0, format!("{}[{}]", ty, n))?;
+        let mut end = off;
+        for i in 0..n {
+            let (_, next) = self.item(ty, &format!("{}[{}]", path, i), end, endian, depth, line)?;
+            end = next;
+        }
+        self.out.fields[index].length = end - off;
+        Ok(end)
+    }
+}
+
+// 从 base 开始展开模板 endian 为未指定字节序时的默认值
+pub(crate) fn apply_template(
+    tpl: &Template,
+    src: &dyn ByteSource,
+    base: usize,
+    endian: Endian,
+) -> ChapResult<Applied> {
+    let mut eval = Eval {
+        tpl,
+        src,
+        scopes: Vec::new(),
+        out: Applied {
+            fields: Vec::new(),
+            highlights: Vec::new(),
+        },
+    };
+    eval.body(&tpl.root, "", base, endian, 0)?;
+    Ok(eval.out)
+}
+
+// pos 处最内层的字段 长度相同时取外层 (位域显示整体的值)
+pub(crate) fn field_at(fields: &[PluginField], pos: usize) -> Option<&PluginField> {
+    fields
+        .iter()
+        .filter(|f| pos >= f.offset && pos < f.offset + f.length)
+        .min_by_key(|f| f.length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TPL: &str = "
+// 测试用的容器格式
+enum Kind : u8 { NONE, TEXT = 3, BLOB, }
+bitfield Flags : u16 { compressed : 1; level : 3; reserved : 12; }
+struct Header {
+    char magic[4];
+    u16 count;
+    Flags flags;
+};
+/* 每个条目带长度 */
+struct Entry {
+    Kind kind;
+    u8 len;
+    u8 data[len];
+}
+Header hdr;
+Entry entries[hdr.count];
+endian big;
+u32 crc;
+i16le delta;
+u16 tail[hdr.count - 1];
+";
+
+    fn data() -> Vec<u8> {
+        let mut b = b"CHAP\x02\x00\x13\x00".to_vec();
+        b.extend([3, 2, b'h', b'i', 5, 0]);
+        b.extend([0xde, 0xad, 0xbe, 0xef, 0xfe, 0xff, 0x01, 0x02]);
+        b
+    }
+
+    #[test]
+    fn test_apply_template() {
+        let tpl = parse_template(TPL).unwrap();
+        let applied = apply_template(&tpl, &data(), 0, Endian::Little).unwrap();
+        let fields: Vec<(&str, usize, usize, &str)> = applied
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.offset, f.length, f.value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                ("hdr", 0, 8, "Header"),
+                ("hdr.magic", 0, 4, "\"CHAP\""),
+                ("hdr.count", 4, 2, "2"),
+                ("hdr.flags", 6, 2, "0x0013 compressed=1 level=1 reserved=1"),
+                ("hdr.flags.compressed", 6, 2, "1"),
+                ("hdr.flags.level", 6, 2, "1"),
+                ("hdr.flags.reserved", 6, 2, "1"),
+                ("entries", 8, 6, "Entry[2]"),
+                ("entries[0]", 8, 4, "Entry"),
+                ("entries[0].kind", 8, 1, "TEXT (3)"),
+                ("entries[0].len", 9, 1, "2"),
+                ("entries[0].data", 10, 2, "6869"),
+                ("entries[1]", 12, 2, "Entry"),
+                ("entries[1].kind", 12, 1, "5 (unknown Kind)"),
+                ("entries[1].len", 13, 1, "0"),
+                ("entries[1].data", 14, 0, ""),
+                ("crc", 14, 4, "3735928559"),
+                ("delta", 18, 2, "-2"),
+                ("tail", 20, 2, "u16[1]"),
+                ("tail[0]", 20, 2, "258"),
+            ]
+        );
+        assert_eq!(applied.highlights.len(), 12);
+        assert_eq!(
+            applied.highlights[..2],
+            [
+                PluginAction::Highlight(0, 4, Color::Blue),
+                PluginAction::Highlight(4, 2, Color::Green)
+            ]
+        );
+
+        assert_eq!(
+            field_at(&applied.fields, 10).unwrap().name,
+            "entries[0].data"
+        );
+        assert_eq!(field_at(&applied.fields, 7).unwrap().name, "hdr.flags");
+        assert!(field_at(&applied.fields, 22).is_none());
+    }
+
+    #[test]
+    fn test_apply_template_base() {
+        let tpl = parse_template("endian big; u16 len; char name[len];").unwrap();
+        let mut b = vec![0u8; 3];
+        b.extend(b"\x00\x05hello");
+        let applied = apply_template(&tpl, &b, 3, Endian::Little).unwrap();
+        assert_eq!(applied.fields[1].offset, 5);
+        assert_eq!(applied.fields[1].value, "\"hello\"");
+    }
+
+    #[test]
+    fn test_template_errors() {
+        let err = |tpl: &str| match parse_template(tpl) {
+            Ok(tpl) => apply_template(&tpl, &data(), 0, Endian::Little)
+                .err()
+                .unwrap()
+                .to_string(),
+            Err(e) => e.to_string(),
+        };
+        assert!(err("u32 a;\nfoo b;").contains("line 2: unknown type foo"));
+        assert!(err("u32 a").contains("expected ';'"));
+        assert!(err("struct A { u8 a; }").contains("no top-level fields"));
+        assert!(err("bitfield B : u8 { a : 9; } B b;").contains("does not fit"));
+        assert!(err("u8 a;\nu8 b[100];").contains("line 2: b at 1 (+100) runs past end"));
+        assert!(err("u8 a[missing];").contains("missing is not an integer field"));
+        assert!(err("struct A { A a; } A a;").contains("nested deeper"));
+    }
+}
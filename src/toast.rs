@@ -0,0 +1,209 @@
+use crate::byteutil::varlena_payload;
+use crate::byteutil::VarlenaType;
+use crate::ChapResult;
+
+// va_tcinfo 低30位为原始长度 高2位为压缩方法
+const VARLENA_EXTSIZE_MASK: u32 = (1u32 << 30) - 1;
+const TOAST_PGLZ_COMPRESSION_ID: u32 = 0;
+const TOAST_LZ4_COMPRESSION_ID: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CompressMethod {
+    Pglz,
+    Lz4,
+}
+
+impl CompressMethod {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            CompressMethod::Pglz => "pglz",
+            CompressMethod::Lz4 => "lz4",
+        }
+    }
+}
+
+// 解压后的 varlena
+pub(crate) struct Detoasted {
+    pub(crate) method: CompressMethod,
+    pub(crate) compressed_len: usize, // 包括 header
+    pub(crate) data: Vec<u8>,
+}
+
+// 解压一个内联压缩的 varlena (varattrib_4b compressed)
+pub(crate) fn decompress_varlena(datum: &[u8]) -> ChapResult<Detoasted> {
+    let (typ, payload) = varlena_payload(datum).ok_or("not a valid varlena")?;
+    match typ {
+        VarlenaType::FourByteCompressed => {}
+        VarlenaType::ToastPointer => {
+            return Err("toast pointer, the value is stored in the toast relation".into())
+        }
+        _ => return Err("varlena is not compressed".into()),
+    }
+    if payload.len() < 4 {
+        return Err("compressed varlena < 8".into());
+    }
+    let tcinfo = u32::from_le_bytes(payload[..4].try_into().unwrap());
+    let rawsize = (tcinfo & VARLENA_EXTSIZE_MASK) as usize;
+    let (method, data) = match tcinfo >> 30 {
+        TOAST_PGLZ_COMPRESSION_ID => (
+            CompressMethod::Pglz,
+            pglz_decompress(&payload[4..], rawsize)?,
+        ),
+        TOAST_LZ4_COMPRESSION_ID => (CompressMethod::Lz4, lz4_decompress(&payload[4..], rawsize)?),
+        id => return Err(format!("unknown compression method id={}", id).into()),
+    };
+    Ok(Detoasted {
+        method,
+        compressed_len: payload.len() + 4,
+        data,
+    })
+}
+
+// 与 postgres pg_lzcompress.c 中 pglz_decompress 一致
+// 控制字节的每一位表示后面是字面量(0)还是回溯引用(1)
+pub(crate) fn pglz_decompress(src: &[u8], rawsize: usize) -> ChapResult<Vec<u8>> {
+    let mut dst: Vec<u8> = Vec::with_capacity(rawsize);
+    let mut sp = 0;
+    while sp < src.len() && dst.len() < rawsize {
+        let mut ctrl = src[sp];
+        sp += 1;
+        for _ in 0..8 {
+            if sp >= src.len() || dst.len() >= rawsize {
+                break;
+            }
+            if ctrl & 1 != 0 {
+                if sp + 1 >= src.len() {
+                    return Err(format!("pglz: truncated tag at {}", sp).into());
+                }
+                //低4位长度 高4位和下一个字节为偏移
+                let mut len = (src[sp] & 0x0f) as usize + 3;
+                let off = ((src[sp] & 0xf0) as usize) << 4 | src[sp + 1] as usize;
+                sp += 2;
+                if len == 18 {
+                    len += *src
+                        .get(sp)
+                        .ok_or_else(|| format!("pglz: truncated length at {}", sp))?
+                        as usize;
+                    sp += 1;
+                }
+                if off == 0 || off > dst.len() {
+                    return Err(format!("pglz: invalid offset {} at {}", off, dst.len()).into());
+                }
+                let len = len.min(rawsize - dst.len());
+                //引用可能和输出重叠 逐字节复制
+                let start = dst.len() - off;
+                for i in 0..len {
+                    dst.push(dst[start + i]);
+                }
+            } else {
+                dst.push(src[sp]);
+                sp += 1;
+            }
+            ctrl >>= 1;
+        }
+    }
+    if dst.len() != rawsize || sp != src.len() {
+        return Err(format!(
+            "pglz: decompressed {} of {} bytes, consumed {} of {}",
+            dst.len(),
+            rawsize,
+            sp,
+            src.len()
+        )
+        .into());
+    }
+    Ok(dst)
+}
+
+// lz4 block 格式解压
+// token 高4位为字面量长度 低4位为匹配长度-4 值为15时后面跟着扩展长度
+pub(crate) fn lz4_decompress(src: &[u8], rawsize: usize) -> ChapResult<Vec<u8>> {
+    let mut dst: Vec<u8> = Vec::with_capacity(rawsize);
+    let mut sp = 0;
+    let ext_len = |sp: &mut usize, mut len: usize| -> ChapResult<usize> {
+        loop {
+            let b = *src
+                .get(*sp)
+                .ok_or_else(|| format!("lz4: truncated length at {}", sp))?;
+            *sp += 1;
+            len += b as usize;
+            if b != 255 {
+                return Ok(len);
+            }
+        }
+    };
+    while sp < src.len() {
+        let token = src[sp];
+        sp += 1;
+        let mut lit_len = (token >> 4) as usize;
+        if lit_len == 15 {
+            lit_len = ext_len(&mut sp, lit_len)?;
+        }
+        let literals = src
+            .get(sp..sp + lit_len)
+            .ok_or_else(|| format!("lz4: truncated literals at {}", sp))?;
+        dst.extend_from_slice(literals);
+        sp += lit_len;
+        //最后一个序列只有字面量
+        if sp == src.len() {
+            break;
+        }
+        if sp + 1 >= src.len() {
+            return Err(format!("lz4: truncated offset at {}", sp).into());
+        }
+        let off = u16::from_le_bytes([src[sp], src[sp + 1]]) as usize;
+        sp += 2;
+        let mut match_len = (token & 0x0f) as usize;
+        if match_len == 15 {
+            match_len = ext_len(&mut sp, match_len)?;
+        }
+        match_len += 4;
+        if off == 0 || off > dst.len() {
+            return Err(format!("lz4: invalid offset {} at {}", off, dst.len()).into());
+        }
+        if dst.len() + match_len > rawsize {
+            return Err(format!("lz4: output exceeds {} bytes", rawsize).into());
+        }
+        let start = dst.len() - off;
+        for i in 0..match_len {
+            dst.push(dst[start + i]);
+        }
+    }
+    if dst.len() != rawsize {
+        return Err(format!("lz4: decompressed {} of {} bytes", dst.len(), rawsize).into());
+    }
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // postgres 15 写入的数据 repeat('abcdefgh', 400) || 'xyz'
+    const PGLZ_DATUM: &str = "ea000000830c0000006162636465666768ff0f08ff0f08ff0f08ff0f08ff0f08ff0f08ff0f08ff0f08ff0f0f08ff0f08ff0f08ff0f08ab78797a";
+    // repeat('chappie', 450) || 'end'
+    const LZ4_DATUM: &str =
+        "96000000510c00407f636861707069650700ffffffffffffffffffffffff3e506965656e64";
+
+    #[test]
+    fn test_decompress_varlena() {
+        let datum = hex::decode(PGLZ_DATUM).unwrap();
+        let d = decompress_varlena(&datum).unwrap();
+        assert_eq!(d.method, CompressMethod::Pglz);
+        assert_eq!(d.compressed_len, datum.len());
+        assert_eq!(d.data, ("abcdefgh".repeat(400) + "xyz").into_bytes());
+
+        let datum = hex::decode(LZ4_DATUM).unwrap();
+        let d = decompress_varlena(&datum).unwrap();
+        assert_eq!(d.method, CompressMethod::Lz4);
+        assert_eq!(d.data, ("chappie".repeat(450) + "end").into_bytes());
+
+        //损坏的数据返回错误
+        let mut datum = hex::decode(PGLZ_DATUM).unwrap();
+        datum[17] = 0x0f;
+        datum[18] = 0xff;
+        assert!(decompress_varlena(&datum).is_err());
+        assert!(decompress_varlena(&[0x28, 0, 0, 0, 1, 2, 3, 4, 5, 6]).is_err());
+        assert!(lz4_decompress(&[0x10, b'a', 0x00, 0x00], 5).is_err());
+    }
+}
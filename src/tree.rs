@@ -0,0 +1,215 @@
+use crate::plugin::PluginField;
+
+// 字段树的一个节点 下标与字段列表相同
+#[derive(Debug, Clone, PartialEq)]
+struct TreeNode {
+    parent: Option<usize>,
+    depth: usize,
+    has_children: bool,
+    expanded: bool,
+}
+
+// 由解析器返回的字段列表构建的树 父节点排在子节点之前
+#[derive(Debug, Default)]
+pub(crate) struct FieldTree {
+    nodes: Vec<TreeNode>,
+}
+
+// 名字以父字段名加 . 或 [ 开头 或者字节范围被父字段严格包含
+fn is_child(parent: &PluginField, f: &PluginField) -> bool {
+    if let Some(rest) = f.name.strip_prefix(parent.name.as_str()) {
+        if rest.starts_with('.') || rest.starts_with('[') {
+            return true;
+        }
+    }
+    parent.length > f.length
+        && f.offset >= parent.offset
+        && f.offset + f.length <= parent.offset + parent.length
+}
+
+impl FieldTree {
+    // 顶层节点默认展开
+    pub(crate) fn new(fields: &[PluginField]) -> FieldTree {
+        let mut nodes: Vec<TreeNode> = Vec::with_capacity(fields.len());
+        let mut stack: Vec<usize> = Vec::new();
+        for (i, f) in fields.iter().enumerate() {
+            while let Some(&top) = stack.last() {
+                if is_child(&fields[top], f) {
+                    break;
+                }
+                stack.pop();
+            }
+            let parent = stack.last().copied();
+            if let Some(p) = parent {
+                nodes[p].has_children = true;
+            }
+            nodes.push(TreeNode {
+                parent,
+                depth: stack.len(),
+                has_children: false,
+                expanded: stack.is_empty(),
+            });
+            stack.push(i);
+        }
+        FieldTree { nodes }
+    }
+
+    pub(crate) fn depth(&self, i: usize) -> usize {
+        self.nodes[i].depth
+    }
+
+    pub(crate) fn has_children(&self, i: usize) -> bool {
+        self.nodes[i].has_children
+    }
+
+    pub(crate) fn is_expanded(&self, i: usize) -> bool {
+        self.nodes[i].expanded
+    }
+
+    // 去掉父字段名前缀后的名字
+    pub(crate) fn label<'a>(&self, fields: &'a [PluginField], i: usize) -> &'a str {
+        let name = fields[i].name.as_str();
+        match self.nodes[i].parent {
+            Some(p) => match name.strip_prefix(fields[p].name.as_str()) {
+                Some(rest) if !rest.is_empty() => rest.strip_prefix('.').unwrap_or(rest),
+                _ => name,
+            },
+            None => name,
+        }
+    }
+
+    // 子树结束位置 (不含)
+    fn subtree_end(&self, i: usize) -> usize {
+        let depth = self.nodes[i].depth;
+        let mut j = i + 1;
+        while j < self.nodes.len() && self.nodes[j].depth > depth {
+            j += 1;
+        }
+        j
+    }
+
+    // 可见的节点 跳过折叠节点的子树
+    pub(crate) fn visible(&self) -> Vec<usize> {
+        let mut rows = Vec::new();
+        let mut i = 0;
+        while i < self.nodes.len() {
+            rows.push(i);
+            if self.nodes[i].has_children && !self.nodes[i].expanded {
+                i = self.subtree_end(i);
+            } else {
+                i += 1;
+            }
+        }
+        rows
+    }
+
+    // 上一个或下一个可见节点
+    pub(crate) fn prev(&self, i: usize) -> usize {
+        let rows = self.visible();
+        match rows.iter().position(|&r| r == i) {
+            Some(pos) => rows[pos.saturating_sub(1)],
+            None => i,
+        }
+    }
+
+    pub(crate) fn next(&self, i: usize) -> usize {
+        let rows = self.visible();
+        match rows.iter().position(|&r| r == i) {
+            Some(pos) => rows[(pos + 1).min(rows.len() - 1)],
+            None => i,
+        }
+    }
+
+    // 展开 已展开时移到第一个子节点
+    pub(crate) fn expand(&mut self, i: usize) -> usize {
+        let node = &mut self.nodes[i];
+        if !node.has_children {
+            return i;
+        }
+        if !node.expanded {
+            node.expanded = true;
+            return i;
+        }
+        i + 1
+    }
+
+    // 折叠 已折叠时移到父节点
+    pub(crate) fn collapse(&mut self, i: usize) -> usize {
+        let node = &mut self.nodes[i];
+        if node.has_children && node.expanded {
+            node.expanded = false;
+            return i;
+        }
+        node.parent.unwrap_or(i)
+    }
+
+    pub(crate) fn toggle(&mut self, i: usize) {
+        let node = &mut self.nodes[i];
+        node.expanded = node.has_children && !node.expanded;
+    }
+
+    // 展开所有祖先 使节点可见
+    pub(crate) fn reveal(&mut self, i: usize) {
+        let mut parent = self.nodes.get(i).and_then(|n| n.parent);
+        while let Some(p) = parent {
+            self.nodes[p].expanded = true;
+            parent = self.nodes[p].parent;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, offset: usize, length: usize) -> PluginField {
+        PluginField {
+            name: name.to_string(),
+            offset,
+            length,
+            value: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_field_tree() {
+        let fields = vec![
+            field("hdr", 0, 8),
+            field("hdr.magic", 0, 4),
+            field("hdr.flags", 4, 4),
+            field("hdr.flags.a", 4, 4),
+            field("entries", 8, 4),
+            field("entries[0]", 8, 2),
+            field("entries[1]", 10, 2),
+            field("entries[1].data", 12, 0),
+            field("tup[1]", 12, 10),
+            field("t_xmin", 12, 4), // 按字节范围归入 tup[1]
+            field("crc", 22, 4),
+        ];
+        let mut tree = FieldTree::new(&fields);
+        let depths: Vec<usize> = (0..fields.len()).map(|i| tree.depth(i)).collect();
+        assert_eq!(depths, [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 0]);
+        assert_eq!(tree.label(&fields, 3), "a");
+        assert_eq!(tree.label(&fields, 5), "[0]");
+        assert_eq!(tree.label(&fields, 9), "t_xmin");
+
+        // 顶层展开 其他折叠
+        assert_eq!(tree.visible(), [0, 1, 2, 4, 5, 6, 8, 9, 10]);
+        assert_eq!(tree.next(2), 4);
+        assert_eq!(tree.prev(4), 2);
+        assert_eq!(tree.expand(2), 2);
+        assert_eq!(tree.expand(2), 3);
+        assert_eq!(tree.collapse(3), 2);
+        assert_eq!(tree.collapse(2), 2);
+        assert_eq!(tree.collapse(0), 0);
+        assert_eq!(tree.visible(), [0, 4, 5, 6, 8, 9, 10]);
+        assert_eq!(tree.next(0), 4);
+
+        // 只展开祖先
+        tree.reveal(7);
+        assert_eq!(tree.visible(), [0, 4, 5, 6, 7, 8, 9, 10]);
+        tree.toggle(4);
+        assert_eq!(tree.visible(), [0, 4, 8, 9, 10]);
+        assert_eq!(tree.next(10), 10);
+    }
+}
@@ -1,6 +1,12 @@
+use crate::bookmark::Bookmark;
+use crate::bookmark::Session;
+use crate::bookmark::SESSION_DIR;
 use crate::byteutil::ByteView;
 use crate::byteutil::Endian;
 use crate::cli::UIType;
+use crate::diff::Diff;
+use crate::diff::DiffKind;
+use crate::diff::DiffView;
 use crate::editor::CacheStr;
 use crate::editor::EditLineMeta;
 use crate::editor::EditTextWarp;
@@ -12,6 +18,7 @@ use crate::editor::TextDisplay;
 use crate::editor::TextOper;
 use crate::editor::TextWarp;
 use crate::editor::TextWarpType;
+use crate::editor::HEX_WITH;
 use crate::error::ChapResult;
 use crate::fuzzy::Match;
 use crate::handle::Handle;
@@ -19,7 +26,12 @@ use crate::handle::HandleEdit;
 use crate::handle::HandleHex;
 use crate::handle::HandleImpl;
 use crate::lua::LuaPlugin;
+use crate::plugin::PluginField;
+use crate::search::FindAll;
+use crate::search::PendingReplace;
+use crate::template::field_at;
 use crate::textwarp::LineMeta;
+use crate::tree::FieldTree;
 use const_hex::Buffer;
 use crossterm::event::KeyEvent;
 use crossterm::event::KeyModifiers;
@@ -47,10 +59,12 @@ use ratatui::widgets::Block;
 use ratatui::widgets::Paragraph;
 use ratatui::Terminal;
 use std::io;
-use std::mem;
 use std::path::Path;
+use std::path::PathBuf;
 use std::process::exit;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use unicode_width::UnicodeWidthChar;
 //use vectorbase::collection::Collection;
 
 pub(crate) enum ChapMod {
@@ -60,6 +74,41 @@ pub(crate) enum ChapMod {
     Vector, //向量分析模式
 }
 
+// 结构树窗口显示的内容
+#[derive(Clone, Copy, PartialEq)]
+enum TreePane {
+    Structure, // 解析器或模板的字段
+    Bookmarks, // 书签列表
+    Results,   // 搜索结果
+}
+
+//16进制模式输入位置
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum HexInput {
+    Cmd,    //命令行
+    Hex,    //16进制列
+    Ascii,  //ascii列
+    Fields, //插件返回的字段列表
+}
+
+impl HexInput {
+    //Tab 在16进制窗口内切换 ascii列之后离开窗口
+    fn next(self) -> Option<HexInput> {
+        match self {
+            HexInput::Cmd => Some(HexInput::Hex),
+            HexInput::Hex => Some(HexInput::Ascii),
+            HexInput::Ascii | HexInput::Fields => None,
+        }
+    }
+}
+
+//16进制编辑方式
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum HexEditMode {
+    Overwrite, //覆盖
+    Insert,    //插入
+}
+
 //u8类型
 enum U8Category {
     Null,
@@ -109,6 +158,7 @@ pub(crate) struct TuiElement {
     pub(crate) cmd_inp: CmdInput,
     pub(crate) assist_tv1: TextView,
     pub(crate) assist_tv2: TextView,
+    pub(crate) tree: TextView, // 结构树
 }
 
 pub(crate) struct ChapTui {
@@ -127,6 +177,21 @@ pub(crate) struct ChapTui {
     pub(crate) is_last_line: bool,       // 是否是最后一行
     pub(crate) endian: Endian,           // 字节序
     pub(crate) assist_tv2_data: String,
+    pub(crate) assist_hex: Vec<u8>, // 辅助窗口的16进制视图 比如解压后的 varlena
+    pub(crate) hex_input: HexInput, // 16进制输入位置
+    pub(crate) hex_edit_mode: HexEditMode, // 覆盖或插入
+    pub(crate) hex_nibble: bool,    // 已输入高4位 等待低4位
+    pub(crate) assist_fields: Vec<PluginField>, // 插件返回的字段
+    pub(crate) field_sel: usize,    // 选中的字段
+    pub(crate) highlights: Vec<Highlight>, // 插件添加的标注
+    pub(crate) file_path: PathBuf,  // 打开的文件
+    pub(crate) template_fields: Vec<PluginField>, // apply 展开的模板字段
+    pub(crate) field_tree: FieldTree, // 字段组成的树
+    pub(crate) session: Session,    // 书签和注释
+    pub(crate) find_all: FindAll,   // 后台搜索的全部匹配
+    pub(crate) replace: Option<PendingReplace>, // 等待确认的替换
+    tree_pane: TreePane,            // 结构树窗口显示的内容
+    focus: Focus,                   // 有焦点的窗口
 }
 
 // 文本编辑器大文件浏览 窗口
@@ -146,26 +211,35 @@ pub(crate) struct AiChatWindow {}
 
 pub(crate) struct TerminalWindow {}
 
+#[derive(Clone, Copy, PartialEq)]
 enum FocusType {
     TxtFuzzy,
     Chat,
+    Tree, //结构树
 }
 
 //焦点
 struct Focus {
     current_focus: usize,
+    cycle: &'static [FocusType], // 可以获得焦点的窗口 按切换顺序
 }
 
 impl Focus {
-    // 创建一个新的 Focus 实例
-    fn new() -> Self {
-        Focus { current_focus: 0 }
+    // 创建一个新的 Focus 实例 16进制模式在16进制窗口和结构树之间切换
+    fn new(chap_mod: &ChapMod) -> Self {
+        let cycle: &'static [FocusType] = match chap_mod {
+            ChapMod::Hex => &[FocusType::TxtFuzzy, FocusType::Tree],
+            _ => &[FocusType::TxtFuzzy, FocusType::Chat],
+        };
+        Focus {
+            current_focus: 0,
+            cycle,
+        }
     }
 
     // 切换焦点
     fn next(&mut self) {
-        self.current_focus = (self.current_focus + 1) % mem::variant_count::<FocusType>();
-        // 0到3循环
+        self.current_focus = (self.current_focus + 1) % self.cycle.len();
     }
 
     fn get_colors(&self) -> (Color, Color, Color, Color) {
@@ -174,18 +248,26 @@ impl Focus {
         match self.current() {
             FocusType::TxtFuzzy => (highlight, highlight, base, base),
             FocusType::Chat => (base, base, highlight, highlight),
+            FocusType::Tree => (base, base, base, base),
+        }
+    }
+
+    fn set(&mut self, focus: FocusType) {
+        self.current_focus = self.cycle.iter().position(|f| *f == focus).unwrap_or(0);
+    }
+
+    // 有焦点的窗口标题高亮
+    fn color(&self, focus: FocusType) -> Color {
+        if self.current() == focus {
+            Color::Yellow
+        } else {
+            Color::White
         }
     }
 
     // 获取当前焦点
     fn current(&self) -> FocusType {
-        match self.current_focus {
-            0 => FocusType::TxtFuzzy,
-            1 => FocusType::Chat,
-            _ => {
-                todo!()
-            }
-        }
+        self.cycle[self.current_focus]
     }
 }
 
@@ -288,6 +370,52 @@ impl TextSelect {
     }
 }
 
+// 16进制视图中的彩色标注 [start, end)
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Highlight {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) color: Color,
+}
+
+// 书签范围的背景色
+const BOOKMARK_COLOR: Color = Color::DarkGray;
+// 搜索匹配的背景色
+const HIT_COLOR: Color = Color::Yellow;
+// 两个文件比较时 内容不同和只在一边的字节的背景色
+const DIFF_CHANGED_COLOR: Color = Color::Red;
+const DIFF_ONE_SIDE_COLOR: Color = Color::Green;
+// 差异列表的高度 包括标题
+const DIFF_LIST_HEIGHT: u16 = 8;
+// 命令行最多输入的字节数 正则 掩码和 replace 命令都可能很长
+pub(crate) const CMD_INPUT_MAX: usize = 1024;
+
+// pos 处标注的背景色 后加入的标注优先
+fn highlight_at(highlights: &[Highlight], pos: usize) -> Option<Color> {
+    highlights
+        .iter()
+        .rev()
+        .find(|h| pos >= h.start && pos < h.end)
+        .map(|h| h.color)
+}
+
+fn highlight_style(highlights: &[Highlight], pos: usize) -> Style {
+    match highlight_at(highlights, pos) {
+        Some(color) => Style::default().bg(color),
+        None => Style::default(),
+    }
+}
+
+// 字节后面的空格 与下一个字节同色时才标注
+fn highlight_space_style(highlights: &[Highlight], pos: usize) -> Style {
+    match highlight_at(highlights, pos) {
+        Some(color) if highlight_at(highlights, pos + 1) == Some(color) => {
+            Style::default().bg(color)
+        }
+        _ => Style::default(),
+    }
+}
+
 #[derive(Default)]
 // 聊天框的类型
 enum ChatType {
@@ -311,6 +439,7 @@ impl ChapTui {
         let terminal = init();
         let size = terminal.size()?;
         let elem = Self::get_react(&ui_type, &chap_mod, &size)?;
+        let focus = Focus::new(&chap_mod);
         Ok(ChapTui {
             chap_mod: chap_mod,
             size: size,
@@ -327,6 +456,21 @@ impl ChapTui {
             is_last_line: false,
             endian: Endian::Little, // 默认字节序为小端
             assist_tv2_data: String::new(),
+            assist_hex: Vec::new(),
+            hex_input: HexInput::Cmd,
+            assist_fields: Vec::new(),
+            field_sel: 0,
+            highlights: Vec::new(),
+            file_path: PathBuf::new(),
+            template_fields: Vec::new(),
+            field_tree: FieldTree::default(),
+            session: Session::default(),
+            find_all: FindAll::default(),
+            replace: None,
+            tree_pane: TreePane::Structure,
+            focus,
+            hex_edit_mode: HexEditMode::Overwrite,
+            hex_nibble: false,
         })
     }
 
@@ -372,7 +516,7 @@ impl ChapTui {
             .constraints([Constraint::Percentage(p), Constraint::Percentage(100 - p)].as_ref())
             .split(rect);
 
-        let (nav_chk, tv_chk, inp_title_chk, seach_chk, assist_tv_chk1, assist_tv_chk2) = {
+        let (nav_chk, tv_chk, inp_title_chk, seach_chk, assist_tv_chk1, tree_chk, assist_tv_chk2) = {
             //文本框和输入框
             let left_chunks = Layout::default()
                 .direction(Direction::Vertical)
@@ -382,7 +526,14 @@ impl ChapTui {
             //LLM聊天和输入框
             let right_chunks = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .constraints(
+                    [
+                        Constraint::Percentage(45),
+                        Constraint::Percentage(35),
+                        Constraint::Percentage(20),
+                    ]
+                    .as_ref(),
+                )
                 .split(chunks[1]); // chunks[1] 是左侧区域
 
             //导航栏和文本框
@@ -402,6 +553,7 @@ impl ChapTui {
                 search_chunks[1],
                 right_chunks[0],
                 right_chunks[1],
+                right_chunks[2],
             )
         };
 
@@ -435,6 +587,14 @@ impl ChapTui {
             rect: assist_tv_chk2,
         };
 
+        //标题占一行
+        let tree = TextView {
+            height: (tree_chk.height as usize).saturating_sub(1),
+            width: assist_tv_width,
+            scroll: 1,
+            rect: tree_chk,
+        };
+
         Ok(TuiElement {
             navi: navi,
             tv: tv,
@@ -442,6 +602,7 @@ impl ChapTui {
             cmd_inp: cmd_inp,
             assist_tv1: assist_tv1,
             assist_tv2: assist_tv2,
+            tree: tree,
         })
     }
 
@@ -456,56 +617,211 @@ impl ChapTui {
         hex_sel: TextSelect,
         td: &'a TextDisplay,
     ) -> ChapResult<&'a RingVec<EditLineMeta>> {
-        let line_meta = {
-            let (content, meta) = td.get_current_page()?;
-            self.terminal.draw(|f| {
-                let (navi, visible_content) = get_hex_content(
-                    content,
-                    &meta,
-                    self.elem.navi.get_cur_line(),
-                    &hex_sel,
-                    self.elem.tv.get_height(),
-                    cursor_y,
-                    cursor_x,
-                );
-                let text_para = Paragraph::new(visible_content)
-                    .block(Block::default())
+        let line_meta =
+            {
+                let (content, meta) = td.get_current_page()?;
+                let has_fields = self.has_fields();
+                // 书签标注在搜索匹配下面 插件标注在最上面
+                let page_start = meta.get(0).map_or(0, |m| m.get_line_file_start());
+                let page_end = meta
+                    .last()
+                    .map_or(0, |m| m.get_line_file_start() + HEX_WITH);
+                let highlights: Vec<Highlight> =
+                    self.session
+                        .marks()
+                        .iter()
+                        .map(|m| Highlight {
+                            start: m.start,
+                            end: m.end,
+                            color: BOOKMARK_COLOR,
+                        })
+                        .chain(self.find_all.visible(page_start, page_end).iter().map(|h| {
+                            Highlight {
+                                start: h.offset,
+                                end: h.offset + h.len,
+                                color: HIT_COLOR,
+                            }
+                        }))
+                        .chain(self.highlights.iter().cloned())
+                        .collect();
+                self.terminal.draw(|f| {
+                    let (navi, visible_content) = get_hex_content(
+                        content,
+                        &meta,
+                        self.elem.navi.get_cur_line(),
+                        &hex_sel,
+                        &highlights,
+                        self.elem.tv.get_height(),
+                        cursor_y,
+                        cursor_x,
+                    );
+                    let text_para = Paragraph::new(visible_content)
+                        .block(Block::default())
+                        .style(Style::default().fg(Color::White));
+                    f.render_widget(text_para, self.elem.tv.get_rect());
+
+                    let nav_paragraph = Paragraph::new(navi);
+                    f.render_widget(nav_paragraph, self.elem.navi.get_rect());
+
+                    let sel_content = td.get_text_from_sel(&hex_sel);
+                    let assist = get_data_inspector_content(
+                        hex_sel.get_start(),
+                        sel_content,
+                        self.endian.clone(),
+                        field_at(&self.template_fields, hex_sel.get_start()),
+                        self.session.at(hex_sel.get_start()),
+                    );
+                    let assist_para1 = Paragraph::new(assist)
+                        .block(Block::default())
+                        .style(Style::default().fg(Color::White));
+                    f.render_widget(assist_para1, self.elem.assist_tv1.get_rect());
+
+                    let title = match self.tree_pane {
+                        TreePane::Results => self.find_all.title(),
+                        TreePane::Structure if has_fields => {
+                            format!("Structure ({} fields)", self.assist_fields.len())
+                        }
+                        TreePane::Bookmarks => format!("Bookmarks ({})", self.assist_fields.len()),
+                        TreePane::Structure => "Structure".to_string(),
+                    };
+                    let tree_para = Paragraph::new(get_tree_content(
+                        &self.assist_fields,
+                        &self.field_tree,
+                        self.field_sel,
+                        self.elem.tree.get_height(),
+                    ))
+                    .block(
+                        Block::default().title(Span::styled(
+                            title,
+                            Style::default()
+                                .fg(self.focus.color(FocusType::Tree))
+                                .add_modifier(Modifier::BOLD),
+                        )),
+                    )
                     .style(Style::default().fg(Color::White));
-                f.render_widget(text_para, self.elem.tv.get_rect());
+                    f.render_widget(tree_para, self.elem.tree.get_rect());
 
-                let nav_paragraph = Paragraph::new(navi);
-                f.render_widget(nav_paragraph, self.elem.navi.get_rect());
+                    let assist_rect = self.elem.assist_tv2.get_rect();
+                    let assist_txt = if self.assist_hex.is_empty() {
+                        Text::raw(&self.assist_tv2_data)
+                    } else {
+                        get_assist_hex_content(&self.assist_hex, assist_rect.height as usize)
+                    };
+                    let assist_para2 = Paragraph::new(assist_txt)
+                        .block(Block::default())
+                        .style(Style::default().fg(Color::White));
+                    f.render_widget(assist_para2, assist_rect);
+
+                    let input_title_box = Paragraph::new(Text::raw(" >: "))
+                        .block(Block::default())
+                        .style(Style::default().fg(Color::White)); // 设置输入框样式
+                    f.render_widget(input_title_box, self.elem.cmd_title);
+
+                    let inp = match (self.hex_input, self.hex_edit_mode) {
+                        (HexInput::Cmd, _) => self.elem.cmd_inp.get_visible(),
+                        (HexInput::Hex, HexEditMode::Overwrite) => "-- HEX OVERWRITE --",
+                        (HexInput::Hex, HexEditMode::Insert) => "-- HEX INSERT --",
+                        (HexInput::Ascii, HexEditMode::Overwrite) => "-- ASCII OVERWRITE --",
+                        (HexInput::Ascii, HexEditMode::Insert) => "-- ASCII INSERT --",
+                        (HexInput::Fields, _) => "-- FIELDS --",
+                    };
+                    let input_box = Paragraph::new(Text::raw(inp))
+                        .block(Block::default())
+                        .style(Style::default().fg(Color::White));
+                    f.render_widget(input_box, self.elem.cmd_inp.get_rect());
+                })?;
+
+                meta
+            };
+        Ok(line_meta)
+    }
 
-                let sel_content = td.get_text_from_sel(&hex_sel);
-                let assist = get_data_inspector_content(
-                    hex_sel.get_start(),
-                    sel_content,
-                    self.endian.clone(),
-                );
-                let assist_para1 = Paragraph::new(assist)
-                    .block(Block::default())
-                    .style(Style::default().fg(Color::White));
-                f.render_widget(assist_para1, self.elem.assist_tv1.get_rect());
+    // 结构树中有字段
+    pub(crate) fn has_fields(&self) -> bool {
+        !self.assist_fields.is_empty()
+    }
 
-                let assist_para2 = Paragraph::new(Text::raw(&self.assist_tv2_data))
-                    .block(Block::default())
-                    .style(Style::default().fg(Color::White));
-                f.render_widget(assist_para2, self.elem.assist_tv2.get_rect());
+    // 设置解析器返回的字段 重新构建结构树
+    pub(crate) fn set_fields(&mut self, fields: Vec<PluginField>) {
+        self.field_tree = FieldTree::new(&fields);
+        self.assist_fields = fields;
+        self.field_sel = 0;
+        self.tree_pane = TreePane::Structure;
+    }
 
-                let input_title_box = Paragraph::new(Text::raw(" >: "))
-                    .block(Block::default())
-                    .style(Style::default().fg(Color::White)); // 设置输入框样式
-                f.render_widget(input_title_box, self.elem.cmd_title);
+    // 书签列表也显示在结构树窗口
+    pub(crate) fn set_bookmark_fields(&mut self) {
+        self.set_fields(self.session.fields());
+        self.tree_pane = TreePane::Bookmarks;
+    }
 
-                let input_box = Paragraph::new(Text::raw(self.elem.cmd_inp.get_inp()))
-                    .block(Block::default())
-                    .style(Style::default().fg(Color::White));
-                f.render_widget(input_box, self.elem.cmd_inp.get_rect());
-            })?;
+    // 搜索结果显示在结构树窗口 后台送回新的匹配时更新
+    pub(crate) fn set_result_fields(&mut self) {
+        self.set_fields(self.find_all.fields());
+        self.tree_pane = TreePane::Results;
+    }
 
-            meta
+    pub(crate) fn shows_results(&self) -> bool {
+        self.tree_pane == TreePane::Results
+    }
+
+    // 取出后台搜索的结果 重建结果列表 近似匹配的排序可能改变
+    // 搜索后内容被修改时 用新的快照重新搜索
+    fn poll_find_all(&mut self, td: &TextDisplay) {
+        let mut changed = false;
+        if self.find_all.is_stale(td.version()) {
+            if let (Some(pattern), Ok(snap)) = (self.find_all.pattern().cloned(), td.snapshot()) {
+                self.find_all.start(snap, pattern);
+                changed = true;
+            }
+        }
+        if (self.find_all.poll() > 0 || changed) && self.shows_results() {
+            self.assist_fields = self.find_all.fields();
+            self.field_tree = FieldTree::new(&self.assist_fields);
+            self.field_sel = self
+                .field_sel
+                .min(self.assist_fields.len().saturating_sub(1));
+        }
+    }
+
+    // 插入删除之后 书签跟着移动 显示书签列表时一起更新
+    pub(crate) fn poll_splices(&mut self, td: &TextDisplay) {
+        let splices = td.take_splices();
+        if splices.is_empty() {
+            return;
+        }
+        for splice in splices.iter() {
+            self.session.shift(splice);
+        }
+        if self.tree_pane == TreePane::Bookmarks {
+            self.assist_fields = self.session.fields();
+            self.field_tree = FieldTree::new(&self.assist_fields);
+        }
+    }
+
+    // Tab 切换 离开16进制窗口时焦点切到下一个窗口 没有字段时跳过结构树
+    pub(crate) fn next_hex_input(&mut self) {
+        if let Some(input) = self.hex_input.next() {
+            self.hex_input = input;
+            return;
+        }
+        self.focus.next();
+        if self.focus.current() == FocusType::Tree && !self.has_fields() {
+            self.focus.next();
+        }
+        self.hex_input = match self.focus.current() {
+            FocusType::Tree => HexInput::Fields,
+            _ => HexInput::Cmd,
         };
-        Ok(line_meta)
+    }
+
+    // 进入字段列表时焦点切换到结构树
+    pub(crate) fn set_hex_input(&mut self, input: HexInput) {
+        self.hex_input = input;
+        self.focus.set(match input {
+            HexInput::Fields => FocusType::Tree,
+            _ => FocusType::TxtFuzzy,
+        });
     }
 
     pub(crate) fn handle_ctrl_c(&mut self) -> ChapResult<()> {
@@ -518,33 +834,6 @@ impl ChapTui {
         exit(0);
     }
 
-    pub(crate) fn handle_ctrl_s<P: AsRef<Path>>(
-        &mut self,
-        p: P,
-        td: &mut TextDisplay,
-    ) -> ChapResult<()> {
-        match self.chap_mod {
-            ChapMod::Edit => {
-                self.elem.cmd_inp.clear();
-                //保存
-                if let Ok(_) = td.save(&p) {
-                    self.elem.cmd_inp.push_str("saved");
-                } else {
-                    self.elem.cmd_inp.push_str("save fail");
-                }
-            }
-            ChapMod::Text => {
-                todo!()
-            }
-            ChapMod::Hex => {
-                todo!()
-                // is_last = false;
-            }
-            _ => {}
-        };
-        Ok(())
-    }
-
     pub(crate) fn handle_char(
         &mut self,
         c: char,
@@ -969,12 +1258,15 @@ impl ChapTui {
     pub(crate) async fn render<P1: AsRef<Path>, P2: AsRef<Path>>(
         &mut self,
         p: P1,
-        plugin: P2,
+        plugin: &[P2],
     ) -> ChapResult<()> {
+        self.file_path = p.as_ref().to_path_buf();
+        let home = dirs::home_dir().ok_or("failed to get home directory")?;
+        self.session = Session::open(&home.join(SESSION_DIR), &self.file_path)?;
         let hand = match self.chap_mod {
             ChapMod::Edit => HandleImpl::Edit(HandleEdit::new()),
             ChapMod::Text => todo!(),
-            ChapMod::Hex => HandleImpl::Hex(HandleHex::new(LuaPlugin::new(plugin))),
+            ChapMod::Hex => HandleImpl::Hex(HandleHex::new(LuaPlugin::new(plugin)?)),
             _ => {
                 todo!()
             }
@@ -1023,6 +1315,8 @@ impl ChapTui {
                 if size != self.size {
                     break 'tui;
                 }
+                self.poll_find_all(&td);
+                self.poll_splices(&td);
                 let line_meta = match self.chap_mod {
                     ChapMod::Edit => {
                         self.render_edit(self.cursor_x, self.cursor_y, self.offset, &td)?
@@ -1041,10 +1335,22 @@ impl ChapTui {
                     self.start_line_num = start_line_meta.get_line_num();
                 }
                 'key: loop {
+                    // 后台搜索进行中时定时重绘 显示进度和新的匹配
+                    if self.find_all.is_running() && !event::poll(Duration::from_millis(100))? {
+                        break 'key;
+                    }
                     if let event::Event::Key(KeyEvent {
                         code, modifiers, ..
                     }) = event::read()?
                     {
+                        // 只有键入的字符可以合并为一步撤销 其他按键会移动光标
+                        if !matches!(
+                            (code, modifiers),
+                            (KeyCode::Char(_), KeyModifiers::NONE | KeyModifiers::SHIFT)
+                                | (KeyCode::Backspace | KeyCode::Delete, _)
+                        ) {
+                            td.end_typing();
+                        }
                         match (code, modifiers) {
                             (KeyCode::Esc, _) => {
                                 hand.handle_esc(self)?;
@@ -1094,6 +1400,16 @@ impl ChapTui {
                                     self.assist_tv2_data = e.to_string(); // 记录错误信息
                                 }
                             }
+                            (KeyCode::Char('z'), KeyModifiers::CONTROL) => {
+                                if let Err(e) = hand.handle_ctrl_z(self, &td) {
+                                    self.assist_tv2_data = e.to_string(); // 记录错误信息
+                                }
+                            }
+                            (KeyCode::Char('y'), KeyModifiers::CONTROL) => {
+                                if let Err(e) = hand.handle_ctrl_y(self, &td) {
+                                    self.assist_tv2_data = e.to_string(); // 记录错误信息
+                                }
+                            }
                             (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
                                 if let Err(e) = hand.handle_ctrl_s(self, &p, &mut td) {
                                     self.assist_tv2_data = e.to_string(); // 记录错误信息
@@ -1114,6 +1430,21 @@ impl ChapTui {
                                     self.assist_tv2_data = e.to_string(); // 记录错误信息
                                 }
                             }
+                            (KeyCode::Tab, _) => {
+                                if let Err(e) = hand.handle_tab(self) {
+                                    self.assist_tv2_data = e.to_string(); // 记录错误信息
+                                }
+                            }
+                            (KeyCode::Insert, _) => {
+                                if let Err(e) = hand.handle_insert(self) {
+                                    self.assist_tv2_data = e.to_string(); // 记录错误信息
+                                }
+                            }
+                            (KeyCode::Delete, _) => {
+                                if let Err(e) = hand.handle_delete(self, line_meta, &td) {
+                                    self.assist_tv2_data = e.to_string(); // 记录错误信息
+                                }
+                            }
                             _ => {}
                         }
                     }
@@ -1123,6 +1454,198 @@ impl ChapTui {
         }
     }
 
+    // 并排比较两个文件 两个16进制视图同步滚动 下面列出差异
+    pub(crate) async fn render_diff<P: AsRef<Path>>(
+        &mut self,
+        left: P,
+        right: P,
+        aligned: bool,
+    ) -> ChapResult<()> {
+        let paths = [left.as_ref(), right.as_ref()];
+        let mut view = DiffView::open(paths[0], paths[1], aligned)?;
+        let mut row = 0; // 光标在窗口中的行
+        let mut msg = view.diff().summary();
+        loop {
+            let size = self.terminal.size()?;
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints(
+                    [
+                        Constraint::Min(6),
+                        Constraint::Length(DIFF_LIST_HEIGHT),
+                        Constraint::Length(1),
+                    ]
+                    .as_ref(),
+                )
+                .split(Rect::new(0, 0, size.width, size.height));
+            let panes = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+                .split(chunks[0]);
+            // 每个窗口第一行是文件名 下面是表头 空行和16进制内容
+            let view_height = (chunks[0].height as usize).saturating_sub(1);
+            let height = view_height.saturating_sub(2).max(1);
+            let mut tds = Vec::with_capacity(2);
+            for p in paths {
+                tds.push(TextDisplay::Hex(TextWarp::new(
+                    HexText::from_file_path(p, height)?,
+                    height,
+                    panes[0].width as usize,
+                    TextWarpType::NoWrap,
+                )));
+            }
+            'tui: loop {
+                if self.terminal.size()? != size {
+                    break 'tui;
+                }
+                let mut pages = Vec::with_capacity(2);
+                for (td, left) in tds.iter().zip([true, false]) {
+                    let cursor = view.cursor(left);
+                    let line = cursor / HEX_WITH + 1;
+                    let (content, meta) = td.get_one_page(line.saturating_sub(row).max(1))?;
+                    let start = meta.get(0).map_or(0, |m| m.get_line_file_start());
+                    let end = meta
+                        .last()
+                        .map_or(0, |m| m.get_line_file_start() + HEX_WITH);
+                    let highlights: Vec<Highlight> = view
+                        .marks(start, end, left)
+                        .into_iter()
+                        .map(|(start, end, kind)| Highlight {
+                            start,
+                            end,
+                            color: match kind {
+                                DiffKind::Changed => DIFF_CHANGED_COLOR,
+                                _ => DIFF_ONE_SIDE_COLOR,
+                            },
+                        })
+                        .collect();
+                    let cursor_y = (cursor / HEX_WITH).saturating_sub(start / HEX_WITH);
+                    pages.push((content, meta, highlights, cursor, cursor_y));
+                }
+                self.terminal.draw(|f| {
+                    for (i, (content, meta, highlights, cursor, cursor_y)) in
+                        pages.iter().enumerate()
+                    {
+                        let left = i == 0;
+                        let pane = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Length(1), Constraint::Min(1)].as_ref())
+                            .split(panes[i]);
+                        let body = Layout::default()
+                            .direction(Direction::Horizontal)
+                            .constraints([Constraint::Length(8), Constraint::Min(1)].as_ref())
+                            .split(pane[1]);
+                        let name_style = if view.is_active(left) {
+                            Style::default()
+                                .fg(Color::Yellow)
+                                .add_modifier(Modifier::BOLD)
+                        } else {
+                            Style::default().fg(Color::White)
+                        };
+                        let name = format!("{} ({} bytes)", paths[i].display(), view.size(left));
+                        f.render_widget(Paragraph::new(Span::styled(name, name_style)), pane[0]);
+                        let (navi, text) = get_hex_content(
+                            content,
+                            meta,
+                            0,
+                            &TextSelect::from_select(*cursor, *cursor),
+                            highlights,
+                            view_height,
+                            *cursor_y,
+                            cursor % HEX_WITH,
+                        );
+                        f.render_widget(Paragraph::new(navi), body[0]);
+                        f.render_widget(
+                            Paragraph::new(text).style(Style::default().fg(Color::White)),
+                            body[1],
+                        );
+                    }
+                    f.render_widget(
+                        Paragraph::new(get_diff_list(
+                            view.diff(),
+                            view.current(),
+                            DIFF_LIST_HEIGHT as usize - 1,
+                        )),
+                        chunks[1],
+                    );
+                    f.render_widget(
+                        Paragraph::new(Text::raw(format!(" >: {}", msg)))
+                            .style(Style::default().fg(Color::White)),
+                        chunks[2],
+                    );
+                })?;
+                if let event::Event::Key(KeyEvent {
+                    code, modifiers, ..
+                }) = event::read()?
+                {
+                    let line = HEX_WITH as isize;
+                    let page = (height * HEX_WITH) as isize;
+                    let before = view.active_cursor() / HEX_WITH;
+                    msg.clear();
+                    // 跳转后光标停在靠上的位置 否则光标移出窗口时滚动
+                    let jump = match (code, modifiers) {
+                        (KeyCode::Char('c'), KeyModifiers::CONTROL)
+                        | (KeyCode::Char('q'), _)
+                        | (KeyCode::Esc, _) => return Ok(()),
+                        (KeyCode::Up, _) | (KeyCode::Char('k'), _) => {
+                            view.move_by(-line);
+                            false
+                        }
+                        (KeyCode::Down, _) | (KeyCode::Char('j'), _) => {
+                            view.move_by(line);
+                            false
+                        }
+                        (KeyCode::Left, _) | (KeyCode::Char('h'), _) => {
+                            view.move_by(-1);
+                            false
+                        }
+                        (KeyCode::Right, _) | (KeyCode::Char('l'), _) => {
+                            view.move_by(1);
+                            false
+                        }
+                        (KeyCode::PageUp, _) => {
+                            view.move_by(-page);
+                            false
+                        }
+                        (KeyCode::PageDown, _) | (KeyCode::Char(' '), _) => {
+                            view.move_by(page);
+                            false
+                        }
+                        (KeyCode::Char('g'), _) => {
+                            view.move_to(0);
+                            true
+                        }
+                        (KeyCode::Char('G'), _) => {
+                            view.move_to(usize::MAX);
+                            true
+                        }
+                        (KeyCode::Tab, _) => {
+                            view.switch_side();
+                            false
+                        }
+                        (KeyCode::Char('a'), _) => {
+                            view.toggle_aligned();
+                            msg = view.diff().summary();
+                            true
+                        }
+                        (KeyCode::Char(c @ ('n' | 'N')), _) => {
+                            msg = view.goto_range(c == 'n');
+                            true
+                        }
+                        _ => false,
+                    };
+                    let after = view.active_cursor() / HEX_WITH;
+                    row = if jump {
+                        after.min(height / 4)
+                    } else {
+                        (row as isize + after as isize - before as isize)
+                            .clamp(0, height as isize - 1) as usize
+                    };
+                }
+            }
+        }
+    }
+
     pub(crate) fn render_edit<'a>(
         &mut self,
         cursor_x: usize,
@@ -1736,7 +2259,7 @@ impl Navigation {
         self.cur_line = self.max_line
     }
 
-    fn set_cur_line(&mut self, cur_line: usize) {
+    pub(crate) fn set_cur_line(&mut self, cur_line: usize) {
         self.cur_line = cur_line
     }
 }
@@ -1841,6 +2364,21 @@ impl CmdInput {
         &self.input
     }
 
+    // 超出输入框宽度时只显示末尾 光标总在最后
+    fn get_visible(&self) -> &str {
+        let width = self.rect.width as usize;
+        let mut used = 0;
+        let mut start = self.input.len();
+        for (i, c) in self.input.char_indices().rev() {
+            used += c.width().unwrap_or(0);
+            if used > width.saturating_sub(1) {
+                break;
+            }
+            start = i;
+        }
+        &self.input[start..]
+    }
+
     fn get_inp_exact(&self) -> (&str, bool) {
         return if let Some(first_char) = &self.input.chars().next() {
             if *first_char == '/' {
@@ -2074,7 +2612,13 @@ static FIELDS: &[(&str, ParserFn)] = &[
     }),
 ];
 
-fn get_data_inspector_content<'a>(seek: usize, buf: Vec<u8>, endian: Endian) -> Text<'a> {
+fn get_data_inspector_content<'a>(
+    seek: usize,
+    buf: Vec<u8>,
+    endian: Endian,
+    field: Option<&PluginField>,
+    mark: Option<&Bookmark>,
+) -> Text<'a> {
     let bv = ByteView::new(buf, endian);
     let mut lines = vec![
         Line::from(Span::styled(
@@ -2089,6 +2633,29 @@ fn get_data_inspector_content<'a>(seek: usize, buf: Vec<u8>, endian: Endian) ->
             Span::raw(seek.to_string()),
         ]),
     ];
+    // 光标所在的模板字段 放在前面避免被截断
+    if let Some(field) = field {
+        lines.push(Line::from(vec![
+            Span::styled("| template field     | ", Style::default().fg(Color::White)),
+            Span::raw(field.name.clone()),
+        ]));
+        lines.push(Line::from(vec![
+            Span::styled("| template value     | ", Style::default().fg(Color::White)),
+            Span::raw(field.value.clone()),
+        ]));
+    }
+    if let Some(mark) = mark {
+        lines.push(Line::from(vec![
+            Span::styled("| bookmark           | ", Style::default().fg(Color::White)),
+            Span::raw(mark.name.clone()),
+        ]));
+        if !mark.comment.is_empty() {
+            lines.push(Line::from(vec![
+                Span::styled("| bookmark comment   | ", Style::default().fg(Color::White)),
+                Span::raw(mark.comment.clone()),
+            ]));
+        }
+    }
     lines.extend(FIELDS.iter().map(|&(label, f)| {
         let spans = vec![
             Span::styled(label, Style::default().fg(Color::White)),
@@ -2101,13 +2668,105 @@ fn get_data_inspector_content<'a>(seek: usize, buf: Vec<u8>, endian: Endian) ->
     text
 }
 
+// 结构树 选中的节点高亮 超出高度时滚动
+fn get_tree_content<'a>(
+    fields: &'a [PluginField],
+    tree: &FieldTree,
+    sel: usize,
+    height: usize,
+) -> Text<'a> {
+    let rows = tree.visible();
+    let labels: Vec<String> = rows
+        .iter()
+        .map(|&i| {
+            let marker = match (tree.has_children(i), tree.is_expanded(i)) {
+                (false, _) => "  ",
+                (true, true) => "▾ ",
+                (true, false) => "▸ ",
+            };
+            format!(
+                "{}{}{}",
+                "  ".repeat(tree.depth(i)),
+                marker,
+                tree.label(fields, i)
+            )
+        })
+        .collect();
+    let name_width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let pos = rows.iter().position(|&i| i == sel).unwrap_or(0);
+    let skip = (pos + 1).saturating_sub(height.max(1));
+    let lines: Vec<Line> = rows
+        .iter()
+        .zip(labels)
+        .skip(skip)
+        .take(height.max(1))
+        .map(|(&i, label)| {
+            let style = if i == sel {
+                Style::default().fg(Color::Black).bg(Color::White)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let f = &fields[i];
+            Line::from(vec![
+                Span::styled(format!("{:<w$} ", label, w = name_width), style),
+                Span::styled(
+                    format!("@{}+{} ", f.offset, f.length),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::raw(f.value.as_str()),
+            ])
+        })
+        .collect();
+    Text::from(lines)
+}
+
 const HEX_TOP: &'static str = "00 01 02 03 04 05 06 07  08 09 0A 0B 0C 0D 0E 0F     ASCII";
 
+// 差异列表 当前差异显示在中间
+fn get_diff_list(diff: &Diff, current: Option<usize>, height: usize) -> Text<'static> {
+    let ranges = diff.ranges();
+    let first = current.map_or(0, |c| c.saturating_sub(height / 2));
+    let mut lines = vec![Line::from(Span::styled(
+        format!("Differences ({})", diff.summary()),
+        Style::default()
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD),
+    ))];
+    for (i, r) in ranges.iter().enumerate().skip(first).take(height) {
+        let kind = r.kind();
+        let color = match kind {
+            DiffKind::Changed => DIFF_CHANGED_COLOR,
+            _ => DIFF_ONE_SIDE_COLOR,
+        };
+        let style = if Some(i) == current {
+            Style::default().bg(Color::DarkGray)
+        } else {
+            Style::default()
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("#{:<6}", i + 1), style),
+            Span::styled(format!("{:<9}", kind.name()), style.fg(color)),
+            Span::styled(
+                format!(
+                    "L {:07x}+{:<8} R {:07x}+{:<8}",
+                    r.left.0,
+                    r.left.1 - r.left.0,
+                    r.right.0,
+                    r.right.1 - r.right.0
+                ),
+                style,
+            ),
+        ]));
+    }
+    Text::from(lines)
+}
+
 fn get_hex_content<'a>(
     txts: &'a RingVec<CacheStr>,
     line_meta: &'a RingVec<EditLineMeta>,
     cur_line: usize,
     hex_sel: &TextSelect,
+    highlights: &[Highlight],
     height: usize,
     cursor_y: usize,
     cursor_x: usize,
@@ -2144,6 +2803,9 @@ fn get_hex_content<'a>(
                 } else {
                     '.'.to_string()
                 };
+                let pos = line_meta.get(i).unwrap().get_line_file_start() + j;
+                let hl_style = highlight_style(highlights, pos);
+                let hl_space = highlight_space_style(highlights, pos);
 
                 if hex_sel.has_selected() {
                     if hex_sel.is_selected(line_meta.get(i).unwrap().get_line_file_start() + j) {
@@ -2156,10 +2818,10 @@ fn get_hex_content<'a>(
                     } else {
                         spans.push(Span::styled(
                             c.to_string().to_uppercase(),
-                            Style::default().fg(color),
+                            hl_style.fg(color),
                         ));
-                        spans.push(Span::raw(space));
-                        str_spans.push(Span::raw(b1));
+                        spans.push(Span::styled(space, hl_space));
+                        str_spans.push(Span::styled(b1, hl_style));
                     }
                 } else {
                     if j == cursor_x {
@@ -2171,11 +2833,11 @@ fn get_hex_content<'a>(
                     } else {
                         spans.push(Span::styled(
                             c.to_string().to_uppercase(),
-                            Style::default().fg(color),
+                            hl_style.fg(color),
                         ));
-                        str_spans.push(Span::raw(b1));
+                        str_spans.push(Span::styled(b1, hl_style));
                     }
-                    spans.push(Span::raw(space));
+                    spans.push(Span::styled(space, hl_space));
                 }
                 j += 1;
             }
@@ -2195,6 +2857,9 @@ fn get_hex_content<'a>(
                 } else {
                     '.'.to_string()
                 };
+                let pos = line_meta.get(i).unwrap().get_line_file_start() + j;
+                let hl_style = highlight_style(highlights, pos);
+                let hl_space = highlight_space_style(highlights, pos);
 
                 if hex_sel.has_selected() {
                     if hex_sel.is_selected(line_meta.get(i).unwrap().get_line_file_start() + j) {
@@ -2207,10 +2872,10 @@ fn get_hex_content<'a>(
                     } else {
                         spans.push(Span::styled(
                             c.to_string().to_uppercase(),
-                            Style::default().fg(color),
+                            hl_style.fg(color),
                         ));
-                        spans.push(Span::raw(space));
-                        str_spans.push(Span::raw(b1));
+                        spans.push(Span::styled(space, hl_space));
+                        str_spans.push(Span::styled(b1, hl_style));
                     }
                 } else {
                     if j == cursor_x {
@@ -2222,11 +2887,11 @@ fn get_hex_content<'a>(
                     } else {
                         spans.push(Span::styled(
                             c.to_string().to_uppercase(),
-                            Style::default().fg(color),
+                            hl_style.fg(color),
                         ));
-                        str_spans.push(Span::raw(b1));
+                        str_spans.push(Span::styled(b1, hl_style));
                     }
-                    spans.push(Span::raw(space));
+                    spans.push(Span::styled(space, hl_space));
                 }
                 j += 1;
             }
@@ -2246,6 +2911,9 @@ fn get_hex_content<'a>(
                 } else {
                     '.'.to_string()
                 };
+                let pos = line_meta.get(i).unwrap().get_line_file_start() + j;
+                let hl_style = highlight_style(highlights, pos);
+                let hl_space = highlight_space_style(highlights, pos);
                 if hex_sel.has_selected() {
                     if hex_sel.is_selected(line_meta.get(i).unwrap().get_line_file_start() + j) {
                         spans.push(Span::styled(
@@ -2257,18 +2925,18 @@ fn get_hex_content<'a>(
                     } else {
                         spans.push(Span::styled(
                             c.to_string().to_uppercase(),
-                            Style::default().fg(color),
+                            hl_style.fg(color),
                         ));
-                        spans.push(Span::raw(space));
-                        str_spans.push(Span::raw(b1));
+                        spans.push(Span::styled(space, hl_space));
+                        str_spans.push(Span::styled(b1, hl_style));
                     }
                 } else {
                     spans.push(Span::styled(
                         c.to_string().to_uppercase(),
-                        Style::default().fg(color),
+                        hl_style.fg(color),
                     ));
-                    spans.push(Span::raw(space));
-                    str_spans.push(Span::raw(b1));
+                    spans.push(Span::styled(space, hl_space));
+                    str_spans.push(Span::styled(b1, hl_style));
                 }
                 j += 1;
             }
@@ -2287,6 +2955,9 @@ fn get_hex_content<'a>(
                 } else {
                     '.'.to_string()
                 };
+                let pos = line_meta.get(i).unwrap().get_line_file_start() + j;
+                let hl_style = highlight_style(highlights, pos);
+                let hl_space = highlight_space_style(highlights, pos);
                 if hex_sel.has_selected() {
                     if hex_sel.is_selected(line_meta.get(i).unwrap().get_line_file_start() + j) {
                         spans.push(Span::styled(
@@ -2298,26 +2969,32 @@ fn get_hex_content<'a>(
                     } else {
                         spans.push(Span::styled(
                             c.to_string().to_uppercase(),
-                            Style::default().fg(color),
+                            hl_style.fg(color),
                         ));
-                        spans.push(Span::raw(space));
-                        str_spans.push(Span::raw(b1));
+                        spans.push(Span::styled(space, hl_space));
+                        str_spans.push(Span::styled(b1, hl_style));
                     }
                 } else {
                     spans.push(Span::styled(
                         c.to_string().to_uppercase(),
-                        Style::default().fg(color),
+                        hl_style.fg(color),
                     ));
-                    spans.push(Span::raw(space));
-                    str_spans.push(Span::raw(b1));
+                    spans.push(Span::styled(space, hl_space));
+                    str_spans.push(Span::styled(b1, hl_style));
                 }
                 j += 1;
             }
         }
 
-        spans.push(Span::raw(
-            "   ".repeat(16_usize.saturating_sub(txt.len()) + 1),
-        ));
+        let mut pad = 16_usize.saturating_sub(txt.len()) + 1;
+        //光标在文件末尾 显示一个空格用于追加
+        if cursor_y == i && !hex_sel.has_selected() && cursor_x == j && j < 16 {
+            spans.push(Span::styled("  ", Style::default().bg(Color::DarkGray)));
+            spans.push(Span::raw(" "));
+            str_spans.push(Span::styled(" ", Style::default().bg(Color::DarkGray)));
+            pad -= 1;
+        }
+        spans.push(Span::raw("   ".repeat(pad)));
         spans.extend_from_slice(&str_spans);
         lines.push(Line::from(spans));
     }
@@ -2346,12 +3023,19 @@ fn get_hex_content<'a>(
                 if i - 2 >= line_meta.len() {
                     return Line::raw(" ");
                 }
+                //选中范围所在的行
+                let start = line_meta.get(i - 2).unwrap().get_line_file_start();
+                let color = if hex_sel.has_selected()
+                    && start <= hex_sel.end()
+                    && hex_sel.start() < start + HEX_WITH
+                {
+                    Color::Yellow
+                } else {
+                    Color::White
+                };
                 Line::from(Span::styled(
-                    format!(
-                        "{:07x}",
-                        line_meta.get(i - 2).unwrap().get_line_file_start()
-                    ),
-                    Style::default().fg(Color::White),
+                    format!("{:07x}", start),
+                    Style::default().fg(color),
                 ))
             })
             .collect::<Vec<Line>>(),
@@ -2361,6 +3045,52 @@ fn get_hex_content<'a>(
     (nav_text, text)
 }
 
+// 辅助窗口的16进制视图 字节配色与主视图一致
+fn get_assist_hex_content(data: &[u8], height: usize) -> Text<'static> {
+    let mut lines = Vec::with_capacity(height);
+    let mut buffer = Buffer::<1>::new();
+    let rows = data.len().div_ceil(HEX_WITH);
+    for (i, chunk) in data.chunks(HEX_WITH).enumerate() {
+        //放不下时最后一行显示剩余的字节数
+        if i + 1 >= height && rows > height {
+            lines.push(Line::from(format!(
+                "... {} more bytes",
+                data.len() - i * HEX_WITH
+            )));
+            break;
+        }
+        let mut spans = Vec::with_capacity(chunk.len() * 3 + 2);
+        spans.push(Span::raw(format!("{:08x}  ", i * HEX_WITH)));
+        let mut str_spans = Vec::with_capacity(chunk.len());
+        for (j, b) in chunk.iter().enumerate() {
+            let color = Byte(*b).category().color();
+            let space = if j != 0 && (j + 1) % 8 == 0 {
+                "  "
+            } else {
+                " "
+            };
+            let b1 = if b.is_ascii() && !b.is_ascii_control() {
+                *b as char
+            } else {
+                '.'
+            };
+            spans.push(Span::styled(
+                buffer.format(&[*b]).to_uppercase(),
+                Style::default().fg(color),
+            ));
+            spans.push(Span::raw(space));
+            str_spans.push(Span::styled(b1.to_string(), Style::default().fg(color)));
+        }
+        //最后一行不足16个字节时补齐 让ascii列对齐
+        let missing = HEX_WITH - chunk.len();
+        let pad = missing * 3 + missing.div_ceil(8);
+        spans.push(Span::raw(" ".repeat(pad)));
+        spans.extend(str_spans);
+        lines.push(Line::from(spans));
+    }
+    Text::from(lines)
+}
+
 fn get_edit_content<'a>(
     txts: &'a RingVec<CacheStr>,
     line_meta: &'a RingVec<EditLineMeta>,
@@ -2559,4 +3289,62 @@ mod tests {
         println!("a:{},b:{},c:{}", a, b, c);
         Ok(())
     }
+
+    #[test]
+    fn test_focus_cycle() {
+        //16进制模式在16进制窗口和结构树之间切换
+        let mut focus = Focus::new(&ChapMod::Hex);
+        assert!(focus.current() == FocusType::TxtFuzzy);
+        focus.next();
+        assert!(focus.current() == FocusType::Tree);
+        focus.next();
+        assert!(focus.current() == FocusType::TxtFuzzy);
+        focus.set(FocusType::Tree);
+        assert!(focus.current() == FocusType::Tree);
+        assert_eq!(focus.color(FocusType::Tree), Color::Yellow);
+
+        let mut focus = Focus::new(&ChapMod::Text);
+        focus.next();
+        assert!(focus.current() == FocusType::Chat);
+        assert!(HexInput::Ascii.next().is_none());
+        assert!(HexInput::Fields.next().is_none());
+    }
+
+    #[test]
+    fn test_cmd_input_visible() {
+        let mut inp = CmdInput::new(Rect::new(0, 0, 8, 1));
+        inp.push_str("f /ab");
+        assert_eq!(inp.get_visible(), "f /ab");
+        inp.push_str("cdefgh/");
+        //留一列给光标
+        assert_eq!(inp.get_visible(), "cdefgh/");
+        inp.clear();
+        inp.push_str("中文中文中文");
+        assert_eq!(inp.get_visible(), "文中文");
+    }
+
+    #[test]
+    fn test_assist_hex_content() {
+        let line = |t: &Text, i: usize| -> String {
+            t.lines[i]
+                .spans
+                .iter()
+                .map(|s| s.content.as_ref())
+                .collect()
+        };
+        let t = get_assist_hex_content(b"0123456789abcdefXY", 10);
+        assert_eq!(t.lines.len(), 2);
+        assert_eq!(
+            line(&t, 0),
+            "00000000  30 31 32 33 34 35 36 37  38 39 61 62 63 64 65 66  0123456789abcdef"
+        );
+        assert_eq!(
+            line(&t, 1),
+            "00000010  58 59                                             XY"
+        );
+        //放不下时最后一行显示剩余字节数
+        let t = get_assist_hex_content(&[0u8; 64], 2);
+        assert_eq!(t.lines.len(), 2);
+        assert_eq!(line(&t, 1), "... 48 more bytes");
+    }
 }
@@ -0,0 +1,791 @@
+use crate::plugin::ByteSource;
+use crate::plugin::PluginField;
+
+// WAL 页大小 XLOG_BLCKSZ
+pub(crate) const XLOG_BLCKSZ: usize = 8192;
+const SIZE_OF_SHORT_PHD: usize = 24; // MAXALIGN(sizeof(XLogPageHeaderData))
+const SIZE_OF_LONG_PHD: usize = 40; // MAXALIGN(sizeof(XLogLongPageHeaderData))
+const SIZE_OF_XLOG_RECORD: usize = 24;
+// wal_segment_size 默认 16MB 可以是 1MB 到 1GB 之间的2的幂
+const DEFAULT_SEG_SIZE: usize = 16 * 1024 * 1024;
+const MIN_SEG_SIZE: usize = 1024 * 1024;
+const MAX_SEG_SIZE: usize = 1024 * 1024 * 1024;
+const XL_CRC_OFFSET: usize = 20; // offsetof(XLogRecord, xl_crc)
+
+// xlp_info
+const XLP_FIRST_IS_CONTRECORD: u16 = 0x0001;
+const XLP_LONG_HEADER: u16 = 0x0002;
+
+// 块引用 id
+const XLR_MAX_BLOCK_ID: u8 = 32;
+const XLR_BLOCK_ID_DATA_SHORT: u8 = 255;
+const XLR_BLOCK_ID_DATA_LONG: u8 = 254;
+const XLR_BLOCK_ID_ORIGIN: u8 = 253;
+const XLR_BLOCK_ID_TOPLEVEL_XID: u8 = 252;
+
+// fork_flags
+const BKPBLOCK_FORK_MASK: u8 = 0x0F;
+const BKPBLOCK_HAS_IMAGE: u8 = 0x10;
+const BKPBLOCK_HAS_DATA: u8 = 0x20;
+const BKPBLOCK_WILL_INIT: u8 = 0x40;
+const BKPBLOCK_SAME_REL: u8 = 0x80;
+
+// bimg_info (postgres 15)
+const BKPIMAGE_HAS_HOLE: u8 = 0x01;
+const BKPIMAGE_COMPRESSED: u8 = 0x04 | 0x08 | 0x10; // pglz lz4 zstd
+
+const RM_XLOG_ID: u8 = 0;
+const XLOG_SWITCH: u8 = 0x40;
+
+const RMGR_NAMES: &[&str] = &[
+    "XLOG",
+    "Transaction",
+    "Storage",
+    "CLOG",
+    "Database",
+    "Tablespace",
+    "MultiXact",
+    "RelMap",
+    "Standby",
+    "Heap2",
+    "Heap",
+    "Btree",
+    "Hash",
+    "Gin",
+    "Gist",
+    "Sequence",
+    "SPGist",
+    "BRIN",
+    "CommitTs",
+    "ReplicationOrigin",
+    "Generic",
+    "LogicalMessage",
+];
+
+const FORK_NAMES: &[&str] = &["main", "fsm", "vm", "init"];
+
+// 常见资源管理器的记录类型
+fn record_type(rmid: u8, info: u8) -> String {
+    let names: &[(u8, &str)] = match rmid {
+        0 => &[
+            (0x00, "CHECKPOINT_SHUTDOWN"),
+            (0x10, "CHECKPOINT_ONLINE"),
+            (0x20, "NOOP"),
+            (0x30, "NEXTOID"),
+            (0x40, "SWITCH"),
+            (0x50, "BACKUP_END"),
+            (0x60, "PARAMETER_CHANGE"),
+            (0x70, "RESTORE_POINT"),
+            (0x80, "FPW_CHANGE"),
+            (0x90, "END_OF_RECOVERY"),
+            (0xA0, "FPI_FOR_HINT"),
+            (0xB0, "FPI"),
+            (0xD0, "OVERWRITE_CONTRECORD"),
+        ],
+        1 => &[
+            (0x00, "COMMIT"),
+            (0x10, "PREPARE"),
+            (0x20, "ABORT"),
+            (0x30, "COMMIT_PREPARED"),
+            (0x40, "ABORT_PREPARED"),
+            (0x50, "ASSIGNMENT"),
+            (0x60, "INVALIDATION"),
+        ],
+        8 => &[
+            (0x00, "LOCK"),
+            (0x10, "RUNNING_XACTS"),
+            (0x20, "INVALIDATIONS"),
+        ],
+        9 => &[
+            (0x00, "REWRITE"),
+            (0x10, "PRUNE"),
+            (0x20, "VACUUM"),
+            (0x30, "FREEZE_PAGE"),
+            (0x40, "VISIBLE"),
+            (0x50, "MULTI_INSERT"),
+            (0x60, "LOCK_UPDATED"),
+            (0x70, "NEW_CID"),
+        ],
+        10 => &[
+            (0x00, "INSERT"),
+            (0x10, "DELETE"),
+            (0x20, "UPDATE"),
+            (0x30, "TRUNCATE"),
+            (0x40, "HOT_UPDATE"),
+            (0x50, "CONFIRM"),
+            (0x60, "LOCK"),
+            (0x70, "INPLACE"),
+        ],
+        11 => &[
+            (0x00, "INSERT_LEAF"),
+            (0x10, "INSERT_UPPER"),
+            (0x20, "INSERT_META"),
+            (0x30, "SPLIT_L"),
+            (0x40, "SPLIT_R"),
+            (0x50, "INSERT_POST"),
+            (0x60, "DEDUP"),
+            (0x70, "DELETE"),
+            (0x80, "UNLINK_PAGE"),
+            (0x90, "UNLINK_PAGE_META"),
+            (0xA0, "NEWROOT"),
+            (0xB0, "MARK_PAGE_HALFDEAD"),
+            (0xC0, "VACUUM"),
+            (0xD0, "REUSE_PAGE"),
+            (0xE0, "META_CLEANUP"),
+        ],
+        _ => &[],
+    };
+    // 事务记录只用 0x70 heap 记录 0x80 为 INIT_PAGE 标志
+    let (mask, init) = match rmid {
+        1 => (0x70, false),
+        9 | 10 => (0x70, info & 0x80 != 0),
+        _ => (0xF0, false),
+    };
+    match names.iter().find(|(v, _)| *v == info & mask) {
+        Some((_, name)) if init => format!("{}+INIT", name),
+        Some((_, name)) => name.to_string(),
+        None => format!("0x{:02X}", info & 0xF0),
+    }
+}
+
+// CRC-32C (Castagnoli) 与 postgres pg_crc32c 一致
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82F63B78
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+fn crc32c_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &b in data {
+        crc = CRC32C_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+pub(crate) fn crc32c(data: &[u8]) -> u32 {
+    !crc32c_update(0xFFFF_FFFF, data)
+}
+
+// 记录的校验和 先计算头部之后的数据 再计算 xl_crc 之前的头部
+fn record_crc(rec: &[u8]) -> u32 {
+    let crc = crc32c_update(0xFFFF_FFFF, &rec[SIZE_OF_XLOG_RECORD..]);
+    !crc32c_update(crc, &rec[..XL_CRC_OFFSET])
+}
+
+fn u16_at(b: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes(b[off..off + 2].try_into().unwrap())
+}
+
+fn u32_at(b: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(b[off..off + 4].try_into().unwrap())
+}
+
+fn u64_at(b: &[u8], off: usize) -> u64 {
+    u64::from_le_bytes(b[off..off + 8].try_into().unwrap())
+}
+
+fn maxalign(n: usize) -> usize {
+    (n + 7) & !7
+}
+
+// 与 postgres 的 LSN 格式一致 %X/%X
+pub(crate) fn format_lsn(lsn: u64) -> String {
+    format!("{:X}/{:X}", lsn >> 32, lsn as u32)
+}
+
+// XLogPageHeaderData
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct XLogPageHeader {
+    pub(crate) magic: u16,
+    pub(crate) info: u16,
+    pub(crate) tli: u32,
+    pub(crate) pageaddr: u64,
+    pub(crate) rem_len: u32,
+    // 长页头 每个段的第一页
+    pub(crate) sysid: Option<u64>,
+    pub(crate) seg_size: u32,
+    pub(crate) xlog_blcksz: u32,
+}
+
+impl XLogPageHeader {
+    fn parse(page: &[u8]) -> Option<XLogPageHeader> {
+        if page.len() < SIZE_OF_SHORT_PHD {
+            return None;
+        }
+        let info = u16_at(page, 2);
+        let long = info & XLP_LONG_HEADER != 0 && page.len() >= SIZE_OF_LONG_PHD;
+        Some(XLogPageHeader {
+            magic: u16_at(page, 0),
+            info,
+            tli: u32_at(page, 4),
+            pageaddr: u64_at(page, 8),
+            rem_len: u32_at(page, 16),
+            sysid: if long { Some(u64_at(page, 24)) } else { None },
+            seg_size: if long { u32_at(page, 32) } else { 0 },
+            xlog_blcksz: if long { u32_at(page, 36) } else { 0 },
+        })
+    }
+
+    fn size(&self) -> usize {
+        if self.sysid.is_some() {
+            SIZE_OF_LONG_PHD
+        } else {
+            SIZE_OF_SHORT_PHD
+        }
+    }
+}
+
+// 块引用 XLogRecordBlockHeader
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BlockRef {
+    pub(crate) id: u8,
+    pub(crate) fork: u8,
+    pub(crate) rel: (u32, u32, u32), // spcNode dbNode relNode
+    pub(crate) blkno: u32,
+    pub(crate) data_len: u16,
+    pub(crate) image_len: Option<u16>,
+    pub(crate) hole: bool,
+    pub(crate) compressed: bool,
+    pub(crate) will_init: bool,
+}
+
+impl BlockRef {
+    fn summary(&self) -> String {
+        let mut s = format!(
+            "blk#{} {}/{}/{}",
+            self.id, self.rel.0, self.rel.1, self.rel.2
+        );
+        if self.fork != 0 {
+            let fork = FORK_NAMES.get(self.fork as usize).unwrap_or(&"?");
+            s.push_str(&format!(" {}", fork));
+        }
+        s.push_str(&format!(" blk {}", self.blkno));
+        if let Some(len) = self.image_len {
+            s.push_str(&format!(" FPW {}", len));
+            if self.hole {
+                s.push_str(" hole");
+            }
+            if self.compressed {
+                s.push_str(" compressed");
+            }
+        }
+        if self.will_init {
+            s.push_str(" init");
+        }
+        s
+    }
+}
+
+// XLogRecord
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct WalRecord {
+    pub(crate) lsn: u64,
+    pub(crate) start: usize, // 文件偏移
+    pub(crate) end: usize,   // 文件偏移 不包括
+    pub(crate) tot_len: u32,
+    pub(crate) xid: u32,
+    pub(crate) prev: u64,
+    pub(crate) info: u8,
+    pub(crate) rmid: u8,
+    pub(crate) crc: u32,
+    pub(crate) crc_ok: bool,
+    pub(crate) blocks: Vec<BlockRef>,
+    pub(crate) main_data_len: u32,
+    pub(crate) error: Option<String>,
+}
+
+impl WalRecord {
+    pub(crate) fn rmgr_name(&self) -> String {
+        match RMGR_NAMES.get(self.rmid as usize) {
+            Some(name) => name.to_string(),
+            None => format!("rmgr{}", self.rmid),
+        }
+    }
+
+    pub(crate) fn summary(&self) -> String {
+        let mut s = format!(
+            "{} {} len={} xid={} prev={} {}",
+            self.rmgr_name(),
+            record_type(self.rmid, self.info),
+            self.tot_len,
+            self.xid,
+            format_lsn(self.prev),
+            if self.crc_ok {
+                "crc ok".to_string()
+            } else {
+                format!("crc BAD 0x{:08X}", self.crc)
+            }
+        );
+        for b in self.blocks.iter() {
+            s.push_str(", ");
+            s.push_str(&b.summary());
+        }
+        if let Some(e) = &self.error {
+            s.push_str(&format!(", {}", e));
+        }
+        s
+    }
+}
+
+// 解析块引用和主数据长度
+fn parse_block_refs(rec: &[u8]) -> Result<(Vec<BlockRef>, u32), String> {
+    let mut blocks: Vec<BlockRef> = Vec::new();
+    let mut main_data_len = 0u32;
+    let mut pos = SIZE_OF_XLOG_RECORD;
+    let need = |pos: usize, n: usize| -> Result<(), String> {
+        if pos + n > rec.len() {
+            Err(format!("block header truncated at {}", pos))
+        } else {
+            Ok(())
+        }
+    };
+    //块数据和主数据跟在所有头之后
+    let mut payload = 0usize;
+    while rec.len().saturating_sub(pos) > payload {
+        let id = rec[pos];
+        pos += 1;
+        match id {
+            XLR_BLOCK_ID_DATA_SHORT => {
+                need(pos, 1)?;
+                main_data_len = rec[pos] as u32;
+                pos += 1;
+                break;
+            }
+            XLR_BLOCK_ID_DATA_LONG => {
+                need(pos, 4)?;
+                main_data_len = u32_at(rec, pos);
+                pos += 4;
+                break;
+            }
+            XLR_BLOCK_ID_ORIGIN => {
+                need(pos, 2)?;
+                pos += 2;
+            }
+            XLR_BLOCK_ID_TOPLEVEL_XID => {
+                need(pos, 4)?;
+                pos += 4;
+            }
+            id if id <= XLR_MAX_BLOCK_ID => {
+                need(pos, 3)?;
+                let fork_flags = rec[pos];
+                let data_len = u16_at(rec, pos + 1);
+                pos += 3;
+                let mut block = BlockRef {
+                    id,
+                    fork: fork_flags & BKPBLOCK_FORK_MASK,
+                    rel: (0, 0, 0),
+                    blkno: 0,
+                    data_len,
+                    image_len: None,
+                    hole: false,
+                    compressed: false,
+                    will_init: fork_flags & BKPBLOCK_WILL_INIT != 0,
+                };
+                if fork_flags & BKPBLOCK_HAS_DATA == 0 && data_len != 0 {
+                    return Err(format!(
+                        "blk#{} has data_len {} without HAS_DATA",
+                        id, data_len
+                    ));
+                }
+                payload += data_len as usize;
+                if fork_flags & BKPBLOCK_HAS_IMAGE != 0 {
+                    need(pos, 5)?;
+                    let image_len = u16_at(rec, pos);
+                    let bimg_info = rec[pos + 4];
+                    pos += 5;
+                    block.image_len = Some(image_len);
+                    block.hole = bimg_info & BKPIMAGE_HAS_HOLE != 0;
+                    block.compressed = bimg_info & BKPIMAGE_COMPRESSED != 0;
+                    if block.hole && block.compressed {
+                        //XLogRecordBlockCompressHeader
+                        need(pos, 2)?;
+                        pos += 2;
+                    }
+                    payload += image_len as usize;
+                }
+                if fork_flags & BKPBLOCK_SAME_REL == 0 {
+                    need(pos, 12)?;
+                    block.rel = (u32_at(rec, pos), u32_at(rec, pos + 4), u32_at(rec, pos + 8));
+                    pos += 12;
+                } else {
+                    match blocks.last() {
+                        Some(last) => block.rel = last.rel,
+                        None => return Err("BKPBLOCK_SAME_REL without previous block".into()),
+                    }
+                }
+                need(pos, 4)?;
+                block.blkno = u32_at(rec, pos);
+                pos += 4;
+                blocks.push(block);
+            }
+            id => return Err(format!("invalid block id {} at {}", id, pos - 1)),
+        }
+    }
+    if pos + payload + main_data_len as usize != rec.len() {
+        return Err(format!(
+            "record length mismatch: headers {} + data {} + main {} != {}",
+            pos,
+            payload,
+            main_data_len,
+            rec.len()
+        ));
+    }
+    Ok((blocks, main_data_len))
+}
+
+// 解析后的 WAL 段文件
+#[derive(Debug)]
+pub(crate) struct WalSegment {
+    pub(crate) header: XLogPageHeader,
+    pub(crate) records: Vec<WalRecord>,
+    pub(crate) end: String, // 停止解析的原因
+}
+
+impl WalSegment {
+    pub(crate) fn summary(&self) -> String {
+        format!(
+            "{} records, tli {}, start {}, {}",
+            self.records.len(),
+            self.header.tli,
+            format_lsn(self.header.pageaddr),
+            self.end
+        )
+    }
+
+    // 每条记录一个字段 选中记录所在的字节
+    pub(crate) fn fields(&self) -> Vec<PluginField> {
+        self.records
+            .iter()
+            .map(|r| PluginField {
+                name: format_lsn(r.lsn),
+                offset: r.start,
+                length: r.end - r.start,
+                value: r.summary(),
+            })
+            .collect()
+    }
+}
+
+// 按逻辑位置读取 跳过每页的页头
+struct WalReader<'a> {
+    data: &'a [u8],
+    blcksz: usize,
+    first: &'a XLogPageHeader,
+}
+
+impl<'a> WalReader<'a> {
+    // 检查页头 返回页头长度
+    fn page_header(&self, page_off: usize) -> Result<XLogPageHeader, String> {
+        let hdr = XLogPageHeader::parse(&self.data[page_off..])
+            .ok_or_else(|| format!("truncated page header at 0x{:X}", page_off))?;
+        let expect = self.first.pageaddr + page_off as u64;
+        if hdr.magic != self.first.magic || hdr.pageaddr != expect {
+            return Err(format!(
+                "end of WAL at 0x{:X} (page addr {} expected {})",
+                page_off,
+                format_lsn(hdr.pageaddr),
+                format_lsn(expect)
+            ));
+        }
+        Ok(hdr)
+    }
+
+    // 从 pos 开始读取 n 字节 返回数据和结束位置
+    fn read(&self, mut pos: usize, n: usize) -> Result<(Vec<u8>, usize), String> {
+        // n 来自记录头 损坏时可能很大 预留空间不超过段中剩余的字节
+        let mut buf = Vec::with_capacity(n.min(self.data.len().saturating_sub(pos)));
+        while buf.len() < n {
+            if pos.is_multiple_of(self.blcksz) {
+                if pos >= self.data.len() {
+                    return Err("record continues in next segment".into());
+                }
+                let hdr = self.page_header(pos)?;
+                if hdr.info & XLP_FIRST_IS_CONTRECORD == 0 {
+                    return Err(format!("missing contrecord at 0x{:X}", pos));
+                }
+                pos += hdr.size();
+            }
+            let page_end = (pos / self.blcksz + 1) * self.blcksz;
+            let take = (n - buf.len()).min(page_end - pos);
+            let chunk = self
+                .data
+                .get(pos..pos + take)
+                .ok_or("record continues in next segment")?;
+            buf.extend_from_slice(chunk);
+            pos += take;
+        }
+        Ok((buf, pos))
+    }
+}
+
+// 段文件的长度 由第一页长页头的 xlp_seg_size 决定 不读取段之后的数据
+pub(crate) fn segment_len(src: &dyn ByteSource) -> usize {
+    let seg_size = XLogPageHeader::parse(&src.read(0, SIZE_OF_LONG_PHD))
+        .filter(|h| h.sysid.is_some())
+        .map_or(0, |h| h.seg_size as usize);
+    let seg_size =
+        if seg_size.is_power_of_two() && (MIN_SEG_SIZE..=MAX_SEG_SIZE).contains(&seg_size) {
+            seg_size
+        } else {
+            DEFAULT_SEG_SIZE
+        };
+    src.size().min(seg_size)
+}
+
+// 解析一个 WAL 段文件
+pub(crate) fn walk_segment(data: &[u8]) -> Result<WalSegment, String> {
+    let header = XLogPageHeader::parse(data).ok_or("file < XLogPageHeaderData")?;
+    if header.sysid.is_none() {
+        return Err(format!(
+            "first page has no long header (xlp_info 0x{:04X})",
+            header.info
+        ));
+    }
+    let blcksz = match header.xlog_blcksz as usize {
+        0 => XLOG_BLCKSZ,
+        n if n.is_power_of_two() && n >= SIZE_OF_LONG_PHD * 2 => n,
+        n => return Err(format!("invalid xlp_xlog_blcksz {}", n)),
+    };
+    let reader = WalReader {
+        data,
+        blcksz,
+        first: &header,
+    };
+    let mut pos = header.size();
+    //跳过上一个段中记录的剩余部分
+    if header.info & XLP_FIRST_IS_CONTRECORD != 0 {
+        let rem = header.rem_len as usize;
+        let mut left = rem;
+        while left > 0 {
+            if pos % blcksz == 0 {
+                if pos >= data.len() {
+                    break;
+                }
+                let hdr = reader.page_header(pos).map_err(|e| e.to_string())?;
+                pos += hdr.size();
+            }
+            let page_end = (pos / blcksz + 1) * blcksz;
+            let take = left.min(page_end - pos);
+            pos += take;
+            left -= take;
+        }
+        pos = maxalign(pos);
+    }
+
+    let mut records = Vec::new();
+    let end = loop {
+        if pos % blcksz == 0 {
+            if pos >= data.len() {
+                break "end of segment".to_string();
+            }
+            match reader.page_header(pos) {
+                Ok(hdr) => pos += hdr.size(),
+                Err(e) => break e,
+            }
+        }
+        //页尾剩余空间不足以放下长度字段时 记录头也会跨页
+        let (head, _) = match reader.read(pos, SIZE_OF_XLOG_RECORD) {
+            Ok(r) => r,
+            Err(e) => break e,
+        };
+        let tot_len = u32_at(&head, 0) as usize;
+        if tot_len == 0 {
+            break format!("end of WAL at 0x{:X}", pos);
+        }
+        if tot_len < SIZE_OF_XLOG_RECORD {
+            break format!("invalid record length {} at 0x{:X}", tot_len, pos);
+        }
+        let (rec, rec_end) = match reader.read(pos, tot_len) {
+            Ok(r) => r,
+            Err(e) => break format!("record at 0x{:X}: {}", pos, e),
+        };
+        let crc = u32_at(&rec, XL_CRC_OFFSET);
+        let mut record = WalRecord {
+            lsn: header.pageaddr + pos as u64,
+            start: pos,
+            end: rec_end,
+            tot_len: tot_len as u32,
+            xid: u32_at(&rec, 4),
+            prev: u64_at(&rec, 8),
+            info: rec[16],
+            rmid: rec[17],
+            crc,
+            crc_ok: record_crc(&rec) == crc,
+            blocks: Vec::new(),
+            main_data_len: 0,
+            error: None,
+        };
+        match parse_block_refs(&rec) {
+            Ok((blocks, main)) => {
+                record.blocks = blocks;
+                record.main_data_len = main;
+            }
+            Err(e) => record.error = Some(e),
+        }
+        let switch = record.rmid == RM_XLOG_ID && record.info & 0xF0 == XLOG_SWITCH;
+        records.push(record);
+        if switch {
+            //段的剩余部分为空
+            break "XLOG_SWITCH".to_string();
+        }
+        pos = maxalign(rec_end);
+    };
+    Ok(WalSegment {
+        header,
+        records,
+        end,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32c() {
+        // 标准测试向量
+        assert_eq!(crc32c(b"123456789"), 0xE3069283);
+    }
+
+    // 构造一条记录 xl_crc 填写正确的值
+    fn record(rmid: u8, info: u8, body: &[u8]) -> Vec<u8> {
+        let mut rec = vec![0u8; SIZE_OF_XLOG_RECORD];
+        rec.extend_from_slice(body);
+        let len = rec.len() as u32;
+        rec[0..4].copy_from_slice(&len.to_le_bytes());
+        rec[4..8].copy_from_slice(&735u32.to_le_bytes());
+        rec[16] = info;
+        rec[17] = rmid;
+        let crc = record_crc(&rec);
+        rec[20..24].copy_from_slice(&crc.to_le_bytes());
+        rec
+    }
+
+    fn page_header(buf: &mut [u8], info: u16, pageaddr: u64, rem_len: u32) {
+        buf[0..2].copy_from_slice(&0xD110u16.to_le_bytes());
+        buf[2..4].copy_from_slice(&info.to_le_bytes());
+        buf[4..8].copy_from_slice(&1u32.to_le_bytes());
+        buf[8..16].copy_from_slice(&pageaddr.to_le_bytes());
+        buf[16..20].copy_from_slice(&rem_len.to_le_bytes());
+    }
+
+    #[test]
+    fn test_walk_segment() {
+        let seg_start = 0x0100_0000u64;
+        let mut data = vec![0u8; XLOG_BLCKSZ * 3];
+        page_header(&mut data, XLP_LONG_HEADER, seg_start, 0);
+        data[24..32].copy_from_slice(&42u64.to_le_bytes());
+        data[32..36].copy_from_slice(&(16u32 << 20).to_le_bytes());
+        data[36..40].copy_from_slice(&(XLOG_BLCKSZ as u32).to_le_bytes());
+
+        //heap insert: 块引用 0 带 2 字节数据 主数据 3 字节
+        let mut body = vec![0u8, BKPBLOCK_HAS_DATA, 2, 0];
+        body.extend_from_slice(&1663u32.to_le_bytes());
+        body.extend_from_slice(&5u32.to_le_bytes());
+        body.extend_from_slice(&16384u32.to_le_bytes());
+        body.extend_from_slice(&7u32.to_le_bytes());
+        body.extend_from_slice(&[XLR_BLOCK_ID_DATA_SHORT, 3]);
+        body.extend_from_slice(&[0xaa, 0xbb, 1, 2, 3]);
+        let r1 = record(10, 0x80, &body);
+        let p1 = SIZE_OF_LONG_PHD;
+        data[p1..p1 + r1.len()].copy_from_slice(&r1);
+
+        //跨3页的记录 主数据 16400 字节
+        let mut body = vec![XLR_BLOCK_ID_DATA_LONG];
+        body.extend_from_slice(&16400u32.to_le_bytes());
+        body.extend(std::iter::repeat(0x5a).take(16400));
+        let r2 = record(1, 0x00, &body);
+        let p2 = maxalign(p1 + r1.len());
+        let first = XLOG_BLCKSZ - p2;
+        data[p2..XLOG_BLCKSZ].copy_from_slice(&r2[..first]);
+        let rest = r2.len() - first;
+        page_header(
+            &mut data[XLOG_BLCKSZ..],
+            XLP_FIRST_IS_CONTRECORD,
+            seg_start + XLOG_BLCKSZ as u64,
+            rest as u32,
+        );
+        let p = XLOG_BLCKSZ + SIZE_OF_SHORT_PHD;
+        let take = XLOG_BLCKSZ - SIZE_OF_SHORT_PHD;
+        data[p..p + take].copy_from_slice(&r2[first..first + take]);
+        page_header(
+            &mut data[XLOG_BLCKSZ * 2..],
+            XLP_FIRST_IS_CONTRECORD,
+            seg_start + 2 * XLOG_BLCKSZ as u64,
+            (rest - take) as u32,
+        );
+        let p = XLOG_BLCKSZ * 2 + SIZE_OF_SHORT_PHD;
+        data[p..p + rest - take].copy_from_slice(&r2[first + take..]);
+        let r2_end = p + rest - take;
+
+        let seg = walk_segment(&data).unwrap();
+        assert_eq!(seg.header.sysid, Some(42));
+        assert_eq!(seg.records.len(), 2);
+        assert!(seg.end.starts_with("end of WAL"), "{}", seg.end);
+        let r = &seg.records[0];
+        assert!(r.crc_ok);
+        assert_eq!(r.lsn, seg_start + p1 as u64);
+        assert_eq!(r.main_data_len, 3);
+        assert_eq!(
+            r.summary(),
+            "Heap INSERT+INIT len=51 xid=735 prev=0/0 crc ok, blk#0 1663/5/16384 blk 7"
+        );
+        let r = &seg.records[1];
+        assert!(r.crc_ok, "{}", r.summary());
+        assert_eq!(r.error, None);
+        assert_eq!((r.start, r.end), (p2, r2_end));
+        assert_eq!(r.main_data_len, 16400);
+        assert!(r.summary().starts_with("Transaction COMMIT"));
+        let fields = seg.fields();
+        assert_eq!(fields[1].name, "0/1000060");
+        assert_eq!(fields[1].length, r2_end - p2);
+
+        //损坏的记录 crc 不一致
+        data[p1 + 30] ^= 0xff;
+        let seg = walk_segment(&data).unwrap();
+        assert!(!seg.records[0].crc_ok);
+        assert!(seg.records[0].summary().contains("crc BAD"));
+    }
+
+    #[test]
+    fn test_segment_len() {
+        //只有第一页的数据 但文件有 4GB
+        struct Big(Vec<u8>);
+        impl ByteSource for Big {
+            fn read(&self, offset: usize, len: usize) -> Vec<u8> {
+                let start = offset.min(self.0.len());
+                self.0[start..(start + len).min(self.0.len())].to_vec()
+            }
+            fn size(&self) -> usize {
+                4 << 30
+            }
+        }
+        let mut page = vec![0u8; XLOG_BLCKSZ];
+        page_header(&mut page, XLP_LONG_HEADER, 0, 0);
+        page[24..32].copy_from_slice(&42u64.to_le_bytes());
+        page[32..36].copy_from_slice(&(1u32 << 20).to_le_bytes());
+        assert_eq!(segment_len(&Big(page.clone())), 1 << 20);
+        //无效的段大小按默认的 16MB
+        page[32..36].copy_from_slice(&3u32.to_le_bytes());
+        assert_eq!(segment_len(&Big(page.clone())), DEFAULT_SEG_SIZE);
+        //短页头
+        page_header(&mut page, 0, 0, 0);
+        assert_eq!(segment_len(&Big(page)), DEFAULT_SEG_SIZE);
+    }
+}